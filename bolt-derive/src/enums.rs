@@ -0,0 +1,100 @@
+//! `#[derive(BoltEnum)]`: fieldless Rust enums as bolt enum types
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr};
+
+/// Reads `#[bolt(name = "...")]` on the enum or `#[bolt(rename = "...")]` on a variant
+fn renamed(attrs: &[syn::Attribute], key: &str) -> syn::Result<Option<String>> {
+    let mut out = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bolt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                out = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error(format!("expected `{key}`")))
+            }
+        })?;
+    }
+    Ok(out)
+}
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "BoltEnum can only be derived for enums",
+        ));
+    };
+
+    let script_name = renamed(&input.attrs, "name")?.unwrap_or_else(|| name.to_string());
+
+    let mut options = Vec::new();
+    let mut from_arms = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "BoltEnum variants cannot have fields",
+            ));
+        }
+
+        let ident = &variant.ident;
+        let option = renamed(&variant.attrs, "rename")?.unwrap_or_else(|| ident.to_string());
+        options.push(quote! { (#option, #name::#ident as u32) });
+        from_arms.push(quote! {
+            if value == #name::#ident as u32 {
+                return ::std::option::Option::Some(#name::#ident);
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl ::bolt_rs::BoltEnum for #name {
+            const NAME: &'static str = #script_name;
+            const OPTIONS: &'static [(&'static str, u32)] = &[#(#options),*];
+
+            fn from_discriminant(value: u32) -> ::std::option::Option<Self> {
+                #(#from_arms)*
+                ::std::option::Option::None
+            }
+
+            fn discriminant(&self) -> u32 {
+                *self as u32
+            }
+        }
+
+        impl ::bolt_rs::FromBoltValue for #name {
+            fn from(val: ::bolt_rs::sys::bt_Value) -> ::std::result::Result<Self, ::bolt_rs::ArgError> {
+                let raw = <::bolt_rs::EnumValue as ::bolt_rs::FromBoltValue>::from(val)?;
+                <Self as ::bolt_rs::BoltEnum>::from_discriminant(raw.0)
+                    .ok_or(::bolt_rs::ArgError::InvalidEnumValue(raw.0))
+            }
+
+            unsafe fn from_unchecked(val: ::bolt_rs::sys::bt_Value) -> Self {
+                unsafe {
+                    let raw = <::bolt_rs::EnumValue as ::bolt_rs::FromBoltValue>::from_unchecked(val);
+                    <Self as ::bolt_rs::BoltEnum>::from_discriminant(raw.0).unwrap_unchecked()
+                }
+            }
+        }
+
+        impl ::bolt_rs::MakeBoltValue for #name {
+            fn make(&self) -> ::bolt_rs::sys::bt_Value {
+                ::bolt_rs::MakeBoltValue::make(&::bolt_rs::EnumValue(
+                    <Self as ::bolt_rs::BoltEnum>::discriminant(self),
+                ))
+            }
+        }
+
+        impl ::bolt_rs::ScalarTypeSignature for #name {
+            fn make_type(ctx: &mut ::bolt_rs::Context) -> ::bolt_rs::types::Type {
+                ctx.register_enum::<Self>()
+                    .expect("enum names generated by BoltEnum are valid")
+            }
+        }
+    })
+}
@@ -1,16 +1,73 @@
 use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemImpl, parse_macro_input};
+
+mod enums;
+mod methods;
+mod userdata;
+mod value;
 
 #[proc_macro_derive(BoltObject)]
 pub fn derive_bolt_object(_input: TokenStream) -> TokenStream {
     todo!()
 }
 
-#[proc_macro_derive(BoltMethods)]
-pub fn derive_bolt_object_impl(_input: TokenStream) -> TokenStream {
-    todo!();
+/// Expose the methods of an impl block to scripts as methods on a userdata type.
+///
+/// The type must implement `BoltUserdata`, and every method taking `&self` or `&mut self`
+/// is registered by `Context::register_methods`, with its parameters and return value
+/// converted like any native's. Methods can be renamed with `#[bolt(rename = "...")]` or
+/// left out with `#[bolt(skip)]`. Signatures must name the type rather than use `Self`.
+#[proc_macro_attribute]
+pub fn bolt_methods(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemImpl);
+    methods::expand(item)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 #[proc_macro_derive(BoltModule)]
 pub fn derive_bolt_object_module(_input: TokenStream) -> TokenStream {
     todo!();
 }
+
+/// Convert a struct to and from a bolt table, one key per field.
+///
+/// Field attributes:
+/// - `#[bolt(rename = "key")]` reads and writes the field under a different key
+/// - `#[bolt(default)]` falls back to `Default::default()` when the key is missing
+/// - `#[bolt(skip)]` never touches the table, always using `Default::default()`
+#[proc_macro_derive(BoltValue, attributes(bolt))]
+pub fn derive_bolt_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    value::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Expose a fieldless enum to scripts as a bolt enum type.
+///
+/// The enum must be `Copy`. It is registered under its Rust name, or
+/// `#[bolt(name = "...")]`, and variants can be renamed with `#[bolt(rename = "...")]`.
+#[proc_macro_derive(BoltEnum, attributes(bolt))]
+pub fn derive_bolt_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    enums::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Expose a struct to scripts as a userdata type, see `Context::create_userdata`.
+///
+/// The type is registered under its Rust name, or `#[bolt(name = "...")]`. Every named
+/// field becomes a script field at the offset `offset_of!` computes, converted like a
+/// native argument. Field attributes:
+/// - `#[bolt(rename = "key")]` exposes the field under a different name
+/// - `#[bolt(readonly)]` lets scripts read but not assign the field
+/// - `#[bolt(skip)]` hides the field from scripts
+#[proc_macro_derive(BoltUserdata, attributes(bolt))]
+pub fn derive_bolt_userdata(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    userdata::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
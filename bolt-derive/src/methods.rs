@@ -0,0 +1,163 @@
+//! `#[bolt_methods]`: an impl block's methods as native methods on a userdata type
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, ImplItem, ItemImpl, LitStr, ReturnType};
+
+#[derive(Default)]
+struct MethodAttrs {
+    rename: Option<String>,
+    skip: bool,
+}
+
+/// Reads and strips `#[bolt(...)]` from a method
+fn method_attrs(attrs: &mut Vec<syn::Attribute>) -> syn::Result<MethodAttrs> {
+    let mut out = MethodAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bolt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                out.skip = true;
+            } else {
+                return Err(meta.error("expected `rename` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+    attrs.retain(|attr| !attr.path().is_ident("bolt"));
+    Ok(out)
+}
+
+pub fn expand(mut item: ItemImpl) -> syn::Result<TokenStream> {
+    if item.trait_.is_some() {
+        return Err(syn::Error::new_spanned(
+            &item.self_ty,
+            "#[bolt_methods] goes on an inherent impl block",
+        ));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "#[bolt_methods] does not support generic impl blocks",
+        ));
+    }
+    let self_ty = item.self_ty.clone();
+
+    let mut registrations = Vec::new();
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(method) = impl_item else {
+            continue;
+        };
+        let attrs = method_attrs(&mut method.attrs)?;
+        let Some(receiver) = method.sig.receiver() else {
+            continue;
+        };
+        if attrs.skip {
+            continue;
+        }
+        if receiver.reference.is_none() {
+            return Err(syn::Error::new_spanned(
+                receiver,
+                "methods exposed to bolt must take `&self` or `&mut self`",
+            ));
+        }
+
+        let ident = &method.sig.ident;
+        let script_name = attrs.rename.unwrap_or_else(|| ident.to_string());
+        let label = format!(
+            "{}.{script_name}",
+            quote!(#self_ty).to_string().replace(' ', "")
+        );
+
+        let mut arg_names = Vec::new();
+        let mut arg_types = Vec::new();
+        for (idx, arg) in method.sig.inputs.iter().enumerate() {
+            if let FnArg::Typed(arg) = arg {
+                arg_names.push(format_ident!("arg{idx}"));
+                arg_types.push((*arg.ty).clone());
+            }
+        }
+
+        let (binding, borrow, this) = if receiver.mutability.is_some() {
+            (
+                quote! { mut this },
+                quote! { borrow_mut },
+                quote! { &mut *this },
+            )
+        } else {
+            (quote! { this }, quote! { borrow }, quote! { &*this })
+        };
+        let (ret_type, call) = match &method.sig.output {
+            ReturnType::Default => (
+                quote! { ctx.type_null() },
+                quote! {
+                    #self_ty::#ident(#this, #(#arg_names),*);
+                },
+            ),
+            ReturnType::Type(_, ty) => (
+                quote! { <#ty as ::bolt_rs::ScalarTypeSignature>::make_type(ctx) },
+                quote! {
+                    let result: #ty = #self_ty::#ident(#this, #(#arg_names),*);
+                    drop(this);
+                    let mut ctx = unsafe { ::bolt_rs::ContextRef::from_raw(ctx) };
+                    let result = ::bolt_rs::Value::from_raw(
+                        ::bolt_rs::MakeBoltValueWithContext::make_with_context(&result, &mut ctx),
+                    );
+                    thread.return_val(&result);
+                },
+            ),
+        };
+
+        registrations.push(quote! {
+            {
+                unsafe extern "C" fn trampoline(
+                    ctx: *mut ::bolt_rs::sys::bt_Context,
+                    thr: *mut ::bolt_rs::sys::bt_Thread,
+                ) {
+                    let body = || {
+                        let mut thread = unsafe { ::bolt_rs::Thread::from_raw_unchecked(thr) };
+                        let (this, #(#arg_names,)*) = match thread
+                            .args::<(::bolt_rs::types::Userdata, #(#arg_types,)*)>()
+                        {
+                            ::std::result::Result::Ok(args) => args,
+                            ::std::result::Result::Err(err) => {
+                                return unsafe {
+                                    ::bolt_rs::userdata::raise(thr, #label, &format!("{err:?}"))
+                                };
+                            }
+                        };
+                        let #binding = match this.#borrow::<#self_ty>() {
+                            ::std::result::Result::Ok(this) => this,
+                            ::std::result::Result::Err(err) => {
+                                return unsafe {
+                                    ::bolt_rs::userdata::raise(thr, #label, &err.to_string())
+                                };
+                            }
+                        };
+                        #call
+                    };
+                    unsafe { ::bolt_rs::unwind::guard(ctx, thr, #label, body) };
+                }
+
+                let ret = #ret_type;
+                let args = [#(<#arg_types as ::bolt_rs::ScalarTypeSignature>::make_type(ctx)),*];
+                ctx.userdata_add_method(ty, #script_name, ::std::option::Option::Some(trampoline), ret, &args)?;
+            }
+        });
+    }
+
+    Ok(quote! {
+        #item
+
+        impl ::bolt_rs::BoltMethods for #self_ty {
+            fn register_methods(
+                ctx: &mut ::bolt_rs::Context,
+                ty: ::bolt_rs::types::Type,
+            ) -> ::std::result::Result<(), ::bolt_rs::Error> {
+                #(#registrations)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,106 @@
+//! `#[derive(BoltUserdata)]`: structs as userdata types with script-visible fields
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr};
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    skip: bool,
+    readonly: bool,
+}
+
+fn field_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut out = FieldAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bolt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") {
+                out.skip = true;
+            } else if meta.path.is_ident("readonly") {
+                out.readonly = true;
+            } else {
+                return Err(meta.error("expected `rename`, `skip` or `readonly`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+/// Reads `#[bolt(name = "...")]` on the struct
+fn type_name(attrs: &[syn::Attribute]) -> syn::Result<Option<String>> {
+    let mut out = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bolt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                out = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `name`"))
+            }
+        })?;
+    }
+    Ok(out)
+}
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "BoltUserdata cannot be derived for generic types",
+        ));
+    }
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "BoltUserdata can only be derived for structs",
+        ));
+    };
+
+    let script_name = type_name(&input.attrs)?.unwrap_or_else(|| name.to_string());
+
+    let mut fields = Vec::new();
+    if let Fields::Named(named) = &data.fields {
+        for field in &named.named {
+            let attrs = field_attrs(&field.attrs)?;
+            if attrs.skip {
+                continue;
+            }
+
+            let ident = field.ident.as_ref().expect("named field");
+            let ty = &field.ty;
+            let key = attrs.rename.unwrap_or_else(|| ident.to_string());
+            let writable = !attrs.readonly;
+            fields.push(quote! {
+                // SAFETY: the offset is taken from the field declared with this type
+                unsafe {
+                    ctx.userdata_add_field::<Self, #ty>(
+                        ty,
+                        #key,
+                        ::core::mem::offset_of!(Self, #ident),
+                        #writable,
+                    )?;
+                }
+            });
+        }
+    }
+
+    Ok(quote! {
+        impl ::bolt_rs::BoltUserdata for #name {
+            const NAME: &'static str = #script_name;
+
+            fn register_fields(
+                ctx: &mut ::bolt_rs::Context,
+                ty: ::bolt_rs::types::Type,
+            ) -> ::std::result::Result<(), ::bolt_rs::Error> {
+                #(#fields)*
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,138 @@
+//! `#[derive(BoltValue)]`: structs as bolt tables, one key per field
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr};
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    default: bool,
+    skip: bool,
+}
+
+fn field_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut out = FieldAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("bolt")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("default") {
+                out.default = true;
+            } else if meta.path.is_ident("skip") {
+                out.skip = true;
+            } else {
+                return Err(meta.error("expected `rename`, `default` or `skip`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "BoltValue can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "BoltValue requires named fields",
+        ));
+    };
+
+    let mut reads = Vec::new();
+    let mut unchecked_reads = Vec::new();
+    let mut writes = Vec::new();
+
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let attrs = field_attrs(&field.attrs)?;
+
+        if attrs.skip {
+            reads.push(quote! { #ident: ::std::default::Default::default() });
+            unchecked_reads.push(quote! { #ident: ::std::default::Default::default() });
+            continue;
+        }
+
+        let key = attrs.rename.unwrap_or_else(|| ident.to_string());
+        let missing = if attrs.default {
+            quote! { ::std::default::Default::default() }
+        } else {
+            quote! {
+                <#ty as ::bolt_rs::FromBoltValue>::from(unsafe { ::bolt_rs::sys::bt_make_null() })
+                    .map_err(|_| ::bolt_rs::ArgError::MissingField(#key.to_owned()))?
+            }
+        };
+
+        reads.push(quote! {
+            #ident: match table.get_str(#key) {
+                Some(value) => <#ty as ::bolt_rs::FromBoltValue>::from(value.as_raw())
+                    .map_err(|error| ::bolt_rs::ArgError::BadField {
+                        field: #key.to_owned(),
+                        error: ::std::boxed::Box::new(error),
+                    })?,
+                None => #missing,
+            }
+        });
+        unchecked_reads.push(quote! {
+            #ident: <#ty as ::bolt_rs::FromBoltValue>::from_unchecked(
+                table
+                    .get_str(#key)
+                    .map(|value| value.as_raw())
+                    .unwrap_or_else(|| ::bolt_rs::sys::bt_make_null()),
+            )
+        });
+        writes.push(quote! {
+            // Either allocation can collect, so keep the key and value alive until the
+            // table references them
+            let key = ::bolt_rs::Value::from_raw(
+                ::bolt_rs::MakeBoltValueWithContext::make_with_context(&#key, ctx),
+            );
+            let key_root = key.as_object().inspect(|obj| ctx.push_root(*obj));
+            let value = ::bolt_rs::Value::from_raw(
+                ::bolt_rs::MakeBoltValueWithContext::make_with_context(&self.#ident, ctx),
+            );
+            let value_root = value.as_object().inspect(|obj| ctx.push_root(*obj));
+            ctx.table_set(table, key, value);
+            for _ in key_root.iter().chain(value_root.iter()) {
+                ctx.pop_root();
+            }
+        });
+    }
+
+    let len = writes.len() as u16;
+
+    Ok(quote! {
+        impl #impl_generics ::bolt_rs::FromBoltValue for #name #ty_generics #where_clause {
+            fn from(val: ::bolt_rs::sys::bt_Value) -> ::std::result::Result<Self, ::bolt_rs::ArgError> {
+                let table = <::bolt_rs::types::Table as ::bolt_rs::FromBoltValue>::from(val)?;
+                ::std::result::Result::Ok(Self { #(#reads,)* })
+            }
+
+            unsafe fn from_unchecked(val: ::bolt_rs::sys::bt_Value) -> Self {
+                unsafe {
+                    let table = <::bolt_rs::types::Table as ::bolt_rs::FromBoltValue>::from_unchecked(val);
+                    Self { #(#unchecked_reads,)* }
+                }
+            }
+        }
+
+        impl #impl_generics ::bolt_rs::MakeBoltValueWithContext for #name #ty_generics #where_clause {
+            fn make_with_context(&self, ctx: &mut ::bolt_rs::Context) -> ::bolt_rs::sys::bt_Value {
+                let table = ctx.make_table(#len);
+                ctx.push_root(table.as_object());
+                #(#writes)*
+                ctx.pop_root();
+                ::bolt_rs::MakeBoltValue::make(&table)
+            }
+        }
+    })
+}
@@ -0,0 +1,65 @@
+//! Compares the generic `Context::call` path against a pre-resolved `CallHandle`
+//!
+//! Run with `cargo bench -p bolt-rs --bench call`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bolt_rs::*;
+
+const ITERATIONS: u32 = 100_000;
+
+fn time(name: &str, mut f: impl FnMut()) -> Duration {
+    // Warm up allocations and thread pools before timing
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<24} {:>10.1?} total {:>8.1?}/call",
+        elapsed,
+        elapsed / ITERATIONS
+    );
+    elapsed
+}
+
+fn main() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx
+        .compile_module(
+            "export fn add(a: number, b: number): number { return a + b }",
+            "bench",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (_, _, add) = module
+        .exports(&mut ctx)
+        .find(|(name, _, _)| name == "add")
+        .expect("add should be exported");
+
+    let generic = time("Context::call", || {
+        let args = [Value::from_raw(1.0.make()), Value::from_raw(2.0.make())];
+        let returned = ctx.call(add, &args).expect("Call failed");
+        black_box(returned.as_number());
+    });
+
+    let mut handle =
+        CallHandle::<(f64, f64), f64>::new(&mut ctx, add).expect("Signature should match");
+    let fast = time("CallHandle::call_fast", || {
+        black_box(handle.call_fast(&mut ctx, (1.0, 2.0)).expect("Call failed"));
+    });
+    handle.release(&mut ctx);
+
+    println!(
+        "call_fast speedup: {:.2}x",
+        generic.as_secs_f64() / fast.as_secs_f64()
+    );
+}
@@ -0,0 +1,47 @@
+//! Compares context creation with eagerly and lazily opened standard libraries
+//!
+//! Run with `cargo bench -p bolt-rs --bench context`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bolt_rs::*;
+
+const ITERATIONS: u32 = 2_000;
+
+fn time(name: &str, mut f: impl FnMut()) -> Duration {
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{name:<24} {:>10.1?} total {:>8.1?}/context",
+        elapsed,
+        elapsed / ITERATIONS
+    );
+    elapsed
+}
+
+fn main() {
+    let eager = time("eager open_all_std", || {
+        let mut ctx = Context::new();
+        ctx.open_all_std();
+        black_box(&mut ctx);
+    });
+
+    let lazy = time("lazy open_all_std", || {
+        let mut ctx = Context::builder().lazy_std(true).build();
+        ctx.open_all_std();
+        black_box(&mut ctx);
+    });
+
+    println!(
+        "lazy std is {:.2}x faster to create",
+        eager.as_secs_f64() / lazy.as_secs_f64()
+    );
+}
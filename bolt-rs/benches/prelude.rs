@@ -0,0 +1,98 @@
+//! Compares registering host modules in every context of a pool against installing a
+//! shared `Prelude`
+//!
+//! Run with `cargo bench -p bolt-rs --bench prelude`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bolt_rs::replay::RecordedValue;
+use bolt_rs::*;
+
+const POOL_SIZE: u32 = 1_000;
+const MODULES: usize = 16;
+
+unsafe extern "C" fn noop(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+
+fn module_name(idx: usize) -> String {
+    format!("host_{idx}")
+}
+
+fn time_pool(name: &str, mut make: impl FnMut() -> Context) -> Duration {
+    let start = Instant::now();
+    let pool: Vec<Context> = (0..POOL_SIZE).map(|_| make()).collect();
+    let elapsed = start.elapsed();
+    black_box(&pool);
+    drop(pool);
+
+    println!(
+        "{name:<24} {:>10.1?} total {:>8.1?}/context",
+        elapsed,
+        elapsed / POOL_SIZE
+    );
+    elapsed
+}
+
+fn main() {
+    let eager = time_pool("eager registration", || {
+        let mut ctx = Context::new();
+        ctx.open_all_std();
+        for idx in 0..MODULES {
+            let number = ctx.type_number();
+            let null = ctx.type_null();
+            let limit = Value::from_raw(64.0.make());
+            ModuleBuilder::new(&module_name(idx))
+                .native("tick", Some(noop), null, &[number])
+                .value("limit", number, limit)
+                .finish(&mut ctx)
+                .expect("Failed to register module");
+        }
+        ctx
+    });
+
+    let mut prelude = Prelude::builder();
+    for idx in 0..MODULES {
+        prelude = prelude.module(&module_name(idx), |module| {
+            module
+                .native(
+                    "tick",
+                    Some(noop),
+                    Context::type_null,
+                    &[Context::type_number],
+                )
+                .value("limit", RecordedValue::Number(64.0))
+        });
+    }
+    let prelude = prelude.build().expect("Failed to build prelude");
+
+    let shared = time_pool("shared prelude", || {
+        let mut ctx = Context::builder().prelude(&prelude).build();
+        ctx.open_all_std();
+        ctx
+    });
+
+    let imported = time_pool("prelude, one import", || {
+        let mut ctx = Context::builder().prelude(&prelude).build();
+        ctx.open_all_std();
+        ctx.run("import host_0").expect("Failed to import");
+        ctx
+    });
+
+    let broken = Prelude::builder()
+        .script("broken", "export let x: number = \"nope\"")
+        .build()
+        .expect("Failed to build prelude");
+    time_pool("prelude, failed import", || {
+        let mut ctx = Context::builder().prelude(&broken).build();
+        ctx.open_all_std();
+        ctx.run("import broken")
+            .expect_err("A broken module should fail its import");
+        ctx
+    });
+
+    println!(
+        "shared prelude is {:.2}x faster to set up ({:.2}x with one module imported)",
+        eager.as_secs_f64() / shared.as_secs_f64(),
+        eager.as_secs_f64() / imported.as_secs_f64()
+    );
+}
@@ -0,0 +1,58 @@
+//! Budgeting native calls
+//!
+//! Wall-clock limits depend on the machine and on whatever else it's doing, so they can't
+//! split work fairly between many tenant scripts. A native call budget can:
+//! [`Context::set_native_call_budget`] gives the context a number of native calls to make,
+//! and once they're spent the next native call raises a runtime error and the run or call
+//! fails with [`Error::NativeCallBudget`].
+//!
+//! Only native calls are counted, not instructions. bolt gives the host no hook into the
+//! interpreter loop, so the budget is spent in the trampoline every native goes through,
+//! see [`crate::stats`], whether it's the host's or the standard library's and whenever it
+//! was registered. Script code between two native calls runs for free, and a loop that
+//! never calls a native is never charged; see the TODO for instruction metering.
+//!
+//! [`Error::NativeCallBudget`]: crate::Error::NativeCallBudget
+
+use bolt_sys::sys;
+
+use crate::Context;
+use crate::state::ContextState;
+
+/// Spend one native call from the budget, raising a runtime error on `thr` if there's none
+/// left. Returns whether the native may run.
+pub(crate) fn consume(state: &ContextState, thr: *mut sys::bt_Thread) -> bool {
+    match state.native_budget.get() {
+        None => true,
+        Some(0) => {
+            state.native_budget_spent.set(true);
+            let msg = c"native call budget exhausted";
+            unsafe { sys::bt_runtime_error(thr, msg.as_ptr(), std::ptr::null_mut()) };
+            false
+        }
+        Some(calls) => {
+            state.native_budget.set(Some(calls - 1));
+            true
+        }
+    }
+}
+
+impl Context {
+    /// Allow the context `calls` more native calls, replacing what was left, see the
+    /// [module docs](self)
+    pub fn set_native_call_budget(&mut self, calls: u64) {
+        crate::state::get(self.as_ptr())
+            .native_budget
+            .set(Some(calls));
+    }
+
+    /// Native calls left in the budget, or `None` if the context isn't budgeted
+    pub fn remaining_native_calls(&self) -> Option<u64> {
+        crate::state::get(self.as_ptr()).native_budget.get()
+    }
+
+    /// Stop budgeting the context's native calls
+    pub fn clear_native_call_budget(&mut self) {
+        crate::state::get(self.as_ptr()).native_budget.set(None);
+    }
+}
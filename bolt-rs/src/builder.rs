@@ -0,0 +1,106 @@
+//! Configurable context construction
+
+use std::rc::Rc;
+
+use crate::loader::PathNormalization;
+use crate::memory::{BoltAllocator, Heap};
+use crate::prelude::Prelude;
+use crate::{Context, Error};
+
+/// Options applied to a [`Context`] as it's opened, see [`Context::builder`]
+#[derive(Default, Clone)]
+pub struct ContextBuilder {
+    path_normalization: PathNormalization,
+    lazy_std: bool,
+    allocator: Option<Rc<dyn BoltAllocator>>,
+    prelude: Option<Prelude>,
+}
+
+impl std::fmt::Debug for ContextBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextBuilder")
+            .field("path_normalization", &self.path_normalization)
+            .field("lazy_std", &self.lazy_std)
+            .field("allocator", &self.allocator.is_some())
+            .field("prelude", &self.prelude)
+            .finish()
+    }
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How module paths are normalized before the file loader opens them
+    pub fn path_normalization(mut self, mode: PathNormalization) -> Self {
+        self.path_normalization = mode;
+        self
+    }
+
+    /// Does nothing: every context checks for interrupts on each native call
+    #[deprecated(note = "every context checks for interrupts on each native call")]
+    pub fn interruptible(self, _enabled: bool) -> Self {
+        self
+    }
+
+    /// Have `open_all_std` build import-only modules on first import, which makes short-lived
+    /// contexts much cheaper to create
+    pub fn lazy_std(mut self, enabled: bool) -> Self {
+        self.lazy_std = enabled;
+        self
+    }
+
+    /// Allocate through `allocator` instead of the global allocator
+    ///
+    /// Covers everything bolt allocates while the context is opened and while it compiles or
+    /// runs code. Objects the host creates directly between calls come from the global
+    /// allocator.
+    pub fn allocator(mut self, allocator: impl BoltAllocator + 'static) -> Self {
+        self.allocator = Some(Rc::new(allocator));
+        self
+    }
+
+    /// Install a shared [`Prelude`] into the context, see [`Context::install_prelude`]
+    pub fn prelude(mut self, prelude: &Prelude) -> Self {
+        self.prelude = Some(prelude.clone());
+        self
+    }
+
+    /// Open the context, panicking if bolt fails to open, see [`ContextBuilder::try_build`]
+    pub fn build(self) -> Context {
+        match self.try_build() {
+            Ok(ctx) => ctx,
+            Err(err) => panic!("Failed to create context: {err}"),
+        }
+    }
+
+    /// Open the context, reporting why it couldn't be opened instead of panicking
+    pub fn try_build(self) -> Result<Context, Error> {
+        let heap = self
+            .allocator
+            .map(|allocator| Rc::new(Heap::new(Some(allocator))));
+        let mut ctx = {
+            let _heap = crate::memory::enter_heap(heap.clone());
+            Context::try_new()?
+        };
+
+        let state = crate::state::get(ctx.as_ptr());
+        if let Some(heap) = heap {
+            *state.heap.borrow_mut() = Some(heap);
+            crate::memory::touch(ctx.as_ptr(), &state);
+        }
+        state.path_normalization.set(self.path_normalization);
+        state.lazy_std.set(self.lazy_std);
+        if let Some(prelude) = &self.prelude {
+            ctx.install_prelude(prelude);
+        }
+        Ok(ctx)
+    }
+}
+
+impl Context {
+    pub fn builder() -> ContextBuilder {
+        ContextBuilder::new()
+    }
+}
@@ -0,0 +1,181 @@
+//! Pre-resolved calls into script functions
+//!
+//! [`Context::call`] accepts any value and any arguments, so every call checks that the
+//! value is callable, borrows a pooled thread and collects the arguments into a fresh
+//! buffer. A [`CallHandle`] does that work once: the signature is checked against the Rust
+//! argument and return types when the handle is created, and each call reuses the same
+//! thread and argument buffer. Calls still go through the same depth, hook and memory
+//! bookkeeping as [`Context::call`], and a call that fails replaces the thread.
+
+use std::marker::PhantomData;
+
+use bolt_sys::sys;
+
+use crate::types::{Callable, Object, Thread, Type, TypeKind};
+use crate::{
+    Context, Error, FromBoltValue, IntoBoltArgs, MakeBoltValue, ScalarTypeSignature, Value,
+    ValueType,
+};
+
+/// A script function bound to a fixed Rust signature, see the [module docs](self)
+///
+/// The function and any interned strings are kept alive until [`CallHandle::release`].
+#[derive(Debug)]
+pub struct CallHandle<A, R> {
+    callable: Object,
+    thread: Thread,
+    args: Vec<sys::bt_Value>,
+    interned: Vec<Object>,
+    _sig: PhantomData<fn(A) -> R>,
+}
+
+/// A string interned ahead of time for use as a [`CallHandle`] argument, so passing it
+/// doesn't allocate
+#[derive(Debug, Clone, Copy)]
+pub struct InternedStr(Value);
+
+impl MakeBoltValue for InternedStr {
+    fn make(&self) -> sys::bt_Value {
+        self.0.0
+    }
+}
+
+impl ScalarTypeSignature for InternedStr {
+    fn make_type(ctx: &mut Context) -> Type {
+        ctx.type_string()
+    }
+}
+
+impl<A: IntoBoltArgs, R: FromBoltValue + ScalarTypeSignature> CallHandle<A, R> {
+    /// Bind `func`, failing if it isn't callable or its signature doesn't accept `A` and
+    /// return `R`
+    pub fn new(ctx: &mut Context, func: Value) -> Result<Self, Error> {
+        let callable = func
+            .as_object()
+            .ok_or(Error::bolt("Value is not callable"))?;
+        let signature = signature_of(callable).ok_or(Error::bolt("Value is not callable"))?;
+        check_args::<A>(ctx, signature)?;
+
+        let returns = R::make_type(ctx);
+        match signature.return_type() {
+            Some(declared) if accepts(returns, declared) => {}
+            declared => {
+                return Err(Error::bolt(format!(
+                    "Function returns {} but the handle expects {}",
+                    declared.map_or_else(|| "nothing".to_owned(), |ty| ty.name()),
+                    returns.name()
+                )));
+            }
+        }
+
+        ctx.add_ref(callable);
+        Ok(Self {
+            callable,
+            thread: ctx.make_thread(),
+            args: Vec::with_capacity(A::COUNT as usize),
+            interned: Vec::new(),
+            _sig: PhantomData,
+        })
+    }
+
+    /// Call the function with minimal per-call checking
+    pub fn call_fast(&mut self, ctx: &mut Context, args: A) -> Result<R, Error> {
+        self.args.clear();
+        ctx.gc_pause();
+        args.make_args(ctx, &mut self.args);
+        ctx.gc_unpause();
+
+        let callable = unsafe { Callable::from_raw_unchecked(self.callable.as_ptr() as *mut _) };
+        let (thread, args) = (&self.thread, &mut self.args);
+        let result = ctx.execute_with(thread, callable, || unsafe {
+            sys::bt_execute_with_args(
+                ctx.as_ptr(),
+                thread.as_ptr(),
+                callable.as_ptr(),
+                args.as_mut_ptr(),
+                args.len() as u8,
+            )
+        });
+        if let Err(err) = result {
+            // The thread was abandoned mid-call, so the next call gets a fresh one
+            let fresh = ctx.make_thread();
+            ctx.destroy_thread(std::mem::replace(&mut self.thread, fresh));
+            return Err(err);
+        }
+
+        let returned = unsafe { sys::bt_get_returned(self.thread.as_ptr()) };
+        Ok(R::from(returned)?)
+    }
+}
+
+impl<A, R> CallHandle<A, R> {
+    /// Intern `s` once so it can be passed to every call without allocating
+    pub fn intern(&mut self, ctx: &mut Context, s: &str) -> Result<InternedStr, Error> {
+        let string = ctx.get_or_make_interned(s)?;
+        ctx.add_ref(string.as_object());
+        self.interned.push(string.as_object());
+        Ok(InternedStr(Value::from_raw(string.as_object().make())))
+    }
+
+    /// Free the handle's thread and allow the function and interned strings to be collected
+    pub fn release(self, ctx: &mut Context) {
+        ctx.destroy_thread(self.thread);
+        ctx.remove_ref(self.callable);
+        for string in self.interned {
+            ctx.remove_ref(string);
+        }
+    }
+}
+
+/// The signature type of a function, native function or closure
+pub(crate) fn signature_of(callable: Object) -> Option<Type> {
+    unsafe {
+        match callable.value_type() {
+            ValueType::Function => {
+                Type::from_raw((*(callable.as_ptr() as *mut sys::bt_Fn)).signature)
+            }
+            ValueType::NativeFunction => {
+                Type::from_raw((*(callable.as_ptr() as *mut sys::bt_NativeFn)).type_)
+            }
+            ValueType::Closure => {
+                let func = (*(callable.as_ptr() as *mut sys::bt_Closure)).fn_;
+                if func.is_null() {
+                    None
+                } else {
+                    Type::from_raw((*func).signature)
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Check that a function with `signature` can be called with the arguments in `A`
+pub(crate) fn check_args<A: IntoBoltArgs>(ctx: &mut Context, signature: Type) -> Result<(), Error> {
+    let declared = signature.signature_args().unwrap_or_default();
+    let expected = A::arg_types(ctx);
+    if declared.len() != expected.len() {
+        return Err(Error::bolt(format!(
+            "Function takes {} arguments but {} are passed",
+            declared.len(),
+            expected.len()
+        )));
+    }
+    for (idx, (declared, expected)) in declared.into_iter().zip(expected).enumerate() {
+        if !accepts(declared, expected) {
+            return Err(Error::bolt(format!(
+                "Argument {idx} is declared as {} but {} is passed",
+                declared.name(),
+                expected.name()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a value of type `actual` can be stored where `declared` is expected
+pub(crate) fn accepts(mut declared: Type, actual: Type) -> bool {
+    declared.kind() == TypeKind::Any
+        || declared.type_is_equal(actual)
+        || (declared.kind() == TypeKind::Union && declared.union_has_variant(actual) >= 0)
+}
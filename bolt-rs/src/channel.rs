@@ -0,0 +1,118 @@
+//! Sending values from scripts to the host over a channel
+//!
+//! [`Context::register_channel`] registers a module exporting `send(value): bool`, which
+//! converts its argument and passes it into an [`mpsc`](std::sync::mpsc) channel. The
+//! receiving end can live on any thread, so the host consumes script events at its own
+//! pace:
+//!
+//! ```ignore
+//! let (sender, events) = std::sync::mpsc::sync_channel::<String>(64);
+//! ctx.register_channel("telemetry", sender, Backpressure::Drop)?;
+//! ctx.run("import send from telemetry\nsend(\"level_loaded\")")?;
+//! for event in events.try_iter() { /* ... */ }
+//! ```
+//!
+//! An unbounded [`Sender`] never fills up. A bounded [`SyncSender`] can, and what `send`
+//! does then is chosen with [`Backpressure`]. Once the receiver is gone, `send` fails with
+//! a runtime error, except under [`Backpressure::Drop`] where it returns `false`.
+
+use std::rc::Rc;
+use std::sync::mpsc::{Sender, SyncSender, TrySendError};
+
+use crate::types::{Module, Type};
+use crate::{
+    Context, Error, FromBoltValue, MakeBoltValue, ModuleBuilder, ScalarTypeSignature, Value,
+};
+
+/// What `send` does when a bounded channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backpressure {
+    /// Wait for the receiver to make room, stalling the script
+    #[default]
+    Block,
+    /// Discard the value and return `false`
+    Drop,
+    /// Fail the script with a runtime error
+    Error,
+}
+
+/// The sending half of a channel, bounded or not
+#[derive(Debug)]
+pub enum ChannelSender<T> {
+    Unbounded(Sender<T>),
+    Bounded(SyncSender<T>),
+}
+
+impl<T> From<Sender<T>> for ChannelSender<T> {
+    fn from(sender: Sender<T>) -> Self {
+        ChannelSender::Unbounded(sender)
+    }
+}
+
+impl<T> From<SyncSender<T>> for ChannelSender<T> {
+    fn from(sender: SyncSender<T>) -> Self {
+        ChannelSender::Bounded(sender)
+    }
+}
+
+impl<T> ChannelSender<T> {
+    /// Send `value`, returning whether it was queued or why the script should fail
+    fn send(&self, value: T, backpressure: Backpressure) -> Result<bool, String> {
+        let sent = match (self, backpressure) {
+            (ChannelSender::Unbounded(sender), _) => sender.send(value).is_ok(),
+            (ChannelSender::Bounded(sender), Backpressure::Block) => sender.send(value).is_ok(),
+            (ChannelSender::Bounded(sender), _) => match sender.try_send(value) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) if backpressure == Backpressure::Drop => {
+                    return Ok(false);
+                }
+                Err(TrySendError::Full(_)) => return Err("channel is full".to_owned()),
+                Err(TrySendError::Disconnected(_)) => false,
+            },
+        };
+        if sent || backpressure == Backpressure::Drop {
+            Ok(sent)
+        } else {
+            Err("channel is closed".to_owned())
+        }
+    }
+}
+
+impl Context {
+    /// Register a module called `name` exporting `send`, which passes values of type `T`
+    /// into `sender`, see the [module docs](crate::channel)
+    pub fn register_channel<T>(
+        &mut self,
+        name: &str,
+        sender: impl Into<ChannelSender<T>>,
+        backpressure: Backpressure,
+    ) -> Result<Module, Error>
+    where
+        T: FromBoltValue + ScalarTypeSignature + 'static,
+    {
+        let sender = sender.into();
+        let ret = self.type_bool();
+        let arg = T::make_type(self);
+        self.gc_pause();
+        let native = crate::meta::closure_native(
+            self,
+            "send",
+            ret,
+            &[arg],
+            Rc::new(move |_ctx, thread| {
+                let (value,) = thread.args::<(T,)>().map_err(|err| err.to_string())?;
+                let sent = sender.send(value, backpressure)?;
+                thread.return_val(&sent);
+                Ok(())
+            }),
+        );
+        let module = native.and_then(|native| {
+            let ty = unsafe { Type::from_raw_unchecked((*native.as_ptr()).type_) };
+            ModuleBuilder::new(name)
+                .value("send", ty, Value::from_raw(native.as_object().make()))
+                .finish(self)
+        });
+        self.gc_unpause();
+        module
+    }
+}
@@ -0,0 +1,130 @@
+//! Script-registered commands for host CLIs and consoles
+//!
+//! Opening the `commands` module exposes `register_command(name, fn, help)` to
+//! scripts. The host can then list what was registered with [`Context::commands`]
+//! and dispatch by name with [`Context::run_command`]; the callback receives the
+//! command arguments as a single `[string]`.
+
+use bolt_sys::sys;
+
+use crate::types::Module;
+use crate::{
+    Context, ContextRef, Error, MakeBoltValue, MakeBoltValueWithContext, ScalarTypeSignature,
+    Thread, Value, ValueType,
+};
+
+/// A command registered by a script
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: String,
+    pub help: String,
+    pub func: Value,
+}
+
+unsafe extern "C" fn register_command(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    unsafe { crate::unwind::guard(ctx, thr, "register_command", || register(ctx, thr)) };
+}
+
+fn register(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+    let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+
+    let Ok((name, func, help)) = thread.args::<(String, Value, String)>() else {
+        unsafe {
+            sys::bt_runtime_error(
+                thr,
+                c"register_command: bad arguments".as_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        return;
+    };
+
+    let Some(callable) = func.as_object().filter(|obj| {
+        matches!(
+            obj.value_type(),
+            ValueType::Function | ValueType::NativeFunction | ValueType::Closure
+        )
+    }) else {
+        unsafe {
+            sys::bt_runtime_error(
+                thr,
+                c"register_command: command must be a function".as_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        return;
+    };
+
+    // Commands outlive the script that registered them, so keep the callback alive
+    ctx.add_ref(callable);
+
+    let state = crate::state::get(ctx.as_ptr());
+    let mut commands = state.commands.borrow_mut();
+    let replaced = match commands.iter_mut().find(|cmd| cmd.name == name) {
+        Some(existing) => {
+            let old = std::mem::replace(&mut existing.func, func);
+            existing.help = help;
+            old.as_object()
+        }
+        None => {
+            commands.push(Command { name, help, func });
+            None
+        }
+    };
+    drop(commands);
+
+    if let Some(old) = replaced {
+        ctx.remove_ref(old);
+    }
+}
+
+impl Context {
+    /// Register the `commands` module so scripts can call `register_command(name, fn, help)`
+    pub fn open_commands(&mut self) -> Result<Module, Error> {
+        let module = self.make_module();
+        let string = String::make_type(self);
+        let any = self.type_any();
+        let null = self.type_null();
+
+        self.module_export_native(
+            module,
+            c"register_command",
+            Some(register_command),
+            null,
+            &[string, any, string],
+        )?;
+
+        let name = "commands".make_with_context(self);
+        self.register_module(Value::from_raw(name), module);
+        Ok(module)
+    }
+
+    /// All commands registered by scripts so far, in registration order
+    pub fn commands(&mut self) -> Vec<Command> {
+        crate::state::get(self.as_ptr()).commands.borrow().clone()
+    }
+
+    /// Invoke the command registered under `name`, passing `args` as a `[string]`
+    pub fn run_command(&mut self, name: &str, args: &[&str]) -> Result<Value, Error> {
+        let func = crate::state::get(self.as_ptr())
+            .commands
+            .borrow()
+            .iter()
+            .find(|cmd| cmd.name == name)
+            .map(|cmd| cmd.func)
+            .ok_or_else(|| Error::bolt(format!("Unknown command '{name}'")))?;
+
+        let arr = self.make_array(args.len() as u32);
+        self.push_root(arr.as_object());
+        for arg in args {
+            // Pushing roots each fresh string until the array holds it
+            arr.push(self, *arg);
+        }
+
+        // The arguments stay rooted until the call has returned
+        let result = self.call(func, &[Value::from_raw(arr.make())]);
+        self.pop_root();
+        result
+    }
+}
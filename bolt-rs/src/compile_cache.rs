@@ -0,0 +1,111 @@
+//! Opt-in reuse of compiled modules
+//!
+//! Mod packs often ship the same script under several names, and every startup compiles
+//! and typechecks each copy again. With the cache enabled through
+//! [`Context::set_compile_cache`], [`Context::compile_module`] looks the source up and hands
+//! back the module already compiled from it instead of compiling it again. Entries keep a
+//! copy of their source, so only identical sources share a module.
+//!
+//! A cache hit is the *same* module object as the original, keeping the name it was first
+//! compiled under, and executing it again reruns its top level over the same exports.
+//! Leave the cache off for scripts that keep per-instance state at module scope.
+
+use std::collections::HashMap;
+
+use crate::Context;
+use crate::types::Module;
+
+/// How well the compile cache has been doing, see [`Context::compile_cache_stats`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CompileCacheStats {
+    /// Compilations answered from the cache
+    pub hits: u64,
+    /// Compilations which had to run the compiler
+    pub misses: u64,
+    /// Modules currently held by the cache
+    pub entries: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct CompileCache {
+    /// Referenced modules, keyed by their source
+    modules: HashMap<Box<[u8]>, Module>,
+    hits: u64,
+    misses: u64,
+}
+
+/// The module cached for `source`, counting the lookup, or whether the compiled module
+/// should be stored
+pub(crate) fn lookup(ctx: &Context, source: &[u8]) -> Result<Module, bool> {
+    let state = crate::state::get(ctx.as_ptr());
+    let mut cache = state.compile_cache.borrow_mut();
+    let Some(cache) = cache.as_mut() else {
+        return Err(false);
+    };
+
+    match cache.modules.get(source) {
+        Some(module) => {
+            cache.hits += 1;
+            Ok(*module)
+        }
+        None => {
+            cache.misses += 1;
+            Err(true)
+        }
+    }
+}
+
+/// Keep `module` alive and hand it out for `source`
+pub(crate) fn store(ctx: &mut Context, source: &[u8], module: Module) {
+    let state = crate::state::get(ctx.as_ptr());
+    if let Some(cache) = state.compile_cache.borrow_mut().as_mut() {
+        ctx.add_ref(module.as_object());
+        cache.modules.insert(source.into(), module);
+    }
+}
+
+impl Context {
+    /// Enable or disable the compile cache, see the [module docs](self).
+    /// Disabling it releases every cached module and resets the stats.
+    pub fn set_compile_cache(&mut self, enabled: bool) {
+        let state = crate::state::get(self.as_ptr());
+        if enabled {
+            state
+                .compile_cache
+                .borrow_mut()
+                .get_or_insert_with(CompileCache::default);
+            return;
+        }
+
+        let cache = state.compile_cache.borrow_mut().take();
+        for module in cache
+            .into_iter()
+            .flat_map(|cache| cache.modules.into_values())
+        {
+            self.remove_ref(module.as_object());
+        }
+    }
+
+    /// Hit and miss counts since the cache was enabled, or `None` if it isn't
+    pub fn compile_cache_stats(&self) -> Option<CompileCacheStats> {
+        let state = crate::state::get(self.as_ptr());
+        let cache = state.compile_cache.borrow();
+        cache.as_ref().map(|cache| CompileCacheStats {
+            hits: cache.hits,
+            misses: cache.misses,
+            entries: cache.modules.len(),
+        })
+    }
+
+    /// Release every cached module, keeping the cache enabled and its stats
+    pub fn clear_compile_cache(&mut self) {
+        let state = crate::state::get(self.as_ptr());
+        let modules = match state.compile_cache.borrow_mut().as_mut() {
+            Some(cache) => std::mem::take(&mut cache.modules),
+            None => return,
+        };
+        for module in modules.into_values() {
+            self.remove_ref(module.as_object());
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Static host data exposed to scripts as a module
+//!
+//! [`Context::register_data_module`] turns a map into a module with one export per entry,
+//! each typed from the value it was converted to. Module exports can't be reassigned from
+//! script, so the data is read-only as far as scripts are concerned.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+
+use crate::types::{Array, Module, Type};
+use crate::{
+    Context, Error, FromBoltValue, MakeBoltValueWithContext, ModuleError, Value, ValueType,
+};
+
+impl Context {
+    /// Register `data` as the module `name`, exporting every entry under its key
+    ///
+    /// Entries are exported in key order with collection paused for the whole build, so large
+    /// tables (string tables, item databases) don't trigger a collection per entry.
+    pub fn register_data_module<K, V, S>(
+        &mut self,
+        name: &str,
+        data: &HashMap<K, V, S>,
+    ) -> Result<Module, Error>
+    where
+        K: AsRef<str>,
+        V: MakeBoltValueWithContext,
+        S: BuildHasher,
+    {
+        let mut entries: Vec<(&str, &V)> = data.iter().map(|(k, v)| (k.as_ref(), v)).collect();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(ModuleError::DuplicateExport(pair[0].0.to_owned()).into());
+        }
+
+        for (key, _) in &entries {
+            crate::names::validate(key)?;
+        }
+
+        self.gc_pause();
+        let module = self.make_module();
+        for (key, value) in entries {
+            let value = Value::from_raw(value.make_with_context(self));
+            let ty = infer_type(self, value);
+            let key = Value::from_raw(key.make_with_context(self));
+            self.module_export(module, ty, key, value)
+                .expect("keys were validated above");
+        }
+        let name = Value::from_raw(name.make_with_context(self));
+        self.register_module(name, module);
+        self.gc_unpause();
+
+        Ok(module)
+    }
+}
+
+/// The narrowest type that describes `value`, falling back to `any`
+pub(crate) fn infer_type(ctx: &mut Context, value: Value) -> Type {
+    match ValueType::from_value(value.0) {
+        ValueType::Null => ctx.type_null(),
+        ValueType::Bool => ctx.type_bool(),
+        ValueType::Number => ctx.type_number(),
+        ValueType::String => ctx.type_string(),
+        ValueType::Table => ctx.type_table(),
+        ValueType::Array => {
+            let array = <Array as FromBoltValue>::from(value.0).ok();
+            let items: Vec<Value> = array.map_or_else(Vec::new, |array| array.iter(ctx).collect());
+            let kind = |item: &Value| std::mem::discriminant(&ValueType::from_value(item.0));
+            let inner = match items.split_first() {
+                Some((first, rest)) if rest.iter().all(|item| kind(item) == kind(first)) => {
+                    infer_type(ctx, *first)
+                }
+                _ => ctx.type_any(),
+            };
+            ctx.make_array_type(inner)
+        }
+        _ => ctx.type_any(),
+    }
+}
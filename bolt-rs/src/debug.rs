@@ -0,0 +1,77 @@
+//! Readable rendering of script values for logging
+
+use std::fmt::Write;
+
+use crate::types::{Array, BoltString, Table};
+use crate::{Context, Value, ValueType};
+
+const INDENT: &str = "  ";
+
+impl Context {
+    /// Render `value` for debugging, expanding arrays and tables up to `max_depth` levels
+    /// deep. Anything nested further is elided as `[...]` or `{...}`.
+    pub fn debug_value(&mut self, value: Value, max_depth: usize) -> String {
+        let mut out = String::new();
+        self.write_debug(&mut out, value, 0, max_depth);
+        out
+    }
+
+    fn write_debug(&mut self, out: &mut String, value: Value, depth: usize, max_depth: usize) {
+        let Some(obj) = value.as_object() else {
+            out.push_str(&value.display(self));
+            return;
+        };
+
+        match obj.value_type() {
+            ValueType::String => {
+                let string = unsafe { BoltString::from_raw_unchecked(obj.as_ptr() as *mut _) };
+                let _ = write!(out, "{:?}", string.to_string_lossy());
+            }
+            ValueType::Array => {
+                let array = unsafe { Array::from_raw_unchecked(obj.as_ptr() as *mut _) };
+                if array.is_empty() {
+                    out.push_str("[]");
+                } else if depth >= max_depth {
+                    out.push_str("[...]");
+                } else {
+                    out.push_str("[\n");
+                    let items: Vec<Value> = array.iter(self).collect();
+                    for item in items {
+                        push_indent(out, depth + 1);
+                        self.write_debug(out, item, depth + 1, max_depth);
+                        out.push_str(",\n");
+                    }
+                    push_indent(out, depth);
+                    out.push(']');
+                }
+            }
+            ValueType::Table => {
+                let table = unsafe { Table::from_raw_unchecked(obj.as_ptr() as *mut _) };
+                if table.is_empty() {
+                    out.push_str("{}");
+                } else if depth >= max_depth {
+                    out.push_str("{...}");
+                } else {
+                    out.push_str("{\n");
+                    let pairs: Vec<(Value, Value)> = table.iter(self).collect();
+                    for (key, item) in pairs {
+                        push_indent(out, depth + 1);
+                        out.push_str(&key.display(self));
+                        out.push_str(": ");
+                        self.write_debug(out, item, depth + 1, max_depth);
+                        out.push_str(",\n");
+                    }
+                    push_indent(out, depth);
+                    out.push('}');
+                }
+            }
+            _ => out.push_str(&value.display(self)),
+        }
+    }
+}
+
+fn push_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
@@ -0,0 +1,139 @@
+//! String deduplication for large data graphs
+//!
+//! Tables loaded from scripts or built from host data often hold many separate copies of
+//! the same string, e.g. a `kind` field repeated on every row. [`Context::dedup_strings`]
+//! points every such copy at one interned string so the duplicates can be collected.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+
+use bolt_sys::sys;
+
+use crate::types::{Array, BoltString, Table};
+use crate::{Context, MakeBoltValue, Value, ValueType};
+
+/// What a [`Context::dedup_strings`] pass changed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DedupReport {
+    /// How many array items and table values now point at a shared string
+    pub replaced: usize,
+    /// Approximate memory freed once the garbage collector reclaims the duplicates
+    pub bytes_saved: usize,
+}
+
+enum Slot {
+    Item(Array, usize),
+    Field(Table, Value),
+}
+
+impl Context {
+    /// Walk every array and table reachable from `value`, replacing duplicated strings in
+    /// array items and table values with a single interned copy. Table keys are left as
+    /// they are.
+    pub fn dedup_strings(&mut self, value: Value) -> DedupReport {
+        let mut slots = Vec::new();
+        collect_strings(value, &mut HashSet::new(), &mut slots);
+
+        let mut groups: HashMap<&[u8], Vec<(&Slot, BoltString)>> = HashMap::new();
+        for (slot, string) in &slots {
+            groups
+                .entry(string.as_bytes())
+                .or_default()
+                .push((slot, *string));
+        }
+
+        let mut report = DedupReport::default();
+        self.gc_pause();
+        for (bytes, group) in groups {
+            let distinct: HashSet<usize> = group.iter().map(|(_, s)| s.as_ptr() as usize).collect();
+            if distinct.len() < 2 {
+                continue;
+            }
+            let Ok(c_str) = CString::new(bytes) else {
+                continue;
+            };
+            let Ok(interned) = self.get_or_make_interned(c_str.as_c_str()) else {
+                continue;
+            };
+
+            let interned_ptr = interned.as_ptr() as usize;
+            let interned_value = Value::from_raw(interned.as_object().make());
+            for (slot, string) in &group {
+                if string.as_ptr() as usize == interned_ptr {
+                    continue;
+                }
+                match slot {
+                    Slot::Item(array, idx) => {
+                        self.array_set(*array, *idx as u64, interned_value);
+                    }
+                    Slot::Field(table, key) => {
+                        self.table_set(*table, *key, interned_value);
+                    }
+                }
+                report.replaced += 1;
+            }
+
+            // Every copy but one is freed, whether or not the interned copy is new
+            let size = std::mem::size_of::<sys::bt_String>() + bytes.len() + 1;
+            report.bytes_saved += (distinct.len() - 1) * size;
+        }
+        self.gc_unpause();
+
+        report
+    }
+}
+
+fn collect_strings(value: Value, visited: &mut HashSet<usize>, out: &mut Vec<(Slot, BoltString)>) {
+    let Some(obj) = value.as_object() else {
+        return;
+    };
+    if !visited.insert(obj.as_ptr() as usize) {
+        return;
+    }
+
+    match obj.value_type() {
+        ValueType::Array => {
+            let array = unsafe { Array::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            let items = unsafe {
+                let raw = array.as_ptr();
+                if (*raw).items.is_null() {
+                    &[][..]
+                } else {
+                    std::slice::from_raw_parts((*raw).items, array.len())
+                }
+            };
+            for (idx, item) in items.iter().enumerate() {
+                visit(Value::from_raw(*item), Slot::Item(array, idx), visited, out);
+            }
+        }
+        ValueType::Table => {
+            let table = unsafe { Table::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            for pair in table.raw_pairs() {
+                let key = Value::from_raw(pair.key);
+                visit(
+                    Value::from_raw(pair.value),
+                    Slot::Field(table, key),
+                    visited,
+                    out,
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+fn visit(
+    value: Value,
+    slot: Slot,
+    visited: &mut HashSet<usize>,
+    out: &mut Vec<(Slot, BoltString)>,
+) {
+    match value.as_object() {
+        Some(obj) if matches!(obj.value_type(), ValueType::String) => {
+            let string = unsafe { BoltString::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            out.push((slot, string));
+        }
+        Some(_) => collect_strings(value, visited, out),
+        None => {}
+    }
+}
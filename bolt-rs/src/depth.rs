@@ -0,0 +1,101 @@
+//! Limits on how deeply calls can nest
+//!
+//! Script calls run on the thread's own call stack, but every native that calls back into
+//! a script through [`Context::call`] nests another execution on the native stack, and
+//! deep enough recursion through the host overflows it and takes the process down.
+//! [`Context::set_max_call_depth`] turns that into an error: the depth of a call is the
+//! number of frames on the running thread plus the number of executions it's nested in,
+//! checked whenever an execution starts and on every native call, and going past the limit
+//! fails the outermost run or call with [`Error::CallDepth`]. Like the native call budget,
+//! the native check happens in the trampoline every native goes through, see
+//! [`crate::stats`].
+//!
+//! Recursion that never leaves the script, e.g. `fn f() { f() }`, doesn't reach either check.
+//! It's bounded by the thread's fixed call stack instead ([`STACK_FRAMES`] frames), and a
+//! run that fails because that stack filled up also reports [`Error::CallDepth`], with the
+//! smaller of the two limits.
+
+use bolt_sys::sys;
+
+use crate::state::ContextState;
+use crate::{Context, Error};
+
+/// Check the depth of a native call on `thr`, raising a runtime error on it if the limit
+/// is exceeded. Returns whether the native may run.
+pub(crate) fn check_native(state: &ContextState, thr: *mut sys::bt_Thread) -> bool {
+    let Some(limit) = state.max_call_depth.get() else {
+        return true;
+    };
+    let frames = unsafe { (*thr).depth } as usize;
+    if frames + state.execution_depth.get() as usize <= limit as usize {
+        return true;
+    }
+    state.call_depth_exceeded.set(true);
+    unsafe {
+        sys::bt_runtime_error(
+            thr,
+            c"maximum call depth exceeded".as_ptr(),
+            std::ptr::null_mut(),
+        )
+    };
+    false
+}
+
+/// How many frames bolt's call stack holds, the length of `bt_Thread::callstack`
+pub(crate) const STACK_FRAMES: u32 = frames(|thread| &thread.callstack) as u32;
+
+/// The length of the array `field` projects out of a thread, without needing a thread
+const fn frames<T, const N: usize>(_field: fn(&sys::bt_Thread) -> &[T; N]) -> usize {
+    N
+}
+
+/// Note whether the runtime error being raised on `thread` comes from bolt's call stack
+/// filling up, called before the stack unwinds
+pub(crate) fn check_overflow(state: &ContextState, thread: *mut sys::bt_Thread) {
+    let frames = unsafe { (*thread).depth } as usize;
+    if frames + 1 >= STACK_FRAMES as usize {
+        state.call_depth_exceeded.set(true);
+    }
+}
+
+/// The limit to report for a failure caused by nesting calls too deeply
+pub(crate) fn reported_limit(state: &ContextState) -> u32 {
+    state
+        .max_call_depth
+        .get()
+        .map_or(STACK_FRAMES, |limit| limit.min(STACK_FRAMES))
+}
+
+/// Check the depth of an execution about to start
+pub(crate) fn check_execution(state: &ContextState) -> Result<(), Error> {
+    let depth = state.execution_depth.get();
+    if depth <= 1 {
+        // A native may have handled a nested failure, so don't hold it against this run
+        state.call_depth_exceeded.set(false);
+    }
+    match state.max_call_depth.get() {
+        Some(limit) if depth > limit => {
+            state.call_depth_exceeded.set(depth > 1);
+            Err(Error::CallDepth { limit })
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Context {
+    /// Fail calls nested deeper than `depth`, see the [module docs](self)
+    pub fn set_max_call_depth(&mut self, depth: u32) {
+        crate::state::get(self.as_ptr())
+            .max_call_depth
+            .set(Some(depth));
+    }
+
+    pub fn max_call_depth(&self) -> Option<u32> {
+        crate::state::get(self.as_ptr()).max_call_depth.get()
+    }
+
+    /// Remove the call depth limit
+    pub fn clear_max_call_depth(&mut self) {
+        crate::state::get(self.as_ptr()).max_call_depth.set(None);
+    }
+}
@@ -0,0 +1,275 @@
+//! Structured errors reported by the bolt parser, compiler and runtime
+//!
+//! The `on_error` handler isn't given a context, so reports are collected per-thread
+//! while a [`capture`] is active, and printed to stderr otherwise, or emitted as `tracing`
+//! events with the `tracing` feature, at a level depending on their kind. When a run,
+//! compile or call fails, [`failure`] turns what was collected into the matching [`Error`] variant.
+
+use bolt_sys::sys;
+use std::cell::RefCell;
+use std::ffi::CStr;
+
+use crate::Error;
+use crate::state::ContextState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    Parse,
+    Compile,
+    Runtime,
+    Unknown,
+}
+
+impl DiagnosticKind {
+    pub(crate) fn from_raw(error_type: sys::bt_ErrorType) -> Self {
+        match error_type {
+            sys::bt_ErrorType::BT_ERROR_PARSE => DiagnosticKind::Parse,
+            sys::bt_ErrorType::BT_ERROR_COMPILE => DiagnosticKind::Compile,
+            sys::bt_ErrorType::BT_ERROR_RUNTIME => DiagnosticKind::Runtime,
+            _ => DiagnosticKind::Unknown,
+        }
+    }
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DiagnosticKind::Parse => "Parse Error",
+            DiagnosticKind::Compile => "Compile Error",
+            DiagnosticKind::Runtime => "Runtime Error",
+            DiagnosticKind::Unknown => "Unknown Error",
+        })
+    }
+}
+
+/// A byte range in a script's source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A single error reported through the context's `on_error` handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub module: String,
+    pub message: String,
+    pub line: u16,
+    pub col: u16,
+    /// Where in the source the error points, filled in by `compile_module` and `typecheck`
+    /// for errors in the source they were given. See [`Diagnostic::span_in`] for the rest.
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    /// The span of the token at this diagnostic's line and column in `source`, or `None` if
+    /// the position is outside of it
+    pub fn span_in(&self, source: &str) -> Option<Span> {
+        let line_start = match self.line {
+            0 => return None,
+            1 => 0,
+            line => source
+                .match_indices('\n')
+                .nth(line as usize - 2)
+                .map(|(idx, _)| idx + 1)?,
+        };
+        let line_text = source[line_start..].lines().next().unwrap_or("");
+        let (offset, first) = line_text
+            .char_indices()
+            .nth(self.col.saturating_sub(1) as usize)?;
+
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let len = if is_word(first) {
+            line_text[offset..]
+                .find(|c: char| !is_word(c))
+                .unwrap_or(line_text.len() - offset)
+        } else {
+            first.len_utf8()
+        };
+        let start = line_start + offset;
+        Some(Span {
+            start,
+            end: start + len,
+        })
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} in {}: {} (line {}, col {})",
+            self.kind, self.module, self.message, self.line, self.col
+        )
+    }
+}
+
+/// Diagnostics collected for a context while it runs
+struct Capture {
+    ctx: *mut sys::bt_Context,
+    diagnostics: Vec<Diagnostic>,
+}
+
+thread_local! {
+    static CAPTURES: RefCell<Vec<Capture>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Fill in the spans of the diagnostics reported against `module`, whose source is `source`
+pub(crate) fn locate(diagnostics: &mut [Diagnostic], module: &CStr, source: &CStr) {
+    let (Ok(module), Ok(source)) = (module.to_str(), source.to_str()) else {
+        return;
+    };
+    for diagnostic in diagnostics {
+        if diagnostic.module == module {
+            diagnostic.span = diagnostic.span_in(source);
+        }
+    }
+}
+
+/// Deliver a diagnostic to the innermost active capture, or print it if there is none
+pub(crate) fn report(diagnostic: Diagnostic) {
+    let uncaptured = CAPTURES.with(|captures| match captures.borrow_mut().last_mut() {
+        Some(capture) => {
+            capture.diagnostics.push(diagnostic);
+            None
+        }
+        None => Some(diagnostic),
+    });
+
+    if let Some(diagnostic) = uncaptured {
+        emit(&diagnostic);
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+fn emit(diagnostic: &Diagnostic) {
+    eprintln!("{diagnostic}");
+}
+
+/// Parse and compile errors are emitted as warnings, since the host usually handles a bad
+/// script by reporting it and carrying on, runtime and unknown errors as errors
+#[cfg(feature = "tracing")]
+fn emit(diagnostic: &Diagnostic) {
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!(
+                target: "bolt",
+                $level,
+                kind = %diagnostic.kind,
+                module = %diagnostic.module,
+                line = diagnostic.line,
+                col = diagnostic.col,
+                "{}",
+                diagnostic.message
+            )
+        };
+    }
+    match diagnostic.kind {
+        DiagnosticKind::Parse | DiagnosticKind::Compile => event!(tracing::Level::WARN),
+        DiagnosticKind::Runtime | DiagnosticKind::Unknown => event!(tracing::Level::ERROR),
+    }
+}
+
+/// Remember the script stack of the context being captured for, called as a runtime error
+/// at `line` is reported and before the stack unwinds
+pub(crate) fn record_traceback(line: u16) {
+    let Some(ctx) = CAPTURES.with(|captures| captures.borrow().last().map(|capture| capture.ctx))
+    else {
+        return;
+    };
+    let state = crate::state::get(ctx);
+    let thread = unsafe { (*ctx).current_thread };
+    if !thread.is_null() {
+        crate::depth::check_overflow(&state, thread);
+    }
+    let traceback = crate::traceback::walk(ctx, line);
+    *state.traceback.borrow_mut() = Some(traceback);
+}
+
+/// Run `f` against `ctx`, collecting every diagnostic reported while it runs
+pub(crate) fn capture<R>(ctx: *mut sys::bt_Context, f: impl FnOnce() -> R) -> (R, Vec<Diagnostic>) {
+    CAPTURES.with(|captures| {
+        captures.borrow_mut().push(Capture {
+            ctx,
+            diagnostics: Vec::new(),
+        })
+    });
+    let out = f();
+    let diagnostics = CAPTURES
+        .with(|captures| captures.borrow_mut().pop())
+        .map(|capture| capture.diagnostics)
+        .unwrap_or_default();
+    (out, diagnostics)
+}
+
+/// Work out why an execution or compilation failed from the context's state and the
+/// diagnostics captured while it ran. Diagnostics that don't end up in the error are
+/// reported as usual.
+pub(crate) fn failure(state: &ContextState, diagnostics: Vec<Diagnostic>) -> Error {
+    let traceback = state.traceback.borrow_mut().take();
+    let host_error = if state.interrupt.take() {
+        Some(Error::Interrupted)
+    } else if state.native_budget_spent.take() {
+        Some(Error::NativeCallBudget)
+    } else if state.call_depth_exceeded.get() {
+        // Left set so every execution the limit unwinds through fails the same way, and
+        // cleared once it reaches the outermost one
+        if state.execution_depth.get() <= 1 {
+            state.call_depth_exceeded.set(false);
+        }
+        Some(Error::CallDepth {
+            limit: crate::depth::reported_limit(state),
+        })
+    } else if crate::memory::take_exceeded(state) {
+        Some(Error::MemoryLimit)
+    } else if let Some(message) = state.host_panic.borrow_mut().take() {
+        Some(Error::HostPanic { message })
+    } else if let Some(thrown) = state.thrown.borrow_mut().take() {
+        Some(Error::Script(thrown))
+    } else if let Some(chain) = state.import_cycle.borrow_mut().take() {
+        Some(Error::ImportCycle { chain })
+    } else {
+        state.prelude_error.borrow_mut().take()
+    };
+    if let Some(error) = host_error {
+        diagnostics.into_iter().for_each(report);
+        return error;
+    }
+
+    let has = |kind| diagnostics.iter().any(|diagnostic| diagnostic.kind == kind);
+    if has(DiagnosticKind::Parse) {
+        return Error::Parse(keep(diagnostics, DiagnosticKind::Parse));
+    }
+    if has(DiagnosticKind::Compile) {
+        return Error::Compile(keep(diagnostics, DiagnosticKind::Compile));
+    }
+
+    // A runtime error aborts execution, so the last one reported is the one that failed it
+    let mut diagnostics = diagnostics;
+    let Some(idx) = diagnostics
+        .iter()
+        .rposition(|diagnostic| diagnostic.kind == DiagnosticKind::Runtime)
+    else {
+        diagnostics.into_iter().for_each(report);
+        return Error::bolt("Execution failed");
+    };
+    let runtime = diagnostics.remove(idx);
+    diagnostics.into_iter().for_each(report);
+    Error::Runtime {
+        module: runtime.module,
+        message: runtime.message,
+        line: runtime.line,
+        col: runtime.col,
+        traceback: traceback.unwrap_or_default(),
+    }
+}
+
+/// The diagnostics of `kind`, reporting the rest
+fn keep(diagnostics: Vec<Diagnostic>, kind: DiagnosticKind) -> Vec<Diagnostic> {
+    let (kept, other): (Vec<_>, Vec<_>) = diagnostics
+        .into_iter()
+        .partition(|diagnostic| diagnostic.kind == kind);
+    other.into_iter().for_each(report);
+    kept
+}
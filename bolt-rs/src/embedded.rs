@@ -0,0 +1,55 @@
+//! Script directories compiled into the host binary
+//!
+//! [`Context::add_module_tree`] takes a manifest of `(path, source)` pairs, such as one
+//! generated by a build script with `include_str!`, and registers every `.bolt` file with
+//! [`Context::add_module_source`] under its path relative to the tree root, minus the
+//! extension. Imports then resolve exactly as they would with the tree on disk behind a
+//! `root/%s.bolt` module path: `mods/util.bolt` is `import mods/util`, and a directory's
+//! `module.bolt` is found through a `%s/module.bolt` spec.
+//!
+//! With the `include-dir` feature, [`Context::add_module_dir`] does the same for an
+//! `include_dir::Dir`.
+
+use crate::{Context, Error};
+
+/// The import name of the module at `path` within a tree, or `None` if it isn't a script
+fn import_name(path: &str) -> Option<String> {
+    let path = path.replace('\\', "/");
+    let path = path.trim_start_matches("./").trim_start_matches('/');
+    let name = path.strip_suffix(".bolt")?;
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+impl Context {
+    /// Register every `.bolt` file in `manifest` as an importable module, returning how many
+    /// were added. Other entries are skipped so a whole asset tree can be passed in.
+    pub fn add_module_tree(&mut self, manifest: &[(&str, &str)]) -> Result<usize, Error> {
+        let mut added = 0;
+        for (path, source) in manifest {
+            if let Some(name) = import_name(path) {
+                self.add_module_source(&name, source)?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Like [`Context::add_module_tree`], for a directory embedded with `include_dir!`.
+    /// Scripts which aren't valid UTF-8 are skipped.
+    #[cfg(feature = "include-dir")]
+    pub fn add_module_dir(&mut self, dir: &include_dir::Dir<'_>) -> Result<usize, Error> {
+        let mut manifest = Vec::new();
+        let mut pending = vec![dir];
+        while let Some(dir) = pending.pop() {
+            pending.extend(dir.dirs());
+            for file in dir.files() {
+                let (Some(path), Some(source)) = (file.path().to_str(), file.contents_utf8())
+                else {
+                    continue;
+                };
+                manifest.push((path, source));
+            }
+        }
+        self.add_module_tree(&manifest)
+    }
+}
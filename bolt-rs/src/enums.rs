@@ -0,0 +1,60 @@
+//! Rust enums as bolt enum types
+//!
+//! Implemented by `#[derive(BoltEnum)]` on fieldless enums, which also provides the
+//! value conversions. Options are exposed to scripts by variant name and carried as
+//! bolt enum values holding the Rust discriminant.
+
+use crate::types::{Table, Type};
+use crate::{Context, EnumValue, Error, MakeBoltValue, MakeBoltValueWithContext, TypeKind, Value};
+
+pub trait BoltEnum: Sized + Copy {
+    /// The script-visible name of the enum type
+    const NAME: &'static str;
+    /// Every option name paired with its discriminant
+    const OPTIONS: &'static [(&'static str, u32)];
+
+    fn from_discriminant(value: u32) -> Option<Self>;
+    fn discriminant(&self) -> u32;
+}
+
+impl Context {
+    /// Find the bolt type for `T`, building and registering it under `T::NAME` the first time
+    ///
+    /// Fails with [`Error::EnumMismatch`] if a type is already registered under `T::NAME`
+    /// that isn't an enum with exactly the options of `T`.
+    pub fn register_enum<T: BoltEnum>(&mut self) -> Result<Type, Error> {
+        let name = Value::from_raw(T::NAME.make_with_context(self));
+        if let Some(existing) = self.find_type(name) {
+            if !has_options::<T>(existing) {
+                return Err(Error::EnumMismatch {
+                    name: T::NAME.to_owned(),
+                });
+            }
+            return Ok(existing);
+        }
+
+        let enum_type = self.make_enum_type(T::NAME, true)?;
+        for (option, discriminant) in T::OPTIONS {
+            let value = Value::from_raw(EnumValue(*discriminant).make());
+            self.enum_push_option(enum_type, *option, value)?;
+        }
+
+        let name = Value::from_raw(T::NAME.make_with_context(self));
+        self.register_type(name, enum_type)?;
+        Ok(enum_type)
+    }
+}
+
+/// Whether `ty` is an enum with the same options and discriminants as `T`
+fn has_options<T: BoltEnum>(ty: Type) -> bool {
+    if ty.kind() != TypeKind::Enum {
+        return false;
+    }
+    let Some(options) = Table::from_raw(unsafe { (*ty.as_ptr()).as_.enum_.options }) else {
+        return false;
+    };
+    options.len() == T::OPTIONS.len()
+        && T::OPTIONS.iter().all(|(option, discriminant)| {
+            options.get_str(option).and_then(|value| value.as_enum()) == Some(*discriminant)
+        })
+}
@@ -2,6 +2,9 @@ use std::ffi::NulError;
 
 use thiserror::Error;
 
+use crate::Diagnostic;
+use crate::errors::ScriptThrow;
+use crate::traceback::Traceback;
 use crate::types::value::ValueType;
 
 #[derive(Error, Debug)]
@@ -12,34 +15,153 @@ pub enum Error {
     StringConversion(#[from] NulError),
     #[error("{msg}")]
     BoltError { msg: String },
+    #[error("serde error: {msg}")]
+    Serde { msg: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("invalid replay file: {msg}")]
+    Replay { msg: String },
+    #[error("import cycle: {}", chain.join(" -> "))]
+    ImportCycle { chain: Vec<String> },
+    #[error("{}", join_diagnostics(.0))]
+    Parse(Vec<Diagnostic>),
+    /// The source parsed, but failed to typecheck or compile
+    #[error("{}", join_diagnostics(.0))]
+    Compile(Vec<Diagnostic>),
+    /// A runtime error raised while executing, at the script location it was raised from
+    #[error(
+        "Runtime Error in {module}: {message} (line {line}, col {col}){}",
+        render_traceback(traceback)
+    )]
+    Runtime {
+        module: String,
+        message: String,
+        line: u16,
+        col: u16,
+        traceback: Traceback,
+    },
+    /// A value a script threw or a native raised, see [`crate::errors`]
+    #[error("{0}")]
+    Script(ScriptThrow),
+    /// Rust code called from a script panicked, see [`crate::unwind`]
+    #[error("a native function panicked: {message}")]
+    HostPanic { message: String },
+    #[error("Execution was interrupted")]
+    Interrupted,
+    /// Calls nested deeper than the context allows, see
+    /// [`Context::set_max_call_depth`](crate::Context::set_max_call_depth)
+    #[error("maximum call depth of {limit} exceeded")]
+    CallDepth { limit: u32 },
+    /// The context spent its native call budget, see
+    /// [`Context::set_native_call_budget`](crate::Context::set_native_call_budget)
+    #[error("native call budget exhausted")]
+    NativeCallBudget,
+    /// The script went past the memory limit, see
+    /// [`Context::set_memory_limit`](crate::Context::set_memory_limit)
+    #[error("memory limit exceeded")]
+    MemoryLimit,
+    #[error("index {idx} is out of bounds for an array of length {len}")]
+    IndexOutOfBounds { idx: usize, len: usize },
+    #[error("key {key} not found in table")]
+    KeyNotFound { key: String },
+    #[error("failed to open context: {0}")]
+    Open(OpenError),
+    #[error("userdata does not hold a {expected}")]
+    UserdataType { expected: &'static str },
+    #[error("{ty} userdata is already borrowed")]
+    AlreadyBorrowed { ty: &'static str },
+    #[error("type {name:?} is already registered, but not as an enum with the same options")]
+    EnumMismatch { name: String },
+    #[error("{ty} userdata has no serialization hooks")]
+    NotSerializable { ty: String },
+    #[error(transparent)]
+    Arg(#[from] ArgError),
+    #[error(transparent)]
+    Module(#[from] ModuleError),
+}
+
+fn render_traceback(traceback: &Traceback) -> String {
+    if traceback.frames.is_empty() {
+        String::new()
+    } else {
+        format!("\n{traceback}")
+    }
+}
+
+fn join_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Why a context couldn't be opened
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenError {
+    #[error("bolt refused to open with the configured handlers")]
+    Rejected,
+    #[error("bolt reported success without creating a context")]
+    NoContext,
 }
 
 impl Error {
-    pub fn bolt(msg: &str) -> Self {
-        Self::BoltError {
-            msg: msg.to_owned(),
-        }
+    pub fn bolt(msg: impl Into<String>) -> Self {
+        Self::BoltError { msg: msg.into() }
     }
 }
 
-#[derive(Debug)]
+#[derive(Error, Debug)]
 pub enum ArgError {
+    #[error("expected a {expected:?}, got a {actual:?}")]
     TypeGuard {
         expected: ValueType,
         actual: ValueType,
     },
-    TypeGuardEnum {
-        actual: ValueType,
-    },
-    IndexOutOfBounds {
-        idx: u8,
-        len: u8,
-    },
+    #[error("expected an enum value, got a {actual:?}")]
+    TypeGuardEnum { actual: ValueType },
+    #[error("argument {idx} is out of bounds, only {len} were passed")]
+    IndexOutOfBounds { idx: u8, len: u8 },
+    #[error("expected {expected} arguments, got {actual}")]
+    ArgCount { expected: u8, actual: u8 },
+    #[error("argument {idx}: {error}")]
+    BadArgument { idx: u8, error: Box<ArgError> },
+    #[error("{0} is not a valid enum value")]
+    InvalidEnumValue(u32),
+    #[error("{0} is not a valid host handle")]
+    InvalidHandle(f64),
+    /// A userdata that doesn't hold the Rust type the native expected
+    #[error("userdata does not hold a {expected}")]
+    UserdataType { expected: &'static str },
+    #[error("missing field {0:?}")]
+    MissingField(String),
+    #[error("field {field:?}: {error}")]
+    BadField { field: String, error: Box<ArgError> },
+    #[error("expected {expected} items, got {actual}")]
+    LengthMismatch { expected: usize, actual: usize },
+    #[error("item {idx}: {error}")]
+    BadItem { idx: usize, error: Box<ArgError> },
 }
 
-#[derive(Debug)]
+#[derive(Error, Debug)]
 pub enum ModuleError {
+    #[error("{0:?} is not a valid module or export name")]
     InvalidName(String),
+    #[error("module {0:?} is already registered")]
     AlreadyRegistered(String),
+    #[error("module {0:?} not found")]
     NotFound(String),
+    #[error("{0:?} is exported more than once")]
+    DuplicateExport(String),
+    #[error("export {export:?} is declared as {declared}, but its value is {actual}")]
+    SignatureMismatch {
+        export: String,
+        declared: String,
+        actual: String,
+    },
+    #[error("export {export:?} uses the unregistered type {ty}")]
+    UnregisteredType { export: String, ty: String },
+    /// Exporting failed for a reason validation doesn't check for
+    #[error(transparent)]
+    Export(Box<Error>),
 }
@@ -0,0 +1,231 @@
+//! Structured errors thrown from scripts
+//!
+//! Opening the `errors` module gives scripts `throw(err: Error)`, where `Error` is the
+//! crate-registered tableshape `{ code: string, message: string, data: any? }`. Throwing
+//! aborts the script like any runtime error, but instead of a flattened message the run or
+//! call that was executing fails with [`Error::Script`], so host code can match on the code:
+//!
+//! ```ignore
+//! match ctx.call(login, &[user]) {
+//!     Err(Error::Script(thrown)) if thrown.code == "E_DENIED" => retry(thrown.data),
+//!     other => other?,
+//! }
+//! ```
+//!
+//! Scripts can also `throw_value(value: any)` to throw a value of their own shape. That
+//! fails the call with [`Error::Script`] as well, with an empty code, the value's string
+//! form as the message and a copy of the value as the data. Whichever way it was thrown,
+//! the context keeps the value itself alive until the next throw, so it can still be read
+//! back as any [`FromBoltValue`] type while that context is around:
+//!
+//! ```ignore
+//! if let Err(Error::Script(thrown)) = ctx.call(validate, &[form]) {
+//!     let problem: FormProblem = thrown.get(&ctx).expect("thrown in ctx")?;
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bolt_sys::sys;
+
+use crate::replay::RecordedValue;
+use crate::types::{Module, Object, Table, Type};
+use crate::{
+    ArgError, Context, ContextRef, Error, FromBoltValue, MakeBoltValueWithContext, Rooted, Thread,
+    Value,
+};
+
+/// A value thrown by a script or raised by a native, copied out of the heap when it was
+/// thrown
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptThrow {
+    /// The `code` of a thrown `Error`, empty for other values
+    pub code: String,
+    pub message: String,
+    /// Whatever extra context the script attached, or a copy of the whole value for values
+    /// that aren't an `Error`. `Null` if none
+    pub data: RecordedValue,
+    id: u64,
+}
+
+impl std::fmt::Display for ScriptThrow {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.code.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", self.code, self.message)
+        }
+    }
+}
+
+/// Tells throws apart across every context, so a [`ScriptThrow`] never finds another
+/// throw's value
+static NEXT_THROW: AtomicU64 = AtomicU64::new(0);
+
+/// The value of the context's last throw, rooted until the next one replaces it
+pub(crate) struct LiveThrow {
+    id: u64,
+    value: Value,
+    _root: Option<Rooted<Object>>,
+}
+
+impl ScriptThrow {
+    /// The thrown value itself, if `ctx` is the context it was thrown in and no later throw
+    /// there has replaced it
+    pub fn value(&self, ctx: &Context) -> Option<Value> {
+        let state = crate::state::get(ctx.as_ptr());
+        let live = state.live_thrown.borrow();
+        live.as_ref()
+            .filter(|live| live.id == self.id)
+            .map(|live| live.value)
+    }
+
+    /// Convert the thrown value, e.g. into a `#[derive(BoltValue)]` struct, if it's still
+    /// alive in `ctx`, see [`ScriptThrow::value`]
+    pub fn get<T: FromBoltValue>(&self, ctx: &Context) -> Option<Result<T, ArgError>> {
+        self.value(ctx).map(|value| T::from(value.0))
+    }
+
+    /// Read the fields of an `Error` table
+    fn from_table(ctx: &mut Context, err: Table) -> Self {
+        let text = |key: &str| {
+            err.get_str(key)
+                .and_then(|value| <String as crate::FromBoltValue>::from(value.0).ok())
+                .unwrap_or_default()
+        };
+        let (code, message) = (text("code"), text("message"));
+        let data = match err.get_str("data") {
+            Some(data) => crate::replay::snapshot(ctx, data),
+            None => RecordedValue::Null,
+        };
+        ScriptThrow {
+            code,
+            message,
+            data,
+            id: NEXT_THROW.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Keep any value, with its string form as the message
+    fn from_any(ctx: &mut Context, value: Value) -> Self {
+        ScriptThrow {
+            code: String::new(),
+            message: value.display(ctx),
+            data: crate::replay::snapshot(ctx, value),
+            id: NEXT_THROW.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Read a value a native raised: a table with a `message` is read like an `Error`, and
+    /// anything else becomes the data, with its string form as the message
+    pub(crate) fn from_value(ctx: &mut Context, value: Value) -> Self {
+        if let Ok(table) = <Table as crate::FromBoltValue>::from(value.0)
+            && table.get_str("message").is_some()
+        {
+            return Self::from_table(ctx, table);
+        }
+        Self::from_any(ctx, value)
+    }
+}
+
+/// Fail the native running on `thr` with `thrown`, so the run or call executing it returns
+/// [`Error::Script`], keeping `value` alive for [`ScriptThrow::value`]
+pub(crate) unsafe fn raise(
+    ctx: *mut sys::bt_Context,
+    thr: *mut sys::bt_Thread,
+    thrown: ScriptThrow,
+    value: Value,
+) {
+    let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+    let root = value.as_object().map(|obj| Rooted::new(&mut ctx, obj));
+    let msg =
+        std::ffi::CString::new(thrown.to_string().replace('\0', " ")).expect("NULs were replaced");
+
+    let state = crate::state::get(ctx.as_ptr());
+    *state.live_thrown.borrow_mut() = Some(LiveThrow {
+        id: thrown.id,
+        value,
+        _root: root,
+    });
+    *state.thrown.borrow_mut() = Some(thrown);
+    unsafe { sys::bt_runtime_error(thr, msg.as_ptr(), std::ptr::null_mut()) };
+}
+
+unsafe extern "C" fn throw(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    unsafe { crate::unwind::guard(ctx, thr, "throw", || throw_error(ctx, thr)) };
+}
+
+fn throw_error(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+    let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+
+    let Ok((err,)) = thread.args::<(Table,)>() else {
+        unsafe {
+            sys::bt_runtime_error(
+                thr,
+                c"throw: expected an Error".as_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        return;
+    };
+
+    let thrown = ScriptThrow::from_table(&mut ctx, err);
+    let value = Value::from_raw(unsafe { sys::bt_arg(thr, 0) });
+    unsafe { raise(ctx.as_ptr(), thr, thrown, value) };
+}
+
+unsafe extern "C" fn throw_value(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    unsafe { crate::unwind::guard(ctx, thr, "throw_value", || throw_any(ctx, thr)) };
+}
+
+fn throw_any(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+    let value = Value::from_raw(unsafe { sys::bt_arg(thr, 0) });
+    let thrown = ScriptThrow::from_any(&mut ctx, value);
+    unsafe { raise(ctx.as_ptr(), thr, thrown, value) };
+}
+
+impl Context {
+    /// The `Error` tableshape, created and registered with the type registry on first use
+    pub fn error_type(&mut self) -> Type {
+        let state = crate::state::get(self.as_ptr());
+        if let Some(ty) = state.error_type.get() {
+            return ty;
+        }
+
+        let shape = self
+            .make_tableshape_type(c"Error", true)
+            .expect("static name is valid");
+        let string = self.type_string();
+        for key in [c"code", c"message"] {
+            let key = Value::from_raw(key.make_with_context(self));
+            self.tableshape_add_layout(shape, string, key, string);
+        }
+        let any = self.type_any();
+        let data = self.type_make_nullable(any);
+        let key = Value::from_raw(c"data".make_with_context(self));
+        self.tableshape_add_layout(shape, string, key, data);
+
+        let name = Value::from_raw("Error".make_with_context(self));
+        self.register_type(name, shape)
+            .expect("static name is valid");
+        state.error_type.set(Some(shape));
+        shape
+    }
+
+    /// Register the `errors` module so scripts can `throw` structured errors and
+    /// `throw_value` anything else
+    pub fn open_errors(&mut self) -> Result<Module, Error> {
+        let module = self.make_module();
+        let error = self.error_type();
+        let null = self.type_null();
+        let any = self.type_any();
+        self.module_export_native(module, c"throw", Some(throw), null, &[error])?;
+        self.module_export_native(module, c"throw_value", Some(throw_value), null, &[any])?;
+
+        let name = "errors".make_with_context(self);
+        self.register_module(Value::from_raw(name), module);
+        Ok(module)
+    }
+}
@@ -0,0 +1,182 @@
+//! Named events dispatched from the host to script callbacks
+//!
+//! Handlers subscribe with the Rust argument tuple they'll be called with, e.g.
+//! `ctx.events().subscribe_typed::<(String, f64)>("damage", on_damage)`. The callback's
+//! signature is checked against the tuple once, when subscribing, so emitting only has to
+//! box the arguments (once, however many handlers there are) and call each handler.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+
+use bolt_sys::sys;
+
+use crate::types::Object;
+use crate::{Context, Error, IntoBoltArgs, MakeBoltValue, Value};
+
+/// Identifies a handler for [`Events::unsubscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(u64);
+
+struct Handler {
+    id: u64,
+    func: Object,
+    /// The argument tuple the handler was checked against
+    args: TypeId,
+    args_name: &'static str,
+}
+
+#[derive(Default)]
+pub(crate) struct EventBus {
+    next: u64,
+    handlers: HashMap<String, Vec<Handler>>,
+}
+
+/// The context's event bus, see the [module docs](self)
+pub struct Events<'a> {
+    ctx: &'a mut Context,
+}
+
+impl Context {
+    pub fn events(&mut self) -> Events<'_> {
+        Events { ctx: self }
+    }
+}
+
+impl Events<'_> {
+    /// Call `func` whenever `event` is emitted with arguments of type `A`
+    ///
+    /// Fails if `func` isn't callable or its parameters don't accept `A`. The function is
+    /// kept alive until it's unsubscribed.
+    pub fn subscribe_typed<A: IntoBoltArgs + 'static>(
+        &mut self,
+        event: &str,
+        func: Value,
+    ) -> Result<Subscription, Error> {
+        let callable = func
+            .as_object()
+            .ok_or(Error::bolt("Value is not callable"))?;
+        let signature =
+            crate::call::signature_of(callable).ok_or(Error::bolt("Value is not callable"))?;
+        crate::call::check_args::<A>(self.ctx, signature)?;
+
+        self.ctx.add_ref(callable);
+        let state = crate::state::get(self.ctx.as_ptr());
+        let mut bus = state.events.borrow_mut();
+        bus.next += 1;
+        let id = bus.next;
+        bus.handlers
+            .entry(event.to_owned())
+            .or_default()
+            .push(Handler {
+                id,
+                func: callable,
+                args: TypeId::of::<A>(),
+                args_name: std::any::type_name::<A>(),
+            });
+        Ok(Subscription(id))
+    }
+
+    /// Remove a handler, returning whether it was still subscribed
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        let state = crate::state::get(self.ctx.as_ptr());
+        let removed = {
+            let mut bus = state.events.borrow_mut();
+            bus.handlers.values_mut().find_map(|handlers| {
+                let idx = handlers.iter().position(|h| h.id == subscription.0)?;
+                Some(handlers.remove(idx))
+            })
+        };
+        match removed {
+            Some(handler) => {
+                self.ctx.remove_ref(handler.func);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How many handlers are subscribed to `event`
+    pub fn handler_count(&self, event: &str) -> usize {
+        let state = crate::state::get(self.ctx.as_ptr());
+        let bus = state.events.borrow();
+        bus.handlers.get(event).map_or(0, Vec::len)
+    }
+
+    /// Call every handler of `event` in subscription order, returning how many were called
+    ///
+    /// The arguments are boxed once and shared by every handler. Emitting stops at the first
+    /// handler that fails. Every handler must have subscribed with the same argument type
+    /// `A`; emitting with a different one is an error and calls nothing.
+    ///
+    /// Handlers may (un)subscribe while the event is dispatched. One unsubscribed by an
+    /// earlier handler isn't called, and one subscribed during dispatch waits for the next
+    /// emit.
+    pub fn emit<A: IntoBoltArgs + 'static>(
+        &mut self,
+        event: &str,
+        args: A,
+    ) -> Result<usize, Error> {
+        let state = crate::state::get(self.ctx.as_ptr());
+        // Snapshot the handlers so they can (un)subscribe while the event is dispatched
+        let handlers: Vec<(u64, Object)> = {
+            let bus = state.events.borrow();
+            let Some(handlers) = bus.handlers.get(event) else {
+                return Ok(0);
+            };
+            if let Some(handler) = handlers.iter().find(|h| h.args != TypeId::of::<A>()) {
+                return Err(Error::bolt(format!(
+                    "Event '{event}' was subscribed with {} but emitted with {}",
+                    handler.args_name,
+                    std::any::type_name::<A>()
+                )));
+            }
+            handlers.iter().map(|h| (h.id, h.func)).collect()
+        };
+        if handlers.is_empty() {
+            return Ok(0);
+        }
+        // Unsubscribing drops the bus' reference, so the snapshot holds one of its own
+        for (_, func) in &handlers {
+            self.ctx.add_ref(*func);
+        }
+
+        let mut raw: Vec<sys::bt_Value> = Vec::with_capacity(A::COUNT as usize);
+        self.ctx.gc_pause();
+        args.make_args(self.ctx, &mut raw);
+        self.ctx.gc_unpause();
+
+        // The boxed arguments have to survive collections triggered by earlier handlers
+        let args: Vec<Value> = raw.into_iter().map(Value::from_raw).collect();
+        let roots: Vec<Object> = args.iter().filter_map(|arg| arg.as_object()).collect();
+        for root in &roots {
+            self.ctx.push_root(*root);
+        }
+
+        let mut called = 0;
+        let mut result = Ok(());
+        for (id, func) in &handlers {
+            let subscribed = state
+                .events
+                .borrow()
+                .handlers
+                .get(event)
+                .is_some_and(|current| current.iter().any(|h| h.id == *id));
+            if !subscribed {
+                continue;
+            }
+            if let Err(err) = self.ctx.call(Value::from_raw(func.make()), &args) {
+                result = Err(err);
+                break;
+            }
+            called += 1;
+        }
+
+        for _ in &roots {
+            self.ctx.pop_root();
+        }
+        for (_, func) in handlers {
+            self.ctx.remove_ref(func);
+        }
+        result.map(|()| called)
+    }
+}
@@ -0,0 +1,106 @@
+//! Precompiled expressions
+//!
+//! `Context::compile_expr` compiles a formula once into a bolt function taking the named
+//! parameters, so it can be evaluated many times with different inputs without
+//! re-parsing or re-typechecking the source.
+
+use std::marker::PhantomData;
+
+use crate::{Context, Error, FromBoltValue, MakeBoltValueWithContext, ScalarTypeSignature, Value};
+
+const EXPR_EXPORT: &str = "__expr";
+
+/// A compiled expression, evaluated with positional arguments matching the parameter list
+/// it was compiled with
+///
+/// The underlying function is kept alive until [`Expr::release`] is called or the context
+/// is closed.
+#[derive(Debug)]
+pub struct Expr<R> {
+    func: Value,
+    arity: usize,
+    _ret: PhantomData<R>,
+}
+
+impl<R> Expr<R> {
+    /// The number of arguments `eval` expects
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Allow the compiled function to be collected
+    pub fn release(self, ctx: &mut Context) {
+        if let Some(obj) = self.func.as_object() {
+            ctx.remove_ref(obj);
+        }
+    }
+}
+
+impl<R: FromBoltValue + MakeBoltValueWithContext> Expr<R> {
+    /// Evaluate the expression with `args` bound to its parameters, in order
+    pub fn eval(&self, ctx: &mut Context, args: &[R]) -> Result<R, Error> {
+        if args.len() != self.arity {
+            return Err(Error::bolt(format!(
+                "Expression takes {} arguments but {} were given",
+                self.arity,
+                args.len()
+            )));
+        }
+
+        ctx.gc_pause();
+        let args: Vec<Value> = args
+            .iter()
+            .map(|arg| Value::from_raw(arg.make_with_context(ctx)))
+            .collect();
+        ctx.gc_unpause();
+
+        let returned = ctx.call(self.func, &args)?;
+        Ok(R::from(returned.0)?)
+    }
+}
+
+impl Context {
+    /// Compile `expr` into a reusable evaluator taking `params` as positional arguments.
+    /// Every parameter, and the result, has the bolt type of `R`.
+    pub fn compile_expr<R: ScalarTypeSignature>(
+        &mut self,
+        expr: &str,
+        params: &[&str],
+    ) -> Result<Expr<R>, Error> {
+        for param in params {
+            crate::names::validate(param)?;
+        }
+
+        let type_name = R::make_type(self).name();
+        if type_name.is_empty() {
+            return Err(Error::bolt("Expression type has no name"));
+        }
+        let params = params
+            .iter()
+            .map(|param| format!("{param}: {type_name}"))
+            .collect::<Vec<_>>();
+        let source = format!(
+            "export fn {EXPR_EXPORT}({}): {type_name} {{ return {expr} }}",
+            params.join(", ")
+        );
+
+        let module = self.compile_module(source.as_str(), c"<expr>")?;
+        self.execute_module(module)?;
+
+        let exports = module
+            .export_table()
+            .ok_or(Error::bolt("Expression module has no exports"))?;
+        let func = exports
+            .get_str(EXPR_EXPORT)
+            .ok_or(Error::bolt("Expression module did not export its function"))?;
+        if let Some(obj) = func.as_object() {
+            self.add_ref(obj);
+        }
+
+        Ok(Expr {
+            func,
+            arity: params.len(),
+            _ret: PhantomData,
+        })
+    }
+}
@@ -0,0 +1,72 @@
+//! Use-after-collect detection for object handles, enabled by the `handle-checks` feature
+//!
+//! Every address handed out as an object wrapper carries a generation. The `free` handler
+//! retires the current generation of an address when bolt releases it, so any wrapper
+//! still holding the old generation panics on its next use, reporting where it was made
+//! (set `RUST_BACKTRACE=1` to capture creation sites).
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Handles {
+    /// Current generation of every address wrapped so far
+    generations: HashMap<usize, u32>,
+    /// Where the first wrapper of the current and the last retired generation was made
+    created: HashMap<(usize, u32), Rc<Backtrace>>,
+}
+
+thread_local! {
+    static HANDLES: RefCell<Handles> = RefCell::new(Handles::default());
+}
+
+/// Generation to stamp a new wrapper of `addr` with
+pub(crate) fn register(addr: usize) -> u32 {
+    HANDLES.with(|handles| {
+        let mut handles = handles.borrow_mut();
+        let generation = *handles.generations.entry(addr).or_default();
+        handles
+            .created
+            .entry((addr, generation))
+            .or_insert_with(|| Rc::new(Backtrace::capture()));
+        generation
+    })
+}
+
+/// Called when bolt frees `addr`, invalidating every wrapper made before now
+pub(crate) fn invalidate(addr: usize) {
+    HANDLES.with(|handles| {
+        let mut handles = handles.borrow_mut();
+        let Some(generation) = handles.generations.get_mut(&addr) else {
+            return;
+        };
+        let retired = *generation;
+        *generation = retired.wrapping_add(1);
+        // Only the generation just retired keeps its creation site for reporting, so an
+        // address reused over and over doesn't keep a backtrace per generation
+        handles.created.remove(&(addr, retired.wrapping_sub(1)));
+    })
+}
+
+/// Panic if a wrapper of `addr` stamped with `generation` has outlived its object
+pub(crate) fn check(addr: usize, generation: u32) {
+    let stale = HANDLES.with(|handles| {
+        let handles = handles.borrow();
+        match handles.generations.get(&addr) {
+            Some(current) if *current != generation => {
+                Some(handles.created.get(&(addr, generation)).cloned())
+            }
+            _ => None,
+        }
+    });
+
+    match stale {
+        Some(Some(created)) => {
+            panic!("use of collected bolt object at {addr:#x}, handle created at:\n{created}")
+        }
+        Some(None) => panic!("use of collected bolt object at {addr:#x}"),
+        None => {}
+    }
+}
@@ -0,0 +1,107 @@
+//! Callbacks as functions are entered and left
+//!
+//! [`Context::set_hook`] registers a callback which receives a [`HookEvent`] for each
+//! call and return selected by its [`HookMask`], the building block for profilers, tracers
+//! and watchdogs:
+//!
+//! ```ignore
+//! ctx.set_hook(HookMask::CALL, |event| println!("{}{}", " ".repeat(event.depth), event.function));
+//! ```
+//!
+//! bolt has no hook into its interpreter loop, so only calls crossing the host boundary are
+//! seen: natives, which all go through the trampoline in [`crate::stats`], and script
+//! functions called from Rust with [`Context::call`]. Calls from one script function to
+//! another and line changes aren't observable. A hook that panics fails the script like a
+//! panicking native, see [`crate::unwind`].
+
+use std::ops::BitOr;
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::Context;
+use crate::types::Object;
+
+/// Which events a hook receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HookMask(u8);
+
+impl HookMask {
+    pub const CALL: HookMask = HookMask(1);
+    pub const RETURN: HookMask = HookMask(1 << 1);
+    pub const ALL: HookMask = HookMask(Self::CALL.0 | Self::RETURN.0);
+
+    pub fn contains(self, other: HookMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for HookMask {
+    type Output = HookMask;
+
+    fn bitor(self, rhs: HookMask) -> HookMask {
+        HookMask(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+    Call,
+    Return,
+}
+
+impl HookKind {
+    fn mask(self) -> HookMask {
+        match self {
+            HookKind::Call => HookMask::CALL,
+            HookKind::Return => HookMask::RETURN,
+        }
+    }
+}
+
+/// A function being entered or left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookEvent {
+    pub kind: HookKind,
+    /// The export name of the function, or the name a native was registered with
+    pub function: String,
+    /// The module a script function was defined in
+    pub module: Option<String>,
+    /// Frames on the running thread's stack, 0 for a call made from Rust
+    pub depth: usize,
+}
+
+pub(crate) type Hook = Rc<dyn Fn(&HookEvent)>;
+
+/// Deliver `kind` for `callable` to the context's hook, if it asked for it
+pub(crate) fn emit(ctx: *mut sys::bt_Context, kind: HookKind, callable: Object, depth: usize) {
+    // Clone the hook out so it can't be replaced while it runs
+    let hook = crate::state::get(ctx)
+        .hook
+        .borrow()
+        .as_ref()
+        .filter(|(mask, _)| mask.contains(kind.mask()))
+        .map(|(_, hook)| hook.clone());
+    let Some(hook) = hook else {
+        return;
+    };
+    let (function, module) = crate::traceback::describe(ctx, callable);
+    hook(&HookEvent {
+        kind,
+        function,
+        module,
+        depth,
+    });
+}
+
+impl Context {
+    /// Call `hook` for every event in `mask`, replacing any previous hook, see the
+    /// [module docs](self)
+    pub fn set_hook(&mut self, mask: HookMask, hook: impl Fn(&HookEvent) + 'static) {
+        *crate::state::get(self.as_ptr()).hook.borrow_mut() = Some((mask, Rc::new(hook)));
+    }
+
+    pub fn clear_hook(&mut self) {
+        *crate::state::get(self.as_ptr()).hook.borrow_mut() = None;
+    }
+}
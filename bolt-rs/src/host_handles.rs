@@ -0,0 +1,136 @@
+//! Integer handles for host objects exposed to scripts
+//!
+//! Hosts that own their objects, e.g. an ECS keeping entities in its own storage, often
+//! don't want to hand scripts GC-owned userdata. Instead [`Context::insert_handle`] stores
+//! the object host-side and gives scripts a [`HostHandle`], which crosses into bolt as a
+//! plain number. Handles are never reused, so once an object is removed every copy of its
+//! handle held by scripts stops resolving rather than aliasing a newer object.
+
+use std::any::Any;
+use std::collections::HashMap;
+
+use bolt_sys::sys;
+
+use crate::types::Type;
+use crate::{ArgError, Context, FromBoltValue, MakeBoltValue, ScalarTypeSignature};
+
+/// A script-visible reference to an object stored with [`Context::insert_handle`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HostHandle(u64);
+
+impl HostHandle {
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Host objects keyed by handle, stored in the context state
+#[derive(Default)]
+pub(crate) struct HandleTable {
+    next: u64,
+    objects: HashMap<u64, Box<dyn Any>>,
+}
+
+impl MakeBoltValue for HostHandle {
+    fn make(&self) -> sys::bt_Value {
+        (self.0 as f64).make()
+    }
+}
+
+/// The largest id a handle can have and still cross into bolt as an exact number
+const MAX_ID: u64 = 1 << 53;
+
+impl FromBoltValue for HostHandle {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        // Anything a script made up rather than got from a handle is refused instead of
+        // being truncated into some other object's handle
+        let id = <f64 as FromBoltValue>::from(val)?;
+        if id.fract() != 0.0 || !(1.0..=MAX_ID as f64).contains(&id) {
+            return Err(ArgError::InvalidHandle(id));
+        }
+        Ok(HostHandle(id as u64))
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        HostHandle(unsafe { <f64 as FromBoltValue>::from_unchecked(val) } as u64)
+    }
+}
+
+impl ScalarTypeSignature for HostHandle {
+    fn make_type(ctx: &mut Context) -> Type {
+        ctx.type_number()
+    }
+}
+
+impl Context {
+    /// Store `object` host-side, returning the handle scripts can use to refer to it
+    pub fn insert_handle<T: Any>(&mut self, object: T) -> HostHandle {
+        let state = crate::state::get(self.as_ptr());
+        let mut handles = state.handles.borrow_mut();
+        handles.next += 1;
+        let id = handles.next;
+        assert!(id <= MAX_ID, "host handle ids exhausted");
+        handles.objects.insert(id, Box::new(object));
+        HostHandle(id)
+    }
+
+    /// Whether `handle` still refers to a stored object
+    pub fn is_handle_valid(&mut self, handle: HostHandle) -> bool {
+        crate::state::get(self.as_ptr())
+            .handles
+            .borrow()
+            .objects
+            .contains_key(&handle.0)
+    }
+
+    /// Run `f` on the object behind `handle`, or return `None` if the handle was removed or
+    /// refers to an object of another type
+    ///
+    /// The object is detached while `f` runs, so `f` may use the context freely but won't
+    /// see this handle as valid.
+    pub fn with_handle<T: Any, R>(
+        &mut self,
+        handle: HostHandle,
+        f: impl FnOnce(&mut Context, &mut T) -> R,
+    ) -> Option<R> {
+        let state = crate::state::get(self.as_ptr());
+        let object = state.handles.borrow_mut().objects.remove(&handle.0)?;
+        // Puts the object back even if `f` panics
+        let mut detached = Detached {
+            state: &state,
+            id: handle.0,
+            object: Some(object),
+        };
+
+        let object = detached.object.as_mut().expect("the object is detached");
+        object.downcast_mut::<T>().map(|object| f(self, object))
+    }
+
+    /// Remove the object behind `handle`, invalidating every copy of the handle
+    pub fn remove_handle(&mut self, handle: HostHandle) -> Option<Box<dyn Any>> {
+        crate::state::get(self.as_ptr())
+            .handles
+            .borrow_mut()
+            .objects
+            .remove(&handle.0)
+    }
+}
+
+/// An object taken out of the handle table, returned to it on drop
+struct Detached<'a> {
+    state: &'a crate::state::ContextState,
+    id: u64,
+    object: Option<Box<dyn Any>>,
+}
+
+impl Drop for Detached<'_> {
+    fn drop(&mut self) {
+        if let Some(object) = self.object.take() {
+            self.state
+                .handles
+                .borrow_mut()
+                .objects
+                .insert(self.id, object);
+        }
+    }
+}
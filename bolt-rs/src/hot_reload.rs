@@ -0,0 +1,201 @@
+//! Reloading script modules when their files change
+//!
+//! A [`HotReloader`] owns a set of script files, each registered as a module under a name.
+//! Watching is done by polling modification times, so there's no background thread and no
+//! platform notification API involved: call [`HotReloader::poll`] once per frame or tick,
+//! and every file that changed since the last poll is recompiled, executed, and registered
+//! in place of the old module. Imports compiled afterwards see the new module.
+//!
+//! Values the host took from the old module (closures, tables it exported) keep pointing
+//! at the old module, so the reload callback gets both modules to migrate state across:
+//!
+//! ```ignore
+//! let mut reloader = HotReloader::new().on_reload(|ctx, name, old, new| {
+//!     log::info!("reloaded {name}");
+//!     carry_over_state(ctx, old, new);
+//! });
+//! reloader.watch(&mut ctx, "player", "scripts/player.bolt")?;
+//! loop {
+//!     for (name, result) in reloader.poll(&mut ctx) {
+//!         if let Err(err) = result {
+//!             log::warn!("{name} failed to reload, keeping the old version: {err}");
+//!         }
+//!     }
+//!     // ...
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::rc::Weak;
+use std::time::SystemTime;
+
+use bolt_sys::sys;
+
+use crate::state::ContextState;
+use crate::types::Module;
+use crate::{Context, ContextRef, Error, MakeBoltValueWithContext, Value};
+
+type ReloadCallback = Box<dyn FnMut(&mut Context, &str, Module, Module)>;
+
+struct Watched {
+    name: String,
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// Referenced by the reloader for as long as it's the current version
+    module: Module,
+}
+
+/// Recompiles and re-registers script modules whose files change, see the
+/// [module docs](self)
+#[derive(Default)]
+pub struct HotReloader {
+    watched: Vec<Watched>,
+    on_reload: Option<ReloadCallback>,
+    /// The context the watched modules are referenced in, to release them on drop. Its
+    /// state only lives as long as the context, so a closed context is never touched.
+    context: Option<(*mut sys::bt_Context, Weak<ContextState>)>,
+}
+
+impl std::fmt::Debug for HotReloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HotReloader")
+            .field(
+                "watched",
+                &self
+                    .watched
+                    .iter()
+                    .map(|watched| (&watched.name, &watched.path))
+                    .collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Compile, run and register the script at `path` as `name`
+fn load(ctx: &mut Context, name: &str, path: &Path) -> Result<Module, Error> {
+    let source = std::fs::read_to_string(path)?;
+    let module = ctx.compile_module(source.as_str(), name)?;
+    ctx.push_root(module.as_object());
+    let executed = ctx.execute_module(module);
+    ctx.pop_root();
+    executed?;
+
+    let key = Value::from_raw(name.make_with_context(ctx));
+    ctx.register_module(key, module);
+    Ok(module)
+}
+
+impl HotReloader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `f` with the context, the module name, and the old and new module after each
+    /// successful reload
+    pub fn on_reload(
+        mut self,
+        f: impl FnMut(&mut Context, &str, Module, Module) + 'static,
+    ) -> Self {
+        self.on_reload = Some(Box::new(f));
+        self
+    }
+
+    /// Load the script at `path`, register it as module `name`, and reload it whenever the
+    /// file changes. Watching a name again replaces the path it's loaded from.
+    pub fn watch(
+        &mut self,
+        ctx: &mut Context,
+        name: &str,
+        path: impl Into<PathBuf>,
+    ) -> Result<Module, Error> {
+        let path = path.into();
+        let modified = modified(&path);
+        let module = load(ctx, name, &path)?;
+        ctx.add_ref(module.as_object());
+        self.context.get_or_insert_with(|| {
+            let state = crate::state::get(ctx.as_ptr());
+            (ctx.as_ptr(), std::rc::Rc::downgrade(&state))
+        });
+
+        let watched = Watched {
+            name: name.to_owned(),
+            path,
+            modified,
+            module,
+        };
+        match self.watched.iter_mut().find(|watched| watched.name == name) {
+            Some(existing) => {
+                let old = std::mem::replace(existing, watched);
+                ctx.remove_ref(old.module.as_object());
+            }
+            None => self.watched.push(watched),
+        }
+        Ok(module)
+    }
+
+    /// Stop watching module `name`, leaving its current version registered. Returns
+    /// whether it was being watched.
+    pub fn unwatch(&mut self, ctx: &mut Context, name: &str) -> bool {
+        let Some(idx) = self.watched.iter().position(|watched| watched.name == name) else {
+            return false;
+        };
+        let watched = self.watched.remove(idx);
+        ctx.remove_ref(watched.module.as_object());
+        true
+    }
+
+    /// Reload every watched module whose file changed since it was last loaded, returning
+    /// the name of each with the new module or why it couldn't be reloaded
+    ///
+    /// A module that fails to reload stays registered as it was, and isn't tried again
+    /// until its file changes again.
+    pub fn poll(&mut self, ctx: &mut Context) -> Vec<(String, Result<Module, Error>)> {
+        let mut reloaded = Vec::new();
+        for watched in &mut self.watched {
+            let modified = modified(&watched.path);
+            if modified == watched.modified {
+                continue;
+            }
+            watched.modified = modified;
+
+            let result = load(ctx, &watched.name, &watched.path);
+            if let Ok(module) = result {
+                ctx.add_ref(module.as_object());
+                let old = std::mem::replace(&mut watched.module, module);
+                if let Some(on_reload) = &mut self.on_reload {
+                    on_reload(ctx, &watched.name, old, module);
+                }
+                ctx.remove_ref(old.as_object());
+            }
+            reloaded.push((watched.name.clone(), result));
+        }
+        reloaded
+    }
+
+    /// Release every watched module, leaving their current versions registered
+    pub fn clear(&mut self, ctx: &mut Context) {
+        for watched in self.watched.drain(..) {
+            ctx.remove_ref(watched.module.as_object());
+        }
+    }
+}
+
+impl Drop for HotReloader {
+    fn drop(&mut self) {
+        let Some((ctx, state)) = &self.context else {
+            return;
+        };
+        // Closing the context already released everything it held
+        if state.upgrade().is_none() {
+            return;
+        }
+        let mut ctx = unsafe { ContextRef::from_raw(*ctx) };
+        self.clear(&mut ctx);
+    }
+}
@@ -0,0 +1,243 @@
+//! File access for scripts through a host-provided backend
+//!
+//! The standard library's `io` module opens files with C stdio, so the host can't see or
+//! control what it touches. [`Context::set_io`] registers a `files` module whose functions
+//! go through a [`BoltIo`] instead, so "files" can live in an asset store, an archive or
+//! memory, and paths the backend refuses fail the script with its error:
+//!
+//! ```ignore
+//! ctx.set_io(AssetStore::new(&bundle))?;
+//! ctx.run("import files\nlet level = files.open(\"levels/1.json\", \"r\").read()")?;
+//! ```
+//!
+//! Scripts get `open(path: string, mode: string): File`, where the mode is `"r"`, `"w"`
+//! or `"a"`, and a `File` has `read(): string`, `write(text: string)` and `close()`.
+//! `read(path)`, `write(path, contents)` and `exists(path)` cover whole files in one call.
+//!
+//! The module is separate from the std `io`, so scripts written against that keep working
+//! and opening the standard library never replaces the backend. Hosts that want scripts
+//! to only touch files through the backend leave [`StdLib::IO`](crate::StdLib::IO) closed.
+
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::meta::MetaFn;
+use crate::types::{Module, Type};
+use crate::userdata::BoltUserdata;
+use crate::{Context, Error, MakeBoltValue, MakeBoltValueWithContext, ModuleBuilder, Value};
+
+/// The name scripts import the backend's module as
+pub const MODULE: &str = "files";
+
+/// How a script opens a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpenMode {
+    Read,
+    /// Create the file, or empty it if it exists
+    Write,
+    /// Create the file, or write after its contents if it exists
+    Append,
+}
+
+impl OpenMode {
+    fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "r" => Ok(OpenMode::Read),
+            "w" => Ok(OpenMode::Write),
+            "a" => Ok(OpenMode::Append),
+            _ => Err(format!(
+                "unknown mode {mode:?}, expected \"r\", \"w\" or \"a\""
+            )),
+        }
+    }
+}
+
+/// An open file handed out by a [`BoltIo`]
+pub trait BoltFile: Read + Write {}
+
+impl<T: Read + Write> BoltFile for T {}
+
+/// The files a script's `files` module opens
+pub trait BoltIo {
+    /// Open `path` as `mode`, or refuse with the error the script should fail with
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn BoltFile>>;
+
+    /// The whole contents of `path`
+    fn read(&self, path: &Path) -> std::io::Result<String> {
+        let mut contents = String::new();
+        self.open(path, OpenMode::Read)?
+            .read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Replace the contents of `path` with `contents`
+    fn write(&self, path: &Path, contents: &str) -> std::io::Result<()> {
+        self.open(path, OpenMode::Write)?
+            .write_all(contents.as_bytes())
+    }
+
+    /// Whether there's anything at `path`, by default whether it can be opened for reading
+    fn exists(&self, path: &Path) -> bool {
+        self.open(path, OpenMode::Read).is_ok()
+    }
+}
+
+/// The options [`std::fs`] opens a file in `mode` with
+pub(crate) fn open_options(mode: OpenMode) -> OpenOptions {
+    let mut options = OpenOptions::new();
+    match mode {
+        OpenMode::Read => options.read(true),
+        OpenMode::Write => options.write(true).create(true).truncate(true),
+        OpenMode::Append => options.append(true).create(true),
+    };
+    options
+}
+
+/// The host filesystem, through [`std::fs`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostFs;
+
+impl BoltIo for HostFs {
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn BoltFile>> {
+        Ok(Box::new(open_options(mode).open(path)?))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+}
+
+/// The host filesystem, confined to directories by [`Context::restrict_filesystem`]
+pub(crate) struct ConfinedFs {
+    pub(crate) roots: Vec<PathBuf>,
+}
+
+impl BoltIo for ConfinedFs {
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn BoltFile>> {
+        Ok(Box::new(crate::loader::open_confined(
+            &self.roots,
+            path,
+            mode,
+        )?))
+    }
+}
+
+/// A file a script opened, closed once it's closed or collected
+struct ScriptFile(RefCell<Option<Box<dyn BoltFile>>>);
+
+impl BoltUserdata for ScriptFile {
+    const NAME: &'static str = "File";
+}
+
+impl ScriptFile {
+    fn with<R>(
+        &self,
+        f: impl FnOnce(&mut dyn BoltFile) -> std::io::Result<R>,
+    ) -> Result<R, String> {
+        let mut file = self.0.borrow_mut();
+        let file = file.as_mut().ok_or("file is closed")?;
+        f(file.as_mut()).map_err(|err| err.to_string())
+    }
+}
+
+impl Context {
+    /// Register the `files` module with `io` behind it, see the [module docs](crate::io).
+    /// Setting it again replaces the backend for scripts compiled from then on.
+    pub fn set_io(&mut self, io: impl BoltIo + 'static) -> Result<Module, Error> {
+        let io: Rc<dyn BoltIo> = Rc::new(io);
+        let file = self
+            .type_builder::<ScriptFile>()?
+            .try_method("read", |file: &ScriptFile, (): ()| {
+                file.with(|file| {
+                    let mut contents = String::new();
+                    file.read_to_string(&mut contents)?;
+                    Ok(contents)
+                })
+            })?
+            .try_method("write", |file: &ScriptFile, (text,): (String,)| {
+                file.with(|file| file.write_all(text.as_bytes()))
+            })?
+            .try_method("close", |file: &ScriptFile, (): ()| {
+                let closed = file.0.borrow_mut().take();
+                closed.map_or(Ok(()), |mut closed| {
+                    closed.flush().map_err(|err| err.to_string())
+                })
+            })?
+            .ty();
+        let string = self.type_string();
+        let null = self.type_null();
+        let bool = self.type_bool();
+
+        let open: MetaFn = {
+            let io = io.clone();
+            Rc::new(move |ctx, thread| {
+                let (path, mode) = thread
+                    .args::<(String, String)>()
+                    .map_err(|err| err.to_string())?;
+                let opened = io
+                    .open(Path::new(&path), OpenMode::parse(&mode)?)
+                    .map_err(|err| format!("{path}: {err}"))?;
+                let file = ctx
+                    .create_userdata(ScriptFile(RefCell::new(Some(opened))))
+                    .map_err(|err| err.to_string())?;
+                thread.return_val(&file);
+                Ok(())
+            })
+        };
+        let read: MetaFn = {
+            let io = io.clone();
+            Rc::new(move |ctx, thread| {
+                let (path,) = thread.args::<(String,)>().map_err(|err| err.to_string())?;
+                let contents = io
+                    .read(Path::new(&path))
+                    .map_err(|err| format!("{path}: {err}"))?;
+                let contents = Value::from_raw(contents.as_str().make_with_context(ctx));
+                thread.return_val(&contents);
+                Ok(())
+            })
+        };
+        let write: MetaFn = {
+            let io = io.clone();
+            Rc::new(move |_ctx, thread| {
+                let (path, contents) = thread
+                    .args::<(String, String)>()
+                    .map_err(|err| err.to_string())?;
+                io.write(Path::new(&path), &contents)
+                    .map_err(|err| format!("{path}: {err}"))
+            })
+        };
+        let exists: MetaFn = Rc::new(move |_ctx, thread| {
+            let (path,) = thread.args::<(String,)>().map_err(|err| err.to_string())?;
+            thread.return_val(&io.exists(Path::new(&path)));
+            Ok(())
+        });
+
+        self.gc_pause();
+        let natives = [
+            ("open", file, vec![string, string], open),
+            ("read", string, vec![string], read),
+            ("write", null, vec![string, string], write),
+            ("exists", bool, vec![string], exists),
+        ];
+        let module = natives
+            .into_iter()
+            .try_fold(
+                ModuleBuilder::new(MODULE),
+                |builder, (name, ret, args, func)| {
+                    let native = crate::meta::closure_native(self, name, ret, &args, func)?;
+                    let ty = unsafe { Type::from_raw_unchecked((*native.as_ptr()).type_) };
+                    Ok::<_, Error>(builder.value(
+                        name,
+                        ty,
+                        Value::from_raw(native.as_object().make()),
+                    ))
+                },
+            )
+            .and_then(|builder| builder.finish(self));
+        self.gc_unpause();
+        module
+    }
+}
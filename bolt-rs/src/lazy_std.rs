@@ -0,0 +1,91 @@
+//! Deferred construction of standard library modules
+//!
+//! Building every std module is a large share of the cost of a fresh context. With
+//! [`ContextBuilder::lazy_std`](crate::ContextBuilder::lazy_std), `open_all_std` only opens
+//! the modules that install methods on builtin types, and the rest are built the first time
+//! a source mentions them in an `import` statement. Sources are scanned before bolt sees
+//! them, both for host entry points and for module files handed over by the loader.
+
+use bolt_sys::sys;
+
+type OpenFn = unsafe extern "C" fn(*mut sys::bt_Context);
+
+/// Modules which are only reachable through `import`, so can safely be opened on demand
+pub(crate) const DEFERRED: [(&str, OpenFn); 4] = [
+    ("math", sys::boltstd_open_math),
+    ("io", sys::boltstd_open_io),
+    ("meta", sys::boltstd_open_meta),
+    ("regex", sys::boltstd_open_regex),
+];
+
+/// Open the eager std modules and mark the rest as pending
+pub(crate) fn defer_all(ctx: *mut sys::bt_Context) {
+    unsafe {
+        sys::boltstd_open_core(ctx);
+        sys::boltstd_open_arrays(ctx);
+        sys::boltstd_open_strings(ctx);
+        sys::boltstd_open_tables(ctx);
+    }
+
+    let state = crate::state::get(ctx);
+    // A restricted context has its own `io`, see `Context::restrict_filesystem`
+    let restricted = state.fs_roots.borrow().is_some();
+    let mut pending = state.pending_std.borrow_mut();
+    pending.clear();
+    pending.extend(
+        DEFERRED
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| !(restricted && *name == "io")),
+    );
+}
+
+/// Open any pending module named by an `import` statement in `source`
+pub(crate) fn open_imported(ctx: *mut sys::bt_Context, source: &[u8]) {
+    let state = crate::state::get(ctx);
+    if state.pending_std.borrow().is_empty() && state.pending_prelude.borrow().is_empty() {
+        return;
+    }
+
+    for name in imported_names(source) {
+        open_pending(ctx, name);
+    }
+}
+
+/// Open `name` if it is a pending std or prelude module
+pub(crate) fn open_pending(ctx: *mut sys::bt_Context, name: &[u8]) {
+    crate::prelude::open_pending(ctx, name);
+
+    let state = crate::state::get(ctx);
+    let mut pending = state.pending_std.borrow_mut();
+    let Some(idx) = pending.iter().position(|p| p.as_bytes() == name) else {
+        return;
+    };
+    let name = pending.swap_remove(idx);
+    drop(pending);
+
+    if let Some((_, open)) = DEFERRED.iter().find(|(deferred, _)| *deferred == name) {
+        unsafe { open(ctx) };
+        crate::stats::route_loaded(&mut *unsafe { crate::ContextRef::from_raw(ctx) });
+    }
+}
+
+/// Every identifier appearing in an `import` statement. This over-approximates, e.g. aliases
+/// are included, which at worst opens a module early.
+fn imported_names(source: &[u8]) -> impl Iterator<Item = &[u8]> {
+    source
+        .split(|b| *b == b'\n' || *b == b';')
+        .filter_map(|statement| {
+            let statement = statement.trim_ascii_start();
+            let rest = statement.strip_prefix(b"import")?;
+            rest.first().is_some_and(|b| !is_ident(*b)).then_some(rest)
+        })
+        .flat_map(|rest| {
+            rest.split(|b| !is_ident(*b))
+                .filter(|word| !word.is_empty())
+        })
+}
+
+fn is_ident(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
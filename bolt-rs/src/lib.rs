@@ -2,14 +2,111 @@
 mod wrappers;
 pub mod types;
 
+mod budget;
+mod builder;
+mod call;
+mod data_module;
+mod debug;
+mod dedup;
+mod depth;
+mod diagnostic;
+mod embedded;
 mod error;
+mod expr;
+#[cfg(feature = "handle-checks")]
+mod handles;
+mod host_handles;
+mod lazy_std;
+mod loader;
+mod memory;
+mod module_builder;
+mod names;
+mod registry;
+mod rollback;
+mod root;
+mod rooted;
+mod state;
+mod stdlib;
+mod stress;
+mod traceback;
+mod transfer;
 
-pub use error::{ArgError, Error, ModuleError};
+pub mod channel;
+pub mod commands;
+pub mod compile_cache;
+pub mod enums;
+pub mod errors;
+pub mod events;
+pub mod hooks;
+#[cfg(feature = "watch")]
+pub mod hot_reload;
+pub mod io;
+pub mod logging;
+#[cfg(feature = "math-types")]
+pub mod math;
+pub mod meta;
+pub mod parallel;
+pub mod pcall;
+pub mod pool;
+pub mod prelude;
+pub mod profiler;
+pub mod replay;
+pub mod result;
+pub mod rules;
+pub mod scope;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod stats;
+pub mod sync_context;
+pub mod unwind;
+pub mod userdata;
+pub mod version;
+pub mod watchdog;
+
+pub use builder::ContextBuilder;
+pub use call::{CallHandle, InternedStr};
+pub use channel::{Backpressure, ChannelSender};
+pub use commands::Command;
+pub use compile_cache::CompileCacheStats;
+pub use dedup::DedupReport;
+pub use diagnostic::{Diagnostic, DiagnosticKind, Span};
+pub use enums::BoltEnum;
+pub use error::{ArgError, Error, ModuleError, OpenError};
+pub use errors::ScriptThrow;
+pub use events::{Events, Subscription};
+pub use expr::Expr;
+pub use hooks::{HookEvent, HookKind, HookMask};
+pub use host_handles::HostHandle;
+#[cfg(feature = "watch")]
+pub use hot_reload::HotReloader;
+pub use io::{BoltFile, BoltIo, HostFs, OpenMode};
+pub use loader::PathNormalization;
+pub use logging::{LogLevel, ScriptLogger};
+pub use memory::BoltAllocator;
+pub use meta::TypeBuilder;
+pub use module_builder::ModuleBuilder;
+pub use pcall::RuntimeError;
+pub use pool::{ContextPool, PooledContext};
+pub use prelude::Prelude;
+pub use profiler::{ProfileReport, Profiler};
+pub use rollback::Transaction;
+pub use root::RootScope;
+pub use rooted::Rooted;
+pub use rules::{RuleOutcome, RuleSet};
+pub use scope::Scope;
+pub use stdlib::StdLib;
+pub use sync_context::SyncContext;
+pub use traceback::{StackFrame, Traceback};
+pub use types::module::Exports;
 pub use types::value::{
-    CallSignature, FromBoltValue, MakeBoltValue, MakeBoltValueWithContext, ScalarTypeSignature,
-    TypeSignature, Value, ValueType,
+    CallSignature, EnumValue, FromBoltArgs, FromBoltValue, HashableValue, IntoBoltArgs,
+    MakeBoltValue, MakeBoltValueWithContext, ScalarTypeSignature, Tuple, TypeSignature, Value,
+    ValueType,
 };
-pub use types::{Context, Thread};
+pub use types::{Context, ContextRef, ObjectHandle, Thread, TypeKind};
+pub use userdata::{BoltMethods, BoltUserdata, TypedUserdata, UserdataRef, UserdataRefMut};
+pub use version::{BoltVersion, BuildInfo, Feature};
+pub use watchdog::{InterruptHandle, Watchdog};
 pub use wrappers::IntoCStr;
 
 // Re-export bolt-sys for raw C interface
@@ -0,0 +1,302 @@
+//! Host-side module file loading
+//!
+//! bolt hands the `read_file` handler a NUL-terminated byte path built from the module
+//! path specs and the imported name. On unix those bytes are taken as the path as is, so
+//! non-UTF-8 paths load correctly. Other platforms have no byte form for such paths, so
+//! module path specs there have to be valid UTF-8.
+//!
+//! bolt only registers a module once it has finished compiling, so an import cycle would
+//! otherwise send the resolver back through `read_file` forever. Every module whose source
+//! is still alive is tracked per context, and reading one of them again is reported as a
+//! cycle with the full import chain.
+//!
+//! Sources added with [`Context::add_module_source`] are consulted before the filesystem:
+//! a path which, relative to the root of the module path spec it was built from and
+//! without its extension, is exactly a registered name loads that source instead. So
+//! `import foo` through a `scripts/%s.bolt` spec resolves to the source registered as
+//! `foo`, and never to one registered as `oo` or `scripts/foo`.
+//!
+//! [`Context::restrict_filesystem`] confines module files to a set of directories. Paths
+//! are resolved against the filesystem before they're checked, so neither `..` nor a
+//! symlink pointing elsewhere gets a module loaded from outside them, and the opened file
+//! is checked again, so a symlink swapped in between the check and the open doesn't either.
+
+use bolt_sys::sys;
+use std::ffi::{CStr, CString, OsStr};
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::io::OpenMode;
+use crate::state::ContextState;
+use crate::{Context, Error};
+
+/// How module paths are cleaned up before being opened
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PathNormalization {
+    /// Open the path exactly as bolt built it
+    #[default]
+    None,
+    /// Resolve `.` and `..` components and use the platform separator, without touching the
+    /// filesystem
+    Lexical,
+    /// Resolve the path against the filesystem, following symlinks
+    Canonical,
+}
+
+/// Encode a host path so it survives the trip through bolt's C strings
+///
+/// Paths are passed as their raw bytes on unix. Elsewhere there is no stable byte form for
+/// a path which isn't valid UTF-8, so those are refused.
+pub(crate) fn encode_path(path: &OsStr) -> Result<CString, Error> {
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(path);
+    #[cfg(not(unix))]
+    let bytes = path
+        .to_str()
+        .ok_or_else(|| {
+            std::io::Error::new(ErrorKind::InvalidInput, "module path is not valid UTF-8")
+        })?
+        .as_bytes();
+    Ok(CString::new(bytes)?)
+}
+
+/// Decode a path produced by bolt, see [`encode_path`]
+///
+/// Returns `None` for bytes that don't form a path on this platform, e.g. an import name
+/// that isn't valid UTF-8 outside of unix.
+pub(crate) fn decode_path(path: &CStr) -> Option<PathBuf> {
+    #[cfg(unix)]
+    let path = Some(<OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(
+        path.to_bytes(),
+    ));
+    #[cfg(not(unix))]
+    let path = path.to_str().ok().map(OsStr::new);
+    path.map(PathBuf::from)
+}
+
+pub(crate) fn normalize(path: PathBuf, mode: PathNormalization) -> PathBuf {
+    match mode {
+        PathNormalization::None => path,
+        PathNormalization::Lexical => normalize_lexically(&path),
+        PathNormalization::Canonical => std::fs::canonicalize(&path).unwrap_or(path),
+    }
+}
+
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Read a module's source from `file`, returning it so bolt can hand it back to `close_file`
+pub(crate) fn read_source(mut file: File) -> Option<(File, CString)> {
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).ok()?;
+    let contents = String::from_utf8(contents).ok()?;
+    let source = CString::new(contents).ok()?;
+    Some((file, source))
+}
+
+/// Resolve `path`, following symlinks, and check it's inside one of the canonical `roots`
+///
+/// A path that doesn't exist yet, e.g. a file about to be written, is judged by the
+/// directory it would be created in.
+pub(crate) fn confine(roots: &[PathBuf], path: &Path) -> std::io::Result<PathBuf> {
+    let resolved = match std::fs::canonicalize(path) {
+        Ok(resolved) => resolved,
+        // A dangling symlink would be followed by whatever opens it next
+        Err(err) if err.kind() != ErrorKind::NotFound || path.symlink_metadata().is_ok() => {
+            return Err(err);
+        }
+        Err(err) => {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(err);
+            };
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            std::fs::canonicalize(parent)?.join(name)
+        }
+    };
+
+    if roots.iter().any(|root| resolved.starts_with(root)) {
+        Ok(resolved)
+    } else {
+        Err(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            "outside the directories the context is restricted to",
+        ))
+    }
+}
+
+/// Open `path` as `mode` if it's inside one of the canonical `roots`
+///
+/// The path is checked before it's opened, so nothing is created outside the roots unless
+/// a directory is swapped for a symlink mid-call, and the opened file is checked against
+/// the path afterwards, so such a swap never hands back a file from outside. Writes only
+/// truncate once the file has passed.
+pub(crate) fn open_confined(
+    roots: &[PathBuf],
+    path: &Path,
+    mode: OpenMode,
+) -> std::io::Result<File> {
+    let resolved = confine(roots, path)?;
+    let mut options = crate::io::open_options(mode);
+    options.truncate(false);
+    let file = options.open(&resolved)?;
+
+    let escaped = || {
+        std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            "file changed while being opened",
+        )
+    };
+    if confine(roots, &resolved)? != resolved {
+        return Err(escaped());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let (opened, named) = (file.metadata()?, std::fs::metadata(&resolved)?);
+        if (opened.dev(), opened.ino()) != (named.dev(), named.ino()) {
+            return Err(escaped());
+        }
+    }
+
+    if mode == OpenMode::Write {
+        file.set_len(0)?;
+    }
+    Ok(file)
+}
+
+/// Remember where the module path `spec` is rooted, so registered sources can be matched
+/// against the paths bolt builds from it
+pub(crate) fn add_module_root(ctx: *mut sys::bt_Context, spec: &[u8]) {
+    let root = spec
+        .windows(2)
+        .position(|window| window == b"%s")
+        .map_or(spec, |idx| &spec[..idx]);
+    crate::state::get(ctx)
+        .module_roots
+        .borrow_mut()
+        .push(root.to_vec());
+}
+
+/// A copy of the registered source the raw module `path` resolves to, if any
+///
+/// The path is taken relative to the first module path root it starts with, or as-is if
+/// none does, and without its extension it must equal a registered name exactly.
+pub(crate) fn embedded_source(state: &ContextState, path: &[u8]) -> Option<CString> {
+    let sources = state.module_sources.borrow();
+    if sources.is_empty() {
+        return None;
+    }
+    let roots = state.module_roots.borrow();
+    let relative = roots
+        .iter()
+        .find_map(|root| path.strip_prefix(root.as_slice()))
+        .unwrap_or(path);
+    let relative = std::str::from_utf8(relative).ok()?.replace('\\', "/");
+    let name = Path::new(&relative).with_extension("");
+    sources.get(name.to_str()?).cloned()
+}
+
+/// Check that `path` isn't already being loaded, returning the import chain that leads back
+/// to it if it is
+pub(crate) fn check_cycle(state: &ContextState, path: &Path) -> Result<(), Vec<String>> {
+    let loading = state.loading.borrow();
+    let Some(start) = loading.iter().position(|(loaded, _)| loaded == path) else {
+        return Ok(());
+    };
+
+    let chain = loading[start..]
+        .iter()
+        .map(|(loaded, _)| loaded.as_path())
+        .chain(std::iter::once(path))
+        .map(module_name)
+        .collect();
+    Err(chain)
+}
+
+/// Track `path` as loading until its `source` is freed
+pub(crate) fn begin_load(state: &ContextState, path: PathBuf, source: *const std::ffi::c_char) {
+    state.loading.borrow_mut().push((path, source as usize));
+}
+
+/// Stop tracking the module whose source is `source`
+pub(crate) fn finish_load(state: &ContextState, source: *const std::ffi::c_char) {
+    let mut loading = state.loading.borrow_mut();
+    if let Some(idx) = loading.iter().rposition(|(_, src)| *src == source as usize) {
+        loading.remove(idx);
+    }
+}
+
+fn module_name(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl Context {
+    /// Make `import name` resolve to `source` without touching the filesystem, replacing any
+    /// source already registered under `name`
+    ///
+    /// `name` may contain `/` to stand in for a module in a subdirectory.
+    pub fn add_module_source(&mut self, name: &str, source: &str) -> Result<(), Error> {
+        let source = CString::new(source)?;
+        let state = crate::state::get(self.as_ptr());
+        state
+            .module_sources
+            .borrow_mut()
+            .insert(name.to_owned(), source);
+        Ok(())
+    }
+
+    /// Only load module files from inside `roots`, and give scripts a
+    /// [`files`](crate::io) module confined to them too, see the [module docs](self)
+    ///
+    /// The std `io` module opens files itself and can't be confined, so this unregisters it
+    /// if it was open, and [`Context::open_std`], [`Context::open_all_std`] and
+    /// [`Context::open_io`] leave it closed from then on. Scripts compiled before keep the
+    /// modules they imported. Sources added with
+    /// [`Context::add_module_source`] have no file behind them and still load.
+    pub fn restrict_filesystem(&mut self, roots: &[PathBuf]) -> Result<(), Error> {
+        let roots = roots
+            .iter()
+            .map(std::fs::canonicalize)
+            .collect::<Result<Vec<_>, _>>()?;
+        let state = crate::state::get(self.as_ptr());
+        *state.fs_roots.borrow_mut() = Some(roots.clone());
+        state.pending_std.borrow_mut().retain(|name| *name != "io");
+        self.unregister_module("io");
+        self.set_io(crate::io::ConfinedFs { roots })?;
+        Ok(())
+    }
+
+    /// The directories the context is restricted to, canonicalized, if it is
+    pub fn filesystem_roots(&self) -> Option<Vec<PathBuf>> {
+        crate::state::get(self.as_ptr()).fs_roots.borrow().clone()
+    }
+
+    /// Forget the source registered under `name`, returning whether there was one
+    pub fn remove_module_source(&mut self, name: &str) -> bool {
+        let state = crate::state::get(self.as_ptr());
+        state.module_sources.borrow_mut().remove(name).is_some()
+    }
+}
@@ -0,0 +1,117 @@
+//! Script logging routed through the host
+//!
+//! Opening the `log` module with [`Context::open_log`] exposes `debug`, `info`, `warn` and
+//! `error` to scripts. Each takes a message and an optional table of fields, e.g.
+//! `log.warn("slow frame", { ms: 40 })`, and is forwarded to the host's [`ScriptLogger`],
+//! so script output shares the application's levels, filtering and sinks.
+
+use bolt_sys::sys;
+use std::rc::Rc;
+
+use crate::types::{Module, Table};
+use crate::{
+    Context, ContextRef, Error, MakeBoltValueWithContext, ScalarTypeSignature, Thread, Value,
+};
+
+/// Severity of a script log message, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// Host backend for the script `log` module
+pub trait ScriptLogger {
+    /// Whether messages at `level` should be recorded. Fields of filtered messages are never
+    /// formatted.
+    fn enabled(&self, level: LogLevel) -> bool {
+        let _ = level;
+        true
+    }
+
+    /// Record a message, with each field's key and value rendered as bolt's `to_string` would
+    fn log(&self, level: LogLevel, message: &str, fields: &[(String, String)]);
+}
+
+fn log_at(level: LogLevel, ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+    let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+
+    let Ok((message, fields)) = thread.args::<(String, Option<Table>)>() else {
+        unsafe {
+            sys::bt_runtime_error(
+                thr,
+                c"log: expected a message and an optional table of fields".as_ptr(),
+                std::ptr::null_mut(),
+            )
+        };
+        return;
+    };
+
+    // Clone the backend out so it can log through the context without holding the state
+    let Some(logger) = crate::state::get(ctx.as_ptr()).logger.borrow().clone() else {
+        return;
+    };
+    if !logger.enabled(level) {
+        return;
+    }
+
+    let fields = fields.map_or_else(Vec::new, |table| {
+        let pairs: Vec<(Value, Value)> = table.iter(&mut ctx).collect();
+        pairs
+            .into_iter()
+            .map(|(key, value)| (key.display(&mut ctx), value.display(&mut ctx)))
+            .collect()
+    });
+    unsafe {
+        crate::unwind::guard(ctx.as_ptr(), thr, "log", || {
+            logger.log(level, &message, &fields)
+        })
+    };
+}
+
+unsafe extern "C" fn log_debug(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    log_at(LogLevel::Debug, ctx, thr);
+}
+
+unsafe extern "C" fn log_info(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    log_at(LogLevel::Info, ctx, thr);
+}
+
+unsafe extern "C" fn log_warn(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    log_at(LogLevel::Warn, ctx, thr);
+}
+
+unsafe extern "C" fn log_error(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    log_at(LogLevel::Error, ctx, thr);
+}
+
+impl Context {
+    /// Register the `log` module, forwarding every script message to `logger`. Opening it
+    /// again replaces the backend.
+    pub fn open_log(&mut self, logger: impl ScriptLogger + 'static) -> Result<Module, Error> {
+        *crate::state::get(self.as_ptr()).logger.borrow_mut() = Some(Rc::new(logger));
+
+        let module = self.make_module();
+        let string = String::make_type(self);
+        let any = self.type_any();
+        let fields = self.type_make_nullable(any);
+        let null = self.type_null();
+
+        let natives: [(&std::ffi::CStr, sys::bt_NativeProc); 4] = [
+            (c"debug", Some(log_debug)),
+            (c"info", Some(log_info)),
+            (c"warn", Some(log_warn)),
+            (c"error", Some(log_error)),
+        ];
+        for (name, proc) in natives {
+            self.module_export_native(module, name, proc, null, &[string, fields])?;
+        }
+
+        let name = "log".make_with_context(self);
+        self.register_module(Value::from_raw(name), module);
+        Ok(module)
+    }
+}
@@ -0,0 +1,469 @@
+//! Vector and matrix userdata for game hosts
+//!
+//! [`Context::open_math_types`] registers the `Vec2`, `Vec3` and `Mat4` userdata types and
+//! a `vecmath` module of constructors. Values are stored inline in the userdata, fields are
+//! readable and writable from script (`v.x = 2`), and arithmetic is exposed as methods:
+//!
+//! ```bolt
+//! import vecmath
+//! let v = vecmath.vec3(1, 2, 3).add(vecmath.vec3(0, 1, 0)).scale(2)
+//! let moved = vecmath.mat4_translation(0, 0, 5).transform(v)
+//! ```
+//!
+//! On the Rust side the types convert like any other value, and with the `glam` feature
+//! they convert to and from the matching glam types.
+
+use std::mem::{offset_of, size_of};
+use std::ops::{Add, Mul, Sub};
+
+use bolt_sys::sys;
+
+use crate::types::{Module, Type};
+use crate::{
+    ArgError, Context, ContextRef, Error, FromBoltValue, MakeBoltValue, MakeBoltValueWithContext,
+    ScalarTypeSignature, Thread, Value, ValueType,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+/// A column-major 4x4 matrix
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Mat4 {
+    pub cols: [[f32; 4]; 4],
+}
+
+impl Vec2 {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// The unit vector in the same direction, or zero for a zero vector
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len == 0.0 { self } else { self * (1.0 / len) }
+    }
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// The unit vector in the same direction, or zero for a zero vector
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len == 0.0 { self } else { self * (1.0 / len) }
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self::from_diagonal(1.0, 1.0, 1.0);
+
+    const fn from_diagonal(x: f32, y: f32, z: f32) -> Self {
+        Self {
+            cols: [
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub const fn from_translation(offset: Vec3) -> Self {
+        let mut mat = Self::IDENTITY;
+        mat.cols[3] = [offset.x, offset.y, offset.z, 1.0];
+        mat
+    }
+
+    pub const fn from_scale(scale: Vec3) -> Self {
+        Self::from_diagonal(scale.x, scale.y, scale.z)
+    }
+
+    /// Transform a point, applying translation
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let c = &self.cols;
+        Vec3::new(
+            c[0][0] * p.x + c[1][0] * p.y + c[2][0] * p.z + c[3][0],
+            c[0][1] * p.x + c[1][1] * p.y + c[2][1] * p.z + c[3][1],
+            c[0][2] * p.x + c[1][2] * p.y + c[2][2] * p.z + c[3][2],
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (col, out_col) in out.iter_mut().enumerate() {
+            for (row, out_cell) in out_col.iter_mut().enumerate() {
+                *out_cell = (0..4).map(|k| self.cols[k][row] * rhs.cols[col][k]).sum();
+            }
+        }
+        Mat4 { cols: out }
+    }
+}
+
+macro_rules! impl_vector_ops {
+    ($name:ident { $($field:ident),* }) => {
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, rhs: $name) -> $name {
+                $name { $($field: self.$field + rhs.$field),* }
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, rhs: $name) -> $name {
+                $name { $($field: self.$field - rhs.$field),* }
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = $name;
+
+            fn mul(self, rhs: f32) -> $name {
+                $name { $($field: self.$field * rhs),* }
+            }
+        }
+    };
+}
+
+impl_vector_ops!(Vec2 { x, y });
+impl_vector_ops!(Vec3 { x, y, z });
+
+/// The userdata types registered for a context
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MathTypes {
+    vec2: Type,
+    vec3: Type,
+    mat4: Type,
+}
+
+/// The context's math types, registering them on first use
+fn math_types(ctx: &mut Context) -> MathTypes {
+    let state = crate::state::get(ctx.as_ptr());
+    if let Some(types) = state.math_types.get() {
+        return types;
+    }
+
+    let vec2 = ctx
+        .make_userdata_type(c"Vec2")
+        .expect("static name is valid");
+    let vec3 = ctx
+        .make_userdata_type(c"Vec3")
+        .expect("static name is valid");
+    let mat4 = ctx
+        .make_userdata_type(c"Mat4")
+        .expect("static name is valid");
+
+    let fields = [
+        (vec2, c"x", offset_of!(Vec2, x)),
+        (vec2, c"y", offset_of!(Vec2, y)),
+        (vec3, c"x", offset_of!(Vec3, x)),
+        (vec3, c"y", offset_of!(Vec3, y)),
+        (vec3, c"z", offset_of!(Vec3, z)),
+    ];
+    for (ty, name, offset) in fields {
+        ctx.userdata_type_field_float(ty, name, offset as u32)
+            .expect("static name is valid");
+    }
+
+    for (name, ty) in [("Vec2", vec2), ("Vec3", vec3), ("Mat4", mat4)] {
+        let name = Value::from_raw(name.make_with_context(ctx));
+        ctx.register_type(name, ty).expect("static name is valid");
+    }
+
+    let types = MathTypes { vec2, vec3, mat4 };
+    state.math_types.set(Some(types));
+    types
+}
+
+/// Read a userdata value of the type `pick` selects
+fn read_userdata<T: Copy>(val: sys::bt_Value, pick: fn(&MathTypes) -> Type) -> Result<T, ArgError> {
+    let mismatch = || ArgError::TypeGuard {
+        expected: ValueType::UserData,
+        actual: ValueType::from_value(val),
+    };
+    let obj = Value::from_raw(val)
+        .as_object()
+        .filter(|obj| matches!(obj.value_type(), ValueType::UserData))
+        .ok_or_else(mismatch)?;
+
+    unsafe {
+        let userdata = obj.as_ptr() as *mut sys::bt_Userdata;
+        let ty = (*userdata).type_;
+        let expected = crate::state::get((*ty).ctx)
+            .math_types
+            .get()
+            .map(|types| pick(&types).as_ptr());
+        if expected != Some(ty) || ((*userdata).size as usize) < size_of::<T>() {
+            return Err(mismatch());
+        }
+        Ok((sys::bt_userdata_get(userdata) as *const T).read_unaligned())
+    }
+}
+
+macro_rules! impl_userdata_value {
+    ($name:ident, $field:ident) => {
+        impl ScalarTypeSignature for $name {
+            fn make_type(ctx: &mut Context) -> Type {
+                math_types(ctx).$field
+            }
+        }
+
+        impl MakeBoltValueWithContext for $name {
+            fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+                let ty = math_types(ctx).$field;
+                let mut data = *self;
+                ctx.make_userdata(
+                    ty,
+                    &mut data as *mut $name as *mut std::ffi::c_void,
+                    size_of::<$name>() as u32,
+                )
+                .as_object()
+                .make()
+            }
+        }
+
+        impl FromBoltValue for $name {
+            fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+                read_userdata(val, |types| types.$field)
+            }
+
+            unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+                unsafe {
+                    let userdata = sys::bt_object(val) as *mut sys::bt_Userdata;
+                    (sys::bt_userdata_get(userdata) as *const $name).read_unaligned()
+                }
+            }
+        }
+    };
+}
+
+impl_userdata_value!(Vec2, vec2);
+impl_userdata_value!(Vec3, vec3);
+impl_userdata_value!(Mat4, mat4);
+
+/// Define a native which extracts `$args`, evaluates `$body` and returns the result
+macro_rules! math_native {
+    ($name:ident, || $body:expr) => {
+        math_native!($name, | | $body);
+    };
+    ($name:ident, |$($arg:ident: $ty:ty),*| $body:expr) => {
+        unsafe extern "C" fn $name(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+            let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+            let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+            let Ok(($($arg,)*)) = thread.args::<($($ty,)*)>() else {
+                unsafe {
+                    sys::bt_runtime_error(
+                        thr,
+                        concat!(stringify!($name), ": bad arguments\0").as_ptr() as *const _,
+                        std::ptr::null_mut(),
+                    )
+                };
+                return;
+            };
+            let result = Value::from_raw($body.make_with_context(&mut ctx));
+            thread.return_val(&result);
+        }
+    };
+}
+
+math_native!(vec2_new, |x: f32, y: f32| Vec2::new(x, y));
+math_native!(vec2_add, |a: Vec2, b: Vec2| a + b);
+math_native!(vec2_sub, |a: Vec2, b: Vec2| a - b);
+math_native!(vec2_scale, |a: Vec2, s: f32| a * s);
+math_native!(vec2_dot, |a: Vec2, b: Vec2| a.dot(b) as f64);
+math_native!(vec2_length, |a: Vec2| a.length() as f64);
+math_native!(vec2_normalize, |a: Vec2| a.normalize());
+
+math_native!(vec3_new, |x: f32, y: f32, z: f32| Vec3::new(x, y, z));
+math_native!(vec3_add, |a: Vec3, b: Vec3| a + b);
+math_native!(vec3_sub, |a: Vec3, b: Vec3| a - b);
+math_native!(vec3_scale, |a: Vec3, s: f32| a * s);
+math_native!(vec3_dot, |a: Vec3, b: Vec3| a.dot(b) as f64);
+math_native!(vec3_cross, |a: Vec3, b: Vec3| a.cross(b));
+math_native!(vec3_length, |a: Vec3| a.length() as f64);
+math_native!(vec3_normalize, |a: Vec3| a.normalize());
+
+math_native!(mat4_identity, || Mat4::IDENTITY);
+math_native!(mat4_translation, |x: f32, y: f32, z: f32| {
+    Mat4::from_translation(Vec3::new(x, y, z))
+});
+math_native!(mat4_scale, |x: f32, y: f32, z: f32| Mat4::from_scale(
+    Vec3::new(x, y, z)
+));
+math_native!(mat4_mul, |a: Mat4, b: Mat4| a * b);
+math_native!(mat4_transform, |m: Mat4, p: Vec3| m.transform_point(p));
+
+impl Context {
+    /// Register the `Vec2`, `Vec3` and `Mat4` types and the `vecmath` module, see the
+    /// [module docs](self)
+    pub fn open_math_types(&mut self) -> Result<Module, Error> {
+        let MathTypes { vec2, vec3, mat4 } = math_types(self);
+        let number = self.type_number();
+        let module = self.make_module();
+
+        let methods: [(Type, &str, sys::bt_NativeProc, Type, &[Type]); 15] = [
+            (vec2, "add", Some(vec2_add), vec2, &[vec2]),
+            (vec2, "sub", Some(vec2_sub), vec2, &[vec2]),
+            (vec2, "scale", Some(vec2_scale), vec2, &[number]),
+            (vec2, "dot", Some(vec2_dot), number, &[vec2]),
+            (vec2, "length", Some(vec2_length), number, &[]),
+            (vec2, "normalize", Some(vec2_normalize), vec2, &[]),
+            (vec3, "add", Some(vec3_add), vec3, &[vec3]),
+            (vec3, "sub", Some(vec3_sub), vec3, &[vec3]),
+            (vec3, "scale", Some(vec3_scale), vec3, &[number]),
+            (vec3, "dot", Some(vec3_dot), number, &[vec3]),
+            (vec3, "cross", Some(vec3_cross), vec3, &[vec3]),
+            (vec3, "length", Some(vec3_length), number, &[]),
+            (vec3, "normalize", Some(vec3_normalize), vec3, &[]),
+            (mat4, "mul", Some(mat4_mul), mat4, &[mat4]),
+            (mat4, "transform", Some(mat4_transform), vec3, &[vec3]),
+        ];
+        for (ty, name, proc, ret, args) in methods {
+            // Methods take the receiver as their first argument
+            let mut signature_args = vec![ty];
+            signature_args.extend_from_slice(args);
+            let signature = self
+                .make_signature_type(ret, &signature_args)
+                .ok_or(Error::bolt("Failed to create signature type"))?;
+            let native = self.make_native(module, signature, proc);
+            let key = Value::from_raw(name.make_with_context(self));
+            self.type_add_field(
+                ty,
+                signature,
+                key,
+                Value::from_raw(native.as_object().make()),
+            );
+        }
+
+        let constructors: [(&std::ffi::CStr, sys::bt_NativeProc, Type, &[Type]); 5] = [
+            (c"vec2", Some(vec2_new), vec2, &[number, number]),
+            (c"vec3", Some(vec3_new), vec3, &[number, number, number]),
+            (c"mat4_identity", Some(mat4_identity), mat4, &[]),
+            (
+                c"mat4_translation",
+                Some(mat4_translation),
+                mat4,
+                &[number, number, number],
+            ),
+            (
+                c"mat4_scale",
+                Some(mat4_scale),
+                mat4,
+                &[number, number, number],
+            ),
+        ];
+        for (name, proc, ret, args) in constructors {
+            self.module_export_native(module, name, proc, ret, args)?;
+        }
+
+        for (name, ty) in [("Vec2", vec2), ("Vec3", vec3), ("Mat4", mat4)] {
+            let type_type = self.type_type();
+            let key = Value::from_raw(name.make_with_context(self));
+            self.module_export(
+                module,
+                type_type,
+                key,
+                Value::from_raw(ty.as_object().make()),
+            )?;
+        }
+
+        let name = Value::from_raw("vecmath".make_with_context(self));
+        self.register_module(name, module);
+        Ok(module)
+    }
+}
+
+#[cfg(feature = "glam")]
+mod glam_conversions {
+    use super::{Mat4, Vec2, Vec3};
+
+    impl From<glam::Vec2> for Vec2 {
+        fn from(v: glam::Vec2) -> Self {
+            Vec2::new(v.x, v.y)
+        }
+    }
+
+    impl From<Vec2> for glam::Vec2 {
+        fn from(v: Vec2) -> Self {
+            glam::Vec2::new(v.x, v.y)
+        }
+    }
+
+    impl From<glam::Vec3> for Vec3 {
+        fn from(v: glam::Vec3) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vec3> for glam::Vec3 {
+        fn from(v: Vec3) -> Self {
+            glam::Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<glam::Mat4> for Mat4 {
+        fn from(m: glam::Mat4) -> Self {
+            Mat4 {
+                cols: m.to_cols_array_2d(),
+            }
+        }
+    }
+
+    impl From<Mat4> for glam::Mat4 {
+        fn from(m: Mat4) -> Self {
+            glam::Mat4::from_cols_array_2d(&m.cols)
+        }
+    }
+}
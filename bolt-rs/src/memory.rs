@@ -0,0 +1,319 @@
+//! Allocation routing, accounting and per-context memory limits
+//!
+//! bolt's allocation handlers don't receive the context they allocate for, so the handlers
+//! resolve it from the current thread: the context marks its [`Heap`] active whenever it
+//! enters bolt to open, compile or run code, and outside of that, allocations belong to the
+//! context that last entered bolt on this thread, by opening, running or calling code,
+//! having one of its natives called, or having its memory limit set. Objects the host
+//! builds directly in between are charged to that context just like the ones its scripts
+//! build. Each allocation records its size and the heap it was charged to, so it is freed
+//! through the same allocator and credited back to the same budget no matter which context
+//! is active at that point.
+//!
+//! bolt assumes its allocator never fails, and raising an error from inside it would unwind
+//! through whatever bolt was in the middle of, so an allocation that crosses the limit is
+//! still served and only marks the heap. The script is aborted at the next safe point, the
+//! next call into a native, and the host call reports
+//! [`Error::MemoryLimit`](crate::Error::MemoryLimit).
+
+use std::alloc::Layout;
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::state::ContextState;
+
+/// Alignment of every allocation handed to bolt, and the size of the header in front of it
+const HEADER: usize = 16;
+
+#[repr(C)]
+struct Header {
+    size: usize,
+    heap: *const Heap,
+}
+
+const _: () = assert!(std::mem::size_of::<Header>() <= HEADER);
+
+/// A source of memory for a context, see [`ContextBuilder::allocator`](crate::ContextBuilder::allocator)
+///
+/// Every layout is 16-byte aligned and includes a small header bolt-rs keeps in front of
+/// each allocation, so the sizes seen here are slightly larger than what bolt asked for.
+pub trait BoltAllocator {
+    /// Allocate memory for `layout`, returning null on failure
+    fn alloc(&self, layout: Layout) -> *mut u8;
+
+    /// Grow or shrink an allocation, returning null on failure
+    ///
+    /// # Safety
+    /// `ptr` was returned by this allocator for `old`, and hasn't been freed.
+    unsafe fn realloc(&self, ptr: *mut u8, old: Layout, new_size: usize) -> *mut u8;
+
+    /// Release an allocation
+    ///
+    /// # Safety
+    /// `ptr` was returned by this allocator for `layout`, and hasn't been freed.
+    unsafe fn free(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// Where a context's memory comes from and how much of it is in use
+pub(crate) struct Heap {
+    allocator: Option<Rc<dyn BoltAllocator>>,
+    limit: Cell<usize>,
+    used: Cell<usize>,
+    exceeded: Cell<bool>,
+}
+
+impl Heap {
+    pub fn new(allocator: Option<Rc<dyn BoltAllocator>>) -> Self {
+        Self {
+            allocator,
+            limit: Cell::new(usize::MAX),
+            used: Cell::new(0),
+            exceeded: Cell::new(false),
+        }
+    }
+}
+
+thread_local! {
+    static ACTIVE: Cell<*const Heap> = const { Cell::new(std::ptr::null()) };
+    /// The context the host last used on this thread, and its heap
+    static HOST: RefCell<(usize, Option<Rc<Heap>>)> = const { RefCell::new((0, None)) };
+}
+
+/// Routes allocations to a heap until dropped, see [`enter`]
+pub(crate) struct Enter {
+    previous: *const Heap,
+    _heap: Option<Rc<Heap>>,
+}
+
+/// Make the heap of a context, if it has one, the one new allocations on this thread use,
+/// and the one host-side allocations are charged to afterwards, see [`touch`]
+pub(crate) fn enter(ctx: *mut sys::bt_Context, state: &ContextState) -> Enter {
+    touch(ctx, state);
+    enter_heap(state.heap.borrow().clone())
+}
+
+pub(crate) fn enter_heap(heap: Option<Rc<Heap>>) -> Enter {
+    let active = heap.as_ref().map_or(std::ptr::null(), Rc::as_ptr);
+    Enter {
+        previous: ACTIVE.with(|current| current.replace(active)),
+        _heap: heap,
+    }
+}
+
+impl Drop for Enter {
+    fn drop(&mut self) {
+        ACTIVE.with(|current| current.set(self.previous));
+    }
+}
+
+/// Charge allocations made outside of [`enter`] to `ctx`, called whenever the host enters
+/// bolt through a context and whenever a context's heap changes
+pub(crate) fn touch(ctx: *mut sys::bt_Context, state: &ContextState) {
+    let _ = HOST.try_with(|host| {
+        if let Ok(mut host) = host.try_borrow_mut() {
+            *host = (ctx as usize, state.heap.borrow().clone());
+        }
+    });
+}
+
+/// Stop charging host-side allocations to `ctx`, once it has been closed
+pub(crate) fn forget(ctx: *mut sys::bt_Context) {
+    let _ = HOST.try_with(|host| {
+        let mut host = host.borrow_mut();
+        if host.0 == ctx as usize {
+            *host = (0, None);
+        }
+    });
+}
+
+/// The heap allocations are charged to right now
+fn current() -> *const Heap {
+    let active = ACTIVE.with(Cell::get);
+    if !active.is_null() {
+        return active;
+    }
+    HOST.try_with(|host| {
+        host.try_borrow()
+            .ok()
+            .and_then(|host| host.1.as_ref().map(Rc::as_ptr))
+    })
+    .ok()
+    .flatten()
+    .unwrap_or(std::ptr::null())
+}
+
+/// Whether the limit was crossed since the last [`take_exceeded`], leaving the flag set
+pub(crate) fn exceeded(state: &ContextState) -> bool {
+    state
+        .heap
+        .borrow()
+        .as_ref()
+        .is_some_and(|heap| heap.exceeded.get())
+}
+
+/// The limit set with `set_memory_limit`, if any
+pub(crate) fn limit(state: &ContextState) -> Option<usize> {
+    let heap = state.heap.borrow();
+    let limit = heap.as_ref()?.limit.get();
+    (limit != usize::MAX).then_some(limit)
+}
+
+/// Whether the limit was crossed since the last call, clearing the flag
+pub(crate) fn take_exceeded(state: &ContextState) -> bool {
+    state
+        .heap
+        .borrow()
+        .as_ref()
+        .is_some_and(|heap| heap.exceeded.replace(false))
+}
+
+fn charge(heap: *const Heap, size: usize) -> *const Heap {
+    if heap.is_null() {
+        return heap;
+    }
+
+    // Every header holds a strong count, so the heap outlives the allocations made from it
+    // even if its context has been closed
+    unsafe { Rc::increment_strong_count(heap) };
+    let heap_ref = unsafe { &*heap };
+    let used = heap_ref.used.get() + size;
+    heap_ref.used.set(used);
+    if used > heap_ref.limit.get() {
+        heap_ref.exceeded.set(true);
+    }
+    heap
+}
+
+fn credit(header: &Header) {
+    if header.heap.is_null() {
+        return;
+    }
+
+    let heap = unsafe { Rc::from_raw(header.heap) };
+    heap.used.set(heap.used.get().saturating_sub(header.size));
+}
+
+fn allocator<'a>(heap: *const Heap) -> Option<&'a dyn BoltAllocator> {
+    if heap.is_null() {
+        return None;
+    }
+    unsafe { (*heap).allocator.as_deref() }
+}
+
+fn layout(size: usize) -> Layout {
+    Layout::from_size_align(size + HEADER, HEADER).expect("allocation size overflows")
+}
+
+pub(crate) unsafe extern "C" fn alloc(size: usize) -> *mut c_void {
+    let heap = current();
+    let base = match allocator(heap) {
+        Some(allocator) => allocator.alloc(layout(size)),
+        None => unsafe { std::alloc::alloc(layout(size)) },
+    };
+    if base.is_null() {
+        std::alloc::handle_alloc_error(layout(size));
+    }
+
+    unsafe {
+        (base as *mut Header).write(Header {
+            size,
+            heap: charge(heap, size),
+        });
+        base.add(HEADER) as _
+    }
+}
+
+pub(crate) unsafe extern "C" fn free(ptr: *mut c_void) {
+    #[cfg(feature = "handle-checks")]
+    crate::handles::invalidate(ptr as usize);
+
+    if ptr.is_null() {
+        return;
+    }
+
+    unsafe {
+        let base = (ptr as *mut u8).sub(HEADER);
+        let header = (base as *mut Header).read();
+        match allocator(header.heap) {
+            Some(allocator) => allocator.free(base, layout(header.size)),
+            None => std::alloc::dealloc(base, layout(header.size)),
+        }
+        credit(&header);
+    }
+}
+
+pub(crate) unsafe extern "C" fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return unsafe { alloc(size) };
+    }
+
+    unsafe {
+        let base = (ptr as *mut u8).sub(HEADER);
+        let header = (base as *mut Header).read();
+
+        // Memory stays with the allocator it came from. Untracked memory is adopted by the
+        // active heap when that doesn't mean moving it to another allocator.
+        let active = current();
+        let owner = if header.heap.is_null() && allocator(active).is_none() {
+            active
+        } else {
+            header.heap
+        };
+
+        let base = match allocator(header.heap) {
+            Some(allocator) => allocator.realloc(base, layout(header.size), size + HEADER),
+            None => std::alloc::realloc(base, layout(header.size), size + HEADER),
+        };
+        if base.is_null() {
+            std::alloc::handle_alloc_error(layout(size));
+        }
+
+        let heap = charge(owner, size);
+        credit(&header);
+        (base as *mut Header).write(Header { size, heap });
+        base.add(HEADER) as _
+    }
+}
+
+impl crate::Context {
+    /// Abort scripts once the memory they have allocated, and not yet freed, exceeds `bytes`
+    ///
+    /// This is not a hard cap: the allocation that crosses the limit, and any made before
+    /// the next check, still succeed. The limit is checked at native calls, so a script
+    /// that crosses it is aborted when it next calls a native, or fails when it finishes if
+    /// it doesn't call one.
+    ///
+    /// Objects the host builds directly (e.g. with
+    /// [`Context::make_table`](crate::Context::make_table)) count towards the limit too,
+    /// when this is the context that last ran code on the current thread.
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        let state = crate::state::get(self.as_ptr());
+        state
+            .heap
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(Heap::new(None)))
+            .limit
+            .set(bytes);
+        touch(self.as_ptr(), &state);
+    }
+
+    /// Remove the limit set by [`Context::set_memory_limit`]
+    pub fn clear_memory_limit(&mut self) {
+        if let Some(heap) = crate::state::get(self.as_ptr()).heap.borrow().as_ref() {
+            heap.limit.set(usize::MAX);
+        }
+    }
+
+    /// Bytes currently charged to this context, or 0 if it has neither a memory limit nor a
+    /// custom allocator
+    pub fn memory_used(&mut self) -> usize {
+        crate::state::get(self.as_ptr())
+            .heap
+            .borrow()
+            .as_ref()
+            .map_or(0, |heap| heap.used.get())
+    }
+}
@@ -0,0 +1,318 @@
+//! Closure-backed methods and operators on userdata types
+//!
+//! [`TypeBuilder`] adds methods to a userdata type with Rust closures behind them: they all
+//! share one native, which finds the closure to call from the native function being run.
+//! bolt resolves operators on a userdata value by looking up a method with the operator's
+//! `@` name (`@add` for `a + b`, `@eq` for `a == b`, `@index` for `a[key]`), so operators
+//! are methods like any other.
+//!
+//! ```ignore
+//! ctx.type_builder::<Vec2>()?
+//!     .meta_add(|a, b| Vec2 { x: a.x + b.x, y: a.y + b.y })?
+//!     .meta_eq(|a, b| a == b)?;
+//! ```
+//!
+//! Since `Box<dyn Trait>` is a local type wherever `Trait` is, a trait object can be handed
+//! to scripts the same way, with each method dispatching dynamically back into Rust:
+//!
+//! ```ignore
+//! impl BoltUserdata for Box<dyn EventSink> {
+//!     const NAME: &'static str = "EventSink";
+//! }
+//!
+//! ctx.type_builder::<Box<dyn EventSink>>()?
+//!     .method_mut("send", |sink, (event,): (String,)| sink.send(&event))?;
+//! let sink = ctx.create_userdata(Box::new(Logger) as Box<dyn EventSink>)?;
+//! ```
+
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::types::{NativeFn, Type, Userdata};
+use crate::userdata::BoltUserdata;
+use crate::{
+    Context, ContextRef, Error, FromBoltArgs, FromBoltValue, IntoBoltArgs,
+    MakeBoltValueWithContext, ScalarTypeSignature, Thread, Value,
+};
+
+pub const META_ADD: &str = "@add";
+pub const META_EQ: &str = "@eq";
+pub const META_INDEX: &str = "@index";
+
+/// A method implementation, which reads its arguments from and returns its result to the
+/// thread it's called on
+pub(crate) type MetaFn = Rc<dyn Fn(&mut Context, &mut Thread) -> Result<(), String>>;
+
+/// A closure-backed method, with the name its errors are reported under
+pub(crate) struct Method {
+    name: String,
+    func: MetaFn,
+}
+
+unsafe extern "C" fn call_meta(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+    let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+    let Some(key) = thread
+        .current_callable()
+        .map(|callable| callable.as_ptr() as usize)
+    else {
+        return;
+    };
+
+    // Clone the closure out so it can register more operators while it runs
+    let state = crate::state::get(ctx);
+    let Some((name, func)) = state
+        .meta_fns
+        .borrow()
+        .get(&key)
+        .map(|method| (method.name.clone(), method.func.clone()))
+    else {
+        return;
+    };
+
+    let result = unsafe {
+        crate::unwind::guard(ctx, thr, &name, || {
+            func(&mut ContextRef::from_raw(ctx), &mut thread)
+        })
+    };
+    if let Some(Err(msg)) = result {
+        unsafe { crate::userdata::raise(thr, &name, &msg) };
+    }
+}
+
+/// Borrow the value of `T` inside `userdata` for the length of `f`
+fn with_borrow<T: BoltUserdata, R>(
+    userdata: Userdata,
+    f: impl FnOnce(&T) -> R,
+) -> Result<R, String> {
+    let value = userdata.borrow::<T>().map_err(|err| err.to_string())?;
+    Ok(f(&value))
+}
+
+/// The userdata and the remaining arguments of a method call
+fn method_args<A: FromBoltArgs>(thread: &Thread) -> Result<(Userdata, A), String> {
+    let args: Vec<sys::bt_Value> = (0..thread.argc())
+        .map(|idx| unsafe { sys::bt_arg(thread.as_ptr(), idx) })
+        .collect();
+    let (this, rest) = args.split_first().ok_or("missing the userdata argument")?;
+    let this = <Userdata as FromBoltValue>::from(*this).map_err(|err| err.to_string())?;
+    let rest = A::from_args(rest).map_err(|err| err.to_string())?;
+    Ok((this, rest))
+}
+
+/// Make a native which runs `func` when called, owned by the context's shared native module
+pub(crate) fn closure_native(
+    ctx: &mut Context,
+    name: &str,
+    ret: Type,
+    args: &[Type],
+    func: MetaFn,
+) -> Result<NativeFn, Error> {
+    let signature = ctx
+        .make_signature_type(ret, args)
+        .ok_or(Error::bolt("Failed to create signature type"))?;
+    let module = ctx.native_owner();
+    let native = ctx.make_named_native(module, signature, Some(call_meta), name);
+    let state = crate::state::get(ctx.as_ptr());
+    state.meta_fns.borrow_mut().insert(
+        native.as_ptr() as usize,
+        Method {
+            name: name.to_owned(),
+            func,
+        },
+    );
+    Ok(native)
+}
+
+/// Swap the closure behind a native made by [`closure_native`], returning the one it had
+pub(crate) fn replace_closure(
+    ctx: *mut sys::bt_Context,
+    native: NativeFn,
+    func: MetaFn,
+) -> Option<MetaFn> {
+    let state = crate::state::get(ctx);
+    let mut meta_fns = state.meta_fns.borrow_mut();
+    let method = meta_fns.get_mut(&(native.as_ptr() as usize))?;
+    Some(std::mem::replace(&mut method.func, func))
+}
+
+/// Adds methods and operators to the userdata type of `T`, see the [module docs](self)
+pub struct TypeBuilder<'a, T> {
+    ctx: &'a mut Context,
+    ty: Type,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl Context {
+    /// Start adding methods and operators to `T`'s userdata type, registering it if needed
+    pub fn type_builder<T: BoltUserdata>(&mut self) -> Result<TypeBuilder<'_, T>, Error> {
+        let ty = self.register_userdata::<T>()?;
+        Ok(TypeBuilder {
+            ctx: self,
+            ty,
+            _value: PhantomData,
+        })
+    }
+}
+
+impl<T: BoltUserdata> TypeBuilder<'_, T> {
+    /// The userdata type being built
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+
+    /// Add a method scripts call as `value.name(args...)`, receiving the arguments after the
+    /// userdata as the tuple `A`
+    pub fn method<A, R>(self, name: &str, f: impl Fn(&T, A) -> R + 'static) -> Result<Self, Error>
+    where
+        A: FromBoltArgs + IntoBoltArgs,
+        R: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        self.try_method(name, move |this, args| Ok::<_, Infallible>(f(this, args)))
+    }
+
+    /// Like [`TypeBuilder::method`], for methods that can fail. An `Err` is raised as a
+    /// runtime error in the calling script.
+    pub fn try_method<A, R, E>(
+        self,
+        name: &str,
+        f: impl Fn(&T, A) -> Result<R, E> + 'static,
+    ) -> Result<Self, Error>
+    where
+        A: FromBoltArgs + IntoBoltArgs,
+        R: MakeBoltValueWithContext + ScalarTypeSignature,
+        E: std::fmt::Display,
+    {
+        crate::names::validate(name)?;
+        let ret = R::make_type(self.ctx);
+        let args = A::arg_types(self.ctx);
+        self.meta(
+            name,
+            ret,
+            &args,
+            Rc::new(move |ctx, thread| {
+                let (this, args) = method_args::<A>(thread)?;
+                let result =
+                    with_borrow(this, |this| f(this, args))?.map_err(|err| err.to_string())?;
+                let result = Value::from_raw(result.make_with_context(ctx));
+                thread.return_val(&result);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Like [`TypeBuilder::method`], with mutable access to the value
+    pub fn method_mut<A, R>(
+        self,
+        name: &str,
+        f: impl Fn(&mut T, A) -> R + 'static,
+    ) -> Result<Self, Error>
+    where
+        A: FromBoltArgs + IntoBoltArgs,
+        R: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        crate::names::validate(name)?;
+        let ret = R::make_type(self.ctx);
+        let args = A::arg_types(self.ctx);
+        self.meta(
+            name,
+            ret,
+            &args,
+            Rc::new(move |ctx, thread| {
+                let (this, args) = method_args::<A>(thread)?;
+                let result = {
+                    let mut value = this.borrow_mut::<T>().map_err(|err| err.to_string())?;
+                    f(&mut value, args)
+                };
+                let result = Value::from_raw(result.make_with_context(ctx));
+                thread.return_val(&result);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Implement `a + b` between two values of `T`
+    pub fn meta_add(self, f: impl Fn(&T, &T) -> T + 'static) -> Result<Self, Error> {
+        let ty = self.ty;
+        self.meta(
+            META_ADD,
+            ty,
+            &[ty],
+            Rc::new(move |ctx, thread| {
+                let (a, b) = thread
+                    .args::<(Userdata, Userdata)>()
+                    .map_err(|err| err.to_string())?;
+                let sum = with_borrow(a, |a| with_borrow(b, |b| f(a, b)))??;
+                let sum = ctx.create_userdata(sum).map_err(|err| err.to_string())?;
+                thread.return_val(&sum);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Implement `a == b` between two values of `T`
+    pub fn meta_eq(self, f: impl Fn(&T, &T) -> bool + 'static) -> Result<Self, Error> {
+        let ty = self.ty;
+        let ret = self.ctx.type_bool();
+        self.meta(
+            META_EQ,
+            ret,
+            &[ty],
+            Rc::new(move |_ctx, thread| {
+                let (a, b) = thread
+                    .args::<(Userdata, Userdata)>()
+                    .map_err(|err| err.to_string())?;
+                let equal = with_borrow(a, |a| with_borrow(b, |b| f(a, b)))??;
+                thread.return_val(&equal);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Implement `value[key]`. An `Err` from `f` is raised as a runtime error in the script.
+    pub fn meta_index<K, V>(
+        self,
+        f: impl Fn(&T, K) -> Result<V, Error> + 'static,
+    ) -> Result<Self, Error>
+    where
+        K: FromBoltValue + ScalarTypeSignature,
+        V: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        let ret = V::make_type(self.ctx);
+        let key = K::make_type(self.ctx);
+        self.meta(
+            META_INDEX,
+            ret,
+            &[key],
+            Rc::new(move |ctx, thread| {
+                let (this, key) = thread
+                    .args::<(Userdata, K)>()
+                    .map_err(|err| err.to_string())?;
+                let value =
+                    with_borrow(this, |this| f(this, key))?.map_err(|err| err.to_string())?;
+                let value = Value::from_raw(value.make_with_context(ctx));
+                thread.return_val(&value);
+                Ok(())
+            }),
+        )
+    }
+
+    /// Add the shared native as `name`, calling `func` when it runs. Adding a method again
+    /// replaces the closure behind it.
+    fn meta(self, name: &str, ret: Type, args: &[Type], func: MetaFn) -> Result<Self, Error> {
+        let native = self
+            .ctx
+            .add_method_native(self.ty, name, Some(call_meta), ret, args)?;
+        let state = crate::state::get(self.ctx.as_ptr());
+        state.meta_fns.borrow_mut().insert(
+            native.as_ptr() as usize,
+            Method {
+                name: name.to_owned(),
+                func,
+            },
+        );
+        Ok(self)
+    }
+}
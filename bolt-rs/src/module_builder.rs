@@ -0,0 +1,201 @@
+//! Two-phase native module registration
+//!
+//! A [`ModuleBuilder`] collects exports without touching the context. Nothing is visible to
+//! scripts until [`ModuleBuilder::finish`] or [`ModuleBuilder::finish_validated`] creates the
+//! module, exports everything and registers it in one go.
+
+use std::collections::HashSet;
+
+use bolt_sys::sys;
+
+use crate::types::{Module, Type, TypeKind};
+use crate::{Context, MakeBoltValueWithContext, ModuleError, Value};
+
+enum Export {
+    Native {
+        name: String,
+        proc: sys::bt_NativeProc,
+        ret: Type,
+        args: Vec<Type>,
+    },
+    Value {
+        name: String,
+        ty: Type,
+        value: Value,
+    },
+}
+
+impl Export {
+    fn name(&self) -> &str {
+        match self {
+            Export::Native { name, .. } | Export::Value { name, .. } => name,
+        }
+    }
+}
+
+/// Collects a module's exports for registration in one step, see the [module docs](self)
+///
+/// Exported values aren't rooted while they wait in the builder, so they must be kept alive
+/// by the caller until the module is finished.
+pub struct ModuleBuilder {
+    name: String,
+    exports: Vec<Export>,
+}
+
+impl ModuleBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            exports: Vec::new(),
+        }
+    }
+
+    /// Export a native function with the given signature
+    pub fn native(
+        mut self,
+        name: &str,
+        proc: sys::bt_NativeProc,
+        ret: Type,
+        args: &[Type],
+    ) -> Self {
+        self.exports.push(Export::Native {
+            name: name.to_owned(),
+            proc,
+            ret,
+            args: args.to_vec(),
+        });
+        self
+    }
+
+    /// Export a value declared as `ty`
+    pub fn value(mut self, name: &str, ty: Type, value: Value) -> Self {
+        self.exports.push(Export::Value {
+            name: name.to_owned(),
+            ty,
+            value,
+        });
+        self
+    }
+
+    /// Check every export, returning all problems found without registering anything
+    pub fn validate(&self, ctx: &mut Context) -> Vec<ModuleError> {
+        let mut problems = Vec::new();
+
+        let name = Value::from_raw(self.name.as_str().make_with_context(ctx));
+        if ctx.find_module(name, true).is_some() {
+            problems.push(ModuleError::AlreadyRegistered(self.name.clone()));
+        }
+
+        let mut seen = HashSet::new();
+        for export in &self.exports {
+            let export_name = export.name();
+            if !crate::names::is_identifier(export_name) {
+                problems.push(ModuleError::InvalidName(export_name.to_owned()));
+            }
+            if !seen.insert(export_name) {
+                problems.push(ModuleError::DuplicateExport(export_name.to_owned()));
+            }
+
+            match export {
+                Export::Native { ret, args, .. } => {
+                    for ty in std::iter::once(ret).chain(args) {
+                        check_registered(ctx, export_name, *ty, &mut problems);
+                    }
+                }
+                Export::Value { ty, value, .. } => {
+                    check_registered(ctx, export_name, *ty, &mut problems);
+
+                    let signature = value.as_object().and_then(crate::call::signature_of);
+                    if let Some(signature) = signature
+                        && !crate::call::accepts(*ty, signature)
+                    {
+                        problems.push(ModuleError::SignatureMismatch {
+                            export: export_name.to_owned(),
+                            declared: ty.name(),
+                            actual: signature.name(),
+                        });
+                    }
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Create and register the module without validating it
+    pub fn finish(self, ctx: &mut Context) -> Result<Module, crate::Error> {
+        let module = ctx.make_module();
+        ctx.push_root(module.as_object());
+        let exported = self.export_all(ctx, module);
+        ctx.pop_root();
+        exported?;
+
+        let name = Value::from_raw(self.name.as_str().make_with_context(ctx));
+        ctx.register_module(name, module);
+        Ok(module)
+    }
+
+    /// Validate every export and register the module only if there were no problems
+    pub fn finish_validated(self, ctx: &mut Context) -> Result<Module, Vec<ModuleError>> {
+        let problems = self.validate(ctx);
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        self.finish(ctx).map_err(|err| {
+            vec![match err {
+                crate::Error::Module(err) => err,
+                err => ModuleError::Export(Box::new(err)),
+            }]
+        })
+    }
+
+    fn export_all(&self, ctx: &mut Context, module: Module) -> Result<(), crate::Error> {
+        for export in &self.exports {
+            match export {
+                Export::Native {
+                    name,
+                    proc,
+                    ret,
+                    args,
+                } => ctx.module_export_native(module, name.as_str(), *proc, *ret, args)?,
+                Export::Value { name, ty, value } => {
+                    let key = Value::from_raw(name.as_str().make_with_context(ctx));
+                    ctx.module_export(module, *ty, key, *value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Named types that can only be referred to once registered must be in the type registry
+fn check_registered(ctx: &mut Context, export: &str, ty: Type, problems: &mut Vec<ModuleError>) {
+    match ty.kind() {
+        TypeKind::Tableshape | TypeKind::Enum | TypeKind::Userdata | TypeKind::Primitive => {
+            let name = ty.name();
+            let key = Value::from_raw(name.as_str().make_with_context(ctx));
+            let registered = ctx
+                .find_type(key)
+                .is_some_and(|mut found| found.type_is_equal(ty));
+            if !registered {
+                problems.push(ModuleError::UnregisteredType {
+                    export: export.to_owned(),
+                    ty: name,
+                });
+            }
+        }
+        TypeKind::Array => {
+            if let Some(inner) = ty.array_element_type() {
+                check_registered(ctx, export, inner, problems);
+            }
+        }
+        TypeKind::Signature | TypeKind::NativeFn => {
+            let args = ty.signature_args().unwrap_or_default();
+            for inner in args.into_iter().chain(ty.return_type()) {
+                check_registered(ctx, export, inner, problems);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,36 @@
+//! Validation of names handed to bolt
+//!
+//! bolt accepts any string as an export or type name, but a name that isn't an identifier
+//! can never be written in a script, so the export is silently unreachable. Names are
+//! checked up front instead, failing with [`ModuleError::InvalidName`].
+
+use crate::{Error, FromBoltValue, ModuleError, Value};
+
+/// Words scripts can't use as identifiers
+const RESERVED: &[&str] = &[
+    "and", "as", "break", "continue", "else", "enum", "export", "false", "final", "fn", "for",
+    "from", "if", "import", "in", "is", "let", "method", "not", "null", "or", "return", "to",
+    "true", "type", "unsealed", "var", "while",
+];
+
+/// Whether `name` can be written as a bolt identifier
+pub(crate) fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&name)
+}
+
+/// Check that `name` is a legal bolt identifier
+pub(crate) fn validate(name: &str) -> Result<(), Error> {
+    if is_identifier(name) {
+        Ok(())
+    } else {
+        Err(ModuleError::InvalidName(name.to_owned()).into())
+    }
+}
+
+/// Like [`validate`] for a name already converted to a bolt value
+pub(crate) fn validate_value(name: Value) -> Result<(), Error> {
+    validate(&<String as FromBoltValue>::from(name.0)?)
+}
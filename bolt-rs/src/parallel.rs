@@ -0,0 +1,165 @@
+//! Running many independent scripts across threads
+//!
+//! A [`Context`] can't leave the thread that created it, so batch work is spread by giving
+//! each worker thread a context of its own. [`run_all`] does that: every worker opens a
+//! context, runs the host's `init` on it, then takes scripts from the batch until none
+//! are left.
+//!
+//! ```ignore
+//! let formulas = ["export let result = 1 + 2", "export let result = 2 * 21"];
+//! let outcomes = parallel::run_all(&formulas, |ctx| {
+//!     ctx.open_all_std();
+//!     Ok(())
+//! });
+//! assert!(matches!(outcomes[1], Ok(RecordedValue::Number(n)) if n == 42.0));
+//! ```
+//!
+//! Each script runs as its own module, and its result is whatever it exports as `result`
+//! (null if nothing). Values can't be carried out of the worker's context, so results come
+//! back as [`RecordedValue`]s. Scripts sharing a worker share its context, so one script's
+//! registry writes and registered modules are visible to the scripts after it. A worker
+//! whose `init` failed tries again with a fresh context for its next script, and the
+//! script it failed for gets the error `init` returned.
+//!
+//! [`Settings::interrupt_after`] interrupts a script at its first native call past that
+//! long, failing it with [`Error::Interrupted`]. Like any interrupt it can't stop a script
+//! that stops calling natives, see [`crate::watchdog`].
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::replay::RecordedValue;
+use crate::{Context, Error};
+
+/// The export a script's result is read from
+pub const RESULT_EXPORT: &str = "result";
+
+/// How a batch is run, see [`run_all_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// The most worker threads to start, one per available core by default
+    pub workers: usize,
+    /// How long until each script is interrupted, never by default
+    pub interrupt_after: Option<Duration>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            workers: std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            interrupt_after: None,
+        }
+    }
+}
+
+/// Run every script in `sources` on a worker per available core, returning each one's
+/// result in the same order
+pub fn run_all<S>(
+    sources: &[S],
+    init: impl Fn(&mut Context) -> Result<(), Error> + Sync,
+) -> Vec<Result<RecordedValue, Error>>
+where
+    S: AsRef<str> + Sync,
+{
+    run_all_with(Settings::default(), sources, init)
+}
+
+/// Like [`run_all`], on at most `workers` threads
+pub fn run_all_with_workers<S>(
+    workers: usize,
+    sources: &[S],
+    init: impl Fn(&mut Context) -> Result<(), Error> + Sync,
+) -> Vec<Result<RecordedValue, Error>>
+where
+    S: AsRef<str> + Sync,
+{
+    let settings = Settings {
+        workers,
+        ..Settings::default()
+    };
+    run_all_with(settings, sources, init)
+}
+
+/// Like [`run_all`], with the worker count and interrupt from `settings`
+pub fn run_all_with<S>(
+    settings: Settings,
+    sources: &[S],
+    init: impl Fn(&mut Context) -> Result<(), Error> + Sync,
+) -> Vec<Result<RecordedValue, Error>>
+where
+    S: AsRef<str> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let workers = settings.workers.clamp(1, sources.len().max(1));
+    let mut outcomes: Vec<Option<Result<RecordedValue, Error>>> = std::iter::repeat_with(|| None)
+        .take(sources.len())
+        .collect();
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| work(sources, &init, settings.interrupt_after, &next)))
+            .collect();
+        for handle in handles {
+            let finished = match handle.join() {
+                Ok(finished) => finished,
+                Err(payload) => std::panic::resume_unwind(payload),
+            };
+            for (idx, outcome) in finished {
+                outcomes[idx] = Some(outcome);
+            }
+        }
+    });
+
+    outcomes
+        .into_iter()
+        .map(|outcome| outcome.expect("every script is taken by a worker"))
+        .collect()
+}
+
+/// Take scripts off the batch until it's empty, returning each one's index and result
+fn work<S: AsRef<str>>(
+    sources: &[S],
+    init: &(impl Fn(&mut Context) -> Result<(), Error> + Sync),
+    interrupt_after: Option<Duration>,
+    next: &AtomicUsize,
+) -> Vec<(usize, Result<RecordedValue, Error>)> {
+    let mut ctx = None;
+    let mut finished = Vec::new();
+    loop {
+        let idx = next.fetch_add(1, Ordering::Relaxed);
+        let Some(source) = sources.get(idx) else {
+            return finished;
+        };
+        let outcome = ready(&mut ctx, init).and_then(|ctx| match interrupt_after {
+            Some(after) => ctx.interrupt_after(after, |ctx| run_one(ctx, idx, source.as_ref())),
+            None => run_one(ctx, idx, source.as_ref()),
+        });
+        finished.push((idx, outcome));
+    }
+}
+
+/// The worker's context, opening and initializing one if it doesn't have one yet
+fn ready<'a>(
+    ctx: &'a mut Option<Context>,
+    init: &impl Fn(&mut Context) -> Result<(), Error>,
+) -> Result<&'a mut Context, Error> {
+    if ctx.is_none() {
+        let mut fresh = Context::new();
+        init(&mut fresh)?;
+        *ctx = Some(fresh);
+    }
+    Ok(ctx.as_mut().expect("the context was just initialized"))
+}
+
+fn run_one(ctx: &mut Context, idx: usize, source: &str) -> Result<RecordedValue, Error> {
+    let module = ctx.compile_module(source, format!("<script {idx}>").as_str())?;
+    ctx.execute_module(module)?;
+    let result = module
+        .export_table()
+        .and_then(|exports| exports.get_str(RESULT_EXPORT));
+    Ok(match result {
+        Some(result) => crate::replay::snapshot(ctx, result),
+        None => RecordedValue::Null,
+    })
+}
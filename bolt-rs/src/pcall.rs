@@ -0,0 +1,80 @@
+//! Calls that trap their runtime errors
+//!
+//! A host calling several script callbacks, say one per plugin, usually wants a callback
+//! that raises to fail on its own rather than take the rest of the work down with it.
+//! [`Context::pcall`] runs the call on a thread of its own and hands back whatever went
+//! wrong as a [`RuntimeError`]. A thread that raised is thrown away rather than reused, so
+//! the next call, protected or not, starts from a clean stack.
+//!
+//! ```ignore
+//! for callback in callbacks {
+//!     if let Err(err) = ctx.pcall(callback, &[event]) {
+//!         log::warn!("callback failed: {err}");
+//!     }
+//! }
+//! ```
+
+use crate::errors::ScriptThrow;
+use crate::traceback::Traceback;
+use crate::{Context, Error, Value};
+
+/// Why a protected call failed
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The error the call failed with
+    pub error: Box<Error>,
+}
+
+impl RuntimeError {
+    /// The value passed to the script `throw`, if that's how the call failed
+    pub fn thrown(&self) -> Option<&ScriptThrow> {
+        match &*self.error {
+            Error::Script(thrown) => Some(thrown),
+            _ => None,
+        }
+    }
+
+    /// The script stack when the error was raised, if it was raised by a script
+    pub fn traceback(&self) -> Option<&Traceback> {
+        match &*self.error {
+            Error::Runtime { traceback, .. } => Some(traceback),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+impl From<Error> for RuntimeError {
+    fn from(error: Error) -> Self {
+        let message = match &error {
+            Error::Runtime { message, .. } => message.clone(),
+            other => other.to_string(),
+        };
+        Self {
+            message,
+            error: Box::new(error),
+        }
+    }
+}
+
+impl Context {
+    /// Call `func` with `args`, trapping any error it raises, see the [module docs](self)
+    ///
+    /// Safe to use from inside a native while a script is running: the call gets its own
+    /// thread, so the script that called the native carries on if this call fails.
+    pub fn pcall(&mut self, func: Value, args: &[Value]) -> Result<Value, RuntimeError> {
+        self.call(func, args).map_err(RuntimeError::from)
+    }
+}
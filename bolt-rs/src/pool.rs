@@ -0,0 +1,218 @@
+//! Reusing initialized contexts across requests
+//!
+//! Opening a context and running the host's setup (opening the standard library,
+//! registering modules and types) costs far more than a typical request script. A
+//! [`ContextPool`] does that setup ahead of time for a number of contexts and hands them
+//! out one request at a time:
+//!
+//! ```ignore
+//! let pool = ContextPool::new(8, |ctx| {
+//!     ctx.open_all_std();
+//!     register_api(ctx)
+//! })?;
+//!
+//! let mut ctx = pool.checkout()?;
+//! let response = ctx.call(handler, &[request])?;
+//! ```
+//!
+//! A checked out context goes back to the pool when it's dropped. Per-request settings are
+//! put back the way `init` left them: the hook, native call budget, call depth and memory
+//! limits, and any running profiler, recording, pending interrupt or unreported failure are
+//! dropped. Script state isn't, so globals and module state a request changed are seen by
+//! the next request to get that context. Call [`PooledContext::discard`] instead when a
+//! request may have left it dirty, and a freshly initialized context takes its place.
+//!
+//! Like [`Context`], a pool stays on the thread that created it; run one pool per worker
+//! thread.
+
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::hooks::{Hook, HookMask};
+use crate::{Context, ContextBuilder, Error};
+
+type Init = Rc<dyn Fn(&mut Context) -> Result<(), Error>>;
+
+/// The per-request settings a context had once `init` ran on it
+struct Settings {
+    hook: Option<(HookMask, Hook)>,
+    native_budget: Option<u64>,
+    max_call_depth: Option<u32>,
+    memory_limit: Option<usize>,
+}
+
+impl Settings {
+    fn of(ctx: &Context) -> Self {
+        let state = crate::state::get(ctx.as_ptr());
+        Self {
+            hook: state.hook.borrow().clone(),
+            native_budget: state.native_budget.get(),
+            max_call_depth: state.max_call_depth.get(),
+            memory_limit: crate::memory::limit(&state),
+        }
+    }
+
+    /// Put `ctx` back the way it was when these settings were taken
+    fn restore(&self, ctx: &mut Context) {
+        let _ = ctx.stop_recording();
+        let state = crate::state::get(ctx.as_ptr());
+        *state.hook.borrow_mut() = self.hook.clone();
+        state.native_budget.set(self.native_budget);
+        state.max_call_depth.set(self.max_call_depth);
+        state.profiler.take();
+        state.interrupt.clear();
+        state.native_budget_spent.set(false);
+        state.call_depth_exceeded.set(false);
+        crate::memory::take_exceeded(&state);
+        state.thrown.take();
+        state.live_thrown.take();
+        state.host_panic.take();
+        state.traceback.take();
+        state.import_cycle.take();
+        state.prelude_error.take();
+        drop(state);
+        match self.memory_limit {
+            Some(bytes) => ctx.set_memory_limit(bytes),
+            None => ctx.clear_memory_limit(),
+        }
+    }
+}
+
+/// Initialized contexts waiting to be checked out, see the [module docs](self)
+pub struct ContextPool {
+    builder: ContextBuilder,
+    init: Init,
+    size: usize,
+    idle: RefCell<Vec<(Context, Settings)>>,
+}
+
+impl std::fmt::Debug for ContextPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContextPool")
+            .field("builder", &self.builder)
+            .field("size", &self.size)
+            .field("idle", &self.idle())
+            .finish()
+    }
+}
+
+impl ContextPool {
+    /// Open `size` default contexts, running `init` on each
+    pub fn new(
+        size: usize,
+        init: impl Fn(&mut Context) -> Result<(), Error> + 'static,
+    ) -> Result<Self, Error> {
+        Self::with_builder(ContextBuilder::new(), size, init)
+    }
+
+    /// Like [`ContextPool::new`], opening each context with `builder`
+    pub fn with_builder(
+        builder: ContextBuilder,
+        size: usize,
+        init: impl Fn(&mut Context) -> Result<(), Error> + 'static,
+    ) -> Result<Self, Error> {
+        let pool = Self {
+            builder,
+            init: Rc::new(init),
+            size,
+            idle: RefCell::new(Vec::with_capacity(size)),
+        };
+        for _ in 0..size {
+            let opened = pool.open()?;
+            pool.idle.borrow_mut().push(opened);
+        }
+        Ok(pool)
+    }
+
+    /// Take an idle context, opening and initializing a new one if they're all checked out
+    pub fn checkout(&self) -> Result<PooledContext<'_>, Error> {
+        let idle = self.idle.borrow_mut().pop();
+        let (ctx, settings) = match idle {
+            Some(idle) => idle,
+            None => self.open()?,
+        };
+        Ok(PooledContext {
+            pool: self,
+            ctx: Some(ctx),
+            settings: Some(settings),
+        })
+    }
+
+    /// How many contexts are ready to be checked out
+    pub fn idle(&self) -> usize {
+        self.idle.borrow().len()
+    }
+
+    /// How many idle contexts the pool keeps, extra ones are closed when they come back
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn open(&self) -> Result<(Context, Settings), Error> {
+        let mut ctx = self.builder.clone().try_build()?;
+        (self.init)(&mut ctx)?;
+        let settings = Settings::of(&ctx);
+        Ok((ctx, settings))
+    }
+
+    fn recycle(&self, mut ctx: Context, settings: Settings) {
+        if self.idle() < self.size {
+            settings.restore(&mut ctx);
+            self.idle.borrow_mut().push((ctx, settings));
+        }
+    }
+}
+
+/// A context checked out of a [`ContextPool`], returned to it when dropped
+///
+/// Derefs to the context, so it can be used anywhere a `&mut Context` is expected.
+pub struct PooledContext<'a> {
+    pool: &'a ContextPool,
+    ctx: Option<Context>,
+    settings: Option<Settings>,
+}
+
+impl std::fmt::Debug for PooledContext<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PooledContext")
+            .field("pool", &self.pool)
+            .field("ctx", &self.ctx)
+            .finish()
+    }
+}
+
+impl PooledContext<'_> {
+    /// Close the context instead of returning it, replacing it with a freshly initialized
+    /// one if the pool would otherwise be short
+    pub fn discard(mut self) -> Result<(), Error> {
+        drop(self.ctx.take());
+        if self.pool.idle() < self.pool.size {
+            let opened = self.pool.open()?;
+            self.pool.idle.borrow_mut().push(opened);
+        }
+        Ok(())
+    }
+}
+
+impl Deref for PooledContext<'_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.ctx.as_ref().expect("only taken on drop or discard")
+    }
+}
+
+impl DerefMut for PooledContext<'_> {
+    fn deref_mut(&mut self) -> &mut Context {
+        self.ctx.as_mut().expect("only taken on drop or discard")
+    }
+}
+
+impl Drop for PooledContext<'_> {
+    fn drop(&mut self) {
+        if let (Some(ctx), Some(settings)) = (self.ctx.take(), self.settings.take()) {
+            self.pool.recycle(ctx, settings);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+//! Host modules prepared once and shared by many contexts
+//!
+//! Hosts that run each script in its own context (one per player, request or sandbox)
+//! otherwise repeat all of their registration code for every context. A [`Prelude`]
+//! describes those modules once. It's frozen when built and cheap to clone, and
+//! [`Context::install_prelude`] only records which modules are available: each one is
+//! built inside a context the first time a source there imports it, the same way
+//! [lazily opened std modules](crate::ContextBuilder::lazy_std) are. Contexts that never
+//! import a module never pay for it.
+//!
+//! A module that fails to build stays unregistered, and the run or compile whose import
+//! triggered it fails with the error building it hit.
+//!
+//! The `prelude` bench compares this with registering modules eagerly across a pool of
+//! 1000 contexts, and measures importing a module that fails to build.
+
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::replay::RecordedValue;
+use crate::types::Type;
+use crate::{
+    Context, Error, FromBoltArgs, IntoBoltArgs, MakeBoltValue, MakeBoltValueWithContext,
+    ModuleError, ScalarTypeSignature, Value,
+};
+
+/// Builds a type inside the context a module is being installed in, e.g.
+/// `Context::type_number`
+pub type TypeFn = fn(&mut Context) -> Type;
+
+enum Export {
+    Native {
+        name: String,
+        proc: sys::bt_NativeProc,
+        ret: TypeFn,
+        args: Vec<TypeFn>,
+    },
+    Value {
+        name: String,
+        value: RecordedValue,
+    },
+}
+
+enum Source {
+    Exports(Vec<Export>),
+    Script(String),
+}
+
+struct PreludeModule {
+    name: String,
+    source: Source,
+}
+
+/// A frozen set of module definitions, see the [module docs](self)
+#[derive(Clone)]
+pub struct Prelude {
+    modules: Rc<[PreludeModule]>,
+}
+
+impl std::fmt::Debug for Prelude {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.modules.iter().map(|module| &module.name))
+            .finish()
+    }
+}
+
+/// Collects the modules of a [`Prelude`]
+#[derive(Default)]
+pub struct PreludeBuilder {
+    modules: Vec<PreludeModule>,
+}
+
+/// The exports of one native module in a [`PreludeBuilder`]
+#[derive(Default)]
+pub struct PreludeModuleBuilder {
+    exports: Vec<Export>,
+}
+
+impl PreludeModuleBuilder {
+    /// Export a native function, with its signature given as type constructors
+    pub fn native(
+        mut self,
+        name: &str,
+        proc: sys::bt_NativeProc,
+        ret: TypeFn,
+        args: &[TypeFn],
+    ) -> Self {
+        self.exports.push(Export::Native {
+            name: name.to_owned(),
+            proc,
+            ret,
+            args: args.to_vec(),
+        });
+        self
+    }
+
+    /// Export a constant, typed the way [`Context::register_data_module`] types its entries
+    pub fn value(mut self, name: &str, value: RecordedValue) -> Self {
+        self.exports.push(Export::Value {
+            name: name.to_owned(),
+            value,
+        });
+        self
+    }
+}
+
+impl Prelude {
+    pub fn builder() -> PreludeBuilder {
+        PreludeBuilder::default()
+    }
+
+    /// Names of the modules the prelude provides
+    pub fn module_names(&self) -> impl Iterator<Item = &str> {
+        self.modules.iter().map(|module| module.name.as_str())
+    }
+}
+
+impl PreludeBuilder {
+    /// Add a module of native functions and constants
+    pub fn module(
+        mut self,
+        name: &str,
+        build: impl FnOnce(PreludeModuleBuilder) -> PreludeModuleBuilder,
+    ) -> Self {
+        let exports = build(PreludeModuleBuilder::default()).exports;
+        self.modules.push(PreludeModule {
+            name: name.to_owned(),
+            source: Source::Exports(exports),
+        });
+        self
+    }
+
+    /// Add a module written in bolt, compiled in each context that imports it
+    pub fn script(mut self, name: &str, source: &str) -> Self {
+        self.modules.push(PreludeModule {
+            name: name.to_owned(),
+            source: Source::Script(source.to_owned()),
+        });
+        self
+    }
+
+    /// Check every name and freeze the prelude
+    pub fn build(self) -> Result<Prelude, Error> {
+        for (idx, module) in self.modules.iter().enumerate() {
+            crate::names::validate(&module.name)?;
+            if self.modules[..idx].iter().any(|m| m.name == module.name) {
+                return Err(ModuleError::AlreadyRegistered(module.name.clone()).into());
+            }
+            if let Source::Exports(exports) = &module.source {
+                for export in exports {
+                    let (Export::Native { name, .. } | Export::Value { name, .. }) = export;
+                    crate::names::validate(name)?;
+                }
+            }
+        }
+
+        Ok(Prelude {
+            modules: self.modules.into(),
+        })
+    }
+}
+
+impl Context {
+    /// Make every module in `prelude` importable, building each one on first import
+    ///
+    /// Modules already installed by an earlier prelude stay available.
+    pub fn install_prelude(&mut self, prelude: &Prelude) {
+        let state = crate::state::get(self.as_ptr());
+        let mut pending = state.pending_prelude.borrow_mut();
+        pending.extend((0..prelude.modules.len()).map(|idx| (prelude.clone(), idx)));
+    }
+
+    /// Make `value` a global visible to every script compiled afterwards, with its type
+    /// taken from `T`
+    pub fn add_prelude<T>(&mut self, name: &str, value: T) -> Result<(), Error>
+    where
+        T: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        crate::names::validate(name)?;
+        let ty = T::make_type(self);
+        self.gc_pause();
+        let value = Value::from_raw(value.make_with_context(self));
+        let key = Value::from_raw(name.make_with_context(self));
+        self.register_prelude(key, ty, value);
+        self.gc_unpause();
+        Ok(())
+    }
+
+    /// Make `f` a global function visible to every script compiled afterwards, taking the
+    /// tuple `A` as its arguments
+    pub fn add_prelude_fn<A, R>(
+        &mut self,
+        name: &str,
+        f: impl Fn(A) -> R + 'static,
+    ) -> Result<(), Error>
+    where
+        A: FromBoltArgs + IntoBoltArgs,
+        R: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        self.add_prelude_try_fn(name, move |args| Ok::<_, Infallible>(f(args)))
+    }
+
+    /// Like [`Context::add_prelude_fn`], for functions that can fail. An `Err` is raised as
+    /// a runtime error in the calling script.
+    pub fn add_prelude_try_fn<A, R, E>(
+        &mut self,
+        name: &str,
+        f: impl Fn(A) -> Result<R, E> + 'static,
+    ) -> Result<(), Error>
+    where
+        A: FromBoltArgs + IntoBoltArgs,
+        R: MakeBoltValueWithContext + ScalarTypeSignature,
+        E: std::fmt::Display,
+    {
+        crate::names::validate(name)?;
+        let ret = R::make_type(self);
+        let args = A::arg_types(self);
+        self.gc_pause();
+        let native = crate::meta::closure_native(
+            self,
+            name,
+            ret,
+            &args,
+            Rc::new(move |ctx, thread| {
+                let args = thread.args::<A>().map_err(|err| err.to_string())?;
+                let result = f(args).map_err(|err| err.to_string())?;
+                let result = Value::from_raw(result.make_with_context(ctx));
+                thread.return_val(&result);
+                Ok(())
+            }),
+        );
+        let registered = native.map(|native| {
+            let ty = unsafe { Type::from_raw_unchecked((*native.as_ptr()).type_) };
+            let key = Value::from_raw(name.make_with_context(self));
+            self.register_prelude(key, ty, Value::from_raw(native.as_object().make()));
+        });
+        self.gc_unpause();
+        registered
+    }
+}
+
+/// Build a pending prelude module if one is called `name`
+pub(crate) fn open_pending(ctx: *mut sys::bt_Context, name: &[u8]) {
+    let state = crate::state::get(ctx);
+    let found = {
+        let mut pending = state.pending_prelude.borrow_mut();
+        pending
+            .iter()
+            .position(|(prelude, idx)| prelude.modules[*idx].name.as_bytes() == name)
+            .map(|pos| pending.swap_remove(pos))
+    };
+    let Some((prelude, idx)) = found else {
+        return;
+    };
+
+    let mut ctx = unsafe { crate::ContextRef::from_raw(ctx) };
+    let module = &prelude.modules[idx];
+    let installed = match &module.source {
+        Source::Exports(exports) => install_exports(&mut ctx, &module.name, exports),
+        Source::Script(source) => install_script(&mut ctx, &module.name, source),
+    };
+    // A module that fails to build is left unregistered, so the import that triggered it
+    // fails, and that failure reports why
+    if let Err(err) = installed {
+        *state.prelude_error.borrow_mut() = Some(err);
+    }
+}
+
+fn install_exports(ctx: &mut Context, name: &str, exports: &[Export]) -> Result<(), Error> {
+    ctx.gc_pause();
+    let module = ctx.make_module();
+    let mut result = Ok(());
+    for export in exports {
+        result = match export {
+            Export::Native {
+                name,
+                proc,
+                ret,
+                args,
+            } => {
+                let ret = ret(ctx);
+                let args: Vec<Type> = args.iter().map(|arg| arg(ctx)).collect();
+                ctx.module_export_native(module, name.as_str(), *proc, ret, &args)
+            }
+            Export::Value { name, value } => match crate::replay::rebuild(ctx, value) {
+                Some(value) => {
+                    let ty = crate::data_module::infer_type(ctx, value);
+                    let key = Value::from_raw(name.as_str().make_with_context(ctx));
+                    ctx.module_export(module, ty, key, value)
+                }
+                None => Err(Error::bolt(format!("Prelude value '{name}' is opaque"))),
+            },
+        };
+        if result.is_err() {
+            break;
+        }
+    }
+    if result.is_ok() {
+        let name = Value::from_raw(name.make_with_context(ctx));
+        ctx.register_module(name, module);
+    }
+    ctx.gc_unpause();
+    result
+}
+
+fn install_script(ctx: &mut Context, name: &str, source: &str) -> Result<(), Error> {
+    let module = ctx.compile_module(source, name)?;
+    // Making the name and registering both allocate, so the module stays rooted until
+    // the module table holds it
+    ctx.push_root(module.as_object());
+    let executed = ctx.execute_module(module);
+    if executed.is_ok() {
+        let name = Value::from_raw(name.make_with_context(ctx));
+        let name_rooted = name.as_object().inspect(|obj| ctx.push_root(*obj));
+        ctx.register_module(name, module);
+        if name_rooted.is_some() {
+            ctx.pop_root();
+        }
+    }
+    ctx.pop_root();
+    executed
+}
@@ -0,0 +1,167 @@
+//! Finding out where scripts spend their time
+//!
+//! [`Context::profiler`] is a native-boundary profiler: it samples the script call stack
+//! whenever a native is entered or left, and attributes the time since the previous sample
+//! to the functions on it: all of it to the innermost function as self time, and to every
+//! function on the stack as total time.
+//!
+//! ```ignore
+//! ctx.profiler().start();
+//! run_frame(&mut ctx)?;
+//! for function in ctx.profiler().stop().functions.iter().take(5) {
+//!     println!("{}: {:?} self, {:?} total", function.function, function.self_time, function.total_time);
+//! }
+//! ```
+//!
+//! There is no timer: bolt can't be interrupted to take a sample, so the only chances to
+//! look at the stack are native calls, which all go through the trampoline in
+//! [`crate::stats`], and the interval only limits how often those are taken. Time spent
+//! between two native calls all goes to whatever is on the stack at the second one, so a
+//! script that rarely calls natives gets a coarse profile, and one that never does gets an
+//! empty one.
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use bolt_sys::sys;
+
+use crate::Context;
+
+/// How often samples are taken unless another interval is given
+pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Time attributed to one function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub function: String,
+    pub module: Option<String>,
+    /// Samples taken while the function was the innermost one on the stack
+    pub samples: u64,
+    /// Time spent in the function itself
+    pub self_time: Duration,
+    /// Time spent in the function and everything it called
+    pub total_time: Duration,
+}
+
+/// What the profiler found between [`Profiler::start`] and [`Profiler::stop`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    /// How long the profiler ran
+    pub duration: Duration,
+    /// Every function sampled, most self time first
+    pub functions: Vec<FunctionProfile>,
+}
+
+impl ProfileReport {
+    /// The profile of the function exported as `function`, or registered under that name
+    pub fn function(&self, function: &str) -> Option<&FunctionProfile> {
+        self.functions
+            .iter()
+            .find(|profile| profile.function == function)
+    }
+}
+
+pub(crate) struct Sampler {
+    interval: Duration,
+    started: Instant,
+    last: Instant,
+    functions: HashMap<(String, Option<String>), FunctionProfile>,
+}
+
+/// Attribute the time since the last sample to the stack of the running thread, leaving
+/// out the `skip` innermost frames
+pub(crate) fn sample(ctx: *mut sys::bt_Context, skip: usize) {
+    let state = crate::state::get(ctx);
+    let mut sampler = state.profiler.borrow_mut();
+    let Some(sampler) = sampler.as_mut() else {
+        return;
+    };
+    let now = Instant::now();
+    let elapsed = now - sampler.last;
+    if elapsed < sampler.interval {
+        return;
+    }
+    sampler.last = now;
+
+    let frames = crate::traceback::walk(ctx, 0).frames;
+    let mut seen = HashSet::new();
+    for (idx, frame) in frames.into_iter().skip(skip).enumerate() {
+        let key = (frame.function, frame.module);
+        let profile = sampler
+            .functions
+            .entry(key.clone())
+            .or_insert_with(|| FunctionProfile {
+                function: key.0.clone(),
+                module: key.1.clone(),
+                samples: 0,
+                self_time: Duration::ZERO,
+                total_time: Duration::ZERO,
+            });
+        if idx == 0 {
+            profile.samples += 1;
+            profile.self_time += elapsed;
+        }
+        // Recursive functions are on the stack more than once, but only ran for `elapsed`
+        if seen.insert(key) {
+            profile.total_time += elapsed;
+        }
+    }
+}
+
+/// Starts and stops the context's profiler, see the [module docs](self)
+pub struct Profiler<'a> {
+    ctx: &'a mut Context,
+}
+
+impl Context {
+    /// Start or stop profiling this context
+    ///
+    /// Samples are only taken at native calls, not on a timer, so pure script code between
+    /// them isn't broken down any further. See the [module docs](self).
+    pub fn profiler(&mut self) -> Profiler<'_> {
+        Profiler { ctx: self }
+    }
+}
+
+impl Profiler<'_> {
+    /// Start sampling at native calls at most every [`DEFAULT_INTERVAL`], discarding any
+    /// profile in progress
+    pub fn start(self) {
+        self.start_with_interval(DEFAULT_INTERVAL);
+    }
+
+    /// Start sampling at most once per `interval`, discarding any profile in progress
+    pub fn start_with_interval(self, interval: Duration) {
+        let now = Instant::now();
+        *crate::state::get(self.ctx.as_ptr()).profiler.borrow_mut() = Some(Sampler {
+            interval,
+            started: now,
+            last: now,
+            functions: HashMap::new(),
+        });
+    }
+
+    pub fn is_running(&self) -> bool {
+        crate::state::get(self.ctx.as_ptr())
+            .profiler
+            .borrow()
+            .is_some()
+    }
+
+    /// Stop sampling and report what was found, an empty report if it wasn't running
+    pub fn stop(self) -> ProfileReport {
+        let Some(sampler) = crate::state::get(self.ctx.as_ptr()).profiler.take() else {
+            return ProfileReport::default();
+        };
+        let mut functions: Vec<FunctionProfile> = sampler.functions.into_values().collect();
+        functions.sort_by(|a, b| {
+            b.self_time
+                .cmp(&a.self_time)
+                .then_with(|| b.total_time.cmp(&a.total_time))
+        });
+        ProfileReport {
+            duration: sampler.started.elapsed(),
+            functions,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! A per-context registry for values the host needs to keep between calls
+//!
+//! Like Lua's registry, this is a table owned by the context rather than by any script, so
+//! native callbacks can stash script objects under a string key without inventing globals.
+//! The table is referenced for the lifetime of the context, so anything stored in it stays
+//! alive until it is removed or replaced.
+
+use bolt_sys::sys;
+
+use crate::types::Table;
+use crate::{Context, MakeBoltValueWithContext, Value};
+
+impl Context {
+    /// Store `value` under `key`, returning the value it replaced
+    pub fn registry_set(&mut self, key: &str, value: Value) -> Option<Value> {
+        let registry = self.registry();
+        self.gc_pause();
+        let previous = registry.get(self, key);
+        let key = Value::from_raw(key.make_with_context(self));
+        self.table_set(registry, key, value);
+        self.gc_unpause();
+        previous
+    }
+
+    /// The value stored under `key`, if any
+    pub fn registry_get(&mut self, key: &str) -> Option<Value> {
+        self.registry().get_str(key)
+    }
+
+    /// Remove the value stored under `key`, allowing it to be collected
+    pub fn registry_remove(&mut self, key: &str) -> Option<Value> {
+        let registry = self.registry();
+        let previous = registry.get_str(key)?;
+        let key = key.make_with_context(self);
+        unsafe { sys::bt_table_delete_key(registry.as_ptr(), key) };
+        Some(previous)
+    }
+
+    /// The registry table, created and referenced on first use
+    fn registry(&mut self) -> Table {
+        let state = crate::state::get(self.as_ptr());
+        if let Some(registry) = state.registry.get() {
+            return registry;
+        }
+
+        let registry = self.make_table(4);
+        self.add_ref(registry.as_object());
+        state.registry.set(Some(registry));
+        registry
+    }
+}
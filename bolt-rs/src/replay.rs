@@ -0,0 +1,710 @@
+//! Recording script executions and replaying them against another build
+//!
+//! While recording is on, every top-level [`Context::run`] and [`Context::call`] is captured
+//! along with its arguments and outcome. A saved [`Recording`] can be replayed into a fresh
+//! context, typically one built against a different revision of the bolt submodule, and
+//! every execution whose outcome changed is reported.
+//!
+//! Values are captured by content: null, booleans, numbers, strings, arrays, tables and
+//! userdata with [serialization hooks](Context::userdata_serialize_with) round-trip exactly,
+//! anything else (functions, other userdata) is kept as its debug rendering and can only be
+//! compared, not passed back in. Executions started from inside a native,
+//! such as a host callback calling back into a script, are part of the outer execution and
+//! aren't recorded separately. Calls through a [`crate::CallHandle`] skip recording to
+//! stay cheap.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::state::ContextState;
+use crate::types::{Array, Object, Table, Userdata};
+use crate::{
+    Context, Error, FromBoltValue, MakeBoltValue, MakeBoltValueWithContext, Value, ValueType,
+};
+
+const HEADER: &str = "bolt-replay 1";
+
+/// Arrays and tables nested deeper than this are captured as opaque values
+const MAX_DEPTH: usize = 64;
+
+/// A script value captured by content
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<RecordedValue>),
+    /// Pairs are kept in a canonical order, so tables compare equal regardless of how the VM
+    /// laid them out
+    Table(Vec<(RecordedValue, RecordedValue)>),
+    /// A userdata with serialization hooks, kept as the table
+    /// [`Context::serialize_userdata`] encoded it as
+    Userdata(Box<RecordedValue>),
+    /// A value that can't be rebuilt, kept as its debug rendering
+    Opaque(String),
+}
+
+/// What an execution returned, or the error it failed with
+pub type Outcome = Result<RecordedValue, String>;
+
+/// The function a recorded call went to
+///
+/// Functions can't be carried between contexts, so each distinct callable gets a number in
+/// the order it was first called. Replaying asks the host to resolve each target again.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CallTarget {
+    pub id: usize,
+    /// The name of the function's signature type, for telling targets apart
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Run {
+        /// FNV-1a hash of `source`, stable across builds and platforms
+        hash: u64,
+        source: String,
+        outcome: Outcome,
+    },
+    Call {
+        target: CallTarget,
+        args: Vec<RecordedValue>,
+        outcome: Outcome,
+    },
+}
+
+impl Event {
+    pub fn outcome(&self) -> &Outcome {
+        match self {
+            Event::Run { outcome, .. } | Event::Call { outcome, .. } => outcome,
+        }
+    }
+}
+
+/// A sequence of executions captured with [`Context::start_recording`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    pub events: Vec<Event>,
+}
+
+/// An execution that behaved differently on replay
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Position of the event in [`Recording::events`]
+    pub index: usize,
+    pub expected: Outcome,
+    pub actual: Outcome,
+}
+
+/// Recording in progress, kept in the context state
+#[derive(Default)]
+pub(crate) struct Recorder {
+    events: Vec<Event>,
+    /// Target ids keyed by callable address. Every recorded callable is referenced until
+    /// recording stops so an address can't be reused by a different function.
+    targets: HashMap<usize, CallTarget>,
+    referenced: Vec<Object>,
+}
+
+/// Tracks how deeply executions are nested, so only the outermost one is recorded
+pub(crate) struct Scope {
+    state: Rc<ContextState>,
+}
+
+impl Scope {
+    pub fn enter(ctx: &Context) -> Self {
+        let state = crate::state::get(ctx.as_ptr());
+        state.execution_depth.set(state.execution_depth.get() + 1);
+        if state.execution_depth.get() == 1 {
+            // A native may have handled a nested failure, and a prelude module may have
+            // failed to build without anything importing it, so don't hold either against
+            // this run
+            state.call_depth_exceeded.set(false);
+            state.prelude_error.take();
+        }
+        Self { state }
+    }
+
+    /// Whether this execution should be captured
+    pub fn records(&self) -> bool {
+        self.state.execution_depth.get() == 1 && self.state.recorder.borrow().is_some()
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        self.state
+            .execution_depth
+            .set(self.state.execution_depth.get() - 1);
+    }
+}
+
+pub(crate) fn record_run(ctx: &mut Context, source: &CStr, result: &Result<(), Error>) {
+    let source = source.to_string_lossy().into_owned();
+    let event = Event::Run {
+        hash: hash_source(source.as_bytes()),
+        source,
+        outcome: match result {
+            Ok(()) => Ok(RecordedValue::Null),
+            Err(err) => Err(err.to_string()),
+        },
+    };
+    push_event(ctx, event);
+}
+
+/// Capture the target and arguments of a call before it runs, in case the call changes them
+pub(crate) fn capture_call(
+    ctx: &mut Context,
+    callable: Object,
+    args: &[Value],
+) -> (CallTarget, Vec<RecordedValue>) {
+    let args = args.iter().map(|arg| capture(ctx, *arg, 0)).collect();
+
+    let state = crate::state::get(ctx.as_ptr());
+    let known = state
+        .recorder
+        .borrow()
+        .as_ref()
+        .and_then(|recorder| recorder.targets.get(&(callable.as_ptr() as usize)).cloned());
+    if let Some(target) = known {
+        return (target, args);
+    }
+
+    let signature = crate::call::signature_of(callable).map_or_else(String::new, |ty| ty.name());
+    ctx.add_ref(callable);
+    let mut recorder = state.recorder.borrow_mut();
+    let recorder = recorder.as_mut().expect("recording is active");
+    let target = CallTarget {
+        id: recorder.targets.len(),
+        signature,
+    };
+    recorder
+        .targets
+        .insert(callable.as_ptr() as usize, target.clone());
+    recorder.referenced.push(callable);
+    (target, args)
+}
+
+pub(crate) fn record_call(
+    ctx: &mut Context,
+    target: CallTarget,
+    args: Vec<RecordedValue>,
+    result: &Result<Value, Error>,
+) {
+    let outcome = match result {
+        Ok(value) => Ok(capture(ctx, *value, 0)),
+        Err(err) => Err(err.to_string()),
+    };
+    push_event(
+        ctx,
+        Event::Call {
+            target,
+            args,
+            outcome,
+        },
+    );
+}
+
+fn push_event(ctx: &Context, event: Event) {
+    let state = crate::state::get(ctx.as_ptr());
+    if let Some(recorder) = state.recorder.borrow_mut().as_mut() {
+        recorder.events.push(event);
+    }
+}
+
+impl Context {
+    /// Start capturing every top-level `run` and `call`, discarding any recording already
+    /// in progress
+    pub fn start_recording(&mut self) {
+        let _ = self.stop_recording();
+        *crate::state::get(self.as_ptr()).recorder.borrow_mut() = Some(Recorder::default());
+    }
+
+    /// Stop capturing and return everything recorded, or `None` if recording wasn't on
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        let recorder = crate::state::get(self.as_ptr()).recorder.take()?;
+        for callable in recorder.referenced {
+            self.remove_ref(callable);
+        }
+        Some(Recording {
+            events: recorder.events,
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        crate::state::get(self.as_ptr()).recorder.borrow().is_some()
+    }
+}
+
+impl Recording {
+    /// Re-execute every event in `ctx`, returning the ones whose outcome differs
+    ///
+    /// `resolve` is asked for the function behind each call target, once per target. Calls
+    /// whose target can't be resolved, or whose arguments can't be rebuilt, diverge with an
+    /// error outcome. The context should be set up the way the recorded one was (same std
+    /// modules, natives and types) before replaying.
+    pub fn replay(
+        &self,
+        ctx: &mut Context,
+        mut resolve: impl FnMut(&mut Context, &CallTarget) -> Option<Value>,
+    ) -> Vec<Divergence> {
+        let mut resolved: HashMap<usize, Option<Value>> = HashMap::new();
+        let mut divergences = Vec::new();
+
+        for (index, event) in self.events.iter().enumerate() {
+            let actual = match event {
+                Event::Run { source, .. } => ctx
+                    .run(source.as_str())
+                    .map(|()| RecordedValue::Null)
+                    .map_err(|err| err.to_string()),
+                Event::Call { target, args, .. } => {
+                    let func = *resolved.entry(target.id).or_insert_with(|| {
+                        let func = resolve(ctx, target);
+                        if let Some(obj) = func.and_then(|func| func.as_object()) {
+                            ctx.add_ref(obj);
+                        }
+                        func
+                    });
+                    replay_call(ctx, func, target, args)
+                }
+            };
+
+            if &actual != event.outcome() {
+                divergences.push(Divergence {
+                    index,
+                    expected: event.outcome().clone(),
+                    actual,
+                });
+            }
+        }
+
+        for obj in resolved
+            .into_values()
+            .flatten()
+            .filter_map(|f| f.as_object())
+        {
+            ctx.remove_ref(obj);
+        }
+        divergences
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let file = std::fs::File::create(path)?;
+        self.write_to(std::io::BufWriter::new(file))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read_from(std::fs::File::open(path)?)
+    }
+
+    /// Write the recording in the replay file format
+    pub fn write_to(&self, mut out: impl Write) -> Result<(), Error> {
+        let mut text = String::new();
+        text.push_str(HEADER);
+        text.push('\n');
+        for event in &self.events {
+            match event {
+                Event::Run {
+                    hash,
+                    source,
+                    outcome,
+                } => {
+                    text.push_str(&format!("run {hash:016x} "));
+                    write_str(&mut text, source);
+                    write_outcome(&mut text, outcome);
+                }
+                Event::Call {
+                    target,
+                    args,
+                    outcome,
+                } => {
+                    text.push_str(&format!("call {} ", target.id));
+                    write_str(&mut text, &target.signature);
+                    text.push_str(&format!(" {}", args.len()));
+                    for arg in args {
+                        text.push(' ');
+                        write_value(&mut text, arg);
+                    }
+                    write_outcome(&mut text, outcome);
+                }
+            }
+            text.push('\n');
+        }
+        out.write_all(text.as_bytes())?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Read a recording written by [`Recording::write_to`]
+    pub fn read_from(mut input: impl Read) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        input.read_to_end(&mut bytes)?;
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.word()? != "bolt-replay" || reader.word()? != "1" {
+            return Err(Error::Replay {
+                msg: "missing or unsupported header".to_owned(),
+            });
+        }
+
+        let mut events = Vec::new();
+        while !reader.at_end() {
+            let event = match reader.word()?.as_str() {
+                "run" => {
+                    let hash = reader.word()?;
+                    let hash = u64::from_str_radix(&hash, 16).map_err(|_| Error::Replay {
+                        msg: format!("bad source hash '{hash}'"),
+                    })?;
+                    let source = reader.string()?;
+                    if hash_source(source.as_bytes()) != hash {
+                        return Err(Error::Replay {
+                            msg: format!("source of event {} doesn't match its hash", events.len()),
+                        });
+                    }
+                    Event::Run {
+                        hash,
+                        source,
+                        outcome: reader.outcome()?,
+                    }
+                }
+                "call" => {
+                    let id = reader.number()?;
+                    let signature = reader.string()?;
+                    let count = reader.number()?;
+                    let args = (0..count)
+                        .map(|_| reader.value())
+                        .collect::<Result<_, _>>()?;
+                    Event::Call {
+                        target: CallTarget { id, signature },
+                        args,
+                        outcome: reader.outcome()?,
+                    }
+                }
+                other => {
+                    return Err(Error::Replay {
+                        msg: format!("unknown event '{other}'"),
+                    });
+                }
+            };
+            events.push(event);
+        }
+        Ok(Self { events })
+    }
+}
+
+fn replay_call(
+    ctx: &mut Context,
+    func: Option<Value>,
+    target: &CallTarget,
+    args: &[RecordedValue],
+) -> Outcome {
+    let func = func.ok_or_else(|| format!("call target {} was not resolved", target.id))?;
+
+    ctx.gc_pause();
+    let rebuilt: Result<Vec<Value>, String> = args
+        .iter()
+        .enumerate()
+        .map(|(idx, arg)| rebuild(ctx, arg).ok_or_else(|| format!("argument {idx} is opaque")))
+        .collect();
+    ctx.gc_unpause();
+
+    let rebuilt = rebuilt?;
+    let roots: Vec<Object> = rebuilt.iter().filter_map(|arg| arg.as_object()).collect();
+    for obj in &roots {
+        ctx.push_root(*obj);
+    }
+    let result = ctx.call(func, &rebuilt);
+    for _ in &roots {
+        ctx.pop_root();
+    }
+
+    match result {
+        Ok(value) => Ok(capture(ctx, value, 0)),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Copy a script value out of the heap
+pub(crate) fn snapshot(ctx: &mut Context, value: Value) -> RecordedValue {
+    capture(ctx, value, 0)
+}
+
+fn capture(ctx: &mut Context, value: Value, depth: usize) -> RecordedValue {
+    if value.is_null() {
+        return RecordedValue::Null;
+    }
+    if let Some(b) = value.as_bool() {
+        return RecordedValue::Bool(b);
+    }
+    if let Some(n) = value.as_number() {
+        return RecordedValue::Number(n);
+    }
+
+    let opaque = |ctx: &mut Context| RecordedValue::Opaque(ctx.debug_value(value, 0));
+    let Some(obj) = value.as_object() else {
+        return opaque(ctx);
+    };
+    match obj.value_type() {
+        ValueType::String => <String as FromBoltValue>::from(value.0)
+            .map_or_else(|_| opaque(ctx), RecordedValue::String),
+        ValueType::Array if depth < MAX_DEPTH => {
+            let array = unsafe { Array::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            let items: Vec<Value> = array.iter(ctx).collect();
+            RecordedValue::Array(
+                items
+                    .into_iter()
+                    .map(|item| capture(ctx, item, depth + 1))
+                    .collect(),
+            )
+        }
+        ValueType::Table if depth < MAX_DEPTH => {
+            let table = unsafe { Table::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            let pairs: Vec<(Value, Value)> = table.iter(ctx).collect();
+            let mut pairs: Vec<(String, RecordedValue, RecordedValue)> = pairs
+                .into_iter()
+                .map(|(key, item)| {
+                    let key = capture(ctx, key, depth + 1);
+                    let mut sort_key = String::new();
+                    write_value(&mut sort_key, &key);
+                    (sort_key, key, capture(ctx, item, depth + 1))
+                })
+                .collect();
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            RecordedValue::Table(pairs.into_iter().map(|(_, k, v)| (k, v)).collect())
+        }
+        ValueType::UserData if depth < MAX_DEPTH => {
+            let userdata = unsafe { Userdata::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            match ctx.serialize_userdata(userdata) {
+                Ok(encoded) => RecordedValue::Userdata(Box::new(capture(ctx, encoded, depth + 1))),
+                Err(_) => opaque(ctx),
+            }
+        }
+        _ => opaque(ctx),
+    }
+}
+
+/// Build a script value from a captured one, or `None` if it contains opaque values
+///
+/// Collection must be paused while this runs.
+pub(crate) fn rebuild(ctx: &mut Context, value: &RecordedValue) -> Option<Value> {
+    let raw = match value {
+        RecordedValue::Null => unsafe { sys::bt_make_null() },
+        RecordedValue::Bool(b) => b.make(),
+        RecordedValue::Number(n) => n.make(),
+        RecordedValue::String(s) => s.as_str().make_with_context(ctx),
+        RecordedValue::Array(items) => {
+            let array = ctx.make_array(items.len() as u32);
+            for item in items {
+                let item = rebuild(ctx, item)?;
+                ctx.array_push(array, item);
+            }
+            return Some(Value::from_raw(array.make()));
+        }
+        RecordedValue::Table(pairs) => {
+            let table = ctx.make_table(pairs.len().min(u16::MAX as usize) as u16);
+            for (key, item) in pairs {
+                let key = rebuild(ctx, key)?;
+                let item = rebuild(ctx, item)?;
+                ctx.table_set(table, key, item);
+            }
+            return Some(Value::from_raw(table.make()));
+        }
+        RecordedValue::Userdata(encoded) => {
+            let encoded = rebuild(ctx, encoded)?;
+            let userdata = ctx.deserialize_userdata(encoded).ok()??;
+            return Some(Value::from_raw(userdata.make()));
+        }
+        RecordedValue::Opaque(_) => return None,
+    };
+    Some(Value::from_raw(raw))
+}
+
+/// 64-bit FNV-1a
+pub(crate) fn hash_source(source: &[u8]) -> u64 {
+    source.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push_str(&format!("{}:", s.len()));
+    out.push_str(s);
+}
+
+fn write_outcome(out: &mut String, outcome: &Outcome) {
+    match outcome {
+        Ok(value) => {
+            out.push_str(" ok ");
+            write_value(out, value);
+        }
+        Err(msg) => {
+            out.push_str(" err ");
+            write_str(out, msg);
+        }
+    }
+}
+
+fn write_value(out: &mut String, value: &RecordedValue) {
+    match value {
+        RecordedValue::Null => out.push_str("null"),
+        RecordedValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        // Bit patterns keep numbers exact, NaN payloads included
+        RecordedValue::Number(n) => out.push_str(&format!("n {:016x}", n.to_bits())),
+        RecordedValue::String(s) => {
+            out.push_str("s ");
+            write_str(out, s);
+        }
+        RecordedValue::Array(items) => {
+            out.push_str(&format!("a {}", items.len()));
+            for item in items {
+                out.push(' ');
+                write_value(out, item);
+            }
+        }
+        RecordedValue::Table(pairs) => {
+            out.push_str(&format!("t {}", pairs.len()));
+            for (key, item) in pairs {
+                out.push(' ');
+                write_value(out, key);
+                out.push(' ');
+                write_value(out, item);
+            }
+        }
+        RecordedValue::Userdata(encoded) => {
+            out.push_str("u ");
+            write_value(out, encoded);
+        }
+        RecordedValue::Opaque(rendered) => {
+            out.push_str("o ");
+            write_str(out, rendered);
+        }
+    }
+}
+
+/// Tokenizer for the replay format: whitespace separated words, with strings written as a
+/// byte length followed by `:` and the raw bytes
+struct Reader {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl Reader {
+    fn skip_whitespace(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.pos >= self.bytes.len()
+    }
+
+    fn word(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(Error::Replay {
+                msg: "unexpected end of file".to_owned(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        let word = self.word()?;
+        word.parse().map_err(|_| Error::Replay {
+            msg: format!("expected a count, found '{word}'"),
+        })
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        let colon = self.bytes[self.pos..]
+            .iter()
+            .position(|b| *b == b':')
+            .map(|offset| self.pos + offset)
+            .ok_or_else(|| Error::Replay {
+                msg: "expected a string".to_owned(),
+            })?;
+        let len: usize = std::str::from_utf8(&self.bytes[self.pos..colon])
+            .ok()
+            .and_then(|len| len.parse().ok())
+            .ok_or_else(|| Error::Replay {
+                msg: "bad string length".to_owned(),
+            })?;
+        let start = colon + 1;
+        let bytes = start
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(start..end))
+            .ok_or_else(|| Error::Replay {
+                msg: "string runs past the end of the file".to_owned(),
+            })?;
+        let string = String::from_utf8(bytes.to_vec()).map_err(|_| Error::Replay {
+            msg: "string is not valid UTF-8".to_owned(),
+        })?;
+        self.pos = start + len;
+        Ok(string)
+    }
+
+    fn outcome(&mut self) -> Result<Outcome, Error> {
+        match self.word()?.as_str() {
+            "ok" => Ok(Ok(self.value()?)),
+            "err" => Ok(Err(self.string()?)),
+            other => Err(Error::Replay {
+                msg: format!("expected an outcome, found '{other}'"),
+            }),
+        }
+    }
+
+    fn value(&mut self) -> Result<RecordedValue, Error> {
+        Ok(match self.word()?.as_str() {
+            "null" => RecordedValue::Null,
+            "true" => RecordedValue::Bool(true),
+            "false" => RecordedValue::Bool(false),
+            "n" => {
+                let bits = self.word()?;
+                let bits = u64::from_str_radix(&bits, 16).map_err(|_| Error::Replay {
+                    msg: format!("bad number '{bits}'"),
+                })?;
+                RecordedValue::Number(f64::from_bits(bits))
+            }
+            "s" => RecordedValue::String(self.string()?),
+            "a" => {
+                let len = self.number()?;
+                RecordedValue::Array((0..len).map(|_| self.value()).collect::<Result<_, _>>()?)
+            }
+            "t" => {
+                let len = self.number()?;
+                let pairs = (0..len)
+                    .map(|_| Ok((self.value()?, self.value()?)))
+                    .collect::<Result<_, Error>>()?;
+                RecordedValue::Table(pairs)
+            }
+            "u" => RecordedValue::Userdata(Box::new(self.value()?)),
+            "o" => RecordedValue::Opaque(self.string()?),
+            other => {
+                return Err(Error::Replay {
+                    msg: format!("unknown value tag '{other}'"),
+                });
+            }
+        })
+    }
+}
@@ -0,0 +1,65 @@
+//! Value-based error handling for scripts
+//!
+//! Fallible host functions can return a Rust `Result`, which crosses into bolt as a
+//! table of the crate-registered `Result` tableshape: `{ ok: any?, err: any? }`, with
+//! exactly one of the two fields non-null.
+
+use bolt_sys::sys;
+
+use crate::types::Type;
+use crate::{Context, MakeBoltValueWithContext, Value};
+
+impl Context {
+    /// The `Result` tableshape, created and registered with the type registry on first use
+    pub fn result_type(&mut self) -> Type {
+        let state = crate::state::get(self.as_ptr());
+        if let Some(ty) = state.result_type.get() {
+            return ty;
+        }
+
+        let shape = self
+            .make_tableshape_type(c"Result", true)
+            .expect("static name is valid");
+        let string = self.type_string();
+        let any = self.type_any();
+        let field = self.type_make_nullable(any);
+        for key in [c"ok", c"err"] {
+            let key = Value::from_raw(key.make_with_context(self));
+            self.tableshape_add_layout(shape, string, key, field);
+        }
+
+        let name = Value::from_raw("Result".make_with_context(self));
+        self.register_type(name, shape)
+            .expect("static name is valid");
+        state.result_type.set(Some(shape));
+        shape
+    }
+}
+
+impl<T: MakeBoltValueWithContext, E: MakeBoltValueWithContext> MakeBoltValueWithContext
+    for Result<T, E>
+{
+    fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+        let shape = ctx.result_type();
+        let table = ctx.make_table_from_proto(shape);
+        ctx.root_scope(|scope| {
+            scope.root(table.as_object());
+
+            let null = Value::from_raw(unsafe { sys::bt_make_null() });
+            let (ok, err) = match self {
+                Ok(val) => (Value::from_raw(val.make_with_context(scope)), null),
+                Err(err) => (null, Value::from_raw(err.make_with_context(scope))),
+            };
+            if let Some(obj) = ok.as_object().or(err.as_object()) {
+                scope.root(obj);
+            }
+
+            let ok_key = c"ok".make_with_context(scope);
+            scope.table_set(table, Value::from_raw(ok_key), ok);
+            let err_key = c"err".make_with_context(scope);
+            scope.table_set(table, Value::from_raw(err_key), err);
+        });
+
+        unsafe { sys::bt_value(table.as_object_ptr()) }
+    }
+}
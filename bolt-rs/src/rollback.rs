@@ -0,0 +1,112 @@
+//! Cleanup for host code that fails partway through building values
+//!
+//! Binding code often allocates several objects before it can tell whether the whole
+//! operation will succeed. Keeping them referenced with `add_ref` protects them while they're
+//! being assembled, but every early return has to remember to release them again, and in a
+//! long-running host the ones that are missed accumulate. [`Context::with_rollback`] does
+//! that bookkeeping: objects tracked through the [`Transaction`] stay alive until the closure
+//! returns, and if it fails, registry writes made through it are undone as well.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::types::{Object, ObjectHandle};
+use crate::{Context, Error, Value};
+
+/// A borrow of a [`Context`] that undoes what was done through it if the operation fails
+///
+/// Derefs to the context, so it can be used anywhere a `&mut Context` is expected.
+pub struct Transaction<'a> {
+    ctx: &'a mut Context,
+    tracked: Vec<Object>,
+    /// Registry keys written through the transaction, with the value each replaced
+    registry: Vec<(String, Option<Value>)>,
+    committed: bool,
+}
+
+impl Transaction<'_> {
+    /// Keep `obj` alive until the transaction ends, returning it for convenience
+    pub fn track<T: ObjectHandle>(&mut self, obj: T) -> T {
+        self.ctx.add_ref(obj.as_object());
+        self.tracked.push(obj.as_object());
+        obj
+    }
+
+    /// Like [`Context::registry_set`], but restores the previous value if the transaction
+    /// is rolled back
+    pub fn registry_set(&mut self, key: &str, value: Value) -> Option<Value> {
+        let previous = self.ctx.registry_set(key, value);
+        if let Some(obj) = previous.and_then(|previous| previous.as_object()) {
+            // The replaced value has to survive until we know whether it's needed again
+            self.track(obj);
+        }
+        self.registry.push((key.to_owned(), previous));
+        previous
+    }
+
+    /// How many objects are being kept alive by the transaction
+    pub fn tracked(&self) -> usize {
+        self.tracked.len()
+    }
+
+    fn rollback(&mut self) {
+        // Undo in reverse so a key written twice ends up with its original value
+        for (key, previous) in std::mem::take(&mut self.registry).into_iter().rev() {
+            match previous {
+                Some(previous) => {
+                    self.ctx.registry_set(&key, previous);
+                }
+                None => {
+                    self.ctx.registry_remove(&key);
+                }
+            }
+        }
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.ctx
+    }
+}
+
+impl DerefMut for Transaction<'_> {
+    fn deref_mut(&mut self) -> &mut Context {
+        self.ctx
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.rollback();
+        }
+        for obj in self.tracked.drain(..) {
+            self.ctx.remove_ref(obj);
+        }
+    }
+}
+
+impl Context {
+    /// Run `f` with a [`Transaction`], releasing everything it tracked once `f` returns
+    ///
+    /// If `f` fails (or panics), registry writes made through the transaction are undone
+    /// before the error is returned. Objects `f` returns, or stores anywhere other than
+    /// through the transaction, are no longer kept alive by it, so store them somewhere
+    /// reachable before the next allocation.
+    pub fn with_rollback<R>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let mut tx = Transaction {
+            ctx: self,
+            tracked: Vec::new(),
+            registry: Vec::new(),
+            committed: false,
+        };
+        let result = f(&mut tx);
+        tx.committed = result.is_ok();
+        result
+    }
+}
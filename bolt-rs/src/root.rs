@@ -0,0 +1,61 @@
+//! Scoped GC rooting
+//!
+//! Objects created from the host aren't reachable from any script value until they're
+//! stored somewhere, so a collection triggered while building them can free them early.
+//! `push_root`/`pop_root` protect them but must be paired by hand; a [`RootScope`] pops
+//! everything it rooted when it's dropped, including on early returns and `?`.
+
+use std::ops::{Deref, DerefMut};
+
+use crate::Context;
+use crate::types::Object;
+
+/// A borrow of a [`Context`] which unroots every object rooted through it when dropped
+///
+/// Derefs to the context, so it can be used anywhere a `&mut Context` is expected.
+pub struct RootScope<'a> {
+    ctx: &'a mut Context,
+    rooted: usize,
+}
+
+impl<'a> RootScope<'a> {
+    pub fn new(ctx: &'a mut Context) -> Self {
+        Self { ctx, rooted: 0 }
+    }
+
+    /// Keep `obj` alive until the scope ends, returning it for convenience
+    pub fn root(&mut self, obj: Object) -> Object {
+        self.ctx.push_root(obj);
+        self.rooted += 1;
+        obj
+    }
+}
+
+impl Deref for RootScope<'_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.ctx
+    }
+}
+
+impl DerefMut for RootScope<'_> {
+    fn deref_mut(&mut self) -> &mut Context {
+        self.ctx
+    }
+}
+
+impl Drop for RootScope<'_> {
+    fn drop(&mut self) {
+        for _ in 0..self.rooted {
+            self.ctx.pop_root();
+        }
+    }
+}
+
+impl Context {
+    /// Run `f` with a [`RootScope`], unrooting everything it rooted once `f` returns
+    pub fn root_scope<R>(&mut self, f: impl FnOnce(&mut RootScope) -> R) -> R {
+        f(&mut RootScope::new(self))
+    }
+}
@@ -0,0 +1,66 @@
+//! Long-lived references to bolt objects held from Rust
+//!
+//! GC roots pushed with `push_root` are a stack, so they can only protect objects for the
+//! duration of a call. A [`Rooted`] instead holds a reference count on its object through
+//! `add_ref`, which keeps it alive across any number of collections until the handle is
+//! dropped, so it can be stored in Rust structs between script calls.
+
+use std::rc::Weak;
+
+use bolt_sys::sys;
+
+use crate::Context;
+use crate::state::ContextState;
+use crate::types::ObjectHandle;
+
+/// An object kept alive until this handle is dropped, see the [module docs](self)
+///
+/// If the context is closed first, dropping the handle does nothing.
+pub struct Rooted<T: ObjectHandle> {
+    obj: T,
+    ctx: *mut sys::bt_Context,
+    state: Weak<ContextState>,
+}
+
+impl<T: ObjectHandle> Rooted<T> {
+    pub fn new(ctx: &mut Context, obj: T) -> Self {
+        ctx.add_ref(obj.as_object());
+        Self {
+            obj,
+            ctx: ctx.as_ptr(),
+            state: std::rc::Rc::downgrade(&crate::state::get(ctx.as_ptr())),
+        }
+    }
+
+    /// The rooted object, valid for as long as this handle is alive
+    pub fn get(&self) -> T {
+        self.obj
+    }
+}
+
+impl<T: ObjectHandle> Clone for Rooted<T> {
+    fn clone(&self) -> Self {
+        if self.state.strong_count() > 0 {
+            unsafe { sys::bt_add_ref(self.ctx, self.obj.as_object().as_object_ptr()) };
+        }
+        Self {
+            obj: self.obj,
+            ctx: self.ctx,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: ObjectHandle> Drop for Rooted<T> {
+    fn drop(&mut self) {
+        if self.state.strong_count() > 0 {
+            unsafe { sys::bt_remove_ref(self.ctx, self.obj.as_object().as_object_ptr()) };
+        }
+    }
+}
+
+impl<T: ObjectHandle + std::fmt::Debug> std::fmt::Debug for Rooted<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Rooted").field(&self.obj).finish()
+    }
+}
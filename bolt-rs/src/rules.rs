@@ -0,0 +1,158 @@
+//! Batch evaluation of small scripted rules
+//!
+//! A [`RuleSet`] declares a sealed input tableshape, compiles each rule once into a function
+//! taking that shape as `input`, then evaluates every rule against a batch of rows. Field
+//! keys are interned once up front and host calls reuse the context's pooled threads, so
+//! the per-row cost is one table allocation plus one call per rule.
+
+use crate::types::Type;
+use crate::{Context, Error, MakeBoltValue, MakeBoltValueWithContext, Value};
+
+const RULE_EXPORT: &str = "__rule";
+
+/// What a single rule produced for a single row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleOutcome {
+    /// The rule evaluated to a boolean, e.g. a filter
+    Matched(bool),
+    /// The rule evaluated to a number, e.g. a score
+    Score(f64),
+}
+
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    func: Value,
+}
+
+/// A set of rules sharing one typed input, see the [module docs](self)
+#[derive(Debug)]
+pub struct RuleSet {
+    input_name: String,
+    input_type: Type,
+    keys: Vec<Value>,
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Declare the input shape `name` with the given fields, in the order rows will supply
+    /// them. The shape is registered as a type so rules can refer to it by name.
+    pub fn new(ctx: &mut Context, name: &str, fields: &[(&str, Type)]) -> Result<Self, Error> {
+        let input_type = ctx.make_tableshape_type(name, true)?;
+
+        let string = ctx.type_string();
+        let mut keys = Vec::with_capacity(fields.len());
+        for (field, ty) in fields {
+            let key = ctx.get_or_make_interned(*field)?;
+            ctx.add_ref(key.as_object());
+            let key = Value::from_raw(key.as_object().make());
+            ctx.tableshape_add_layout(input_type, string, key, *ty);
+            keys.push(key);
+        }
+
+        let type_name = Value::from_raw(name.make_with_context(ctx));
+        ctx.register_type(type_name, input_type)?;
+
+        Ok(Self {
+            input_name: name.to_owned(),
+            input_type,
+            keys,
+            rules: Vec::new(),
+        })
+    }
+
+    /// Compile `expr` as a rule. The expression sees the row as `input` and must evaluate
+    /// to a boolean or a number.
+    pub fn add_rule(&mut self, ctx: &mut Context, name: &str, expr: &str) -> Result<(), Error> {
+        let source = format!(
+            "export fn {RULE_EXPORT}(input: {}) {{ return {expr} }}",
+            self.input_name
+        );
+        let module_name = format!("<rule {name}>");
+
+        let module = ctx.compile_module(source.as_str(), module_name.as_str())?;
+        ctx.execute_module(module)?;
+
+        let func = module
+            .export_table()
+            .and_then(|exports| exports.get_str(RULE_EXPORT))
+            .ok_or_else(|| Error::bolt(format!("Rule '{name}' did not export its function")))?;
+        if let Some(obj) = func.as_object() {
+            ctx.add_ref(obj);
+        }
+
+        self.rules.push(Rule {
+            name: name.to_owned(),
+            func,
+        });
+        Ok(())
+    }
+
+    /// Names of the compiled rules, in the order their outcomes are reported
+    pub fn rule_names(&self) -> impl Iterator<Item = &str> {
+        self.rules.iter().map(|rule| rule.name.as_str())
+    }
+
+    /// Evaluate every rule against every row. Each row supplies the input fields in
+    /// declaration order, and each result holds one outcome per rule.
+    pub fn evaluate(
+        &self,
+        ctx: &mut Context,
+        rows: &[&[Value]],
+    ) -> Result<Vec<Vec<RuleOutcome>>, Error> {
+        let mut results = Vec::with_capacity(rows.len());
+        for (idx, row) in rows.iter().enumerate() {
+            if row.len() != self.keys.len() {
+                return Err(Error::bolt(format!(
+                    "Row {idx} has {} fields but the input has {}",
+                    row.len(),
+                    self.keys.len()
+                )));
+            }
+
+            let input = ctx.make_table_from_proto(self.input_type);
+            ctx.push_root(input.as_object());
+            for (key, value) in self.keys.iter().zip(row.iter()) {
+                ctx.table_set(input, *key, *value);
+            }
+
+            let input = Value::from_raw(input.as_object().make());
+            let outcomes = self
+                .rules
+                .iter()
+                .map(|rule| {
+                    let returned = ctx.call(rule.func, &[input])?;
+                    outcome(rule, returned)
+                })
+                .collect::<Result<Vec<_>, Error>>();
+            ctx.pop_root();
+
+            results.push(outcomes?);
+        }
+        Ok(results)
+    }
+
+    /// Allow the compiled rules and interned keys to be collected
+    pub fn release(self, ctx: &mut Context) {
+        let values = self
+            .keys
+            .iter()
+            .chain(self.rules.iter().map(|rule| &rule.func));
+        for obj in values.filter_map(|value| value.as_object()) {
+            ctx.remove_ref(obj);
+        }
+    }
+}
+
+fn outcome(rule: &Rule, returned: Value) -> Result<RuleOutcome, Error> {
+    if let Some(matched) = returned.as_bool() {
+        Ok(RuleOutcome::Matched(matched))
+    } else if let Some(score) = returned.as_number() {
+        Ok(RuleOutcome::Score(score))
+    } else {
+        Err(Error::bolt(format!(
+            "Rule '{}' returned neither a bool nor a number",
+            rule.name
+        )))
+    }
+}
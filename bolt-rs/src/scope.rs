@@ -0,0 +1,135 @@
+//! Script functions borrowing non-`'static` host data
+//!
+//! Natives made with [`Context::add_prelude_fn`] and friends live as long as the context,
+//! so their closures have to own whatever they touch, which pushes host state into
+//! `Rc<RefCell<..>>`. Functions made through a [`Scope`] may borrow from the caller
+//! instead, mutably too, because they're disarmed when [`Context::scope`] returns:
+//!
+//! ```ignore
+//! let mut hits = 0;
+//! ctx.scope(|scope| {
+//!     let hit = scope.create_function(|(): ()| hits += 1)?;
+//!     scope.call(hit, &[])?;
+//!     Ok(())
+//! })?;
+//! assert_eq!(hits, 1);
+//! ```
+//!
+//! The functions themselves are ordinary values and may outlive the scope, e.g. if a
+//! script stored one. Calling one after its scope ended fails with a runtime error.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::meta::MetaFn;
+use crate::types::NativeFn;
+use crate::{
+    Context, ContextRef, Error, FromBoltArgs, IntoBoltArgs, MakeBoltValue,
+    MakeBoltValueWithContext, ScalarTypeSignature, Thread, Value,
+};
+
+/// A `MetaFn` before its borrows are erased
+type ScopedFn<'env> = Rc<dyn Fn(&mut Context, &mut Thread) -> Result<(), String> + 'env>;
+
+/// A borrow of a [`Context`] whose functions may capture borrows living for `'env`
+///
+/// Derefs to `&Context`, but not mutably: a context swapped out of the scope could carry its
+/// functions past the scope's end. [`Scope::call`] and [`Scope::make_value`] cover running
+/// them.
+pub struct Scope<'scope, 'env: 'scope> {
+    ctx: &'scope mut Context,
+    /// The context the functions were made in, kept apart from `ctx` for `Drop`
+    raw: *mut sys::bt_Context,
+    /// Functions made so far, each kept alive until the scope ends
+    created: Vec<NativeFn>,
+    /// Invariant, so a scope can't be passed off as one with a longer `'env`
+    _env: PhantomData<&'scope mut &'env ()>,
+}
+
+impl<'env> Scope<'_, 'env> {
+    /// Make a script function calling `f` with its arguments as the tuple `A`, which can
+    /// be called until the scope ends
+    pub fn create_function<A, R>(&mut self, f: impl FnMut(A) -> R + 'env) -> Result<Value, Error>
+    where
+        A: FromBoltArgs + IntoBoltArgs,
+        R: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        let ret = R::make_type(self.ctx);
+        let args = A::arg_types(self.ctx);
+        let f = RefCell::new(f);
+        let func: ScopedFn<'env> = Rc::new(move |ctx, thread| {
+            let args = thread.args::<A>().map_err(|err| err.to_string())?;
+            let mut f = f
+                .try_borrow_mut()
+                .map_err(|_| "scoped function called while it was already running")?;
+            let result = Value::from_raw(f(args).make_with_context(ctx));
+            thread.return_val(&result);
+            Ok(())
+        });
+        // SAFETY: the closure is swapped out in `Drop`, before `'env` can end, and nothing
+        // else keeps a copy past a single call
+        let func = unsafe { std::mem::transmute::<ScopedFn<'env>, MetaFn>(func) };
+
+        self.ctx.gc_pause();
+        let native = crate::meta::closure_native(self.ctx, "<scoped>", ret, &args, func);
+        if let Ok(native) = native {
+            // Rooted so its address can't be reused by another native before `Drop` runs
+            self.ctx.add_ref(native.as_object());
+            self.created.push(native);
+        }
+        self.ctx.gc_unpause();
+        Ok(Value::from_raw(native?.as_object().make()))
+    }
+
+    /// Call `func`, like [`Context::call`]
+    pub fn call(&mut self, func: Value, args: &[Value]) -> Result<Value, Error> {
+        self.ctx.call(func, args)
+    }
+
+    /// Make a script value out of `value`, e.g. a string to pass to [`Scope::call`]
+    pub fn make_value(&mut self, value: impl MakeBoltValueWithContext) -> Value {
+        Value::from_raw(value.make_with_context(self.ctx))
+    }
+}
+
+impl Deref for Scope<'_, '_> {
+    type Target = Context;
+
+    fn deref(&self) -> &Context {
+        self.ctx
+    }
+}
+
+impl Drop for Scope<'_, '_> {
+    fn drop(&mut self) {
+        let expired: MetaFn =
+            Rc::new(|_, _| Err("scoped function called after its scope ended".to_owned()));
+        let mut ctx = unsafe { ContextRef::from_raw(self.raw) };
+        for native in self.created.drain(..) {
+            // Dropping the borrowing closure here, while `'env` is still alive
+            crate::meta::replace_closure(self.raw, native, expired.clone());
+            ctx.remove_ref(native.as_object());
+        }
+    }
+}
+
+impl Context {
+    /// Run `f` with a [`Scope`] for making functions that borrow from the caller, see the
+    /// [module docs](crate::scope)
+    pub fn scope<'env, R>(
+        &mut self,
+        f: impl for<'scope> FnOnce(&mut Scope<'scope, 'env>) -> R,
+    ) -> R {
+        let mut scope = Scope {
+            raw: self.as_ptr(),
+            ctx: self,
+            created: Vec::new(),
+            _env: PhantomData,
+        };
+        f(&mut scope)
+    }
+}
@@ -0,0 +1,685 @@
+//! serde support: any `Serialize`/`Deserialize` type to and from bolt values
+//!
+//! The data model maps onto bolt as follows: booleans and `null` are themselves, every
+//! integer and float becomes a number, strings and chars become strings, sequences and
+//! tuples become arrays, maps and structs become tables. Enum variants follow serde's
+//! externally tagged convention: unit variants are strings, everything else is a
+//! single-entry table keyed by the variant name.
+//!
+//! Userdata with [serialization hooks](Context::userdata_serialize_with) deserializes as the
+//! table it's encoded as. [`to_value`] leaves such tables as they are, so a map that happens
+//! to have a `$userdata` key stays a map; [`to_value_with_userdata`] opts into turning them
+//! back into userdata.
+
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Serialize};
+use std::fmt::Display;
+
+use crate::types::{Array, Table, Userdata};
+use crate::{
+    Context, Error, FromBoltValue, MakeBoltValue, MakeBoltValueWithContext, Value, ValueType,
+};
+
+/// Serialize `value` into a freshly allocated bolt value
+pub fn to_value<T: Serialize + ?Sized>(ctx: &mut Context, value: &T) -> Result<Value, Error> {
+    // Intermediate objects aren't reachable from anything until the outermost container
+    // is returned, so hold off collection until then
+    serialize(ctx, value, false)
+}
+
+/// Like [`to_value`], rebuilding every map in the shape [`Context::serialize_userdata`]
+/// encodes userdata as into that userdata
+pub fn to_value_with_userdata<T: Serialize + ?Sized>(
+    ctx: &mut Context,
+    value: &T,
+) -> Result<Value, Error> {
+    serialize(ctx, value, true)
+}
+
+fn serialize<T: Serialize + ?Sized>(
+    ctx: &mut Context,
+    value: &T,
+    userdata: bool,
+) -> Result<Value, Error> {
+    ctx.gc_pause();
+    let out = value.serialize(&mut Serializer {
+        ctx: &mut *ctx,
+        userdata,
+    });
+    ctx.gc_unpause();
+    out
+}
+
+/// Deserialize a `T` out of a bolt value
+pub fn from_value<T: DeserializeOwned>(ctx: &mut Context, value: Value) -> Result<T, Error> {
+    T::deserialize(Deserializer { ctx, value })
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Serde {
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Serde {
+            msg: msg.to_string(),
+        }
+    }
+}
+
+pub struct Serializer<'c> {
+    ctx: &'c mut Context,
+    /// Whether encoded userdata maps are rebuilt, see [`to_value_with_userdata`]
+    userdata: bool,
+}
+
+impl Serializer<'_> {
+    fn string(&mut self, s: &str) -> Value {
+        Value::from_raw(s.make_with_context(self.ctx))
+    }
+
+    /// Wrap `inner` as `{ variant: inner }`
+    fn tagged(&mut self, variant: &'static str, inner: Value) -> Value {
+        let table = self.ctx.make_table(1);
+        let key = self.string(variant);
+        self.ctx.table_set(table, key, inner);
+        Value::from_raw(table.make())
+    }
+}
+
+impl<'a, 'c> ser::Serializer for &'a mut Serializer<'c> {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer<'a, 'c>;
+    type SerializeTuple = SeqSerializer<'a, 'c>;
+    type SerializeTupleStruct = SeqSerializer<'a, 'c>;
+    type SerializeTupleVariant = SeqSerializer<'a, 'c>;
+    type SerializeMap = MapSerializer<'a, 'c>;
+    type SerializeStruct = MapSerializer<'a, 'c>;
+    type SerializeStructVariant = MapSerializer<'a, 'c>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::from_raw(v.make()))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::from_raw(v.make()))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(self.string(v.encode_utf8(&mut [0; 4])))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(self.string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        let array = self.ctx.make_array(v.len() as u32);
+        for byte in v {
+            array.push(self.ctx, *byte as f64);
+        }
+        Ok(Value::from_raw(array.make()))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::from_raw(None::<f64>.make()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(self.string(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let inner = value.serialize(&mut *self)?;
+        Ok(self.tagged(variant, inner))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        let array = self.ctx.make_array(len.unwrap_or(0) as u32);
+        Ok(SeqSerializer {
+            ser: self,
+            array,
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        let mut seq = self.serialize_seq(Some(len))?;
+        seq.variant = Some(variant);
+        Ok(seq)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        let table = self.ctx.make_table(len.unwrap_or(0) as u16);
+        Ok(MapSerializer {
+            ser: self,
+            table,
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        let mut map = self.serialize_map(Some(len))?;
+        map.variant = Some(variant);
+        Ok(map)
+    }
+}
+
+pub struct SeqSerializer<'a, 'c> {
+    ser: &'a mut Serializer<'c>,
+    array: Array,
+    variant: Option<&'static str>,
+}
+
+impl SeqSerializer<'_, '_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let value = value.serialize(&mut *self.ser)?;
+        self.ser.ctx.array_push(self.array, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, Error> {
+        let array = Value::from_raw(self.array.make());
+        Ok(match self.variant {
+            Some(variant) => self.ser.tagged(variant, array),
+            None => array,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+pub struct MapSerializer<'a, 'c> {
+    ser: &'a mut Serializer<'c>,
+    table: Table,
+    next_key: Option<Value>,
+    variant: Option<&'static str>,
+}
+
+impl MapSerializer<'_, '_> {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: Value, value: &T) -> Result<(), Error> {
+        let value = value.serialize(&mut *self.ser)?;
+        self.ser.ctx.table_set(self.table, key, value);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, Error> {
+        let table = Value::from_raw(self.table.make());
+        Ok(match self.variant {
+            Some(variant) => self.ser.tagged(variant, table),
+            None if !self.ser.userdata => table,
+            None => match self.ser.ctx.deserialize_userdata(table)? {
+                Some(userdata) => Value::from_raw(userdata.make()),
+                None => table,
+            },
+        })
+    }
+}
+
+impl ser::SerializeMap for MapSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(&mut *self.ser)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.next_key.take().ok_or_else(|| {
+            <Error as ser::Error>::custom("serialize_value called before serialize_key")
+        })?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = self.ser.string(key);
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer<'_, '_> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = self.ser.string(key);
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        self.finish()
+    }
+}
+
+pub struct Deserializer<'c> {
+    ctx: &'c mut Context,
+    value: Value,
+}
+
+impl Deserializer<'_> {
+    fn unexpected(&self) -> Error {
+        <Error as de::Error>::custom(format!(
+            "cannot deserialize bolt value of type {:?}",
+            ValueType::from_value(self.value.0)
+        ))
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value;
+        if value.is_null() {
+            return visitor.visit_unit();
+        }
+        if let Some(b) = value.as_bool() {
+            return visitor.visit_bool(b);
+        }
+        if let Some(n) = value.as_number() {
+            // Integral numbers are offered as integers so integer fields accept them
+            const EXACT: f64 = (1u64 << 53) as f64;
+            return if n.fract() == 0.0 && n.abs() <= EXACT {
+                if n < 0.0 {
+                    visitor.visit_i64(n as i64)
+                } else {
+                    visitor.visit_u64(n as u64)
+                }
+            } else {
+                visitor.visit_f64(n)
+            };
+        }
+
+        match value.as_object().map(|obj| obj.value_type()) {
+            Some(ValueType::String) => {
+                visitor.visit_string(unsafe { String::from_unchecked(value.0) })
+            }
+            Some(ValueType::Array) => {
+                let array = unsafe { Array::from_unchecked(value.0) };
+                visitor.visit_seq(SeqAccess {
+                    ctx: self.ctx,
+                    array,
+                    idx: 0,
+                })
+            }
+            Some(ValueType::Table) => {
+                let table = unsafe { Table::from_unchecked(value.0) };
+                let pairs = table
+                    .raw_pairs()
+                    .iter()
+                    .map(|pair| (Value::from_raw(pair.key), Value::from_raw(pair.value)))
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                visitor.visit_map(MapAccess {
+                    ctx: self.ctx,
+                    pairs,
+                    next_value: None,
+                })
+            }
+            Some(ValueType::UserData) => {
+                // Userdata with serialization hooks deserializes as its encoded table
+                let userdata = unsafe { Userdata::from_unchecked(value.0) };
+                let encoded = self.ctx.serialize_userdata(userdata)?;
+                let table = encoded.as_object().expect("encoded userdata is a table");
+                self.ctx.push_root(table);
+                let out = Deserializer {
+                    ctx: &mut *self.ctx,
+                    value: encoded,
+                }
+                .deserialize_any(visitor);
+                self.ctx.pop_root();
+                out
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value.as_object().map(|obj| obj.value_type()) {
+            Some(ValueType::String) => {
+                let variant = unsafe { String::from_unchecked(self.value.0) };
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Some(ValueType::Table) => {
+                let table = unsafe { Table::from_unchecked(self.value.0) };
+                let [pair] = table.raw_pairs() else {
+                    return Err(<Error as de::Error>::custom(
+                        "expected a table with a single variant key",
+                    ));
+                };
+                visitor.visit_enum(EnumAccess {
+                    ctx: self.ctx,
+                    variant: Value::from_raw(pair.key),
+                    content: Value::from_raw(pair.value),
+                })
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct SeqAccess<'c> {
+    ctx: &'c mut Context,
+    array: Array,
+    idx: usize,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let Some(value) = self.array.get(self.ctx, self.idx) else {
+            return Ok(None);
+        };
+        self.idx += 1;
+        seed.deserialize(Deserializer {
+            ctx: &mut *self.ctx,
+            value,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.array.len().saturating_sub(self.idx))
+    }
+}
+
+struct MapAccess<'c> {
+    ctx: &'c mut Context,
+    pairs: std::vec::IntoIter<(Value, Value)>,
+    next_value: Option<Value>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.pairs.next() else {
+            return Ok(None);
+        };
+
+        self.next_value = Some(value);
+        seed.deserialize(Deserializer {
+            ctx: &mut *self.ctx,
+            value: key,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self.next_value.take().ok_or_else(|| {
+            <Error as de::Error>::custom("next_value_seed called before next_key_seed")
+        })?;
+        seed.deserialize(Deserializer {
+            ctx: &mut *self.ctx,
+            value,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.pairs.len())
+    }
+}
+
+struct EnumAccess<'c> {
+    ctx: &'c mut Context,
+    variant: Value,
+    content: Value,
+}
+
+impl<'de, 'c> de::EnumAccess<'de> for EnumAccess<'c> {
+    type Error = Error;
+    type Variant = Deserializer<'c>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(Deserializer {
+            ctx: &mut *self.ctx,
+            value: self.variant,
+        })?;
+        Ok((
+            variant,
+            Deserializer {
+                ctx: self.ctx,
+                value: self.content,
+            },
+        ))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
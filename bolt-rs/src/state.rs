@@ -0,0 +1,122 @@
+//! Rust-side state attached to a bolt context
+//!
+//! Native callbacks only receive a raw `bt_Context` pointer, so anything the
+//! wrapper needs to remember per-context lives here, keyed by that pointer.
+
+use bolt_sys::sys;
+use std::any::TypeId;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::commands::Command;
+use crate::events::EventBus;
+use crate::host_handles::HandleTable;
+use crate::loader::PathNormalization;
+use crate::logging::ScriptLogger;
+use crate::memory::Heap;
+use crate::prelude::Prelude;
+use crate::replay::Recorder;
+use crate::stats::TrackedNative;
+use crate::stress::GcSettings;
+use crate::types::{Module, Table, Thread, Type};
+use crate::watchdog::InterruptHandle;
+
+#[derive(Default)]
+pub(crate) struct ContextState {
+    pub commands: RefCell<Vec<Command>>,
+    pub result_type: Cell<Option<Type>>,
+    pub error_type: Cell<Option<Type>>,
+    pub native_stats_enabled: Cell<bool>,
+    /// Tracked natives keyed by their `bt_NativeFn` pointer
+    pub natives: RefCell<HashMap<usize, TrackedNative>>,
+    /// Threads kept around between host calls so `Context::call` doesn't allocate one each time
+    pub idle_threads: RefCell<Vec<Thread>>,
+    pub path_normalization: Cell<PathNormalization>,
+    /// Modules whose source is still being compiled, with the address of that source
+    pub loading: RefCell<Vec<(PathBuf, usize)>>,
+    /// Sources registered with `add_module_source`, by import name
+    pub module_sources: RefCell<HashMap<String, std::ffi::CString>>,
+    /// The part of each appended module path spec before its `%s`, in order
+    pub module_roots: RefCell<Vec<Vec<u8>>>,
+    /// Canonical directories module files and `io` are confined to, if restricted
+    pub fs_roots: RefCell<Option<Vec<PathBuf>>>,
+    /// Modules compiled so far by source hash, while the compile cache is enabled
+    pub compile_cache: RefCell<Option<crate::compile_cache::CompileCache>>,
+    /// The chain of the last import cycle the loader refused, until `run` reports it
+    pub import_cycle: RefCell<Option<Vec<String>>>,
+    /// Why the last prelude module built on import failed, until the import reports it
+    pub prelude_error: RefCell<Option<crate::Error>>,
+    pub interrupt: InterruptHandle,
+    /// Native calls left in the budget, `None` while unbudgeted
+    pub native_budget: Cell<Option<u64>>,
+    /// Set when a native call found the budget spent, until the failed execution reports it
+    pub native_budget_spent: Cell<bool>,
+    pub max_call_depth: Cell<Option<u32>>,
+    pub hook: RefCell<Option<(crate::hooks::HookMask, crate::hooks::Hook)>>,
+    /// Samples collected so far, while the profiler is running
+    pub profiler: RefCell<Option<crate::profiler::Sampler>>,
+    /// Set when a call went past `max_call_depth`, until the outermost execution reports it
+    pub call_depth_exceeded: Cell<bool>,
+    /// Backend for the script `log` module, once opened
+    pub logger: RefCell<Option<Rc<dyn ScriptLogger>>>,
+    /// Table backing `registry_set`/`registry_get`, referenced once created
+    pub registry: Cell<Option<Table>>,
+    pub handles: RefCell<HandleTable>,
+    /// Set once a memory limit or custom allocator is configured
+    pub heap: RefCell<Option<Rc<Heap>>>,
+    /// Whether `open_all_std` defers modules until they're imported
+    pub lazy_std: Cell<bool>,
+    /// Deferred std modules which haven't been imported yet
+    pub pending_std: RefCell<Vec<&'static str>>,
+    /// Prelude modules which haven't been imported yet, by index into their prelude
+    pub pending_prelude: RefCell<Vec<(Prelude, usize)>>,
+    /// Collector settings to restore once GC stress mode is turned off
+    pub gc_stress: Cell<Option<GcSettings>>,
+    /// Executions in progress, so nested ones aren't recorded twice
+    pub execution_depth: Cell<u32>,
+    pub recorder: RefCell<Option<Recorder>>,
+    /// What a script threw or a native raised, until the failed execution reports it
+    pub thrown: RefCell<Option<crate::errors::ScriptThrow>>,
+    /// The message of a panic caught in a native, until the failed execution reports it
+    pub host_panic: RefCell<Option<String>>,
+    /// The value behind the last `ScriptThrow`, see `ScriptThrow::value`
+    pub live_thrown: RefCell<Option<crate::errors::LiveThrow>>,
+    /// The script stack at the last runtime error, until the failed execution reports it
+    pub traceback: RefCell<Option<crate::traceback::Traceback>>,
+    pub events: RefCell<EventBus>,
+    /// Userdata types built by `register_userdata`, keyed by the Rust type they hold
+    pub userdata_types: RefCell<HashMap<TypeId, Type>>,
+    /// The same types keyed by their `bt_Type` pointer, for finalizers to recognise them
+    pub userdata_type_ptrs: RefCell<HashSet<usize>>,
+    /// Rust types whose `BoltMethods` have been registered
+    pub userdata_methods: RefCell<HashSet<TypeId>>,
+    /// Owner of every userdata method and closure native, referenced once created
+    pub method_module: Cell<Option<Module>>,
+    /// Closures behind userdata methods and operators, keyed by the `bt_NativeFn` that calls
+    /// them
+    pub meta_fns: RefCell<HashMap<usize, crate::meta::Method>>,
+    /// Computed userdata properties, indexed by the field offset bolt passes their callbacks
+    pub properties: RefCell<Vec<crate::userdata::Property>>,
+    /// Serialization hooks for userdata types, keyed by type name
+    pub userdata_codecs: RefCell<HashMap<String, crate::userdata::Codec>>,
+    #[cfg(feature = "math-types")]
+    pub math_types: Cell<Option<crate::math::MathTypes>>,
+}
+
+thread_local! {
+    static STATES: RefCell<HashMap<usize, Rc<ContextState>>> = RefCell::new(HashMap::new());
+}
+
+/// Fetch the state for `ctx`, creating it on first use
+pub(crate) fn get(ctx: *mut sys::bt_Context) -> Rc<ContextState> {
+    STATES.with(|states| states.borrow_mut().entry(ctx as usize).or_default().clone())
+}
+
+/// Drop the state for `ctx`, called once the context has been closed
+pub(crate) fn release(ctx: *mut sys::bt_Context) {
+    let state = STATES.with(|states| states.borrow_mut().remove(&(ctx as usize)));
+    crate::memory::forget(ctx);
+    drop(state);
+}
@@ -0,0 +1,193 @@
+//! Opt-in call statistics for native functions
+//!
+//! Every native is routed through a trampoline: natives registered through
+//! [`Context::make_native`] and [`Context::module_export_native`] as they're made, and the
+//! ones bolt registers itself, like the standard library's, as their modules are opened.
+//! When enabled with [`Context::set_native_stats`], the trampoline counts invocations and
+//! accumulates wall-clock time.
+//!
+//! The same trampoline is where a context checks for interrupts, the native call budget and call depth, see
+//! [`crate::watchdog`], so every native call is a point where a script can be stopped, no
+//! matter when the native was registered.
+
+use bolt_sys::sys;
+use std::time::{Duration, Instant};
+
+use crate::hooks::HookKind;
+use crate::types::{Table, Type};
+use crate::{Context, Thread, ValueType};
+
+/// Call counts and cumulative time for one native function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeStats {
+    pub name: String,
+    pub calls: u64,
+    pub total: Duration,
+}
+
+impl NativeStats {
+    /// Mean time spent per call
+    pub fn average(&self) -> Duration {
+        if self.calls == 0 {
+            return Duration::ZERO;
+        }
+        // Dividing the `Duration` itself would truncate the count to `u32`
+        let nanos = self.total.as_nanos() / u128::from(self.calls);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+pub(crate) struct TrackedNative {
+    pub proc: sys::bt_NativeProc,
+    pub stats: NativeStats,
+}
+
+/// Route `native` through the trampoline, calling `proc` from it
+pub(crate) fn track(
+    ctx: *mut sys::bt_Context,
+    native: *mut sys::bt_NativeFn,
+    proc: sys::bt_NativeProc,
+    name: &str,
+) {
+    crate::state::get(ctx).natives.borrow_mut().insert(
+        native as usize,
+        TrackedNative {
+            proc,
+            stats: NativeStats {
+                name: name.to_owned(),
+                calls: 0,
+                total: Duration::ZERO,
+            },
+        },
+    );
+    unsafe { (*native).fn_ = Some(native_trampoline) };
+}
+
+/// Route the natives bolt registered itself, i.e. exports of registered modules and
+/// methods of builtin and registered types, through the trampoline
+///
+/// Bolt has no API for this, so it walks bolt's internals and swaps each native's `fn_`
+/// in place. That relies on the layout of `bt_Context`, `bt_Module`, `bt_Type` and
+/// `bt_NativeFn` matching the bindings, and on the VM reading `fn_` on every call rather
+/// than caching it when the function is made. Both hold for the bolt revision bolt-sys
+/// is built from, and a bolt update that changes either shows up as std natives missing
+/// from [`Context::native_stats`] and no longer honouring interrupts.
+pub(crate) fn route_loaded(ctx: &mut Context) {
+    let raw = ctx.as_ptr();
+    let mut tables = Vec::new();
+    if let Some(modules) = Table::from_raw(unsafe { (*raw).loaded_modules }) {
+        let modules = modules
+            .iter(ctx)
+            .filter_map(|(_, module)| module.as_object());
+        tables.extend(
+            modules
+                .filter(|module| matches!(module.value_type(), ValueType::Module))
+                .map(|module| unsafe { (*(module.as_ptr() as *mut sys::bt_Module)).exports }),
+        );
+    }
+    let mut types = vec![
+        ctx.type_string(),
+        ctx.type_array(),
+        ctx.type_table(),
+        ctx.type_number(),
+        ctx.type_bool(),
+    ];
+    if let Some(registry) = Table::from_raw(unsafe { (*raw).type_registry }) {
+        let registered = registry.iter(ctx).filter_map(|(_, ty)| ty.as_object());
+        types.extend(
+            registered
+                .filter(|ty| matches!(ty.value_type(), ValueType::Type))
+                .map(|ty| unsafe { Type::from_raw_unchecked(ty.as_ptr() as *mut _) }),
+        );
+    }
+    tables.extend(
+        types
+            .iter()
+            .map(|ty| unsafe { (*ty.as_ptr()).prototype_values }),
+    );
+
+    let trampoline = native_trampoline as unsafe extern "C" fn(_, _) as usize;
+    for table in tables.into_iter().filter_map(Table::from_raw) {
+        for (key, value) in table.iter(ctx).collect::<Vec<_>>() {
+            let Some(obj) = value.as_object() else {
+                continue;
+            };
+            if !matches!(obj.value_type(), ValueType::NativeFunction) {
+                continue;
+            }
+            let native = obj.as_ptr() as *mut sys::bt_NativeFn;
+            let proc = unsafe { (*native).fn_ };
+            if proc.is_some_and(|proc| proc as usize != trampoline) {
+                let name = <String as crate::FromBoltValue>::from(key.0).unwrap_or_default();
+                track(raw, native, proc, &name);
+            }
+        }
+    }
+}
+
+/// Shared entry point for every tracked native, dispatching to the real proc by looking
+/// up the native function object executing on the current frame
+pub(crate) unsafe extern "C" fn native_trampoline(
+    ctx: *mut sys::bt_Context,
+    thr: *mut sys::bt_Thread,
+) {
+    let state = crate::state::get(ctx);
+    // Whatever the native builds belongs to the context calling it
+    crate::memory::touch(ctx, &state);
+    if state.interrupt.is_interrupted() {
+        unsafe { sys::bt_runtime_error(thr, c"interrupted".as_ptr(), std::ptr::null_mut()) };
+        return;
+    }
+    if crate::memory::exceeded(&state) {
+        let msg = c"memory limit exceeded";
+        unsafe { sys::bt_runtime_error(thr, msg.as_ptr(), std::ptr::null_mut()) };
+        return;
+    }
+    if !crate::budget::consume(&state, thr) || !crate::depth::check_native(&state, thr) {
+        return;
+    }
+
+    // Only tracked natives call into here, so failing to find the proc means bolt's frame
+    // layout isn't what the bindings expect, which must not look like a call that returned
+    let proc = unsafe { Thread::from_raw_unchecked(thr) }
+        .current_callable()
+        .and_then(|callable| {
+            let key = callable.as_ptr() as usize;
+            let proc = state.natives.borrow().get(&key).map(|native| native.proc);
+            proc.map(|proc| (callable, key, proc))
+        });
+    let Some((callable, key, proc)) = proc else {
+        let msg = c"native called through the stats trampoline isn't tracked";
+        unsafe { sys::bt_runtime_error(thr, msg.as_ptr(), std::ptr::null_mut()) };
+        return;
+    };
+    // A native made without a proc has nothing to run
+    let Some(proc) = proc else {
+        return;
+    };
+
+    let depth = unsafe { (*thr).depth } as usize;
+    let hook = |kind| unsafe {
+        crate::unwind::guard(ctx, thr, "hook", || {
+            crate::hooks::emit(ctx, kind, callable, depth)
+        })
+    };
+    if hook(HookKind::Call).is_none() {
+        return;
+    }
+    // Time up to the call was spent in the script, and the native is its innermost frame
+    crate::profiler::sample(ctx, 1);
+    let start = state.native_stats_enabled.get().then(Instant::now);
+    unsafe { proc(ctx, thr) };
+    let elapsed = start.map(|start| start.elapsed());
+    crate::profiler::sample(ctx, 0);
+    hook(HookKind::Return);
+
+    let Some(elapsed) = elapsed else {
+        return;
+    };
+    if let Some(native) = state.natives.borrow_mut().get_mut(&key) {
+        native.stats.calls += 1;
+        native.stats.total += elapsed;
+    }
+}
@@ -0,0 +1,119 @@
+//! Opening a chosen set of standard library modules
+//!
+//! [`Context::open_std`] takes the modules to open as one [`StdLib`] value, so a host's
+//! policy on what scripts may use is written in one place, and presets like
+//! [`StdLib::ALL_SAFE`] pick up modules added to the standard library later:
+//!
+//! ```ignore
+//! ctx.open_std(StdLib::CORE | StdLib::MATH | StdLib::STRINGS);
+//! ```
+
+use std::ops::BitOr;
+
+use bolt_sys::sys;
+
+use crate::Context;
+
+/// A set of standard library modules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StdLib(u16);
+
+impl StdLib {
+    pub const NONE: StdLib = StdLib(0);
+    pub const CORE: StdLib = StdLib(1);
+    pub const ARRAYS: StdLib = StdLib(1 << 1);
+    pub const STRINGS: StdLib = StdLib(1 << 2);
+    pub const TABLES: StdLib = StdLib(1 << 3);
+    pub const MATH: StdLib = StdLib(1 << 4);
+    pub const IO: StdLib = StdLib(1 << 5);
+    pub const META: StdLib = StdLib(1 << 6);
+    pub const REGEX: StdLib = StdLib(1 << 7);
+
+    /// Every module the standard library has
+    pub const ALL: StdLib = {
+        let mut all = 0;
+        let mut idx = 0;
+        while idx < MODULES.len() {
+            all |= MODULES[idx].0.0;
+            idx += 1;
+        }
+        StdLib(all)
+    };
+
+    /// Every module that can't reach outside the script, i.e. all but `io`
+    pub const ALL_SAFE: StdLib = Self::ALL.without(Self::IO);
+
+    pub fn contains(self, other: StdLib) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// This set with the modules in `other` taken out
+    pub const fn without(self, other: StdLib) -> StdLib {
+        StdLib(self.0 & !other.0)
+    }
+
+    /// The import names of the modules in the set
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        MODULES
+            .iter()
+            .filter(move |(lib, _, _)| self.contains(*lib))
+            .map(|(_, name, _)| *name)
+    }
+}
+
+impl BitOr for StdLib {
+    type Output = StdLib;
+
+    fn bitor(self, rhs: StdLib) -> StdLib {
+        StdLib(self.0 | rhs.0)
+    }
+}
+
+type OpenFn = unsafe extern "C" fn(*mut sys::bt_Context);
+
+/// Every module, in the order they're opened
+const MODULES: [(StdLib, &str, OpenFn); 8] = [
+    (StdLib::CORE, "core", sys::boltstd_open_core),
+    (StdLib::ARRAYS, "arrays", sys::boltstd_open_arrays),
+    (StdLib::STRINGS, "strings", sys::boltstd_open_strings),
+    (StdLib::TABLES, "tables", sys::boltstd_open_tables),
+    (StdLib::MATH, "math", sys::boltstd_open_math),
+    (StdLib::IO, "io", sys::boltstd_open_io),
+    (StdLib::META, "meta", sys::boltstd_open_meta),
+    (StdLib::REGEX, "regex", sys::boltstd_open_regex),
+];
+
+impl Context {
+    /// Open the standard library modules in `libs`
+    ///
+    /// On a context built with [`ContextBuilder::lazy_std`](crate::ContextBuilder::lazy_std),
+    /// modules that are only reachable through `import` are built on first import, like
+    /// with [`Context::open_all_std`]. On a context restricted with
+    /// [`Context::restrict_filesystem`], `io` is left alone.
+    pub fn open_std(&mut self, libs: StdLib) {
+        let state = crate::state::get(self.as_ptr());
+        let lazy = state.lazy_std.get();
+        let libs = if state.fs_roots.borrow().is_some() {
+            libs.without(StdLib::IO)
+        } else {
+            libs
+        };
+        for (lib, name, open) in MODULES {
+            if !libs.contains(lib) {
+                continue;
+            }
+            let deferred = crate::lazy_std::DEFERRED
+                .iter()
+                .any(|(deferred, _)| *deferred == name);
+            if lazy && deferred {
+                let mut pending = state.pending_std.borrow_mut();
+                if !pending.contains(&name) {
+                    pending.push(name);
+                }
+            } else {
+                unsafe { open(self.as_ptr()) };
+            }
+        }
+        crate::stats::route_loaded(self);
+    }
+}
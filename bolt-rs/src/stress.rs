@@ -0,0 +1,52 @@
+//! GC stress testing
+//!
+//! Objects a native or wrapper creates but forgets to root usually survive, because
+//! collections are rare. [`Context::gc_stress`] tunes the collector so every allocation
+//! collects first, which turns those latent use-after-free bugs into immediate failures.
+
+use crate::Context;
+
+/// Collector settings replaced while stress mode is on
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GcSettings {
+    min_size: usize,
+    growth_pct: usize,
+    pause_growth_pct: usize,
+    next_cycle: usize,
+}
+
+impl Context {
+    /// Collect before every allocation while `enabled`, restoring the previous collector
+    /// settings once disabled. This is very slow and only meant for tests.
+    pub fn gc_stress(&mut self, enabled: bool) {
+        let state = crate::state::get(self.as_ptr());
+        match (enabled, state.gc_stress.get()) {
+            (true, None) => {
+                state.gc_stress.set(Some(GcSettings {
+                    min_size: self.gc_get_min_size(),
+                    growth_pct: self.gc_get_growth_pct(),
+                    pause_growth_pct: self.gc_get_pause_growth_pct(),
+                    next_cycle: self.gc_get_next_cycle(),
+                }));
+                // With no minimum and no growth, the next cycle is always due immediately
+                self.gc_set_min_size(0);
+                self.gc_set_growth_pct(0);
+                self.gc_set_pause_growth_pct(0);
+                self.gc_set_next_cycle(0);
+            }
+            (false, Some(settings)) => {
+                state.gc_stress.set(None);
+                self.gc_set_min_size(settings.min_size);
+                self.gc_set_growth_pct(settings.growth_pct);
+                self.gc_set_pause_growth_pct(settings.pause_growth_pct);
+                self.gc_set_next_cycle(settings.next_cycle);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether [`Context::gc_stress`] is enabled
+    pub fn is_gc_stressed(&mut self) -> bool {
+        crate::state::get(self.as_ptr()).gc_stress.get().is_some()
+    }
+}
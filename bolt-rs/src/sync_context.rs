@@ -0,0 +1,120 @@
+//! Sharing a context between threads
+//!
+//! A [`Context`] stays on the thread that created it, see its docs for why. A
+//! [`SyncContext`] gives it a thread of its own instead, and is itself `Send` and `Sync`:
+//! any thread holding one runs closures on the context with [`SyncContext::with`], which
+//! queues them on the owning thread and waits for their result. Calls from several threads
+//! run one at a time, in the order they were queued.
+//!
+//! ```ignore
+//! let shared = Arc::new(SyncContext::new(|ctx| {
+//!     ctx.open_all_std();
+//!     Ok(())
+//! })?);
+//! let worker = std::thread::spawn({
+//!     let shared = shared.clone();
+//!     move || shared.with(|ctx| ctx.run("let x = 1 + 2").is_ok())
+//! });
+//! assert!(worker.join().unwrap());
+//! ```
+//!
+//! Closures and their results cross threads, so they have to be `Send`, which keeps values
+//! and other handles into the context on its thread. Carry plain Rust data, or a
+//! [`RecordedValue`](crate::replay::RecordedValue), in and out instead.
+
+use std::any::Any;
+use std::sync::mpsc::{Sender, channel};
+use std::thread::JoinHandle;
+
+use crate::{Context, Error, InterruptHandle};
+
+type Job = Box<dyn FnOnce(&mut Context) + Send>;
+
+/// A context living on a thread of its own, usable from any thread, see the
+/// [module docs](self)
+#[derive(Debug)]
+pub struct SyncContext {
+    jobs: Option<Sender<Job>>,
+    owner: Option<JoinHandle<()>>,
+    interrupt: InterruptHandle,
+}
+
+impl SyncContext {
+    /// Open a context on a new thread and run `init` on it, failing if either fails
+    pub fn new(
+        init: impl FnOnce(&mut Context) -> Result<(), Error> + Send + 'static,
+    ) -> Result<Self, Error> {
+        let (jobs, queue) = channel::<Job>();
+        let (opened, ready) = channel();
+        let owner = std::thread::spawn(move || {
+            let mut ctx = match Context::try_new().and_then(|mut ctx| {
+                init(&mut ctx)?;
+                Ok(ctx)
+            }) {
+                Ok(ctx) => ctx,
+                Err(err) => {
+                    let _ = opened.send(Err(err));
+                    return;
+                }
+            };
+            let _ = opened.send(Ok(ctx.interrupt_handle()));
+            // Runs until every handle to the queue is gone, i.e. the `SyncContext` dropped
+            for job in queue {
+                job(&mut ctx);
+            }
+        });
+
+        match ready.recv() {
+            Ok(Ok(interrupt)) => Ok(Self {
+                jobs: Some(jobs),
+                owner: Some(owner),
+                interrupt,
+            }),
+            Ok(Err(err)) => Err(err),
+            Err(_) => match owner.join() {
+                Err(payload) => std::panic::resume_unwind(payload),
+                Ok(()) => unreachable!("the owner thread reports how opening went"),
+            },
+        }
+    }
+
+    /// Run `f` on the context and return what it returned, waiting for calls queued by
+    /// other threads first
+    ///
+    /// A panic in `f` is carried over to the calling thread, and the context stays usable.
+    pub fn with<R: Send + 'static>(&self, f: impl FnOnce(&mut Context) -> R + Send + 'static) -> R {
+        let (done, result) = channel::<Result<R, Box<dyn Any + Send>>>();
+        let job: Job = Box::new(move |ctx| {
+            let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(ctx)));
+            let _ = done.send(outcome);
+        });
+        self.jobs
+            .as_ref()
+            .expect("the queue lives as long as the SyncContext")
+            .send(job)
+            .expect("the owner thread runs until the SyncContext is dropped");
+        match result
+            .recv()
+            .expect("the owner thread reports the result of every job")
+        {
+            Ok(value) => value,
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    }
+
+    /// A handle which interrupts whatever the context is running, see
+    /// [`Context::interrupt_handle`]
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+}
+
+impl Drop for SyncContext {
+    fn drop(&mut self) {
+        // Closing the queue ends the owner thread, which closes the context
+        drop(self.jobs.take());
+        if let Some(owner) = self.owner.take() {
+            let _ = owner.join();
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Script call stacks captured when a runtime error is raised
+//!
+//! bolt reports a runtime error through `on_error` before unwinding, so while the wrapper
+//! is capturing diagnostics for a context, the call stack of the thread that raised it is
+//! still intact and can be walked. bolt doesn't keep an instruction pointer per frame, so
+//! only the innermost frame has a line, the one the error was reported at. Functions are
+//! named after the export of their module they're bound to, where there is one.
+//!
+//! [`Thread::frames`] walks any thread's stack the same way, e.g. from a native to report
+//! where it was called from. bolt keeps locals in anonymous stack slots with no debug
+//! names, so frames carry no locals.
+
+use bolt_sys::sys;
+
+use crate::types::{BoltString, Module, Object};
+use crate::{Context, Thread, Value, ValueType};
+
+/// One call on the script stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub function: String,
+    /// The module the function was defined in, if it could be worked out
+    pub module: Option<String>,
+    pub line: Option<u16>,
+}
+
+/// The script call stack at the point a runtime error was raised, innermost call first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Traceback {
+    pub frames: Vec<StackFrame>,
+}
+
+impl std::fmt::Display for Traceback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Traceback (most recent call last):")?;
+        for frame in self.frames.iter().rev() {
+            write!(f, "\n  {}", frame.function)?;
+            if let Some(module) = &frame.module {
+                write!(f, " in {module}")?;
+            }
+            if let Some(line) = frame.line {
+                write!(f, ", line {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Walk the stack of the thread currently executing in `ctx`, giving the innermost frame
+/// `line`
+pub(crate) fn walk(ctx: *mut sys::bt_Context, line: u16) -> Traceback {
+    let thread = unsafe { (*ctx).current_thread };
+    if thread.is_null() {
+        return Traceback::default();
+    }
+    walk_thread(ctx, thread, Some(line))
+}
+
+fn walk_thread(
+    ctx: *mut sys::bt_Context,
+    thread: *mut sys::bt_Thread,
+    line: Option<u16>,
+) -> Traceback {
+    let depth = unsafe { (*thread).depth } as usize;
+    let callstack = unsafe { &(*thread).callstack };
+    let frames = callstack[..depth.min(callstack.len())]
+        .iter()
+        .rev()
+        .filter_map(|frame| Object::from_raw(frame.callable as *mut sys::bt_Object))
+        .enumerate()
+        .map(|(idx, callable)| {
+            let (function, module) = describe(ctx, callable);
+            StackFrame {
+                function,
+                module,
+                line: line.filter(|_| idx == 0),
+            }
+        })
+        .collect();
+    Traceback { frames }
+}
+
+impl Thread {
+    /// The calls on this thread's stack, innermost first
+    ///
+    /// Called from inside a native, the first frame is the native itself. Frames have no
+    /// line, since bolt only reports one when raising an error.
+    pub fn frames(&self, ctx: &Context) -> Vec<StackFrame> {
+        walk_thread(ctx.as_ptr(), self.as_ptr(), None).frames
+    }
+}
+
+/// The name of a callable and of the module it came from
+pub(crate) fn describe(ctx: *mut sys::bt_Context, callable: Object) -> (String, Option<String>) {
+    let ptr = callable.as_ptr();
+    let module = match callable.value_type() {
+        ValueType::Module => {
+            let module = unsafe { Module::from_raw_unchecked(ptr as *mut _) };
+            return ("<top level>".to_owned(), module.name());
+        }
+        ValueType::NativeFunction => {
+            let state = crate::state::get(ctx);
+            let natives = state.natives.borrow();
+            let name = natives
+                .get(&(ptr as usize))
+                .map_or_else(|| "<native>".to_owned(), |native| native.stats.name.clone());
+            return (name, None);
+        }
+        ValueType::Function => unsafe { (*(ptr as *mut sys::bt_Fn)).module },
+        ValueType::Closure => unsafe { (*(*(ptr as *mut sys::bt_Closure)).fn_).module },
+        _ => std::ptr::null_mut(),
+    };
+
+    let Some(module) = Module::from_raw(module) else {
+        return ("<anonymous>".to_owned(), None);
+    };
+    let name = module
+        .export_table()
+        .and_then(|exports| {
+            exports.raw_pairs().iter().find_map(|pair| {
+                let value = Value::from_raw(pair.value).as_object()?;
+                if value.as_ptr() != ptr {
+                    return None;
+                }
+                let key = Value::from_raw(pair.key).as_object()?;
+                matches!(key.value_type(), ValueType::String).then(|| {
+                    unsafe { BoltString::from_raw_unchecked(key.as_ptr() as *mut _) }
+                        .to_string_lossy()
+                })
+            })
+        })
+        .unwrap_or_else(|| "<anonymous>".to_owned());
+    (name, module.name())
+}
@@ -0,0 +1,97 @@
+//! Copying values between contexts
+
+use std::collections::HashMap;
+
+use crate::types::{Array, Table};
+use crate::{
+    Context, Error, FromBoltValue, MakeBoltValue, MakeBoltValueWithContext, Value, ValueType,
+};
+
+/// The objects copied so far, by their address in the source context
+type Copied = HashMap<usize, Value>;
+
+impl Value {
+    /// Deep-copy this value out of `src` into `dst`
+    ///
+    /// Objects belong to the context that allocated them, so the value is rebuilt in `dst`:
+    /// numbers, bools and null are copied as they are, and strings, arrays and tables are
+    /// copied deeply, keeping shared and cyclic references intact. Functions, userdata and
+    /// other objects have no meaning outside their context, so they fail the transfer, see
+    /// [`Value::transfer_with`].
+    pub fn transfer(self, src: &mut Context, dst: &mut Context) -> Result<Value, Error> {
+        self.transfer_with(src, dst, |_, _, value| {
+            Err(Error::bolt(format!(
+                "Can't transfer a {:?} value between contexts",
+                value
+                    .as_object()
+                    .map_or(ValueType::None, |obj| obj.value_type())
+            )))
+        })
+    }
+
+    /// Like [`Value::transfer`], calling `map` with any value that can't be copied, which
+    /// returns its replacement in `dst` or fails the transfer
+    pub fn transfer_with(
+        self,
+        src: &mut Context,
+        dst: &mut Context,
+        mut map: impl FnMut(&mut Context, &mut Context, Value) -> Result<Value, Error>,
+    ) -> Result<Value, Error> {
+        // The copies aren't reachable from anything in `dst` until the transfer returns
+        dst.gc_pause();
+        let result = copy(src, dst, self, &mut Copied::new(), &mut map);
+        dst.gc_unpause();
+        result
+    }
+}
+
+fn copy(
+    src: &mut Context,
+    dst: &mut Context,
+    value: Value,
+    copied: &mut Copied,
+    map: &mut dyn FnMut(&mut Context, &mut Context, Value) -> Result<Value, Error>,
+) -> Result<Value, Error> {
+    let Some(obj) = value.as_object() else {
+        return Ok(value);
+    };
+    let key = obj.as_ptr() as usize;
+    if let Some(copy) = copied.get(&key) {
+        return Ok(*copy);
+    }
+
+    match obj.value_type() {
+        ValueType::String => {
+            let string = <String as FromBoltValue>::from(value.0)?;
+            Ok(Value::from_raw(string.as_str().make_with_context(dst)))
+        }
+        ValueType::Array => {
+            let array = unsafe { Array::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            let items: Vec<Value> = array.iter(src).collect();
+            let copy = dst.make_array(items.len() as u32);
+            copied.insert(key, Value::from_raw(copy.make()));
+            for item in items {
+                let item = self::copy(src, dst, item, copied, map)?;
+                dst.array_push(copy, item);
+            }
+            Ok(Value::from_raw(copy.make()))
+        }
+        ValueType::Table => {
+            let table = unsafe { Table::from_raw_unchecked(obj.as_ptr() as *mut _) };
+            let pairs: Vec<(Value, Value)> = table.iter(src).collect();
+            let copy = dst.make_table(pairs.len().min(u16::MAX as usize) as u16);
+            copied.insert(key, Value::from_raw(copy.make()));
+            for (key, item) in pairs {
+                let key = self::copy(src, dst, key, copied, map)?;
+                let item = self::copy(src, dst, item, copied, map)?;
+                dst.table_set(copy, key, item);
+            }
+            Ok(Value::from_raw(copy.make()))
+        }
+        _ => {
+            let replacement = map(src, dst, value)?;
+            copied.insert(key, replacement);
+            Ok(replacement)
+        }
+    }
+}
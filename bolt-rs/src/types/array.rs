@@ -0,0 +1,169 @@
+use bolt_sys::sys;
+
+use super::{Array, Value};
+use crate::{Context, Error, MakeBoltValueWithContext};
+
+impl Context {
+    /// Build an array from any iterator, preallocating from its size hint
+    pub fn make_array_from_iter<I>(&mut self, items: I) -> Array
+    where
+        I: IntoIterator,
+        I::Item: MakeBoltValueWithContext,
+    {
+        let mut items = items.into_iter();
+        let capacity = items.size_hint().0;
+        self.build_array(capacity, |ctx| {
+            items.next().map(|item| item.make_with_context(ctx))
+        })
+    }
+
+    /// Build a rooted array with room for `capacity` items, pushing values from `next`
+    /// until it returns `None`
+    pub(crate) fn build_array(
+        &mut self,
+        capacity: usize,
+        mut next: impl FnMut(&mut Context) -> Option<sys::bt_Value>,
+    ) -> Array {
+        let array = self.make_array(capacity.min(u32::MAX as usize) as u32);
+        self.push_root(array.as_object());
+        while let Some(item) = next(self) {
+            // Growing the array can collect, and nothing references a fresh item yet
+            let item = Value::from_raw(item);
+            let rooted = item.as_object().inspect(|obj| self.push_root(*obj));
+            self.array_push(array, item);
+            if rooted.is_some() {
+                self.pop_root();
+            }
+        }
+        self.pop_root();
+        array
+    }
+}
+
+impl Array {
+    /// Number of items in the array
+    pub fn len(&self) -> usize {
+        unsafe { sys::bt_array_length(self.as_ptr()) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The item at `idx`, or `None` if it's out of bounds
+    pub fn get(&self, ctx: &mut Context, idx: usize) -> Option<Value> {
+        (idx < self.len()).then(|| ctx.array_get(*self, idx as u64))
+    }
+
+    /// Like [`Array::get`], but reports out of bounds access as an error
+    pub fn at(&self, ctx: &mut Context, idx: usize) -> Result<Value, Error> {
+        self.get(ctx, idx).ok_or(Error::IndexOutOfBounds {
+            idx,
+            len: self.len(),
+        })
+    }
+
+    /// Overwrite the item at `idx`, returning `false` if it's out of bounds
+    pub fn set(&self, ctx: &mut Context, idx: usize, value: impl MakeBoltValueWithContext) -> bool {
+        if idx >= self.len() {
+            return false;
+        }
+        let value = Value::from_raw(value.make_with_context(ctx));
+        let rooted = value.as_object().inspect(|obj| ctx.push_root(*obj));
+        let set = ctx.array_set(*self, idx as u64, value);
+        if rooted.is_some() {
+            ctx.pop_root();
+        }
+        set
+    }
+
+    /// Append an item, returning the new length
+    pub fn push(&self, ctx: &mut Context, value: impl MakeBoltValueWithContext) -> usize {
+        // Growing the array can collect, and nothing references a fresh item yet
+        let value = Value::from_raw(value.make_with_context(ctx));
+        let rooted = value.as_object().inspect(|obj| ctx.push_root(*obj));
+        let len = ctx.array_push(*self, value) as usize;
+        if rooted.is_some() {
+            ctx.pop_root();
+        }
+        len
+    }
+
+    /// The items currently stored in the array
+    pub(crate) fn raw_items(&self) -> &[sys::bt_Value] {
+        unsafe {
+            let raw = self.as_ptr();
+            if (*raw).items.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts((*raw).items, self.len())
+            }
+        }
+    }
+
+    /// Iterate over the items in order
+    pub fn iter<'a>(&self, ctx: &'a mut Context) -> ArrayIter<'a> {
+        ArrayIter {
+            ctx,
+            array: *self,
+            idx: 0,
+        }
+    }
+}
+
+/// Iterator over the items of an [`Array`], see [`Array::iter`]
+pub struct ArrayIter<'a> {
+    ctx: &'a mut Context,
+    array: Array,
+    idx: usize,
+}
+
+impl Iterator for ArrayIter<'_> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let item = self.array.get(self.ctx, self.idx)?;
+        self.idx += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.array.len().saturating_sub(self.idx);
+        (remaining, Some(remaining))
+    }
+}
+
+impl IntoIterator for &Array {
+    type Item = Value;
+    type IntoIter = Items;
+
+    /// Iterate without holding the context. The array is re-read on every step, so pushing
+    /// to it mid-iteration is safe.
+    fn into_iter(self) -> Items {
+        Items {
+            array: *self,
+            idx: 0,
+        }
+    }
+}
+
+/// Iterator over the items of a borrowed [`Array`]
+pub struct Items {
+    array: Array,
+    idx: usize,
+}
+
+impl Iterator for Items {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        let item = *self.array.raw_items().get(self.idx)?;
+        self.idx += 1;
+        Some(Value::from_raw(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.array.len().saturating_sub(self.idx);
+        (remaining, Some(remaining))
+    }
+}
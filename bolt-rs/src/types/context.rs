@@ -4,26 +4,106 @@
 //! C API wrappers and high-level ergonomic methods.
 
 use super::*;
-use crate::{Error, wrappers::IntoCStr};
+use crate::{Error, MakeBoltValue, MakeBoltValueWithContext, ValueType, wrappers::IntoCStr};
 use bolt_sys::sys::{self, *};
 
 /// Safe wrapper around bt_Context
-#[derive(Debug, Clone)]
+///
+/// A `Context` owns the underlying context and closes it when dropped, so it can't be
+/// cloned. Code handed a raw pointer to a context owned elsewhere, e.g. a native callback,
+/// should borrow it through [`ContextRef`] instead.
+///
+/// # Threads
+///
+/// A context is neither `Send` nor `Sync`, and stays on the thread that created it. bolt
+/// itself has no thread affinity, but the state bolt-rs keeps per context (registered
+/// closures, hooks, pooled threads, the profiler) lives in a thread local and holds `Rc`s
+/// and closures that aren't `Send` either, so a context moved to another thread would lose
+/// it. Run one context per thread instead, passing plain Rust data (or a
+/// [`RecordedValue`](crate::replay::RecordedValue)) between them, or share one through a
+/// [`SyncContext`](crate::SyncContext), which keeps it on a thread of its own. The one thing
+/// meant to be used from other threads is the [`InterruptHandle`](crate::InterruptHandle).
+///
+/// ```compile_fail
+/// fn assert_send<T: Send>() {}
+/// assert_send::<bolt_rs::Context>();
+/// ```
+///
+/// ```compile_fail
+/// fn assert_sync<T: Sync>() {}
+/// assert_sync::<bolt_rs::Context>();
+/// ```
+#[derive(Debug)]
 pub struct Context {
     ptr: ::std::ptr::NonNull<sys::bt_Context>,
+    /// Opts out of `Send` and `Sync` on purpose rather than by way of the raw pointer
+    _thread_bound: ::std::marker::PhantomData<::std::rc::Rc<()>>,
+}
+
+/// A borrowed [`Context`] which is never closed, see [`ContextRef::from_raw`]
+#[derive(Debug)]
+pub struct ContextRef<'a> {
+    ctx: ::std::mem::ManuallyDrop<Context>,
+    _borrow: ::std::marker::PhantomData<&'a mut Context>,
+}
+
+impl ContextRef<'_> {
+    /// Borrow the context behind `ptr` without taking ownership of it
+    ///
+    /// # Safety
+    /// `ptr` is a live context which outlives the returned borrow, e.g. the context passed to
+    /// a native callback.
+    #[inline]
+    pub unsafe fn from_raw(ptr: *mut sys::bt_Context) -> Self {
+        Self {
+            ctx: ::std::mem::ManuallyDrop::new(unsafe { Context::from_raw_unchecked(ptr) }),
+            _borrow: ::std::marker::PhantomData,
+        }
+    }
+}
+
+impl ::std::ops::Deref for ContextRef<'_> {
+    type Target = Context;
+
+    #[inline]
+    fn deref(&self) -> &Context {
+        &self.ctx
+    }
+}
+
+impl ::std::ops::DerefMut for ContextRef<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Context {
+        &mut self.ctx
+    }
 }
 
 impl Context {
+    /// Take ownership of the context behind `ptr`, which is closed once the wrapper is
+    /// dropped
+    ///
+    /// # Safety
+    /// `ptr` is null or a context opened with `bt_open` that nothing else owns: nothing
+    /// else closes it or wraps it in another `Context`, and it isn't used once the wrapper
+    /// is dropped.
     #[inline]
-    pub fn from_raw(ptr: *mut sys::bt_Context) -> Option<Self> {
-        ::std::ptr::NonNull::new(ptr).map(|ptr| Self { ptr })
+    pub unsafe fn from_raw(ptr: *mut sys::bt_Context) -> Option<Self> {
+        ::std::ptr::NonNull::new(ptr).map(|ptr| Self {
+            ptr,
+            _thread_bound: ::std::marker::PhantomData,
+        })
     }
 
+    /// Like [`Context::from_raw`], without checking for null
+    ///
+    /// # Safety
+    /// `ptr` isn't null, and the contract of [`Context::from_raw`] holds.
     #[inline]
     pub unsafe fn from_raw_unchecked(ptr: *mut sys::bt_Context) -> Self {
         unsafe {
             Self {
                 ptr: ::std::ptr::NonNull::new_unchecked(ptr),
+                _thread_bound: ::std::marker::PhantomData,
             }
         }
     }
@@ -207,8 +287,11 @@ impl Context {
         unsafe { sys::bt_type_set_field(self.as_ptr(), type_.as_ptr(), name.0, value.0) }
     }
 
-    pub fn register_type(&mut self, name: Value, type_: Type) {
+    /// Register `type_` under `name`, which must be a valid identifier
+    pub fn register_type(&mut self, name: Value, type_: Type) -> Result<(), crate::Error> {
+        crate::names::validate_value(name)?;
         unsafe { sys::bt_register_type(self.as_ptr(), name.0, type_.as_ptr()) }
+        Ok(())
     }
 
     pub fn register_prelude(&mut self, name: Value, type_: Type, value: Value) {
@@ -305,19 +388,41 @@ impl Context {
     bt_def_userdata_field!(string);
     bt_def_userdata_field!(bool);
 
-    bt_def!(make_string(s: &CStr) -> BoltString);
-    bt_def!(make_string_hashed(s: &CStr) -> BoltString);
     bt_def!(string_concat(a: BoltString, b: BoltString) -> BoltString);
     bt_def!(remove_interned(str: BoltString));
 
+    /// Make a string holding `s`, which may contain NULs
+    pub fn make_string(&mut self, s: impl IntoCStr) -> BoltString {
+        let bytes = s.raw_bytes();
+        unsafe {
+            BoltString::from_raw_unchecked(sys::bt_make_string_len(
+                self.as_ptr(),
+                bytes.as_ptr() as *const std::ffi::c_char,
+                bytes.len() as u32,
+            ))
+        }
+    }
+
+    /// Like [`Context::make_string`], hashing the string up front
+    pub fn make_string_hashed(&mut self, s: impl IntoCStr) -> BoltString {
+        let bytes = s.raw_bytes();
+        unsafe {
+            BoltString::from_raw_unchecked(sys::bt_make_string_hashed_len(
+                self.as_ptr(),
+                bytes.as_ptr() as *const std::ffi::c_char,
+                bytes.len() as u32,
+            ))
+        }
+    }
+
     pub fn get_or_make_interned(&mut self, s: impl IntoCStr) -> Result<BoltString, crate::Error> {
-        let c_str = s.as_c_str()?;
+        let bytes = s.raw_bytes();
         unsafe {
             Ok(BoltString::from_raw_unchecked(
                 sys::bt_get_or_make_interned(
                     self.as_ptr(),
-                    c_str.as_ptr(),
-                    c_str.to_bytes().len() as u32,
+                    bytes.as_ptr() as *const std::ffi::c_char,
+                    bytes.len() as u32,
                 ),
             ))
         }
@@ -338,16 +443,18 @@ impl Context {
         }
     }
 
+    /// Make a string from the first `len` bytes of `s`, or all of them if it's shorter
     pub fn make_string_len(
         &mut self,
         s: impl IntoCStr,
         len: u32,
     ) -> Result<BoltString, crate::Error> {
-        let c_str = s.as_c_str()?;
+        let bytes = s.raw_bytes();
+        let len = len.min(bytes.len() as u32);
         unsafe {
             Ok(BoltString::from_raw_unchecked(sys::bt_make_string_len(
                 self.as_ptr(),
-                c_str.as_ptr(),
+                bytes.as_ptr() as *const std::ffi::c_char,
                 len,
             )))
         }
@@ -358,10 +465,15 @@ impl Context {
         s: impl IntoCStr,
         len: u32,
     ) -> Result<BoltString, crate::Error> {
-        let c_str = s.as_c_str()?;
+        let bytes = s.raw_bytes();
+        let len = len.min(bytes.len() as u32);
         unsafe {
             Ok(BoltString::from_raw_unchecked(
-                sys::bt_make_string_hashed_len(self.as_ptr(), c_str.as_ptr(), len),
+                sys::bt_make_string_hashed_len(
+                    self.as_ptr(),
+                    bytes.as_ptr() as *const std::ffi::c_char,
+                    len,
+                ),
             ))
         }
     }
@@ -440,11 +552,43 @@ impl Context {
     bt_def!(make_module -> Module);
     bt_def_bool!(find_module(name: Value, suppress_errors: bool) -> Module);
 
+    /// Make `module` importable as `name`
+    ///
+    /// Registering a module under a name that's already taken replaces the old module:
+    /// imports compiled afterwards resolve to the new one, while code compiled before keeps
+    /// the module it imported.
     pub fn register_module(&mut self, name: Value, module: Module) {
         unsafe { sys::bt_register_module(self.as_ptr(), name.0, module.as_ptr()) }
     }
 
-    pub fn module_export(&mut self, module: Module, type_: Type, key: Value, value: Value) {
+    /// The module registered as `name`, without falling back to loading it from a file
+    pub fn registered_module(&self, name: &str) -> Option<Module> {
+        let modules = Table::from_raw(unsafe { (*self.as_ptr()).loaded_modules })?;
+        let module = modules.get_str(name)?.as_object()?;
+        Module::from_raw(module.as_ptr() as *mut sys::bt_Module)
+    }
+
+    /// Remove the module registered as `name`, returning it
+    ///
+    /// Later imports of `name` go back to the module paths, failing if there's no file for
+    /// it. Code that already imported the module keeps using it.
+    pub fn unregister_module(&mut self, name: &str) -> Option<Module> {
+        let module = self.registered_module(name)?;
+        let modules = Table::from_raw(unsafe { (*self.as_ptr()).loaded_modules })?;
+        let key = name.make_with_context(self);
+        unsafe { sys::bt_table_delete_key(modules.as_ptr(), key) };
+        Some(module)
+    }
+
+    /// Export `value` from `module` as `key`, which must be a valid identifier
+    pub fn module_export(
+        &mut self,
+        module: Module,
+        type_: Type,
+        key: Value,
+        value: Value,
+    ) -> Result<(), crate::Error> {
+        crate::names::validate_value(key)?;
         unsafe {
             sys::bt_module_export(
                 self.as_ptr(),
@@ -454,6 +598,7 @@ impl Context {
                 value.0,
             )
         }
+        Ok(())
     }
 
     pub fn module_export_native(
@@ -465,25 +610,36 @@ impl Context {
         args: &[Type],
     ) -> Result<(), crate::Error> {
         let c_str = name.as_c_str()?;
+        crate::names::validate(&c_str.to_string_lossy())?;
+
+        let signature = self
+            .make_signature_type(ret_type, args)
+            .ok_or(Error::bolt("Failed to create signature type"))?;
+        let native = self.make_named_native(module, signature, proc, &c_str.to_string_lossy());
+        let key = Value::from_raw(c_str.as_ref().make_with_context(self));
+        let value = Value::from_raw(native.as_object().make());
+        self.module_export(module, signature, key, value)
+    }
+
+    pub fn append_module_path(&mut self, spec: impl IntoCStr) -> Result<(), crate::Error> {
+        let c_str = spec.as_c_str()?;
+        crate::loader::add_module_root(self.as_ptr(), c_str.to_bytes());
         unsafe {
-            let arg_ptrs: Vec<*mut sys::bt_Type> = args.iter().map(|t| t.as_ptr()).collect();
-            sys::bt_module_export_native(
-                self.as_ptr(),
-                module.as_ptr(),
-                c_str.as_ptr(),
-                proc,
-                ret_type.as_ptr(),
-                arg_ptrs.as_ptr() as *mut *mut sys::bt_Type,
-                args.len() as u8,
-            );
+            sys::bt_append_module_path(self.as_ptr(), c_str.as_ptr());
         }
         Ok(())
     }
 
-    pub fn append_module_path(&mut self, spec: impl IntoCStr) -> Result<(), crate::Error> {
-        let c_str = spec.as_c_str();
+    /// Like [`Context::append_module_path`], but accepts any OS path, including ones which
+    /// aren't valid UTF-8 on unix
+    pub fn append_module_path_os(
+        &mut self,
+        spec: impl AsRef<std::ffi::OsStr>,
+    ) -> Result<(), crate::Error> {
+        let c_str = crate::loader::encode_path(spec.as_ref())?;
+        crate::loader::add_module_root(self.as_ptr(), c_str.to_bytes());
         unsafe {
-            sys::bt_append_module_path(self.as_ptr(), c_str?.as_ptr());
+            sys::bt_append_module_path(self.as_ptr(), c_str.as_ptr());
         }
         Ok(())
     }
@@ -495,9 +651,47 @@ impl Context {
     ) -> Result<Module, crate::Error> {
         let source_c = source.as_c_str()?;
         let name_c = mod_name.as_c_str()?;
-        unsafe {
+        let cache = match crate::compile_cache::lookup(self, source_c.to_bytes()) {
+            Ok(module) => return Ok(module),
+            Err(cache) => cache,
+        };
+        crate::lazy_std::open_imported(self.as_ptr(), source_c.to_bytes());
+        let state = crate::state::get(self.as_ptr());
+        let budget = crate::memory::enter(self.as_ptr(), &state);
+        let (module, mut diagnostics) = crate::diagnostic::capture(self.as_ptr(), || unsafe {
             let ptr = sys::bt_compile_module(self.as_ptr(), source_c.as_ptr(), name_c.as_ptr());
-            Module::from_raw(ptr).ok_or(Error::bolt("Module failed to compile"))
+            Module::from_raw(ptr)
+        });
+        drop(budget);
+        crate::diagnostic::locate(&mut diagnostics, &name_c, &source_c);
+        if crate::memory::take_exceeded(&state) {
+            diagnostics.into_iter().for_each(crate::diagnostic::report);
+            return Err(Error::MemoryLimit);
+        }
+        let Some(module) = module else {
+            return Err(crate::diagnostic::failure(&state, diagnostics));
+        };
+        diagnostics.into_iter().for_each(crate::diagnostic::report);
+        if cache {
+            crate::compile_cache::store(self, source_c.to_bytes(), module);
+        }
+        Ok(module)
+    }
+
+    /// Run a compiled module's top level, populating its exports
+    pub fn execute_module(&mut self, module: Module) -> Result<(), crate::Error> {
+        let state = crate::state::get(self.as_ptr());
+        let budget = crate::memory::enter(self.as_ptr(), &state);
+        let (succeeded, diagnostics) = crate::diagnostic::capture(self.as_ptr(), || unsafe {
+            sys::bt_execute(self.as_ptr(), module.as_ptr() as *mut sys::bt_Callable)
+                == BT_TRUE as u8
+        });
+        drop(budget);
+        if succeeded {
+            diagnostics.into_iter().for_each(crate::diagnostic::report);
+            Ok(())
+        } else {
+            Err(crate::diagnostic::failure(&state, diagnostics))
         }
     }
 
@@ -507,13 +701,57 @@ impl Context {
         signature: Type,
         proc: sys::bt_NativeProc,
     ) -> NativeFn {
-        unsafe {
+        self.make_named_native(module, signature, proc, "<native>")
+    }
+
+    pub(crate) fn make_named_native(
+        &mut self,
+        module: Module,
+        signature: Type,
+        proc: sys::bt_NativeProc,
+        name: &str,
+    ) -> NativeFn {
+        let native = unsafe {
             NativeFn::from_raw_unchecked(sys::bt_make_native(
                 self.as_ptr(),
                 module.as_ptr(),
                 signature.as_ptr(),
-                proc,
+                Some(crate::stats::native_trampoline),
             ))
+        };
+        crate::stats::track(self.as_ptr(), native.as_ptr(), proc, name);
+        native
+    }
+
+    /// Start or stop counting calls to natives and the time spent in them
+    pub fn set_native_stats(&mut self, enabled: bool) {
+        crate::state::get(self.as_ptr())
+            .native_stats_enabled
+            .set(enabled);
+    }
+
+    /// Statistics for every native called while stats were enabled, most expensive first
+    pub fn native_stats(&mut self) -> Vec<crate::stats::NativeStats> {
+        let mut stats: Vec<_> = crate::state::get(self.as_ptr())
+            .natives
+            .borrow()
+            .values()
+            .filter(|native| native.stats.calls > 0)
+            .map(|native| native.stats.clone())
+            .collect();
+        stats.sort_by_key(|native| std::cmp::Reverse(native.total));
+        stats
+    }
+
+    /// Zero the counters of every native
+    pub fn reset_native_stats(&mut self) {
+        for native in crate::state::get(self.as_ptr())
+            .natives
+            .borrow_mut()
+            .values_mut()
+        {
+            native.stats.calls = 0;
+            native.stats.total = std::time::Duration::ZERO;
         }
     }
 
@@ -562,48 +800,53 @@ impl Context {
     }
 
     /// Create a new Context with Rust-based handlers for allocation, I/O, and error reporting
+    ///
+    /// Panics if bolt fails to open, see [`Context::try_new`].
     pub fn new() -> Self {
+        match Self::try_new() {
+            Ok(ctx) => ctx,
+            Err(err) => panic!("Failed to create context: {err}"),
+        }
+    }
+
+    /// Create a new Context, reporting why it couldn't be opened instead of panicking
+    pub fn try_new() -> Result<Self, crate::Error> {
         unsafe {
             let mut handlers = sys::bt_default_handlers();
             Self::override_handlers(&mut handlers);
             let mut ctx = std::ptr::null_mut();
-            sys::bt_open(&mut ctx, &mut handlers);
-            Context::from_raw(ctx).expect("Failed to create context")
-        }
-    }
-
-    fn override_handlers(handlers: &mut sys::bt_Handlers) {
-        unsafe extern "C" fn rust_alloc(size: usize) -> *mut std::ffi::c_void {
-            unsafe {
-                std::alloc::alloc(std::alloc::Layout::array::<u8>(size).unwrap_unchecked()) as _
-            }
-        }
-
-        unsafe extern "C" fn rust_free(ptr: *mut std::ffi::c_void) {
-            if !ptr.is_null() {
-                unsafe { std::alloc::dealloc(ptr as *mut u8, std::alloc::Layout::new::<u8>()) }
-            }
-        }
-
-        unsafe extern "C" fn rust_realloc(
-            ptr: *mut std::ffi::c_void,
-            size: usize,
-        ) -> *mut std::ffi::c_void {
-            if ptr.is_null() {
-                unsafe {
-                    std::alloc::alloc(std::alloc::Layout::array::<u8>(size).unwrap_unchecked()) as _
-                }
-            } else {
-                unsafe {
-                    std::alloc::realloc(ptr as *mut u8, std::alloc::Layout::new::<u8>(), size) as _
+            let opened = sys::bt_open(&mut ctx, &mut handlers) == BT_TRUE as u8;
+            if !opened {
+                // Close whatever bolt managed to set up before it gave up, without going
+                // through `Drop`, which expects a fully opened context
+                if !ctx.is_null() {
+                    sys::bt_close(ctx);
+                    crate::state::release(ctx);
                 }
+                return Err(Error::Open(crate::OpenError::Rejected));
             }
+            // Nothing else owns what bolt just opened
+            let Some(mut ctx) = Context::from_raw(ctx) else {
+                return Err(Error::Open(crate::OpenError::NoContext));
+            };
+            // Whatever bolt registered while opening should be stoppable too
+            crate::stats::route_loaded(&mut ctx);
+            crate::memory::touch(ctx.as_ptr(), &crate::state::get(ctx.as_ptr()));
+            Ok(ctx)
         }
+    }
 
+    fn override_handlers(handlers: &mut sys::bt_Handlers) {
         unsafe extern "C" fn rust_write(_ctx: *mut sys::bt_Context, msg: *const std::ffi::c_char) {
             if !msg.is_null()
                 && let Ok(msg_str) = unsafe { std::ffi::CStr::from_ptr(msg) }.to_str() {
+                    #[cfg(not(feature = "tracing"))]
                     print!("{}", msg_str);
+                    // Scripts print a line at a time, with the newline written on its own
+                    #[cfg(feature = "tracing")]
+                    if !msg_str.trim_end().is_empty() {
+                        tracing::info!(target: "bolt::output", "{}", msg_str.trim_end());
+                    }
                 }
         }
 
@@ -614,81 +857,108 @@ impl Context {
             line: u16,
             col: u16,
         ) {
-            let error_type_str = match error_type {
-                sys::bt_ErrorType_BT_ERROR_PARSE => "Parse Error",
-                sys::bt_ErrorType_BT_ERROR_COMPILE => "Compile Error",
-                sys::bt_ErrorType_BT_ERROR_RUNTIME => "Runtime Error",
-                _ => "Unknown Error",
-            };
-
             let module_str = if !module.is_null() {
-                unsafe { std::ffi::CStr::from_ptr(module) }
-                    .to_str()
-                    .unwrap_or("unknown")
+                unsafe { std::ffi::CStr::from_ptr(module) }.to_string_lossy()
             } else {
-                "unknown"
+                "unknown".into()
             };
 
             let message_str = if !message.is_null() {
-                unsafe { std::ffi::CStr::from_ptr(message) }
-                    .to_str()
-                    .unwrap_or("unknown error")
+                unsafe { std::ffi::CStr::from_ptr(message) }.to_string_lossy()
             } else {
-                "unknown error"
+                "unknown error".into()
             };
 
-            eprintln!(
-                "{} in {}: {} (line {}, col {})",
-                error_type_str, module_str, message_str, line, col
-            );
+            let kind = crate::DiagnosticKind::from_raw(error_type);
+            if kind == crate::DiagnosticKind::Runtime {
+                crate::diagnostic::record_traceback(line);
+            }
+            crate::diagnostic::report(crate::Diagnostic {
+                kind,
+                module: module_str.into_owned(),
+                message: message_str.into_owned(),
+                line,
+                col,
+                span: None,
+            });
         }
 
         unsafe extern "C" fn rust_read_file(
-            _ctx: *mut sys::bt_Context,
+            ctx: *mut sys::bt_Context,
             path: *const std::ffi::c_char,
             out_handle: *mut *mut std::ffi::c_void,
         ) -> *mut std::ffi::c_char {
-            let Some(path) = (if path.is_null() { None } else { Some(path) }) else {
+            if path.is_null() || out_handle.is_null() {
                 return std::ptr::null_mut();
-            };
-            let Some(out_handle) = (if out_handle.is_null() {
-                None
-            } else {
-                Some(out_handle)
-            }) else {
-                return std::ptr::null_mut();
-            };
+            }
 
-            let Ok(path_str) = unsafe { std::ffi::CStr::from_ptr(path) }.to_str() else {
+            let path = unsafe { std::ffi::CStr::from_ptr(path) };
+            let state = crate::state::get(ctx);
+            let embedded = crate::loader::embedded_source(&state, path.to_bytes());
+            let Some(path) = crate::loader::decode_path(path) else {
                 return std::ptr::null_mut();
             };
-
-            let Ok(file) = std::fs::File::open(path_str) else {
+            let path = crate::loader::normalize(path, state.path_normalization.get());
+
+            if let Err(chain) = crate::loader::check_cycle(&state, &path) {
+                crate::diagnostic::report(crate::Diagnostic {
+                    kind: crate::DiagnosticKind::Compile,
+                    module: path.display().to_string(),
+                    message: format!("import cycle: {}", chain.join(" -> ")),
+                    line: 0,
+                    col: 0,
+                    span: None,
+                });
+                *state.import_cycle.borrow_mut() = Some(chain);
                 return std::ptr::null_mut();
-            };
-
-            let boxed_file = Box::new(file);
-            unsafe {
-                *out_handle = Box::into_raw(boxed_file) as *mut _;
             }
 
-            let Ok(contents) = std::fs::read_to_string(path_str) else {
-                unsafe {
-                    let _ = Box::from_raw(*out_handle);
-                    *out_handle = std::ptr::null_mut();
+            // Registered sources have no file behind them, so their handle stays null
+            let (file, source) = match embedded {
+                Some(source) => (None, source),
+                None => {
+                    let roots = state.fs_roots.borrow().clone();
+                    let file = match roots {
+                        Some(roots) => match crate::loader::open_confined(
+                            &roots,
+                            &path,
+                            crate::io::OpenMode::Read,
+                        ) {
+                            Ok(file) => file,
+                            Err(err) => {
+                                crate::diagnostic::report(crate::Diagnostic {
+                                    kind: crate::DiagnosticKind::Compile,
+                                    module: path.display().to_string(),
+                                    message: format!("can't load module: {err}"),
+                                    line: 0,
+                                    col: 0,
+                                    span: None,
+                                });
+                                return std::ptr::null_mut();
+                            }
+                        },
+                        None => match std::fs::File::open(&path) {
+                            Ok(file) => file,
+                            Err(_) => return std::ptr::null_mut(),
+                        },
+                    };
+                    match crate::loader::read_source(file) {
+                        Some((file, source)) => (Some(file), source),
+                        None => return std::ptr::null_mut(),
+                    }
                 }
-                return std::ptr::null_mut();
             };
+            crate::lazy_std::open_imported(ctx, source.as_bytes());
 
-            let Ok(c_string) = std::ffi::CString::new(contents) else {
-                unsafe {
-                    let _ = Box::from_raw(*out_handle);
-                    *out_handle = std::ptr::null_mut();
-                }
-                return std::ptr::null_mut();
-            };
-
-            c_string.into_raw()
+            unsafe {
+                *out_handle = match file {
+                    Some(file) => Box::into_raw(Box::new(file)) as *mut _,
+                    None => std::ptr::null_mut(),
+                };
+            }
+            let source = source.into_raw();
+            crate::loader::begin_load(&state, path, source);
+            source
         }
 
         unsafe extern "C" fn rust_close_file(
@@ -704,19 +974,20 @@ impl Context {
         }
 
         unsafe extern "C" fn rust_free_source(
-            _ctx: *mut sys::bt_Context,
+            ctx: *mut sys::bt_Context,
             source: *mut std::ffi::c_char,
         ) {
             if !source.is_null() {
+                crate::loader::finish_load(&crate::state::get(ctx), source);
                 unsafe {
                     let _ = std::ffi::CString::from_raw(source);
                 }
             }
         }
 
-        handlers.alloc = Some(rust_alloc);
-        handlers.free = Some(rust_free);
-        handlers.realloc = Some(rust_realloc);
+        handlers.alloc = Some(crate::memory::alloc);
+        handlers.free = Some(crate::memory::free);
+        handlers.realloc = Some(crate::memory::realloc);
         handlers.write = Some(rust_write);
         handlers.on_error = Some(rust_on_error);
         handlers.read_file = Some(rust_read_file);
@@ -725,10 +996,25 @@ impl Context {
     }
 
     /// Open all standard library modules
+    ///
+    /// If the context was built with [`ContextBuilder::lazy_std`](crate::ContextBuilder::lazy_std),
+    /// modules that are only reachable through `import` are built on first import instead.
     pub fn open_all_std(&mut self) {
+        if crate::state::get(self.as_ptr()).lazy_std.get() {
+            crate::lazy_std::defer_all(self.as_ptr());
+            crate::stats::route_loaded(self);
+            return;
+        }
+        // `io` can't be confined, so a restricted context opens the rest one by one
+        if crate::state::get(self.as_ptr()).fs_roots.borrow().is_some() {
+            self.open_std(crate::StdLib::ALL);
+            return;
+        }
+
         unsafe {
             sys::boltstd_open_all(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the core standard library module
@@ -736,6 +1022,7 @@ impl Context {
         unsafe {
             sys::boltstd_open_core(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the arrays standard library module
@@ -743,6 +1030,7 @@ impl Context {
         unsafe {
             sys::boltstd_open_arrays(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the strings standard library module
@@ -750,6 +1038,7 @@ impl Context {
         unsafe {
             sys::boltstd_open_strings(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the tables standard library module
@@ -757,6 +1046,7 @@ impl Context {
         unsafe {
             sys::boltstd_open_tables(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the math standard library module
@@ -764,13 +1054,19 @@ impl Context {
         unsafe {
             sys::boltstd_open_math(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
-    /// Open the I/O standard library module
+    /// Open the I/O standard library module, unless the context is restricted with
+    /// [`Context::restrict_filesystem`]
     pub fn open_io(&mut self) {
+        if crate::state::get(self.as_ptr()).fs_roots.borrow().is_some() {
+            return;
+        }
         unsafe {
             sys::boltstd_open_io(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the meta-programming standard library module
@@ -778,6 +1074,7 @@ impl Context {
         unsafe {
             sys::boltstd_open_meta(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
     /// Open the regex standard library module
@@ -785,18 +1082,226 @@ impl Context {
         unsafe {
             sys::boltstd_open_regex(self.as_ptr());
         }
+        crate::stats::route_loaded(self);
     }
 
+    /// Compile and execute `code`. If parsing fails, every parse diagnostic reported during
+    /// the call is returned together in [`Error::Parse`].
     pub fn run(&mut self, code: impl crate::IntoCStr) -> Result<(), crate::Error> {
-        unsafe {
-            if sys::bt_run(self.as_ptr(), code.as_c_str()?.as_ptr()) == BT_TRUE as u8 {
-                Ok(())
-            } else {
-                Err(Error::bolt("Execution failed"))
+        let code = code.as_c_str()?;
+        let scope = crate::replay::Scope::enter(self);
+        let result = self.run_source(&code);
+        if scope.records() {
+            crate::replay::record_run(self, &code, &result);
+        }
+        result
+    }
+
+    /// Run `code` with each of `bindings` visible to it by name, like a prelude entry that
+    /// only exists for this chunk
+    ///
+    /// Each value is typed the way [`Context::register_data_module`] types its entries.
+    /// Names must be valid identifiers and can't shadow anything already in the prelude.
+    /// The bindings are removed again once the chunk has run, whether or not it succeeded.
+    /// Chunks run this way aren't captured by [`Context::start_recording`].
+    pub fn run_with(
+        &mut self,
+        code: impl crate::IntoCStr,
+        bindings: &[(&str, Value)],
+    ) -> Result<(), crate::Error> {
+        let code = code.as_c_str()?;
+        let prelude = Table::from_raw(unsafe { (*self.as_ptr()).prelude })
+            .ok_or(Error::bolt("Context has no prelude table"))?;
+        for (idx, (name, _)) in bindings.iter().enumerate() {
+            crate::names::validate(name)?;
+            if bindings[..idx].iter().any(|(other, _)| other == name) {
+                return Err(crate::ModuleError::DuplicateExport((*name).to_owned()).into());
+            }
+            if prelude.get_str(name).is_some() {
+                return Err(Error::bolt(format!(
+                    "'{name}' would shadow an existing prelude entry"
+                )));
+            }
+        }
+
+        self.gc_pause();
+        let mut keys = Vec::with_capacity(bindings.len());
+        for (name, value) in bindings {
+            let ty = crate::data_module::infer_type(self, *value);
+            let key = Value::from_raw(name.make_with_context(self));
+            self.register_prelude(key, ty, *value);
+            keys.push(key);
+        }
+        self.gc_unpause();
+
+        let scope = crate::replay::Scope::enter(self);
+        let result = self.run_source(&code);
+        drop(scope);
+
+        for key in keys {
+            unsafe { sys::bt_table_delete_key(prelude.as_ptr(), key.0) };
+        }
+        result
+    }
+
+    fn run_source(&mut self, code: &std::ffi::CStr) -> Result<(), crate::Error> {
+        crate::lazy_std::open_imported(self.as_ptr(), code.to_bytes());
+        let state = crate::state::get(self.as_ptr());
+        crate::depth::check_execution(&state)?;
+        let budget = crate::memory::enter(self.as_ptr(), &state);
+        let (succeeded, diagnostics) = crate::diagnostic::capture(self.as_ptr(), || unsafe {
+            sys::bt_run(self.as_ptr(), code.as_ptr()) == BT_TRUE as u8
+        });
+        drop(budget);
+
+        if succeeded {
+            diagnostics.into_iter().for_each(crate::diagnostic::report);
+            return Ok(());
+        }
+        Err(crate::diagnostic::failure(&state, diagnostics))
+    }
+
+    /// Call a function, native function or closure value with the given arguments, returning
+    /// whatever it returned. Threads are pooled per context, so repeated calls don't allocate.
+    pub fn call(&mut self, func: Value, args: &[Value]) -> Result<Value, crate::Error> {
+        let callable = func
+            .as_object()
+            .filter(|obj| {
+                matches!(
+                    obj.value_type(),
+                    ValueType::Function | ValueType::NativeFunction | ValueType::Closure
+                )
+            })
+            .ok_or(Error::bolt("Value is not callable"))?;
+
+        let scope = crate::replay::Scope::enter(self);
+        let recorded = scope
+            .records()
+            .then(|| crate::replay::capture_call(self, callable, args));
+        let result = self.call_callable(callable, args);
+        if let Some((target, args)) = recorded {
+            crate::replay::record_call(self, target, args, &result);
+        }
+        result
+    }
+
+    fn call_callable(&mut self, callable: Object, args: &[Value]) -> Result<Value, crate::Error> {
+        let state = crate::state::get(self.as_ptr());
+        let pooled = state.idle_threads.borrow_mut().pop();
+        let thread = pooled.unwrap_or_else(|| self.make_thread());
+        let callable = unsafe { Callable::from_raw_unchecked(callable.as_ptr() as *mut _) };
+        match self.call_on_thread(&thread, callable, args) {
+            Ok(returned) => {
+                state.idle_threads.borrow_mut().push(thread);
+                Ok(returned)
+            }
+            Err(err) => {
+                // The thread was abandoned mid-call, so don't hand its stack to the next call
+                self.destroy_thread(thread);
+                Err(err)
             }
         }
     }
 
+    /// Run `callable` on `thread` with no arguments, returning whatever it returned
+    ///
+    /// Unlike [`Context::call`], the caller chooses the thread, e.g. one kept per script
+    /// entity. A thread that failed is left as it was when the error was raised, so destroy
+    /// it rather than running anything else on it.
+    pub fn execute_on_thread(
+        &mut self,
+        thread: &Thread,
+        callable: impl Into<Callable>,
+    ) -> Result<Value, crate::Error> {
+        let callable = callable.into();
+        self.execute_with(thread, callable, || unsafe {
+            sys::bt_execute_on_thread(self.as_ptr(), thread.as_ptr(), callable.as_ptr())
+        })
+    }
+
+    /// Like [`Context::execute_on_thread`], passing `args` to `callable`
+    pub fn call_on_thread(
+        &mut self,
+        thread: &Thread,
+        callable: impl Into<Callable>,
+        args: &[Value],
+    ) -> Result<Value, crate::Error> {
+        let callable = callable.into();
+        let mut raw_args: Vec<sys::bt_Value> = args.iter().map(|arg| arg.0).collect();
+        self.execute_with(thread, callable, || unsafe {
+            sys::bt_execute_with_args(
+                self.as_ptr(),
+                thread.as_ptr(),
+                callable.as_ptr(),
+                raw_args.as_mut_ptr(),
+                raw_args.len() as u8,
+            )
+        })
+    }
+
+    /// Run `execute` with the bookkeeping every call into a script needs, reading the
+    /// result off `thread`
+    pub(crate) fn execute_with(
+        &self,
+        thread: &Thread,
+        callable: Callable,
+        execute: impl FnOnce() -> sys::bt_bool,
+    ) -> Result<Value, crate::Error> {
+        let state = crate::state::get(self.as_ptr());
+        crate::depth::check_execution(&state)?;
+        let budget = crate::memory::enter(self.as_ptr(), &state);
+        let callable = callable.as_object();
+        crate::hooks::emit(self.as_ptr(), crate::HookKind::Call, callable, 0);
+        let (succeeded, diagnostics) =
+            crate::diagnostic::capture(self.as_ptr(), || execute() == BT_TRUE as u8);
+        crate::hooks::emit(self.as_ptr(), crate::HookKind::Return, callable, 0);
+        drop(budget);
+        if !succeeded {
+            return Err(crate::diagnostic::failure(&state, diagnostics));
+        }
+
+        let returned = unsafe { Value::from_raw(sys::bt_get_returned(thread.as_ptr())) };
+        diagnostics.into_iter().for_each(crate::diagnostic::report);
+        Ok(returned)
+    }
+
+    /// Parse and typecheck `source` against everything registered with this context without
+    /// executing it, returning every problem found. An empty list means the script is valid.
+    pub fn typecheck(&mut self, source: impl IntoCStr) -> Vec<crate::Diagnostic> {
+        let source = match source.as_c_str() {
+            Ok(source) => source,
+            Err(err) => {
+                return vec![crate::Diagnostic {
+                    kind: crate::DiagnosticKind::Parse,
+                    module: "<typecheck>".to_owned(),
+                    message: err.to_string(),
+                    line: 0,
+                    col: 0,
+                    span: None,
+                }];
+            }
+        };
+        crate::lazy_std::open_imported(self.as_ptr(), source.to_bytes());
+
+        let (module, mut diagnostics) = crate::diagnostic::capture(self.as_ptr(), || unsafe {
+            sys::bt_compile_module(self.as_ptr(), source.as_ptr(), c"<typecheck>".as_ptr())
+        });
+        crate::diagnostic::locate(&mut diagnostics, c"<typecheck>", &source);
+
+        if module.is_null() && diagnostics.is_empty() {
+            diagnostics.push(crate::Diagnostic {
+                kind: crate::DiagnosticKind::Compile,
+                module: "<typecheck>".to_owned(),
+                message: "Module failed to compile".to_owned(),
+                line: 0,
+                col: 0,
+                span: None,
+            });
+        }
+
+        diagnostics
+    }
+
     pub fn create_module(&mut self, name: &str) -> Result<Module, crate::ModuleError> {
         use crate::types::value::MakeBoltValueWithContext;
 
@@ -809,16 +1314,29 @@ impl Context {
     pub fn get_module(&mut self, name: &str) -> Result<Module, crate::ModuleError> {
         use crate::types::value::MakeBoltValueWithContext;
 
+        crate::lazy_std::open_pending(self.as_ptr(), name.as_bytes());
         let name_value = name.make_with_context(self);
         self.find_module(Value::from_raw(name_value), false)
             .ok_or_else(|| crate::ModuleError::NotFound(name.to_string()))
     }
+
+    /// Find or load the module `name`, as a script `import` would, and give typed access to
+    /// its exports
+    pub fn import(&mut self, name: &str) -> Result<crate::Exports, crate::Error> {
+        let module = self.get_module(name)?;
+        Ok(crate::Exports::from_module(module))
+    }
 }
 
 impl Drop for Context {
     fn drop(&mut self) {
+        let idle = std::mem::take(&mut *crate::state::get(self.as_ptr()).idle_threads.borrow_mut());
+        for thread in idle {
+            self.destroy_thread(thread);
+        }
         unsafe {
             sys::bt_close(self.as_ptr());
         }
+        crate::state::release(self.as_ptr());
     }
 }
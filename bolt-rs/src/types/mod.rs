@@ -3,14 +3,24 @@
 //! This module provides safe NonNull-based wrappers around raw C pointers.
 use bolt_sys::sys;
 
+pub mod array;
 pub mod context;
+pub mod module;
 pub mod object;
+pub mod string;
+pub mod table;
 pub mod thread;
 pub mod ty;
 pub mod value;
 
-pub use context::Context;
+/// Implemented by every wrapper around a garbage-collected bolt object
+pub trait ObjectHandle: Copy {
+    fn as_object(&self) -> Object;
+}
+
+pub use context::{Context, ContextRef};
 pub use thread::Thread;
+pub use ty::TypeKind;
 pub use value::Value;
 
 define_wrappers! {
@@ -29,6 +39,7 @@ define_object_wrappers! {
     BoltFn => sys::bt_Fn,
     NativeFn => sys::bt_NativeFn,
     Closure => sys::bt_Closure,
+    Callable => sys::bt_Callable,
     Array => sys::bt_Array,
     Table => sys::bt_Table,
     Userdata => sys::bt_Userdata,
@@ -0,0 +1,157 @@
+use super::{BoltString, Module, Table, Type, TypeKind, Value};
+use crate::{ArgError, Context, Error, FromBoltValue, ValueType};
+
+/// An export along with its full type, see [`Module::exports_with_types`]
+#[derive(Debug, Clone)]
+pub struct ExportInfo {
+    pub name: String,
+    pub ty: Type,
+    pub value: Value,
+    /// The decomposed signature, for function exports
+    pub signature: Option<FunctionSignature>,
+}
+
+/// The argument and return types of a function type
+#[derive(Debug, Clone)]
+pub struct FunctionSignature {
+    pub args: Vec<Type>,
+    /// `None` if the function returns nothing
+    pub ret: Option<Type>,
+}
+
+impl FunctionSignature {
+    /// Decompose `ty`, or `None` if it isn't a function type
+    pub fn of(ty: Type) -> Option<Self> {
+        if !matches!(ty.kind(), TypeKind::Signature | TypeKind::NativeFn) {
+            return None;
+        }
+        Some(Self {
+            args: ty.signature_args()?,
+            ret: ty.return_type(),
+        })
+    }
+}
+
+impl Module {
+    /// The name the module was compiled or registered under, if it has one
+    pub fn name(&self) -> Option<String> {
+        let name = unsafe { BoltString::from_raw((*self.as_ptr()).name) }?;
+        Some(name.to_string_lossy())
+    }
+
+    /// The path the module was loaded from, if it came from a file
+    pub fn path(&self) -> Option<String> {
+        let path = unsafe { BoltString::from_raw((*self.as_ptr()).path) }?;
+        Some(path.to_string_lossy())
+    }
+
+    /// The table holding this module's exported values
+    pub(crate) fn export_table(&self) -> Option<Table> {
+        unsafe { Table::from_raw((*self.as_ptr()).exports) }
+    }
+
+    /// Iterate over every `(name, type, value)` this module exports
+    pub fn exports<'a>(&self, ctx: &'a mut Context) -> ModuleExports<'a> {
+        ModuleExports {
+            ctx,
+            module: *self,
+            idx: 0,
+        }
+    }
+
+    /// Every export with its declared type, and the argument and return types of each
+    /// function export, e.g. to check a plugin against the API a host expects
+    pub fn exports_with_types(&self, ctx: &mut Context) -> Vec<ExportInfo> {
+        self.exports(ctx)
+            .map(|(name, ty, value)| ExportInfo {
+                name,
+                ty,
+                value,
+                signature: FunctionSignature::of(ty),
+            })
+            .collect()
+    }
+}
+
+/// Iterator over the exports of a [`Module`], see [`Module::exports`]
+pub struct ModuleExports<'a> {
+    ctx: &'a mut Context,
+    module: Module,
+    idx: usize,
+}
+
+impl Iterator for ModuleExports<'_> {
+    type Item = (String, Type, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let exports = self.module.export_table()?;
+        loop {
+            let pair = *exports.raw_pairs().get(self.idx)?;
+            self.idx += 1;
+
+            let key = Value::from_raw(pair.key);
+            let Some(name) = key
+                .as_object()
+                .filter(|obj| matches!(obj.value_type(), ValueType::String))
+            else {
+                continue;
+            };
+            let name = unsafe { BoltString::from_raw_unchecked(name.as_ptr() as *mut _) };
+
+            let module_type = unsafe { Type::from_raw((*self.module.as_ptr()).type_) };
+            let ty = module_type
+                .and_then(|shape| self.ctx.type_get_field_type(shape, key))
+                .unwrap_or_else(|| self.ctx.type_any());
+
+            return Some((name.to_string_lossy(), ty, Value::from_raw(pair.value)));
+        }
+    }
+}
+
+/// Typed access to the exports of a loaded module, see [`Context::import`]
+#[derive(Debug, Clone, Copy)]
+pub struct Exports {
+    module: Module,
+}
+
+impl Exports {
+    pub(crate) fn from_module(module: Module) -> Self {
+        Self { module }
+    }
+
+    pub fn module(&self) -> Module {
+        self.module
+    }
+
+    /// The value exported as `key`, if there is one
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        self.module.export_table()?.get_str(key)
+    }
+
+    /// The value exported as `key`, converted to `T`
+    pub fn get<T: FromBoltValue>(&self, key: &str) -> Result<T, Error> {
+        let value = self.get_value(key).ok_or_else(|| Error::KeyNotFound {
+            key: key.to_owned(),
+        })?;
+        Ok(T::from(value.0)?)
+    }
+
+    /// The function exported as `key`, ready to pass to [`Context::call`]
+    pub fn get_function(&self, key: &str) -> Result<Value, Error> {
+        let value = self.get_value(key).ok_or_else(|| Error::KeyNotFound {
+            key: key.to_owned(),
+        })?;
+        let actual = ValueType::from_value(value.0);
+        if !matches!(
+            actual,
+            ValueType::Function | ValueType::NativeFunction | ValueType::Closure
+        ) {
+            return Err(ArgError::TypeGuard {
+                expected: ValueType::Function,
+                actual,
+            }
+            .into());
+        }
+        Ok(value)
+    }
+}
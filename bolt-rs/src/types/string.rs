@@ -0,0 +1,27 @@
+use bolt_sys::sys;
+
+use super::BoltString;
+
+impl BoltString {
+    /// Length of the string in bytes
+    pub fn len(&self) -> usize {
+        unsafe { sys::bt_string_length(self.as_ptr()) as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Raw bytes of the string, valid for as long as the string object is alive
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let data = sys::bt_string_get(self.as_ptr()) as *const u8;
+            std::slice::from_raw_parts(data, self.len())
+        }
+    }
+
+    /// Copy the string out, replacing invalid UTF-8 sequences
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(self.as_bytes()).into_owned()
+    }
+}
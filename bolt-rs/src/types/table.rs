@@ -0,0 +1,167 @@
+use bolt_sys::sys;
+
+use super::{Table, Value};
+use crate::{Context, Error, MakeBoltValueWithContext, ValueType};
+
+impl Table {
+    /// Number of pairs stored directly in this table
+    pub fn len(&self) -> usize {
+        unsafe { (*self.as_ptr()).length as usize }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The key/value pairs stored directly in this table, not including its prototype
+    pub(crate) fn raw_pairs(&self) -> &[sys::bt_TablePair] {
+        unsafe {
+            let tbl = self.as_ptr();
+            let pairs = if (*tbl).is_inline != 0 {
+                tbl.add(1) as *const sys::bt_TablePair
+            } else {
+                (*tbl).outline as *const sys::bt_TablePair
+            };
+
+            if pairs.is_null() {
+                &[]
+            } else {
+                std::slice::from_raw_parts(pairs, (*tbl).length as usize)
+            }
+        }
+    }
+
+    /// Look up a string key without needing a context to build the key value
+    pub fn get_str(&self, key: &str) -> Option<Value> {
+        self.raw_pairs().iter().find_map(|pair| {
+            let pair_key = Value::from_raw(pair.key).as_object()?;
+            if !matches!(pair_key.value_type(), ValueType::String) {
+                return None;
+            }
+
+            let pair_key =
+                unsafe { super::BoltString::from_raw_unchecked(pair_key.as_ptr() as *mut _) };
+            (pair_key.as_bytes() == key.as_bytes()).then(|| Value::from_raw(pair.value))
+        })
+    }
+
+    /// The value stored under `key`, or `None` if the table doesn't contain it
+    pub fn get(&self, ctx: &mut Context, key: impl MakeBoltValueWithContext) -> Option<Value> {
+        let key = key.make_with_context(ctx);
+        self.find(key).map(|pair| Value::from_raw(pair.value))
+    }
+
+    /// Like [`Table::get`], but reports a missing key as an error naming it
+    pub fn at(
+        &self,
+        ctx: &mut Context,
+        key: impl MakeBoltValueWithContext,
+    ) -> Result<Value, Error> {
+        let key = Value::from_raw(key.make_with_context(ctx));
+        match self.find(key.0) {
+            Some(pair) => Ok(Value::from_raw(pair.value)),
+            None => Err(Error::KeyNotFound {
+                key: key.display(ctx),
+            }),
+        }
+    }
+
+    /// Insert or overwrite the value stored under `key`
+    pub fn set(
+        &self,
+        ctx: &mut Context,
+        key: impl MakeBoltValueWithContext,
+        value: impl MakeBoltValueWithContext,
+    ) -> bool {
+        // Building the value or growing the table can collect, and nothing references
+        // either of them yet
+        let key = Value::from_raw(key.make_with_context(ctx));
+        let key_rooted = key.as_object().inspect(|obj| ctx.push_root(*obj));
+        let value = Value::from_raw(value.make_with_context(ctx));
+        let value_rooted = value.as_object().inspect(|obj| ctx.push_root(*obj));
+        let inserted = ctx.table_set(*self, key, value);
+        for _ in key_rooted.iter().chain(value_rooted.iter()) {
+            ctx.pop_root();
+        }
+        inserted
+    }
+
+    pub fn contains_key(&self, ctx: &mut Context, key: impl MakeBoltValueWithContext) -> bool {
+        let key = key.make_with_context(ctx);
+        self.find(key).is_some()
+    }
+
+    /// Iterate over the `(key, value)` pairs stored directly in this table
+    pub fn iter<'a>(&self, ctx: &'a mut Context) -> TableIter<'a> {
+        TableIter {
+            _ctx: ctx,
+            table: *self,
+            idx: 0,
+        }
+    }
+
+    fn find(&self, key: sys::bt_Value) -> Option<&sys::bt_TablePair> {
+        self.raw_pairs()
+            .iter()
+            .find(|pair| unsafe { sys::bt_value_is_equal(pair.key, key) == sys::BT_TRUE as u8 })
+    }
+}
+
+/// Iterator over the pairs of a [`Table`], see [`Table::iter`]
+///
+/// Holds the context mutably so the table can't be modified mid-iteration.
+pub struct TableIter<'a> {
+    _ctx: &'a mut Context,
+    table: Table,
+    idx: usize,
+}
+
+impl Iterator for TableIter<'_> {
+    type Item = (Value, Value);
+
+    fn next(&mut self) -> Option<(Value, Value)> {
+        let pair = self.table.raw_pairs().get(self.idx)?;
+        self.idx += 1;
+        Some((Value::from_raw(pair.key), Value::from_raw(pair.value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.table.len().saturating_sub(self.idx);
+        (remaining, Some(remaining))
+    }
+}
+
+impl IntoIterator for &Table {
+    type Item = (Value, Value);
+    type IntoIter = Pairs;
+
+    /// Iterate without holding the context. The table is re-read on every step, so
+    /// inserting into it mid-iteration is safe, though pairs may be skipped or repeated.
+    fn into_iter(self) -> Pairs {
+        Pairs {
+            table: *self,
+            idx: 0,
+        }
+    }
+}
+
+/// Iterator over the pairs of a borrowed [`Table`]
+pub struct Pairs {
+    table: Table,
+    idx: usize,
+}
+
+impl Iterator for Pairs {
+    type Item = (Value, Value);
+
+    fn next(&mut self) -> Option<(Value, Value)> {
+        let pair = self.table.raw_pairs().get(self.idx)?;
+        self.idx += 1;
+        Some((Value::from_raw(pair.key), Value::from_raw(pair.value)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.table.len().saturating_sub(self.idx);
+        (remaining, Some(remaining))
+    }
+}
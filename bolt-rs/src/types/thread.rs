@@ -49,6 +49,23 @@ impl Thread {
         unsafe { sys::bt_return(self.as_ptr(), val.make()) }
     }
 
+    /// Return several values at once, e.g. `thread.return_vals(ctx, (x, y))`. bolt functions
+    /// return a single value, so they're boxed into an array, see [`Tuple`].
+    ///
+    /// [`Tuple`]: crate::types::value::Tuple
+    pub fn return_vals<T>(&mut self, ctx: &mut crate::Context, vals: T)
+    where
+        crate::types::value::Tuple<T>: crate::types::value::MakeBoltValueWithContext,
+    {
+        let vals = crate::types::value::Tuple(vals);
+        unsafe {
+            sys::bt_return(
+                self.as_ptr(),
+                crate::types::value::MakeBoltValueWithContext::make_with_context(&vals, ctx),
+            )
+        }
+    }
+
     pub fn get_arg<T: crate::types::value::FromBoltValue>(
         &mut self,
         idx: u8,
@@ -63,6 +80,20 @@ impl Thread {
         T::from(val)
     }
 
+    /// Extract every argument at once as a tuple, e.g. `thread.args::<(f64, String, Option<bool>)>()`.
+    ///
+    /// Trailing arguments the caller left out are read as `null`, so they only succeed for
+    /// `Option` parameters. Conversion failures report which position was at fault.
+    pub fn args<T: crate::types::value::FromBoltArgs>(&mut self) -> Result<T, crate::ArgError> {
+        let args: Vec<sys::bt_Value> = unsafe {
+            let len = sys::bt_argc(self.as_ptr());
+            (0..len)
+                .map(|idx| sys::bt_arg(self.as_ptr(), idx))
+                .collect()
+        };
+        T::from_args(&args)
+    }
+
     pub unsafe fn get_arg_unchecked<T: crate::types::value::FromBoltValue>(
         &mut self,
         idx: u8,
@@ -79,6 +110,26 @@ impl Thread {
         }
     }
 
+    /// Fail the native function being run with a runtime error, which the run or call
+    /// executing it returns as [`Error::Runtime`]. Return from the native right after.
+    ///
+    /// [`Error::Runtime`]: crate::Error::Runtime
+    pub fn error(&mut self, msg: &str) {
+        let msg = std::ffi::CString::new(msg.replace('\0', " ")).expect("NULs were replaced");
+        unsafe { sys::bt_runtime_error(self.as_ptr(), msg.as_ptr(), std::ptr::null_mut()) };
+    }
+
+    /// Like [`Thread::error`], with `value` as the payload: the run or call executing the
+    /// native returns [`Error::Script`], as if the script had thrown it with `errors.throw`.
+    /// A table with a `message` field is read like an `Error`, any other value is kept as
+    /// the data.
+    ///
+    /// [`Error::Script`]: crate::Error::Script
+    pub fn error_value(&mut self, ctx: &mut crate::Context, value: crate::Value) {
+        let thrown = crate::errors::ScriptThrow::from_value(ctx, value);
+        unsafe { crate::errors::raise(ctx.as_ptr(), self.as_ptr(), thrown, value) };
+    }
+
     pub fn call(&mut self, argc: u8) {
         unsafe {
             sys::bt_call(self.as_ptr(), argc);
@@ -104,4 +155,18 @@ impl Thread {
     pub fn argc(&self) -> u8 {
         unsafe { sys::bt_argc(self.as_ptr()) }
     }
+
+    /// The callable executing in the innermost frame, i.e. the native function currently
+    /// being serviced when called from inside a native callback
+    pub(crate) fn current_callable(&self) -> Option<crate::types::Object> {
+        unsafe {
+            let thread = self.as_ptr();
+            let depth = (*thread).depth as usize;
+            if depth == 0 {
+                return None;
+            }
+            let frame = (*thread).callstack.get(depth - 1)?;
+            crate::types::Object::from_raw(frame.callable as *mut sys::bt_Object)
+        }
+    }
 }
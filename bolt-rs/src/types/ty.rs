@@ -1,5 +1,27 @@
-use super::Type;
-use bolt_sys::sys::*;
+use super::{BoltString, Type};
+use crate::ValueType;
+use bolt_sys::sys::{self, *};
+
+/// The broad category of a [`Type`], see [`Type::kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TypeKind {
+    Any,
+    Null,
+    Number,
+    Bool,
+    String,
+    /// A primitive registered by the host, e.g. through `make_primitive_type`
+    Primitive,
+    Array,
+    Tableshape,
+    Signature,
+    NativeFn,
+    Userdata,
+    Union,
+    Enum,
+    /// The type of types
+    Type,
+}
 
 impl Type {
     bt_def!(type_dealias -> Type);
@@ -12,4 +34,99 @@ impl Type {
     pub fn union_get_variant(&mut self, idx: u32) -> Type {
         unsafe { Type::from_raw_unchecked(bt_union_get_variant(self.as_ptr(), idx)) }
     }
+
+    /// The name bolt uses for this type in diagnostics and annotations
+    pub fn name(&self) -> String {
+        let name = unsafe { (*self.as_ptr()).name };
+        if name.is_null() {
+            return String::new();
+        }
+        unsafe { std::ffi::CStr::from_ptr(name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn kind(&self) -> TypeKind {
+        let ty = self.as_ptr();
+        match sys::bt_TypeCategory(unsafe { (*ty).category } as u32) {
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_TYPE => TypeKind::Type,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_ARRAY => TypeKind::Array,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_TABLESHAPE => TypeKind::Tableshape,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_SIGNATURE => TypeKind::Signature,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_NATIVE_FN => TypeKind::NativeFn,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_USERDATA => TypeKind::Userdata,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_UNION => TypeKind::Union,
+            sys::bt_TypeCategory::BT_TYPE_CATEGORY_ENUM => TypeKind::Enum,
+            _ => unsafe {
+                let ctx = (*ty).ctx;
+                let builtins: [(unsafe extern "C" fn(*mut bt_Context) -> *mut bt_Type, _); 5] = [
+                    (bt_type_any, TypeKind::Any),
+                    (bt_type_null, TypeKind::Null),
+                    (bt_type_number, TypeKind::Number),
+                    (bt_type_bool, TypeKind::Bool),
+                    (bt_type_string, TypeKind::String),
+                ];
+                builtins
+                    .into_iter()
+                    .find(|(builtin, _)| builtin(ctx) == ty)
+                    .map_or(TypeKind::Primitive, |(_, kind)| kind)
+            },
+        }
+    }
+
+    /// The type of the items of an array type
+    pub fn array_element_type(&self) -> Option<Type> {
+        if self.kind() != TypeKind::Array {
+            return None;
+        }
+        unsafe { Type::from_raw((*self.as_ptr()).as_.array.inner) }
+    }
+
+    /// The argument types of a function signature, in order
+    pub fn signature_args(&self) -> Option<Vec<Type>> {
+        if !matches!(self.kind(), TypeKind::Signature | TypeKind::NativeFn) {
+            return None;
+        }
+        let args = unsafe { (*self.as_ptr()).as_.fn_.args };
+        if args.elements.is_null() {
+            return Some(Vec::new());
+        }
+        let args = unsafe { std::slice::from_raw_parts(args.elements, args.length as usize) };
+        Some(args.iter().filter_map(|&arg| Type::from_raw(arg)).collect())
+    }
+
+    /// The return type of a function signature, `None` if it isn't a signature or returns
+    /// nothing
+    pub fn return_type(&self) -> Option<Type> {
+        if !matches!(self.kind(), TypeKind::Signature | TypeKind::NativeFn) {
+            return None;
+        }
+        unsafe { Type::from_raw((*self.as_ptr()).as_.fn_.return_type) }
+    }
+
+    /// The `(name, type)` of every string-keyed field in a tableshape's layout
+    pub fn fields(&self) -> Option<Vec<(String, Type)>> {
+        if self.kind() != TypeKind::Tableshape {
+            return None;
+        }
+        let layout = unsafe { super::Table::from_raw((*self.as_ptr()).as_.table_shape.layout) };
+        let Some(layout) = layout else {
+            return Some(Vec::new());
+        };
+
+        let fields = layout.raw_pairs().iter().filter_map(|pair| {
+            let key = super::Value::from_raw(pair.key).as_object()?;
+            let value = super::Value::from_raw(pair.value).as_object()?;
+            if !matches!(key.value_type(), ValueType::String)
+                || !matches!(value.value_type(), ValueType::Type)
+            {
+                return None;
+            }
+
+            let key = unsafe { BoltString::from_raw_unchecked(key.as_ptr() as *mut _) };
+            let ty = unsafe { Type::from_raw_unchecked(value.as_ptr() as *mut _) };
+            Some((key.to_string_lossy(), ty))
+        });
+        Some(fields.collect())
+    }
 }
@@ -79,6 +79,64 @@ impl Value {
             None
         }
     }
+
+    /// Render the value the way bolt's `to_string` does
+    pub fn display(&self, ctx: &mut Context) -> String {
+        ctx.to_string(*self).to_string_lossy()
+    }
+
+    /// Compare using script `==` semantics, so strings compare by contents. The derived
+    /// `PartialEq` compares raw bits, i.e. object identity.
+    pub fn bolt_eq(&self, other: &Value) -> bool {
+        unsafe { sys::bt_value_is_equal(self.0, other.0) == sys::BT_TRUE as u8 }
+    }
+
+    fn as_string(&self) -> Option<BoltString> {
+        self.as_object()
+            .filter(|obj| matches!(obj.value_type(), ValueType::String))
+            .map(|obj| unsafe { BoltString::from_raw_unchecked(obj.as_ptr() as *mut _) })
+    }
+}
+
+/// A [`Value`] that hashes and compares the way bolt does, so it can key a `HashMap`
+/// consistently with script tables
+///
+/// Strings hash by contents, numbers by value, and every other object by identity. Unlike
+/// in scripts, every NaN matches every other NaN, since a key has to equal itself to be
+/// found again. The wrapped value must be kept alive, for example with
+/// `Context::add_ref`, for as long as it's used as a key.
+#[derive(Debug, Clone, Copy)]
+pub struct HashableValue(pub Value);
+
+impl PartialEq for HashableValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.0.as_number(), other.0.as_number()) {
+            (Some(a), Some(b)) if a.is_nan() && b.is_nan() => true,
+            _ => self.0.bolt_eq(&other.0),
+        }
+    }
+}
+
+impl Eq for HashableValue {}
+
+impl std::hash::Hash for HashableValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        if let Some(num) = self.0.as_number() {
+            // -0.0 == 0.0, and every NaN equals every other, so they must hash the same
+            let num = if num == 0.0 {
+                0.0
+            } else if num.is_nan() {
+                f64::NAN
+            } else {
+                num
+            };
+            num.to_bits().hash(state);
+        } else if let Some(string) = self.0.as_string() {
+            string.as_bytes().hash(state);
+        } else {
+            self.0.0.hash(state);
+        }
+    }
 }
 
 impl From<sys::bt_Value> for Value {
@@ -109,6 +167,28 @@ pub trait FromBoltValue: Sized {
     unsafe fn from_unchecked(val: sys::bt_Value) -> Self;
 }
 
+/// A full native-function argument list which can be extracted in one go, implemented for
+/// tuples of [`FromBoltValue`] types
+pub trait FromBoltArgs: Sized {
+    /// How many arguments the list holds
+    const COUNT: u8;
+
+    fn from_args(args: &[sys::bt_Value]) -> Result<Self, ArgError>;
+}
+
+/// A typed argument list for calling into script functions, implemented for tuples of
+/// [`ScalarTypeSignature`] values
+pub trait IntoBoltArgs {
+    /// How many arguments the list holds
+    const COUNT: u8;
+
+    /// The bolt type of each argument, in order
+    fn arg_types(ctx: &mut Context) -> Vec<Type>;
+
+    /// Box every argument, appending them to `out`
+    fn make_args(&self, ctx: &mut Context, out: &mut Vec<sys::bt_Value>);
+}
+
 /// Types which can be boxed into bolt values for use in function calls and return values
 /// without help from the context.
 pub trait MakeBoltValue: Sized {
@@ -117,7 +197,7 @@ pub trait MakeBoltValue: Sized {
 
 /// Types which can be boxed into bolt values for use in function calls and return values
 /// but that need help from the context.
-pub trait MakeBoltValueWithContext: Sized {
+pub trait MakeBoltValueWithContext {
     fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value;
 }
 
@@ -189,6 +269,40 @@ impl ValueType {
     }
 }
 
+// Anything that can be made without a context can trivially be made with one
+impl<T: MakeBoltValue> MakeBoltValueWithContext for T {
+    fn make_with_context(&self, _ctx: &mut Context) -> sys::bt_Value {
+        self.make()
+    }
+}
+
+// Unit is `null`, for natives and methods which don't return anything
+impl ScalarTypeSignature for () {
+    fn make_type(ctx: &mut Context) -> Type {
+        ctx.type_null()
+    }
+}
+
+impl FromBoltValue for () {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        match ValueType::from_value(val) {
+            ValueType::Null => Ok(()),
+            actual => Err(ArgError::TypeGuard {
+                expected: ValueType::Null,
+                actual,
+            }),
+        }
+    }
+
+    unsafe fn from_unchecked(_val: sys::bt_Value) -> Self {}
+}
+
+impl MakeBoltValue for () {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_make_null() }
+    }
+}
+
 // Scalar implementations
 impl ScalarTypeSignature for f64 {
     fn make_type(ctx: &mut Context) -> Type {
@@ -224,12 +338,136 @@ impl MakeBoltValue for f64 {
     }
 }
 
+impl ScalarTypeSignature for f32 {
+    fn make_type(ctx: &mut Context) -> Type {
+        f64::make_type(ctx)
+    }
+}
+
+impl FromBoltValue for f32 {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        <f64 as FromBoltValue>::from(val).map(|num| num as f32)
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe { <f64 as FromBoltValue>::from_unchecked(val) as f32 }
+    }
+}
+
+impl MakeBoltValue for f32 {
+    fn make(&self) -> sys::bt_Value {
+        (*self as f64).make()
+    }
+}
+
+// Bool implementations
+impl ScalarTypeSignature for bool {
+    fn make_type(ctx: &mut Context) -> Type {
+        unsafe {
+            let type_ptr = sys::bt_type_bool(ctx.as_ptr());
+            Type::from_raw(type_ptr).expect("Failed to get bool type")
+        }
+    }
+}
+
+impl FromBoltValue for bool {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        Value::from_raw(val)
+            .as_bool()
+            .ok_or_else(|| ArgError::TypeGuard {
+                expected: ValueType::Bool,
+                actual: ValueType::from_value(val),
+            })
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe { sys::bt_get_bool(val) != 0 }
+    }
+}
+
+impl MakeBoltValue for bool {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_make_bool(*self as sys::bt_bool) }
+    }
+}
+
+/// A raw bolt enum value, as stored in enum options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnumValue(pub u32);
+
+impl FromBoltValue for EnumValue {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        Value::from_raw(val)
+            .as_enum()
+            .map(EnumValue)
+            .ok_or_else(|| ArgError::TypeGuardEnum {
+                actual: ValueType::from_value(val),
+            })
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        EnumValue(unsafe { sys::bt_get_enum_val(val) })
+    }
+}
+
+impl MakeBoltValue for EnumValue {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_make_enum_val(self.0) }
+    }
+}
+
+// Optional implementations, `null` maps to `None`
+impl<T: FromBoltValue> FromBoltValue for Option<T> {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        if Value::from_raw(val).is_null() {
+            Ok(None)
+        } else {
+            T::from(val).map(Some)
+        }
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        if Value::from_raw(val).is_null() {
+            None
+        } else {
+            Some(unsafe { T::from_unchecked(val) })
+        }
+    }
+}
+
+impl<T: MakeBoltValue> MakeBoltValue for Option<T> {
+    fn make(&self) -> sys::bt_Value {
+        match self {
+            Some(val) => val.make(),
+            None => unsafe { sys::bt_make_null() },
+        }
+    }
+}
+
 // String implementations
+impl ScalarTypeSignature for String {
+    fn make_type(ctx: &mut Context) -> Type {
+        unsafe {
+            let type_ptr = sys::bt_type_string(ctx.as_ptr());
+            Type::from_raw(type_ptr).expect("Failed to get string type")
+        }
+    }
+}
+
+impl ScalarTypeSignature for &str {
+    fn make_type(ctx: &mut Context) -> Type {
+        String::make_type(ctx)
+    }
+}
+
 impl MakeBoltValueWithContext for &str {
     fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
         unsafe {
-            let c_str = CString::new(*self).unwrap_or_else(|_| CString::new("").unwrap());
-            let string_obj = sys::bt_make_string(ctx.as_ptr(), c_str.as_ptr());
+            let string_obj = sys::bt_make_string_len(
+                ctx.as_ptr(),
+                self.as_ptr() as *const std::ffi::c_char,
+                self.len() as u32,
+            );
             sys::bt_value(string_obj as *mut sys::bt_Object)
         }
     }
@@ -259,6 +497,42 @@ impl MakeBoltValueWithContext for &std::ffi::CStr {
     }
 }
 
+impl FromBoltValue for String {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        match Value::from_raw(val).as_object().map(|obj| obj.value_type()) {
+            Some(ValueType::String) => Ok(unsafe { Self::from_unchecked(val) }),
+            _ => Err(ArgError::TypeGuard {
+                expected: ValueType::String,
+                actual: ValueType::from_value(val),
+            }),
+        }
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe {
+            let obj_ptr = sys::bt_object(val);
+            BoltString::from_raw_unchecked(obj_ptr as *mut sys::bt_String).to_string_lossy()
+        }
+    }
+}
+
+// Raw value passthrough, for arguments whose type is checked by the caller
+impl FromBoltValue for Value {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        Ok(Value::from_raw(val))
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        Value::from_raw(val)
+    }
+}
+
+impl MakeBoltValue for Value {
+    fn make(&self) -> sys::bt_Value {
+        self.0
+    }
+}
+
 // Type wrapper implementations
 impl FromBoltValue for Type {
     fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
@@ -324,3 +598,370 @@ impl MakeBoltValue for Module {
         unsafe { sys::bt_value(self.as_ptr() as *mut sys::bt_Object) }
     }
 }
+
+// Callable wrapper implementations
+impl FromBoltValue for Callable {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        match Value::from_raw(val).as_object().map(|obj| obj.value_type()) {
+            Some(
+                ValueType::Function
+                | ValueType::NativeFunction
+                | ValueType::Closure
+                | ValueType::Module,
+            ) => Ok(unsafe { Self::from_unchecked(val) }),
+            actual => Err(ArgError::TypeGuard {
+                expected: ValueType::Function,
+                actual: actual.unwrap_or_else(|| ValueType::from_value(val)),
+            }),
+        }
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe {
+            let obj_ptr = sys::bt_object(val);
+            Callable::from_raw_unchecked(obj_ptr as *mut sys::bt_Callable)
+        }
+    }
+}
+
+impl MakeBoltValue for Callable {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_value(self.as_object_ptr()) }
+    }
+}
+
+macro_rules! impl_into_callable {
+    ($($name:ident),*) => {
+        $(
+            impl From<$name> for Callable {
+                fn from(callable: $name) -> Self {
+                    unsafe { Callable::from_raw_unchecked(callable.as_ptr() as *mut sys::bt_Callable) }
+                }
+            }
+        )*
+    };
+}
+
+impl_into_callable!(BoltFn, NativeFn, Closure, Module);
+
+// Array wrapper implementations
+impl FromBoltValue for Array {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        match Value::from_raw(val).as_object().map(|obj| obj.value_type()) {
+            Some(ValueType::Array) => Ok(unsafe { Self::from_unchecked(val) }),
+            _ => Err(ArgError::TypeGuard {
+                expected: ValueType::Array,
+                actual: ValueType::from_value(val),
+            }),
+        }
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe { Array::from_raw_unchecked(sys::bt_object(val) as *mut sys::bt_Array) }
+    }
+}
+
+impl MakeBoltValue for Array {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_value(self.as_object_ptr()) }
+    }
+}
+
+// Sequence implementations, slices become arrays and slices of pairs become tables
+impl<T: MakeBoltValueWithContext> MakeBoltValueWithContext for [T] {
+    fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+        let mut items = self.iter();
+        ctx.build_array(self.len(), |ctx| {
+            items.next().map(|item| item.make_with_context(ctx))
+        })
+        .make()
+    }
+}
+
+impl<T: MakeBoltValueWithContext> MakeBoltValueWithContext for Vec<T> {
+    fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+        self.as_slice().make_with_context(ctx)
+    }
+}
+
+// Fixed-size arrays check their length on the way back, e.g. `[f32; 3]` for vectors
+impl<T: MakeBoltValueWithContext, const N: usize> MakeBoltValueWithContext for [T; N] {
+    fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+        self.as_slice().make_with_context(ctx)
+    }
+}
+
+impl<T: FromBoltValue, const N: usize> FromBoltValue for [T; N] {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        let array = <Array as FromBoltValue>::from(val)?;
+        let items = array.raw_items();
+        if items.len() != N {
+            return Err(ArgError::LengthMismatch {
+                expected: N,
+                actual: items.len(),
+            });
+        }
+
+        let items = items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| {
+                T::from(*item).map_err(|error| ArgError::BadItem {
+                    idx,
+                    error: Box::new(error),
+                })
+            })
+            .collect::<Result<Vec<T>, ArgError>>()?;
+        Ok(items
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("length was checked")))
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        let array = unsafe { <Array as FromBoltValue>::from_unchecked(val) };
+        let items: Vec<T> = array
+            .raw_items()
+            .iter()
+            .map(|item| unsafe { T::from_unchecked(*item) })
+            .collect();
+        items
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("caller guarantees the length"))
+    }
+}
+
+impl<T: ScalarTypeSignature, const N: usize> ScalarTypeSignature for [T; N] {
+    fn make_type(ctx: &mut Context) -> Type {
+        let inner = T::make_type(ctx);
+        ctx.make_array_type(inner)
+    }
+}
+
+impl<K: MakeBoltValueWithContext, V: MakeBoltValueWithContext> MakeBoltValueWithContext
+    for [(K, V)]
+{
+    fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+        let table = ctx.make_table(self.len().min(u16::MAX as usize) as u16);
+        ctx.push_root(table.as_object());
+        for (key, value) in self {
+            // Building the value or growing the table can collect, keep both alive until
+            // the table references them
+            let key = Value::from_raw(key.make_with_context(ctx));
+            let key_rooted = key.as_object().inspect(|obj| ctx.push_root(*obj));
+            let value = Value::from_raw(value.make_with_context(ctx));
+            let value_rooted = value.as_object().inspect(|obj| ctx.push_root(*obj));
+            ctx.table_set(table, key, value);
+            for _ in key_rooted.iter().chain(value_rooted.iter()) {
+                ctx.pop_root();
+            }
+        }
+        ctx.pop_root();
+        table.make()
+    }
+}
+
+impl<K: MakeBoltValueWithContext, V: MakeBoltValueWithContext> MakeBoltValueWithContext
+    for Vec<(K, V)>
+{
+    fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+        self.as_slice().make_with_context(ctx)
+    }
+}
+
+// Argument list implementations, missing trailing arguments are read as `null` so that
+// `Option` parameters may be omitted by the caller
+macro_rules! impl_from_bolt_args {
+    ($count:literal; $($ty:ident => $idx:tt),*) => {
+        impl<$($ty: FromBoltValue),*> FromBoltArgs for ($($ty,)*) {
+            const COUNT: u8 = $count;
+
+            #[allow(unused_variables)]
+            fn from_args(args: &[sys::bt_Value]) -> Result<Self, ArgError> {
+                if args.len() > $count {
+                    return Err(ArgError::ArgCount {
+                        expected: $count,
+                        actual: args.len() as u8,
+                    });
+                }
+
+                let null = unsafe { sys::bt_make_null() };
+                Ok(($(
+                    $ty::from(args.get($idx).copied().unwrap_or(null)).map_err(|error| {
+                        ArgError::BadArgument {
+                            idx: $idx,
+                            error: Box::new(error),
+                        }
+                    })?,
+                )*))
+            }
+        }
+    };
+}
+
+impl_from_bolt_args!(0;);
+impl_from_bolt_args!(1; A => 0);
+impl_from_bolt_args!(2; A => 0, B => 1);
+impl_from_bolt_args!(3; A => 0, B => 1, C => 2);
+impl_from_bolt_args!(4; A => 0, B => 1, C => 2, D => 3);
+impl_from_bolt_args!(5; A => 0, B => 1, C => 2, D => 3, E => 4);
+impl_from_bolt_args!(6; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5);
+impl_from_bolt_args!(7; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6);
+impl_from_bolt_args!(8; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6, H => 7);
+
+macro_rules! impl_into_bolt_args {
+    ($count:literal; $($ty:ident => $idx:tt),*) => {
+        impl<$($ty: ScalarTypeSignature + MakeBoltValueWithContext),*> IntoBoltArgs
+            for ($($ty,)*)
+        {
+            const COUNT: u8 = $count;
+
+            #[allow(unused_variables)]
+            fn arg_types(ctx: &mut Context) -> Vec<Type> {
+                vec![$($ty::make_type(ctx)),*]
+            }
+
+            #[allow(unused_variables)]
+            fn make_args(&self, ctx: &mut Context, out: &mut Vec<sys::bt_Value>) {
+                $(out.push(self.$idx.make_with_context(ctx));)*
+            }
+        }
+    };
+}
+
+impl_into_bolt_args!(0;);
+impl_into_bolt_args!(1; A => 0);
+impl_into_bolt_args!(2; A => 0, B => 1);
+impl_into_bolt_args!(3; A => 0, B => 1, C => 2);
+impl_into_bolt_args!(4; A => 0, B => 1, C => 2, D => 3);
+impl_into_bolt_args!(5; A => 0, B => 1, C => 2, D => 3, E => 4);
+impl_into_bolt_args!(6; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5);
+impl_into_bolt_args!(7; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6);
+impl_into_bolt_args!(8; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6, H => 7);
+
+/// Several values travelling as one, for natives and script functions that return more than
+/// a single value
+///
+/// bolt functions return a single value, so a `Tuple` is boxed as an array with one item per
+/// element, typed `[T]` when every element has the same type and `[any]` otherwise, and is
+/// only read back from an array of exactly that length. (Bare pairs already box as table
+/// entries, see the `[(K, V)]` implementation.)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Tuple<T>(pub T);
+
+macro_rules! impl_tuple_value {
+    ($count:literal; $($ty:ident => $idx:tt),*) => {
+        impl<$($ty: MakeBoltValueWithContext),*> MakeBoltValueWithContext for Tuple<($($ty,)*)> {
+            fn make_with_context(&self, ctx: &mut Context) -> sys::bt_Value {
+                let mut idx = 0;
+                ctx.build_array($count, |ctx| {
+                    let item = match idx {
+                        $($idx => self.0.$idx.make_with_context(ctx),)*
+                        _ => return None,
+                    };
+                    idx += 1;
+                    Some(item)
+                })
+                .make()
+            }
+        }
+
+        impl<$($ty: FromBoltValue),*> FromBoltValue for Tuple<($($ty,)*)> {
+            fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+                let array = <Array as FromBoltValue>::from(val)?;
+                let items = array.raw_items();
+                if items.len() != $count {
+                    return Err(ArgError::LengthMismatch {
+                        expected: $count,
+                        actual: items.len(),
+                    });
+                }
+
+                Ok(Tuple(($(
+                    $ty::from(items[$idx]).map_err(|error| ArgError::BadItem {
+                        idx: $idx,
+                        error: Box::new(error),
+                    })?,
+                )*)))
+            }
+
+            unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+                let array = unsafe { <Array as FromBoltValue>::from_unchecked(val) };
+                let items = array.raw_items();
+                Tuple(($(unsafe { $ty::from_unchecked(items[$idx]) },)*))
+            }
+        }
+
+        impl<$($ty: ScalarTypeSignature),*> ScalarTypeSignature for Tuple<($($ty,)*)> {
+            fn make_type(ctx: &mut Context) -> Type {
+                let items = [$($ty::make_type(ctx)),*];
+                let inner = if items.iter().all(|item| item.as_ptr() == items[0].as_ptr()) {
+                    items[0]
+                } else {
+                    ctx.type_any()
+                };
+                ctx.make_array_type(inner)
+            }
+        }
+    };
+}
+
+impl_tuple_value!(2; A => 0, B => 1);
+impl_tuple_value!(3; A => 0, B => 1, C => 2);
+impl_tuple_value!(4; A => 0, B => 1, C => 2, D => 3);
+impl_tuple_value!(5; A => 0, B => 1, C => 2, D => 3, E => 4);
+impl_tuple_value!(6; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5);
+impl_tuple_value!(7; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6);
+impl_tuple_value!(8; A => 0, B => 1, C => 2, D => 3, E => 4, F => 5, G => 6, H => 7);
+
+// Table wrapper implementations
+impl FromBoltValue for Table {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        match Value::from_raw(val).as_object().map(|obj| obj.value_type()) {
+            Some(ValueType::Table) => Ok(unsafe { Self::from_unchecked(val) }),
+            _ => Err(ArgError::TypeGuard {
+                expected: ValueType::Table,
+                actual: ValueType::from_value(val),
+            }),
+        }
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe { Table::from_raw_unchecked(sys::bt_object(val) as *mut sys::bt_Table) }
+    }
+}
+
+impl MakeBoltValue for Table {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_value(self.as_object_ptr()) }
+    }
+}
+
+// Userdata wrapper implementations
+impl FromBoltValue for Userdata {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        match Value::from_raw(val).as_object().map(|obj| obj.value_type()) {
+            Some(ValueType::UserData) => Ok(unsafe { Self::from_unchecked(val) }),
+            _ => Err(ArgError::TypeGuard {
+                expected: ValueType::UserData,
+                actual: ValueType::from_value(val),
+            }),
+        }
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        unsafe { Userdata::from_raw_unchecked(sys::bt_object(val) as *mut sys::bt_Userdata) }
+    }
+}
+
+impl MakeBoltValue for Userdata {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_value(self.as_object_ptr()) }
+    }
+}
+
+// Generic object implementations
+impl MakeBoltValue for Object {
+    fn make(&self) -> sys::bt_Value {
+        unsafe { sys::bt_value(self.as_ptr()) }
+    }
+}
@@ -0,0 +1,55 @@
+//! Panics in Rust code called from scripts
+//!
+//! A panic can't unwind through bolt's C frames, so the natives this crate defines or
+//! generates run their Rust code inside [`guard`]. A panic there is caught and raised as a
+//! runtime error in the script that called the native, and the run or call executing the
+//! script fails with [`Error::HostPanic`] carrying the panic message. Hand-written natives
+//! can do the same:
+//!
+//! ```ignore
+//! unsafe extern "C" fn parse(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+//!     unsafe { bolt_rs::unwind::guard(ctx, thr, "parse", || parse_impl(ctx, thr)) };
+//! }
+//! ```
+//!
+//! [`Error::HostPanic`]: crate::Error::HostPanic
+
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+
+use bolt_sys::sys;
+
+/// Run `f`, returning its result, or `None` if it panicked
+///
+/// # Safety
+/// `ctx` is a live context and `thr` is either null or the thread running on it. A panic
+/// raises a runtime error on `thr`, so the native should return right after.
+pub unsafe fn guard<R>(
+    ctx: *mut sys::bt_Context,
+    thr: *mut sys::bt_Thread,
+    label: &str,
+    f: impl FnOnce() -> R,
+) -> Option<R> {
+    let payload = match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => return Some(result),
+        Err(payload) => payload,
+    };
+    let message = panic_message(&*payload);
+    drop(payload);
+
+    *crate::state::get(ctx).host_panic.borrow_mut() = Some(message.clone());
+    if !thr.is_null() {
+        unsafe { crate::userdata::raise(thr, label, &format!("panicked: {message}")) };
+    }
+    None
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        (*msg).to_owned()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
@@ -0,0 +1,783 @@
+//! Rust values owned by the bolt garbage collector
+//!
+//! [`Context::create_userdata`] moves any [`BoltUserdata`] value into a userdata object of
+//! the type registered for it. The object keeps a stamp of the Rust type alongside the
+//! value, so [`Userdata::borrow`] can check it's reading what it thinks it is, and the value
+//! is dropped when the object is collected.
+//!
+//! Userdata handles are `Copy` and the same object is usually reachable from scripts and
+//! from any number of natives, so access goes through a `RefCell`: conflicting borrows are
+//! reported as [`Error::AlreadyBorrowed`] rather than aliasing the value. Fields exposed
+//! with [`Context::userdata_add_field`] go through the same cell, so a script touching a
+//! field the host has borrowed gets a runtime error.
+//!
+//! Every type registered this way is remembered per context by its `TypeId`, so a native
+//! can take a [`TypedUserdata<T>`] argument and have both its bolt type and the check that
+//! it holds a `T` worked out from the registry, without passing the `Type` around.
+//!
+//! Computed properties added with [`Context::userdata_add_property`] are backed by Rust
+//! closures instead of a field. bolt's field callbacks don't carry any user pointer, so the
+//! `offset` bolt hands back to them is an index into the context's property table.
+//!
+//! A type opts into serialization with [`Context::userdata_serialize_with`]. Its values are
+//! then encoded as a table tagged with the type's name, which the serde layer and recorded
+//! replays both use to round-trip structures holding userdata instead of giving up on them.
+
+use std::any::TypeId;
+use std::cell::{Ref, RefCell, RefMut};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use bolt_sys::sys;
+
+use crate::types::{NativeFn, Table, Type, Userdata};
+use crate::{
+    ArgError, Context, Error, FromBoltValue, MakeBoltValue, MakeBoltValueWithContext,
+    ScalarTypeSignature, Thread, Value,
+};
+
+/// A Rust type that can live in a userdata object
+///
+/// `#[derive(BoltUserdata)]` implements this for structs, exposing their fields to scripts.
+pub trait BoltUserdata: 'static {
+    /// The script-visible name of the userdata type
+    const NAME: &'static str;
+
+    /// Add the fields scripts can access to `ty`, called once when the type is registered
+    fn register_fields(ctx: &mut Context, ty: Type) -> Result<(), Error> {
+        let _ = (ctx, ty);
+        Ok(())
+    }
+}
+
+/// Methods scripts can call on a userdata type, implemented by `#[bolt_methods]` on an
+/// impl block
+pub trait BoltMethods: BoltUserdata {
+    /// Add every method to `ty`, the userdata type registered for `Self`
+    fn register_methods(ctx: &mut Context, ty: Type) -> Result<(), Error>;
+}
+
+/// What's actually stored in the userdata. The value itself lives in a boxed `RefCell`, so
+/// references to it are properly aligned whatever alignment bolt gives the userdata's
+/// storage.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Stamp {
+    type_id: TypeId,
+    value: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+unsafe fn drop_boxed<T>(value: *mut ()) {
+    drop(unsafe { Box::from_raw(value as *mut RefCell<T>) });
+}
+
+unsafe extern "C" fn finalize(ctx: *mut sys::bt_Context, userdata: *mut sys::bt_Userdata) {
+    let Some(stamp) = (unsafe { stamp_of(userdata) }) else {
+        return;
+    };
+    // No script is waiting on a collection, so a panicking `Drop` is only recorded and
+    // reported by the next execution that fails
+    unsafe {
+        crate::unwind::guard(ctx, std::ptr::null_mut(), "finalize", || {
+            (stamp.drop)(stamp.value)
+        })
+    };
+}
+
+/// The stamp of a userdata created by [`Context::create_userdata`]
+unsafe fn stamp_of(userdata: *mut sys::bt_Userdata) -> Option<Stamp> {
+    unsafe {
+        let ty = (*userdata).type_;
+        let state = crate::state::get((*ty).ctx);
+        let ours = state.userdata_type_ptrs.borrow().contains(&(ty as usize));
+        if !ours || ((*userdata).size as usize) < size_of::<Stamp>() {
+            return None;
+        }
+        Some((sys::bt_userdata_get(userdata) as *const Stamp).read_unaligned())
+    }
+}
+
+/// The cell holding a `T` in the storage of a userdata created by `create_userdata`
+unsafe fn cell_at<'a, T: BoltUserdata>(data: *mut u8) -> Option<&'a RefCell<T>> {
+    let stamp = unsafe { (data as *const Stamp).read_unaligned() };
+    (stamp.type_id == TypeId::of::<T>()).then(|| unsafe { &*(stamp.value as *const RefCell<T>) })
+}
+
+/// Report a failed field access on whatever thread is running
+unsafe fn field_error(ctx: *mut sys::bt_Context, msg: &str) {
+    let thread = unsafe { (*ctx).current_thread };
+    if !thread.is_null() {
+        unsafe { raise(thread, "field access", msg) };
+    }
+}
+
+unsafe extern "C" fn get_field<T: BoltUserdata, F: MakeBoltValueWithContext>(
+    ctx: *mut sys::bt_Context,
+    data: *mut u8,
+    offset: u32,
+) -> sys::bt_Value {
+    let Some(cell) = (unsafe { cell_at::<T>(data) }) else {
+        return unsafe { sys::bt_make_null() };
+    };
+    let Ok(value) = cell.try_borrow() else {
+        unsafe { field_error(ctx, &format!("{} userdata is already borrowed", T::NAME)) };
+        return unsafe { sys::bt_make_null() };
+    };
+    let field = unsafe { &*((&*value as *const T as *const u8).add(offset as usize) as *const F) };
+    let mut ctx = unsafe { crate::ContextRef::from_raw(ctx) };
+    field.make_with_context(&mut ctx)
+}
+
+unsafe extern "C" fn set_field<T: BoltUserdata, F: FromBoltValue>(
+    ctx: *mut sys::bt_Context,
+    data: *mut u8,
+    offset: u32,
+    value: sys::bt_Value,
+) {
+    let Some(cell) = (unsafe { cell_at::<T>(data) }) else {
+        return;
+    };
+    let Ok(mut target) = cell.try_borrow_mut() else {
+        unsafe { field_error(ctx, &format!("{} userdata is already borrowed", T::NAME)) };
+        return;
+    };
+    match F::from(value) {
+        Ok(value) => unsafe {
+            *((&mut *target as *mut T as *mut u8).add(offset as usize) as *mut F) = value;
+        },
+        Err(err) => unsafe { field_error(ctx, &err.to_string()) },
+    }
+}
+
+type PropertyGetter = Rc<dyn Fn(&mut Context, *mut u8) -> Result<sys::bt_Value, String>>;
+type PropertySetter = Rc<dyn Fn(&mut Context, *mut u8, sys::bt_Value) -> Result<(), String>>;
+
+/// A computed property, see [`Context::userdata_add_property`]
+pub(crate) struct Property {
+    get: PropertyGetter,
+    /// `None` for read-only properties
+    set: Option<PropertySetter>,
+}
+
+fn property(
+    ctx: *mut sys::bt_Context,
+    idx: u32,
+) -> Option<(PropertyGetter, Option<PropertySetter>)> {
+    let state = crate::state::get(ctx);
+    let properties = state.properties.borrow();
+    let property = properties.get(idx as usize)?;
+    Some((property.get.clone(), property.set.clone()))
+}
+
+unsafe extern "C" fn get_property(
+    ctx: *mut sys::bt_Context,
+    data: *mut u8,
+    idx: u32,
+) -> sys::bt_Value {
+    let Some((get, _)) = property(ctx, idx) else {
+        return unsafe { sys::bt_make_null() };
+    };
+    let mut ctx_ref = unsafe { crate::ContextRef::from_raw(ctx) };
+    let thread = unsafe { (*ctx).current_thread };
+    match unsafe { crate::unwind::guard(ctx, thread, "field access", || get(&mut ctx_ref, data)) } {
+        Some(Ok(value)) => value,
+        Some(Err(msg)) => {
+            unsafe { field_error(ctx, &msg) };
+            unsafe { sys::bt_make_null() }
+        }
+        None => unsafe { sys::bt_make_null() },
+    }
+}
+
+unsafe extern "C" fn set_property(
+    ctx: *mut sys::bt_Context,
+    data: *mut u8,
+    idx: u32,
+    value: sys::bt_Value,
+) {
+    let Some((_, set)) = property(ctx, idx) else {
+        return;
+    };
+    let mut ctx_ref = unsafe { crate::ContextRef::from_raw(ctx) };
+    let thread = unsafe { (*ctx).current_thread };
+    let result = match set {
+        Some(set) => unsafe {
+            crate::unwind::guard(ctx, thread, "field access", || {
+                set(&mut ctx_ref, data, value)
+            })
+        },
+        None => Some(Err("property is read-only".to_owned())),
+    };
+    if let Some(Err(msg)) = result {
+        unsafe { field_error(ctx, &msg) };
+    }
+}
+
+impl Context {
+    /// Find the userdata type for `T`, building and registering it under `T::NAME` the first
+    /// time
+    pub fn register_userdata<T: BoltUserdata>(&mut self) -> Result<Type, Error> {
+        let state = crate::state::get(self.as_ptr());
+        if let Some(ty) = state.userdata_types.borrow().get(&TypeId::of::<T>()) {
+            return Ok(*ty);
+        }
+
+        crate::names::validate(T::NAME)?;
+        let ty = self.make_userdata_type(T::NAME)?;
+        unsafe { sys::bt_userdata_type_set_finalizer(ty.as_ptr(), Some(finalize)) };
+        // Listed before its fields are added, so a field holding another `T` finds the
+        // type instead of registering it again, and forgotten if registration fails
+        state
+            .userdata_types
+            .borrow_mut()
+            .insert(TypeId::of::<T>(), ty);
+        state
+            .userdata_type_ptrs
+            .borrow_mut()
+            .insert(ty.as_ptr() as usize);
+
+        let registered = T::register_fields(self, ty).and_then(|()| {
+            let name = Value::from_raw(T::NAME.make_with_context(self));
+            self.register_type(name, ty)
+        });
+        if let Err(err) = registered {
+            state.userdata_types.borrow_mut().remove(&TypeId::of::<T>());
+            state
+                .userdata_type_ptrs
+                .borrow_mut()
+                .remove(&(ty.as_ptr() as usize));
+            return Err(err);
+        }
+        Ok(ty)
+    }
+
+    /// The userdata type registered for `T`, if it has been
+    pub fn userdata_type<T: BoltUserdata>(&self) -> Option<Type> {
+        let state = crate::state::get(self.as_ptr());
+        state
+            .userdata_types
+            .borrow()
+            .get(&TypeId::of::<T>())
+            .copied()
+    }
+
+    /// Register `T`'s userdata type along with its methods, which are only added once
+    pub fn register_methods<T: BoltMethods>(&mut self) -> Result<Type, Error> {
+        let ty = self.register_userdata::<T>()?;
+        let state = crate::state::get(self.as_ptr());
+        if state
+            .userdata_methods
+            .borrow_mut()
+            .insert(TypeId::of::<T>())
+        {
+            T::register_methods(self, ty)?;
+        }
+        Ok(ty)
+    }
+
+    /// Expose the field of `T` at byte `offset` (from `offset_of!`) to scripts as `name`
+    ///
+    /// Reads and writes convert the field like a native argument or return value, and fail
+    /// with a runtime error if the host holds a conflicting borrow.
+    ///
+    /// # Safety
+    /// `offset` is the offset of a field of `T` whose type is `F`, e.g. from
+    /// `offset_of!(T, field)` on a field declared as `F`. Scripts read and write the field
+    /// as an `F` through it.
+    pub unsafe fn userdata_add_field<T, F>(
+        &mut self,
+        ty: Type,
+        name: &str,
+        offset: usize,
+        writable: bool,
+    ) -> Result<(), Error>
+    where
+        T: BoltUserdata,
+        F: FromBoltValue + MakeBoltValueWithContext + ScalarTypeSignature + 'static,
+    {
+        crate::names::validate(name)?;
+        assert!(
+            offset + size_of::<F>() <= size_of::<T>(),
+            "field offset is outside of {}",
+            T::NAME
+        );
+        let field_type = F::make_type(self);
+        let setter: sys::bt_UserdataFieldSetter = if writable {
+            Some(set_field::<T, F>)
+        } else {
+            None
+        };
+        self.userdata_type_push_field(
+            ty,
+            name,
+            offset as u32,
+            field_type,
+            Some(get_field::<T, F>),
+            setter,
+        )
+    }
+
+    /// Expose a read-only property computed by `get`, e.g. a `length` derived from the
+    /// value. Assigning to it is a runtime error.
+    pub fn userdata_add_property<T, V>(
+        &mut self,
+        ty: Type,
+        name: &str,
+        get: impl Fn(&T) -> V + 'static,
+    ) -> Result<(), Error>
+    where
+        T: BoltUserdata,
+        V: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        self.push_property::<T, V>(ty, name, get, None)
+    }
+
+    /// Expose a property computed by `get` that scripts can assign through `set`
+    pub fn userdata_add_property_mut<T, V>(
+        &mut self,
+        ty: Type,
+        name: &str,
+        get: impl Fn(&T) -> V + 'static,
+        set: impl Fn(&mut T, V) + 'static,
+    ) -> Result<(), Error>
+    where
+        T: BoltUserdata,
+        V: FromBoltValue + MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        let set: PropertySetter = Rc::new(move |_ctx, data, value| {
+            let value = V::from(value).map_err(|err| err.to_string())?;
+            let cell = unsafe { cell_at::<T>(data) }.ok_or(format!("not a {}", T::NAME))?;
+            let mut target = cell
+                .try_borrow_mut()
+                .map_err(|_| format!("{} userdata is already borrowed", T::NAME))?;
+            set(&mut target, value);
+            Ok(())
+        });
+        self.push_property::<T, V>(ty, name, get, Some(set))
+    }
+
+    fn push_property<T, V>(
+        &mut self,
+        ty: Type,
+        name: &str,
+        get: impl Fn(&T) -> V + 'static,
+        set: Option<PropertySetter>,
+    ) -> Result<(), Error>
+    where
+        T: BoltUserdata,
+        V: MakeBoltValueWithContext + ScalarTypeSignature,
+    {
+        crate::names::validate(name)?;
+        let get: PropertyGetter = Rc::new(move |ctx, data| {
+            let cell = unsafe { cell_at::<T>(data) }.ok_or(format!("not a {}", T::NAME))?;
+            let value = cell
+                .try_borrow()
+                .map_err(|_| format!("{} userdata is already borrowed", T::NAME))?;
+            let value = get(&value);
+            Ok(value.make_with_context(ctx))
+        });
+
+        let state = crate::state::get(self.as_ptr());
+        let idx = {
+            let mut properties = state.properties.borrow_mut();
+            properties.push(Property { get, set });
+            properties.len() - 1
+        };
+        let value_type = V::make_type(self);
+        self.userdata_type_push_field(
+            ty,
+            name,
+            idx as u32,
+            value_type,
+            Some(get_property),
+            Some(set_property),
+        )
+    }
+
+    /// Add a native method to a userdata type. Methods receive the userdata as their first
+    /// argument, followed by `args`.
+    pub fn userdata_add_method(
+        &mut self,
+        ty: Type,
+        name: &str,
+        proc: sys::bt_NativeProc,
+        ret: Type,
+        args: &[Type],
+    ) -> Result<(), Error> {
+        crate::names::validate(name)?;
+        self.add_method_native(ty, name, proc, ret, args)?;
+        Ok(())
+    }
+
+    /// Add a method under any name, including the `@` names of operators
+    pub(crate) fn add_method_native(
+        &mut self,
+        ty: Type,
+        name: &str,
+        proc: sys::bt_NativeProc,
+        ret: Type,
+        args: &[Type],
+    ) -> Result<NativeFn, Error> {
+        let mut signature_args = vec![ty];
+        signature_args.extend_from_slice(args);
+        let signature = self
+            .make_signature_type(ret, &signature_args)
+            .ok_or(Error::bolt("Failed to create signature type"))?;
+
+        let module = self.native_owner();
+        let native = self.make_named_native(module, signature, proc, name);
+        let key = Value::from_raw(name.make_with_context(self));
+        self.type_add_field(
+            ty,
+            signature,
+            key,
+            Value::from_raw(native.as_object().make()),
+        );
+        Ok(native)
+    }
+
+    /// The module owning natives that aren't exported from any module, like userdata
+    /// methods. Natives need an owning module, so these share one per context.
+    pub(crate) fn native_owner(&mut self) -> crate::types::Module {
+        let state = crate::state::get(self.as_ptr());
+        match state.method_module.get() {
+            Some(module) => module,
+            None => {
+                let module = self.make_module();
+                self.add_ref(module.as_object());
+                state.method_module.set(Some(module));
+                module
+            }
+        }
+    }
+
+    /// Move `value` into a new userdata object of `T`'s registered type, registering the
+    /// type if needed. `value` is dropped when the object is collected.
+    pub fn create_userdata<T: BoltUserdata>(&mut self, value: T) -> Result<Userdata, Error> {
+        self.create_typed_userdata(value)
+            .map(|typed| typed.userdata)
+    }
+
+    /// Like [`Context::create_userdata`], keeping track of the type in the handle
+    pub fn create_typed_userdata<T: BoltUserdata>(
+        &mut self,
+        value: T,
+    ) -> Result<TypedUserdata<T>, Error> {
+        let ty = self.register_userdata::<T>()?;
+        let mut stamp = Stamp {
+            type_id: TypeId::of::<T>(),
+            value: Box::into_raw(Box::new(RefCell::new(value))) as *mut (),
+            drop: drop_boxed::<T>,
+        };
+        let userdata = self.make_userdata(
+            ty,
+            &mut stamp as *mut Stamp as *mut std::ffi::c_void,
+            size_of::<Stamp>() as u32,
+        );
+        Ok(TypedUserdata {
+            userdata,
+            _value: PhantomData,
+        })
+    }
+}
+
+impl Userdata {
+    /// Borrow the value inside, failing if this userdata wasn't created from a `T` or the
+    /// value is currently borrowed mutably
+    pub fn borrow<T: BoltUserdata>(&self) -> Result<Ref<'_, T>, Error> {
+        self.cell::<T>()?
+            .try_borrow()
+            .map_err(|_| Error::AlreadyBorrowed { ty: T::NAME })
+    }
+
+    /// Mutably borrow the value inside, failing if this userdata wasn't created from a `T`
+    /// or the value is currently borrowed
+    pub fn borrow_mut<T: BoltUserdata>(&self) -> Result<RefMut<'_, T>, Error> {
+        self.cell::<T>()?
+            .try_borrow_mut()
+            .map_err(|_| Error::AlreadyBorrowed { ty: T::NAME })
+    }
+
+    /// Whether this userdata was created from a `T`
+    pub fn is<T: BoltUserdata>(&self) -> bool {
+        self.cell::<T>().is_ok()
+    }
+
+    fn cell<T: BoltUserdata>(&self) -> Result<&RefCell<T>, Error> {
+        unsafe { cell_of(*self) }
+    }
+}
+
+/// The cell holding the `T` inside `userdata`
+///
+/// # Safety
+/// The userdata must stay alive for `'a`.
+unsafe fn cell_of<'a, T: BoltUserdata>(userdata: Userdata) -> Result<&'a RefCell<T>, Error> {
+    unsafe { stamp_of(userdata.as_ptr()) }
+        .filter(|stamp| stamp.type_id == TypeId::of::<T>())
+        .map(|stamp| unsafe { &*(stamp.value as *const RefCell<T>) })
+        .ok_or(Error::UserdataType { expected: T::NAME })
+}
+
+/// A `T` borrowed in place from a native's userdata argument, see
+/// [`Thread::get_arg_userdata`]
+pub struct UserdataRef<'a, T>(Ref<'a, T>);
+
+impl<T> Deref for UserdataRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// A `T` mutably borrowed in place from a native's userdata argument, see
+/// [`Thread::get_arg_userdata_mut`]
+pub struct UserdataRefMut<'a, T>(RefMut<'a, T>);
+
+impl<T> Deref for UserdataRefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for UserdataRefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl Thread {
+    /// Borrow the `T` inside userdata argument `idx` without copying it out
+    ///
+    /// The borrow lasts as long as the borrow of the thread, and arguments stay alive for
+    /// the whole callback, so this is the way to read large payloads like meshes or images.
+    pub fn get_arg_userdata<T: BoltUserdata>(&self, idx: u8) -> Result<UserdataRef<'_, T>, Error> {
+        let cell = unsafe { cell_of::<T>(self.userdata_arg(idx)?)? };
+        let value = cell
+            .try_borrow()
+            .map_err(|_| Error::AlreadyBorrowed { ty: T::NAME })?;
+        Ok(UserdataRef(value))
+    }
+
+    /// Like [`Thread::get_arg_userdata`], borrowing the value mutably
+    pub fn get_arg_userdata_mut<T: BoltUserdata>(
+        &self,
+        idx: u8,
+    ) -> Result<UserdataRefMut<'_, T>, Error> {
+        let cell = unsafe { cell_of::<T>(self.userdata_arg(idx)?)? };
+        let value = cell
+            .try_borrow_mut()
+            .map_err(|_| Error::AlreadyBorrowed { ty: T::NAME })?;
+        Ok(UserdataRefMut(value))
+    }
+
+    fn userdata_arg(&self, idx: u8) -> Result<Userdata, Error> {
+        let argc = self.argc();
+        if idx >= argc {
+            return Err(ArgError::IndexOutOfBounds { idx, len: argc }.into());
+        }
+        let value = unsafe { sys::bt_arg(self.as_ptr(), idx) };
+        Ok(<Userdata as FromBoltValue>::from(value)?)
+    }
+}
+
+/// A userdata known to hold a `T`, for native arguments and return values
+///
+/// Its bolt type is the one registered for `T`, registering it on first use, and extracting
+/// it from an argument fails unless the userdata was created from a `T`.
+pub struct TypedUserdata<T: BoltUserdata> {
+    userdata: Userdata,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T: BoltUserdata> Clone for TypedUserdata<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: BoltUserdata> Copy for TypedUserdata<T> {}
+
+impl<T: BoltUserdata> std::fmt::Debug for TypedUserdata<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TypedUserdata")
+            .field(&T::NAME)
+            .field(&self.userdata.as_ptr())
+            .finish()
+    }
+}
+
+impl<T: BoltUserdata> TypedUserdata<T> {
+    /// Check that `userdata` holds a `T`
+    pub fn new(userdata: Userdata) -> Option<Self> {
+        userdata.is::<T>().then_some(Self {
+            userdata,
+            _value: PhantomData,
+        })
+    }
+
+    pub fn userdata(&self) -> Userdata {
+        self.userdata
+    }
+
+    pub fn borrow(&self) -> Result<Ref<'_, T>, Error> {
+        self.userdata.borrow::<T>()
+    }
+
+    pub fn borrow_mut(&self) -> Result<RefMut<'_, T>, Error> {
+        self.userdata.borrow_mut::<T>()
+    }
+}
+
+impl<T: BoltUserdata> FromBoltValue for TypedUserdata<T> {
+    fn from(val: sys::bt_Value) -> Result<Self, ArgError> {
+        let userdata = <Userdata as FromBoltValue>::from(val)?;
+        Self::new(userdata).ok_or(ArgError::UserdataType { expected: T::NAME })
+    }
+
+    unsafe fn from_unchecked(val: sys::bt_Value) -> Self {
+        Self {
+            userdata: unsafe { <Userdata as FromBoltValue>::from_unchecked(val) },
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T: BoltUserdata> MakeBoltValue for TypedUserdata<T> {
+    fn make(&self) -> sys::bt_Value {
+        self.userdata.make()
+    }
+}
+
+impl<T: BoltUserdata> ScalarTypeSignature for TypedUserdata<T> {
+    fn make_type(ctx: &mut Context) -> Type {
+        ctx.register_userdata::<T>()
+            .unwrap_or_else(|err| panic!("failed to register userdata {}: {err}", T::NAME))
+    }
+}
+
+/// The key naming the userdata type in the table a serializable userdata is encoded as
+pub const SERIALIZED_TYPE_KEY: &str = "$userdata";
+/// The key holding the encoded value in the same table
+pub const SERIALIZED_VALUE_KEY: &str = "value";
+
+type Encoder = Rc<dyn Fn(&mut Context, Userdata) -> Result<Value, Error>>;
+type Decoder = Rc<dyn Fn(&mut Context, Value) -> Result<Userdata, Error>>;
+
+/// Serialization hooks, see [`Context::userdata_serialize_with`]
+pub(crate) struct Codec {
+    ty: Type,
+    to_value: Encoder,
+    from_value: Decoder,
+}
+
+impl Context {
+    /// Let values of `T` be serialized, encoding them with `to_value` and rebuilding them with
+    /// `from_value`
+    ///
+    /// Registering hooks again replaces the previous ones.
+    pub fn userdata_serialize_with<T, E, D>(
+        &mut self,
+        to_value: E,
+        from_value: D,
+    ) -> Result<(), Error>
+    where
+        T: BoltUserdata,
+        E: Fn(&mut Context, &T) -> Result<Value, Error> + 'static,
+        D: Fn(&mut Context, Value) -> Result<T, Error> + 'static,
+    {
+        let ty = self.register_userdata::<T>()?;
+        let codec = Codec {
+            ty,
+            to_value: Rc::new(move |ctx, userdata| {
+                let value = userdata.borrow::<T>()?;
+                to_value(ctx, &value)
+            }),
+            from_value: Rc::new(move |ctx, value| {
+                let value = from_value(ctx, value)?;
+                ctx.create_userdata(value)
+            }),
+        };
+        let state = crate::state::get(self.as_ptr());
+        state
+            .userdata_codecs
+            .borrow_mut()
+            .insert(T::NAME.to_owned(), codec);
+        Ok(())
+    }
+
+    /// Encode `userdata` as `{ "$userdata": name, "value": encoded }`, failing if its type
+    /// has no serialization hooks
+    pub fn serialize_userdata(&mut self, userdata: Userdata) -> Result<Value, Error> {
+        let ty = unsafe { (*userdata.as_ptr()).type_ };
+        let state = crate::state::get(self.as_ptr());
+        let found = state
+            .userdata_codecs
+            .borrow()
+            .iter()
+            .find(|(_, codec)| codec.ty.as_ptr() == ty)
+            .map(|(name, codec)| (name.clone(), codec.to_value.clone()));
+        let Some((name, to_value)) = found else {
+            let ty = unsafe { Type::from_raw_unchecked(ty) }.name();
+            return Err(Error::NotSerializable { ty });
+        };
+
+        // Nothing references the encoded value until it's stored in the table
+        self.gc_pause();
+        let encoded = to_value(self, userdata).map(|inner| {
+            let table = self.make_table(2);
+            let key = Value::from_raw(SERIALIZED_TYPE_KEY.make_with_context(self));
+            let name = Value::from_raw(name.as_str().make_with_context(self));
+            self.table_set(table, key, name);
+            let key = Value::from_raw(SERIALIZED_VALUE_KEY.make_with_context(self));
+            self.table_set(table, key, inner);
+            Value::from_raw(table.make())
+        });
+        self.gc_unpause();
+        encoded
+    }
+
+    /// Rebuild a userdata encoded by [`Context::serialize_userdata`], or `None` if `value`
+    /// isn't an encoded userdata at all
+    pub fn deserialize_userdata(&mut self, value: Value) -> Result<Option<Userdata>, Error> {
+        let Ok(table) = <Table as FromBoltValue>::from(value.0) else {
+            return Ok(None);
+        };
+        let Some(name) = table
+            .get_str(SERIALIZED_TYPE_KEY)
+            .and_then(|name| <String as FromBoltValue>::from(name.0).ok())
+        else {
+            return Ok(None);
+        };
+
+        let state = crate::state::get(self.as_ptr());
+        let from_value = state
+            .userdata_codecs
+            .borrow()
+            .get(&name)
+            .map(|codec| codec.from_value.clone())
+            .ok_or_else(|| Error::NotSerializable { ty: name.clone() })?;
+        let inner = table
+            .get_str(SERIALIZED_VALUE_KEY)
+            .ok_or_else(|| Error::KeyNotFound {
+                key: SERIALIZED_VALUE_KEY.to_owned(),
+            })?;
+        from_value(self, inner).map(Some)
+    }
+}
+
+/// Raise a script error from a generated method trampoline
+///
+/// # Safety
+/// `thr` is the thread the trampoline was called on.
+#[doc(hidden)]
+pub unsafe fn raise(thr: *mut sys::bt_Thread, method: &str, msg: &str) {
+    let msg = std::ffi::CString::new(format!("{method}: {msg}").replace('\0', " "))
+        .expect("NULs were replaced");
+    unsafe { sys::bt_runtime_error(thr, msg.as_ptr(), std::ptr::null_mut()) };
+}
@@ -0,0 +1,101 @@
+//! The vendored bolt, and what it can do
+//!
+//! bolt-rs is built against whatever revision of the bolt submodule it was compiled with.
+//! [`Context::version`] and [`Context::build_info`] report that revision and how it was
+//! built, and [`Context::supports`] lets hosts and tests check for a [`Feature`] up front
+//! instead of failing obscurely on an older or differently configured bolt.
+//!
+//! Only what actually varies between builds is a [`Feature`]. Anything bolt-rs calls
+//! unconditionally, like userdata finalizers, is present in every bolt it links against.
+
+use bolt_sys::sys;
+
+use crate::Context;
+
+/// A bolt release, ordered by precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BoltVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub revision: u32,
+}
+
+impl BoltVersion {
+    /// The version of the bolt this crate was compiled against
+    pub const CURRENT: BoltVersion = BoltVersion::new(
+        sys::BOLT_VERSION_MAJOR,
+        sys::BOLT_VERSION_MINOR,
+        sys::BOLT_VERSION_REVISION,
+    );
+
+    pub const fn new(major: u32, minor: u32, revision: u32) -> Self {
+        Self {
+            major,
+            minor,
+            revision,
+        }
+    }
+}
+
+impl std::fmt::Display for BoltVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.revision)
+    }
+}
+
+/// How the vendored bolt was built
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: BoltVersion,
+    /// The CMake build type, e.g. `Debug` or `Release`
+    pub profile: &'static str,
+    pub debug_assertions: bool,
+    /// Import names of the standard library modules compiled in
+    pub std_modules: &'static [&'static str],
+}
+
+/// Something not every bolt build or context provides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    /// A standard library module, by import name, e.g. `Std("regex")`
+    Std(&'static str),
+    /// bolt's internal assertions, which catch API misuse at a cost on every call
+    DebugAssertions,
+}
+
+const BUILD: BuildInfo = BuildInfo {
+    version: BoltVersion::CURRENT,
+    profile: sys::build::PROFILE,
+    debug_assertions: sys::build::DEBUG_ASSERTIONS,
+    std_modules: sys::build::STD_MODULES,
+};
+
+impl Context {
+    /// The version of the bolt this crate was compiled against
+    pub fn version(&self) -> BoltVersion {
+        BUILD.version
+    }
+
+    pub fn build_info(&self) -> BuildInfo {
+        BUILD
+    }
+
+    /// Whether `feature` is available here
+    ///
+    /// A std module is available once it's compiled in and opened on this context, or
+    /// deferred until first import with [`ContextBuilder::lazy_std`](crate::ContextBuilder::lazy_std).
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::Std(name) => {
+                BUILD.std_modules.contains(&name)
+                    && (self.registered_module(name).is_some()
+                        || crate::state::get(self.as_ptr())
+                            .pending_std
+                            .borrow()
+                            .contains(&name))
+            }
+            Feature::DebugAssertions => BUILD.debug_assertions,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Interrupting long-running scripts
+//!
+//! bolt has no way to stop the interpreter from the outside, so interruption is
+//! cooperative: every native goes through a trampoline, see [`crate::stats`], which raises
+//! a runtime error instead of calling the native once an interrupt is requested. Scripts
+//! are stopped at their next native call, whether it's to a host native or the standard
+//! library, and no matter when the native was registered.
+//!
+//! The runtime error is reported like any other, with the script location it was raised
+//! at, and `run`/`call` return [`Error::Interrupted`].
+//!
+//! **Interrupts are not a timeout.** bolt gives the host no hook into the interpreter loop,
+//! so a loop which never calls a native, like a bare `while true {}`, can't be stopped by
+//! an interrupt or a [`Watchdog`], and runs until it's done, so nothing here bounds how
+//! long a script runs. Hosts running untrusted code should also set a memory limit and run
+//! each script on a thread they can afford to abandon. The TODO tracks what bolt would
+//! need for real timeouts.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{Context, Error};
+
+/// A handle used to interrupt a context from any thread, see [`Context::interrupt_handle`]
+///
+/// The handle is `Send` and `Sync`, so it can be moved into a Ctrl-C handler or shared with
+/// a UI thread while the context runs scripts on another.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Ask the context to stop at its next native call, failing the run or call executing
+    /// it with [`Error::Interrupted`]. A request made while nothing is running stops the
+    /// next script instead, unless it's [cleared](InterruptHandle::clear) first. A script
+    /// that never calls another native isn't stopped, see the [module docs](self).
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Withdraw a pending interrupt, returning whether there was one
+    pub fn clear(&self) -> bool {
+        self.take()
+    }
+
+    /// Clear a pending interrupt, returning whether there was one
+    pub(crate) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Interrupts a context from a monitor thread if it isn't disarmed within a timeout
+///
+/// Like any interrupt, firing stops the script at its next native call, so a runaway loop
+/// that calls no natives keeps running, see the [module docs](self). Dropping the watchdog
+/// disarms it and joins the monitor thread.
+#[derive(Debug)]
+pub struct Watchdog {
+    disarmed: Arc<(Mutex<bool>, Condvar)>,
+    fired: Arc<AtomicBool>,
+    monitor: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start a monitor which interrupts `handle` once `timeout` has elapsed
+    pub fn start(handle: InterruptHandle, timeout: Duration) -> Self {
+        let disarmed = Arc::new((Mutex::new(false), Condvar::new()));
+        let fired = Arc::new(AtomicBool::new(false));
+
+        let monitor = {
+            let disarmed = disarmed.clone();
+            let fired = fired.clone();
+            std::thread::spawn(move || {
+                let (lock, signal) = &*disarmed;
+                let guard = lock.lock().unwrap_or_else(|err| err.into_inner());
+                let (guard, _) = signal
+                    .wait_timeout_while(guard, timeout, |disarmed| !*disarmed)
+                    .unwrap_or_else(|err| err.into_inner());
+                if !*guard {
+                    fired.store(true, Ordering::Release);
+                    handle.interrupt();
+                }
+            })
+        };
+
+        Self {
+            disarmed,
+            fired,
+            monitor: Some(monitor),
+        }
+    }
+
+    /// Whether the timeout elapsed and the interrupt was requested
+    pub fn fired(&self) -> bool {
+        self.fired.load(Ordering::Acquire)
+    }
+
+    /// Stop the monitor, returning whether it had already fired
+    pub fn disarm(mut self) -> bool {
+        self.stop();
+        self.fired()
+    }
+
+    fn stop(&mut self) {
+        let (lock, signal) = &*self.disarmed;
+        *lock.lock().unwrap_or_else(|err| err.into_inner()) = true;
+        signal.notify_all();
+        if let Some(monitor) = self.monitor.take() {
+            let _ = monitor.join();
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Context {
+    /// Does nothing: every context checks for interrupts on each native call
+    #[deprecated(note = "every context checks for interrupts on each native call")]
+    pub fn set_interruptible(&mut self, _enabled: bool) {}
+
+    /// Ask the running script to stop at its next native call
+    pub fn interrupt(&self) {
+        crate::state::get(self.as_ptr()).interrupt.interrupt();
+    }
+
+    /// A handle which can interrupt this context from any thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        crate::state::get(self.as_ptr()).interrupt.clone()
+    }
+
+    /// Run `f` with a [`Watchdog`] interrupting the context once `after` has elapsed
+    pub(crate) fn interrupt_after<R>(
+        &mut self,
+        after: Duration,
+        f: impl FnOnce(&mut Self) -> Result<R, Error>,
+    ) -> Result<R, Error> {
+        let interrupt = crate::state::get(self.as_ptr()).interrupt.clone();
+        let watchdog = Watchdog::start(interrupt.clone(), after);
+        let result = f(self);
+        if watchdog.disarm() {
+            // The watchdog may have fired after the script finished on its own
+            interrupt.take();
+        }
+        result
+    }
+}
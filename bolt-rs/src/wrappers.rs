@@ -9,12 +9,26 @@ use std::ffi::{CStr, CString, NulError};
 /// Use `CString` or `&CStr` directly if you want to invoke without allocating.
 pub trait IntoCStr {
     fn as_c_str(&self) -> Result<Cow<'_, CStr>, NulError>;
+
+    /// The bytes of the string without a terminator, interior NULs included, for bolt's
+    /// length-based functions
+    fn raw_bytes(&self) -> Cow<'_, [u8]> {
+        match self.as_c_str() {
+            Ok(Cow::Borrowed(c_str)) => Cow::Borrowed(c_str.to_bytes()),
+            Ok(Cow::Owned(c_string)) => Cow::Owned(c_string.into_bytes()),
+            Err(err) => Cow::Owned(err.into_vec()),
+        }
+    }
 }
 
 impl IntoCStr for &str {
     fn as_c_str(&self) -> Result<Cow<'_, CStr>, NulError> {
         CString::new(*self).map(Cow::Owned)
     }
+
+    fn raw_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
 }
 
 impl IntoCStr for &CStr {
@@ -27,6 +41,10 @@ impl IntoCStr for String {
     fn as_c_str(&self) -> Result<Cow<'_, CStr>, NulError> {
         CString::new(self.as_str()).map(Cow::Owned)
     }
+
+    fn raw_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.as_bytes())
+    }
 }
 
 impl IntoCStr for CString {
@@ -407,15 +425,21 @@ macro_rules! define_wrapper_with_drop {
 macro_rules! define_object_wrapper {
     ($name:ident, $c_type:ty) => {
         #[derive(Debug, Clone, Copy)]
-        #[repr(transparent)]
+        #[cfg_attr(not(feature = "handle-checks"), repr(transparent))]
         pub struct $name {
             ptr: ::std::ptr::NonNull<$c_type>,
+            #[cfg(feature = "handle-checks")]
+            generation: u32,
         }
 
         impl $name {
             #[inline]
             pub fn from_raw(ptr: *mut $c_type) -> Option<Self> {
-                ::std::ptr::NonNull::new(ptr).map(|ptr| Self { ptr })
+                ::std::ptr::NonNull::new(ptr).map(|ptr| Self {
+                    ptr,
+                    #[cfg(feature = "handle-checks")]
+                    generation: $crate::handles::register(ptr.as_ptr() as usize),
+                })
             }
 
             #[inline]
@@ -423,18 +447,28 @@ macro_rules! define_object_wrapper {
                 unsafe {
                     Self {
                         ptr: ::std::ptr::NonNull::new_unchecked(ptr),
+                        #[cfg(feature = "handle-checks")]
+                        generation: $crate::handles::register(ptr as usize),
                     }
                 }
             }
 
             #[inline]
             pub fn as_ptr(&self) -> *mut $c_type {
+                #[cfg(feature = "handle-checks")]
+                $crate::handles::check(self.ptr.as_ptr() as usize, self.generation);
                 self.ptr.as_ptr()
             }
 
             #[inline]
             pub fn as_object_ptr(&self) -> *mut $crate::sys::bt_Object {
-                self.ptr.as_ptr() as *mut $crate::sys::bt_Object
+                self.as_ptr() as *mut $crate::sys::bt_Object
+            }
+
+            /// View this wrapper as a generic object, e.g. for rooting
+            #[inline]
+            pub fn as_object(&self) -> $crate::types::Object {
+                unsafe { $crate::types::Object::from_raw_unchecked(self.as_object_ptr()) }
             }
 
             #[inline]
@@ -461,6 +495,13 @@ macro_rules! define_object_wrapper {
                 unsafe { self.ptr.as_mut() }
             }
         }
+
+        impl $crate::types::ObjectHandle for $name {
+            #[inline]
+            fn as_object(&self) -> $crate::types::Object {
+                $name::as_object(self)
+            }
+        }
     };
 }
 
@@ -15,6 +15,24 @@ fn add(a: i32, b: i32) -> i32 {
     a + b
 }
 
+/// A directory under the temp dir no other test run shares
+fn unique_temp_dir(name: &str) -> std::path::PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    std::env::temp_dir().join(format!("{name}_{}_{nanos}", std::process::id()))
+}
+
+/// The value `module` exports as `name`, panicking if there's no such export
+fn export(ctx: &mut Context, module: types::Module, name: &str) -> Value {
+    module
+        .exports(ctx)
+        .find(|(export, _, _)| export == name)
+        .map(|(_, _, value)| value)
+        .unwrap_or_else(|| panic!("{name} is not exported"))
+}
+
 #[test]
 fn test_statement() {
     let mut ctx = Context::new();
@@ -39,7 +57,8 @@ fn test_make_primitive_type() {
         .expect("custom type");
 
     let type_name_value = "custom".make_with_context(&mut ctx);
-    ctx.register_type(Value::from_raw(type_name_value), custom_type);
+    ctx.register_type(Value::from_raw(type_name_value), custom_type)
+        .expect("Failed to register type");
 
     ctx.run("fn test_custom(x: custom) {}")
         .expect("Failed to define function with custom primitive type");
@@ -97,7 +116,8 @@ fn test_bolt_native_fn() {
         add_sig,
         Value::from_raw(addit_name),
         func_value,
-    );
+    )
+    .expect("Failed to export function");
 
     let module_name = "test_module".make_with_context(&mut ctx);
     ctx.register_module(Value::from_raw(module_name), module);
@@ -119,3 +139,4094 @@ fn test_bolt_native_fn() {
     )
     .expect("Native function returned wrong result");
 }
+
+#[test]
+fn test_script_commands() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.open_commands().expect("Failed to open commands module");
+
+    ctx.run(
+        "import register_command from commands
+         register_command(\"greet\", fn(args: [string]): [string] { return args }, \"Say hello\")",
+    )
+    .expect("Failed to register command");
+
+    let commands = ctx.commands();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].name, "greet");
+    assert_eq!(commands[0].help, "Say hello");
+
+    // The callback hands its arguments back, so the host sees exactly what it received
+    let received = ctx
+        .run_command("greet", &["hello", "world"])
+        .expect("Failed to run command");
+    let received =
+        <types::Array as FromBoltValue>::from(received.0).expect("Arguments are an array");
+    let received: Vec<String> = received
+        .iter(&mut ctx)
+        .map(|arg| <String as FromBoltValue>::from(arg.0).expect("Arguments are strings"))
+        .collect();
+    assert_eq!(received, ["hello", "world"]);
+    assert!(ctx.run_command("missing", &[]).is_err());
+}
+
+#[test]
+fn test_tuple_args() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx.make_module();
+
+    extern "C" fn scale(_ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut thr_wrap = Thread::from_raw(thr).expect("Null Thread");
+        let (value, factor, negate) = thr_wrap
+            .args::<(f64, f64, Option<bool>)>()
+            .expect("Bad args");
+        let ret = if negate.unwrap_or(false) {
+            -value * factor
+        } else {
+            value * factor
+        };
+        thr_wrap.return_val(&ret)
+    }
+
+    let number = f64::make_type(&mut ctx);
+    let boolean = bool::make_type(&mut ctx);
+    let maybe_bool = ctx.type_make_nullable(boolean);
+    ctx.module_export_native(
+        module,
+        "scale",
+        Some(scale),
+        number,
+        &[number, number, maybe_bool],
+    )
+    .expect("Failed to export native");
+
+    let module_name = "test_module".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(module_name), module);
+
+    ctx.run(
+        "import scale from test_module
+         import throw from core
+         if scale(2, 3, true) != -6 {
+            throw(\"throw\")
+         }",
+    )
+    .expect("Tuple args produced wrong result");
+}
+
+#[test]
+fn test_result_value() {
+    let mut ctx = Context::new();
+
+    let ok_key = Value::from_raw("ok".make_with_context(&mut ctx));
+    let err_key = Value::from_raw("err".make_with_context(&mut ctx));
+
+    let ok = Value::from_raw(Ok::<f64, String>(3.0).make_with_context(&mut ctx));
+    let ok_obj = ok.as_object().expect("Result should be a table");
+    assert_eq!(ctx.get(ok_obj, ok_key).as_number(), Some(3.0));
+    assert!(ctx.get(ok_obj, err_key).is_null());
+
+    let err = Value::from_raw(Err::<f64, String>("bad".into()).make_with_context(&mut ctx));
+    let err_obj = err.as_object().expect("Result should be a table");
+    assert!(ctx.get(err_obj, ok_key).is_null());
+    assert!(!ctx.get(err_obj, err_key).is_null());
+}
+
+#[derive(BoltValue, Debug, PartialEq)]
+struct SliderConfig {
+    #[bolt(rename = "label")]
+    tooltip: String,
+    max: f64,
+    #[bolt(default)]
+    min: f64,
+    step: Option<f64>,
+    #[bolt(skip)]
+    dirty: bool,
+}
+
+#[test]
+fn test_derive_bolt_value() {
+    let mut ctx = Context::new();
+
+    let config = SliderConfig {
+        tooltip: "Volume".into(),
+        max: 11.0,
+        min: 0.0,
+        step: Some(0.5),
+        dirty: true,
+    };
+
+    let value = config.make_with_context(&mut ctx);
+    let table = Value::from_raw(value)
+        .as_object()
+        .expect("Struct should become a table");
+    let label_key = Value::from_raw("label".make_with_context(&mut ctx));
+    assert!(!ctx.get(table, label_key).is_null());
+
+    let back = <SliderConfig as FromBoltValue>::from(value).expect("Failed to read table");
+    assert_eq!(
+        back,
+        SliderConfig {
+            dirty: false,
+            ..config
+        }
+    );
+
+    // Every field allocates, each allocation preceded by a full collection
+    ctx.gc_stress(true);
+    let stressed = SliderConfig {
+        tooltip: "Balance".into(),
+        max: 1.0,
+        min: -1.0,
+        step: None,
+        dirty: false,
+    };
+    let value = stressed.make_with_context(&mut ctx);
+    let back = <SliderConfig as FromBoltValue>::from(value).expect("Failed to read table");
+    ctx.gc_stress(false);
+    assert_eq!(back, stressed);
+}
+
+#[test]
+fn test_typecheck() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    assert!(ctx.typecheck("let x: number = 5").is_empty());
+
+    let diagnostics = ctx.typecheck("let x: number = \"five\"");
+    assert!(!diagnostics.is_empty());
+}
+
+#[derive(BoltEnum, Debug, Clone, Copy, PartialEq)]
+enum Weather {
+    Sunny,
+    Rainy,
+    #[bolt(rename = "Snowing")]
+    Snowy = 10,
+}
+
+#[test]
+fn test_derive_bolt_enum() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let weather = Weather::make_type(&mut ctx);
+
+    let value = Weather::Snowy.make();
+    assert_eq!(
+        <Weather as FromBoltValue>::from(value).ok(),
+        Some(Weather::Snowy)
+    );
+    assert!(<Weather as FromBoltValue>::from(EnumValue(3).make()).is_err());
+
+    assert_eq!(Weather::OPTIONS.len(), 3);
+    assert!(weather.as_ptr() == ctx.register_enum::<Weather>().unwrap().as_ptr());
+
+    // Another enum can't take over the name with different options
+    #[derive(Clone, Copy)]
+    struct Impostor;
+    impl BoltEnum for Impostor {
+        const NAME: &'static str = "Weather";
+        const OPTIONS: &'static [(&'static str, u32)] = &[("Sunny", 0)];
+        fn from_discriminant(_: u32) -> Option<Self> {
+            Some(Impostor)
+        }
+        fn discriminant(&self) -> u32 {
+            0
+        }
+    }
+    assert!(matches!(
+        ctx.register_enum::<Impostor>(),
+        Err(Error::EnumMismatch { name }) if name == "Weather"
+    ));
+
+    ctx.run("let w: Weather = Weather.Snowing")
+        .expect("Failed to use derived enum from script");
+}
+
+#[test]
+fn test_native_stats() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.set_native_stats(true);
+
+    extern "C" fn double(_ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut thr_wrap = Thread::from_raw(thr).expect("Null Thread");
+        let (value,) = thr_wrap.args::<(f64,)>().expect("Bad args");
+        thr_wrap.return_val(&(value * 2.0))
+    }
+
+    let module = ctx.make_module();
+    let number = f64::make_type(&mut ctx);
+    ctx.module_export_native(module, "double", Some(double), number, &[number])
+        .expect("Failed to export native");
+    let module_name = "test_module".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(module_name), module);
+
+    ctx.run(
+        "import double from test_module
+         for i in 0 to 10 { double(i) }",
+    )
+    .expect("Failed to call native");
+
+    let stats = ctx.native_stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].name, "double");
+    assert_eq!(stats[0].calls, 10);
+
+    // Call counts past `u32::MAX` don't wrap when averaging
+    let many = bolt_rs::stats::NativeStats {
+        name: "many".to_owned(),
+        calls: 1 << 33,
+        total: std::time::Duration::from_secs(1 << 33),
+    };
+    assert_eq!(many.average(), std::time::Duration::from_secs(1));
+
+    ctx.reset_native_stats();
+    assert!(ctx.native_stats().is_empty());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_roundtrip() {
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Point,
+        Circle { radius: f64 },
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Scene {
+        name: String,
+        layers: Vec<u32>,
+        shapes: Vec<Shape>,
+        visible: Option<bool>,
+    }
+
+    let mut ctx = Context::new();
+    let scene = Scene {
+        name: "intro".into(),
+        layers: vec![1, 2, 3],
+        shapes: vec![Shape::Point, Shape::Circle { radius: 2.5 }],
+        visible: None,
+    };
+
+    let value = bolt_rs::serde::to_value(&mut ctx, &scene).expect("Failed to serialize");
+    let back: Scene = bolt_rs::serde::from_value(&mut ctx, value).expect("Failed to deserialize");
+    assert_eq!(back, scene);
+}
+
+#[test]
+fn test_array_wrapper() {
+    let mut ctx = Context::new();
+
+    let arr = ctx.make_array(4);
+    assert!(arr.is_empty());
+    arr.push(&mut ctx, 1.0);
+    arr.push(&mut ctx, 2.0);
+    assert_eq!(arr.push(&mut ctx, 3.0), 3);
+
+    assert!(arr.set(&mut ctx, 1, 20.0));
+    assert!(!arr.set(&mut ctx, 5, 0.0));
+    assert_eq!(arr.get(&mut ctx, 1).and_then(|v| v.as_number()), Some(20.0));
+    assert!(arr.get(&mut ctx, 3).is_none());
+
+    let items: Vec<f64> = arr.iter(&mut ctx).filter_map(|v| v.as_number()).collect();
+    assert_eq!(items, vec![1.0, 20.0, 3.0]);
+}
+
+#[test]
+fn test_compile_expr() {
+    let mut ctx = Context::new();
+
+    let expr = ctx
+        .compile_expr::<f64>("a * b + c", &["a", "b", "c"])
+        .expect("expression should compile");
+    assert_eq!(expr.arity(), 3);
+
+    for i in 0..100 {
+        let x = i as f64;
+        let result = expr.eval(&mut ctx, &[x, 2.0, 1.0]).expect("eval failed");
+        assert_eq!(result, x * 2.0 + 1.0);
+    }
+
+    assert!(expr.eval(&mut ctx, &[1.0]).is_err());
+    assert!(ctx.compile_expr::<f64>("a +", &["a"]).is_err());
+    assert!(ctx.compile_expr::<f64>("1", &["not valid"]).is_err());
+    expr.release(&mut ctx);
+}
+
+#[test]
+fn test_table_wrapper() {
+    let mut ctx = Context::new();
+
+    let tbl = ctx.make_table(4);
+    assert!(tbl.is_empty());
+    assert!(tbl.set(&mut ctx, "width", 10.0));
+    assert!(tbl.set(&mut ctx, "height", 20.0));
+    tbl.set(&mut ctx, "width", 15.0);
+    assert_eq!(tbl.len(), 2);
+
+    assert!(tbl.contains_key(&mut ctx, "height"));
+    assert!(!tbl.contains_key(&mut ctx, "depth"));
+    assert_eq!(
+        tbl.get(&mut ctx, "width").and_then(|v| v.as_number()),
+        Some(15.0)
+    );
+    assert!(tbl.get(&mut ctx, "depth").is_none());
+
+    let total: f64 = tbl
+        .iter(&mut ctx)
+        .filter_map(|(_, value)| value.as_number())
+        .sum();
+    assert_eq!(total, 35.0);
+}
+
+#[test]
+fn test_module_exports() {
+    let mut ctx = Context::new();
+
+    let module = ctx
+        .create_module("consts")
+        .expect("Failed to create module");
+    let number = ctx.type_number();
+    let key = Value::from_raw("answer".make_with_context(&mut ctx));
+    ctx.module_export(module, number, key, Value::from_raw(42.0.make()))
+        .expect("Failed to export value");
+
+    let exports: Vec<_> = module.exports(&mut ctx).collect();
+    assert_eq!(exports.len(), 1);
+    let (name, mut ty, value) = exports[0].clone();
+    assert_eq!(name, "answer");
+    assert!(ty.type_is_equal(number));
+    assert_eq!(value.as_number(), Some(42.0));
+}
+
+#[test]
+fn test_exports_with_types() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx
+        .compile_module(
+            "export let limit = 3\nexport fn clamp(x: number, max: number): number { return x }",
+            "api",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+
+    let mut exports = module.exports_with_types(&mut ctx);
+    exports.sort_by(|a, b| a.name.cmp(&b.name));
+    assert_eq!(
+        exports
+            .iter()
+            .map(|export| export.name.as_str())
+            .collect::<Vec<_>>(),
+        ["clamp", "limit"]
+    );
+
+    let signature = exports[0]
+        .signature
+        .as_ref()
+        .expect("clamp should have a signature");
+    assert_eq!(signature.args.len(), 2);
+    assert!(
+        signature
+            .args
+            .iter()
+            .all(|arg| arg.kind() == TypeKind::Number)
+    );
+    assert_eq!(signature.ret.map(|ret| ret.kind()), Some(TypeKind::Number));
+
+    assert!(exports[1].signature.is_none());
+    assert_eq!(exports[1].ty.kind(), TypeKind::Number);
+}
+
+#[test]
+fn test_import_exports() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx
+        .compile_module(
+            "export let volume = 0.5\nexport let title = \"demo\"\nexport fn louder(x: number): number { return x * 2 }",
+            "settings",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let name = Value::from_raw("settings".make_with_context(&mut ctx));
+    ctx.register_module(name, module);
+
+    let settings = ctx.import("settings").expect("Failed to import settings");
+    assert_eq!(settings.module().as_ptr(), module.as_ptr());
+    assert_eq!(settings.get::<f64>("volume").unwrap(), 0.5);
+    assert_eq!(settings.get::<String>("title").unwrap(), "demo");
+    assert!(matches!(
+        settings.get::<f64>("missing"),
+        Err(Error::KeyNotFound { .. })
+    ));
+    assert!(settings.get::<f64>("title").is_err());
+    assert!(settings.get_function("volume").is_err());
+
+    let louder = settings
+        .get_function("louder")
+        .expect("louder should be a function");
+    let result = ctx
+        .call(louder, &[Value::from_raw(3.0.make())])
+        .expect("Failed to call louder");
+    assert_eq!(result.as_number(), Some(6.0));
+}
+
+#[test]
+fn test_add_prelude() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let logged = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    ctx.add_prelude("VERSION", 3.0)
+        .expect("Failed to add VERSION");
+    ctx.add_prelude("GAME_NAME", "demo")
+        .expect("Failed to add GAME_NAME");
+    ctx.add_prelude_fn("log_line", {
+        let logged = logged.clone();
+        move |(line,): (String,)| logged.borrow_mut().push(line)
+    })
+    .expect("Failed to add log_line");
+    ctx.add_prelude_fn("twice", |(x,): (f64,)| x * 2.0)
+        .expect("Failed to add twice");
+
+    ctx.run("log_line(GAME_NAME)\nif twice(VERSION) == 6 { log_line(\"six\") }")
+        .expect("Failed to run with prelude globals");
+    assert_eq!(*logged.borrow(), ["demo", "six"]);
+
+    assert!(matches!(
+        ctx.add_prelude("not valid", 1.0),
+        Err(Error::Module(ModuleError::InvalidName(_)))
+    ));
+}
+
+#[test]
+fn test_native_errors() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    extern "C" fn reject(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+        let mut thread = Thread::from_raw(thr).expect("Null Thread");
+        let (reason,) = thread.args::<(String,)>().expect("Bad args");
+        if reason.is_empty() {
+            thread.error("no reason given");
+            return;
+        }
+        let reason = Value::from_raw(reason.make_with_context(&mut ctx));
+        thread.error_value(&mut ctx, reason);
+    }
+
+    let module = ctx.make_module();
+    let string = ctx.type_string();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "reject", Some(reject), null, &[string])
+        .expect("Failed to export native");
+    let name = "gate".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+
+    match ctx.run("import reject from gate\nreject(\"\")") {
+        Err(Error::Runtime { message, .. }) => assert!(message.contains("no reason given")),
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+    match ctx.run("import reject from gate\nreject(\"closed\")") {
+        Err(Error::Script(thrown)) => {
+            assert_eq!(thrown.message, "closed");
+            assert_eq!(
+                thrown.data,
+                bolt_rs::replay::RecordedValue::String("closed".to_owned())
+            );
+        }
+        other => panic!("expected a thrown error, got {other:?}"),
+    }
+
+    ctx.add_prelude_try_fn("parse_count", |(text,): (String,)| {
+        text.parse::<f64>()
+            .map_err(|err| format!("bad count {text:?}: {err}"))
+    })
+    .expect("Failed to add parse_count");
+    ctx.run("let n = parse_count(\"12\")")
+        .expect("Valid counts parse");
+    match ctx.run("let n = parse_count(\"twelve\")") {
+        Err(Error::Runtime { message, .. }) => assert!(message.contains("bad count")),
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_hooks() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    ctx.set_hook(HookMask::CALL | HookMask::RETURN, {
+        let events = events.clone();
+        move |event| {
+            events
+                .borrow_mut()
+                .push((event.kind, event.function.clone()))
+        }
+    });
+
+    extern "C" fn tick(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "tick", Some(tick), null, &[])
+        .expect("Failed to export native");
+    let name = "ticker".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+
+    let script = ctx
+        .compile_module(
+            "import tick from ticker\nexport fn update() { tick() }",
+            "game",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(script)
+        .expect("Failed to execute module");
+    let update = export(&mut ctx, script, "update");
+
+    ctx.call(update, &[]).expect("Failed to call update");
+    assert_eq!(
+        *events.borrow(),
+        [
+            (HookKind::Call, "update".to_owned()),
+            (HookKind::Call, "tick".to_owned()),
+            (HookKind::Return, "tick".to_owned()),
+            (HookKind::Return, "update".to_owned()),
+        ]
+    );
+
+    ctx.clear_hook();
+    ctx.call(update, &[]).expect("Failed to call update");
+    assert_eq!(events.borrow().len(), 4);
+
+    // A panicking hook fails the script instead of unwinding through bolt
+    ctx.set_hook(HookMask::CALL, |event| {
+        assert_ne!(event.function, "tick", "hook rejected tick");
+    });
+    match ctx.call(update, &[]) {
+        Err(Error::HostPanic { message }) => assert!(message.contains("hook rejected tick")),
+        other => panic!("expected a host panic, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_profiler() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.profiler()
+        .start_with_interval(std::time::Duration::ZERO);
+    assert!(ctx.profiler().is_running());
+
+    extern "C" fn busy(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "busy", Some(busy), null, &[])
+        .expect("Failed to export native");
+    let name = "work".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+
+    ctx.run("import busy from work\nfor i in 0 to 5 { busy() }")
+        .expect("Failed to run");
+    let report = ctx.profiler().stop();
+    assert!(!ctx.profiler().is_running());
+
+    let busy = report.function("busy").expect("busy was sampled");
+    assert_eq!(busy.samples, 5);
+    assert!(busy.self_time >= std::time::Duration::from_millis(5));
+    assert!(busy.total_time >= busy.self_time);
+    assert!(report.duration >= busy.total_time);
+}
+
+#[test]
+fn test_max_call_depth() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.set_max_call_depth(16);
+
+    // Calls back into the script, nesting another execution each time
+    extern "C" fn reenter(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+        let mut thread = Thread::from_raw(thr).expect("Null Thread");
+        let (n,) = thread.args::<(f64,)>().expect("Bad args");
+        let down = ctx.registry_get("down").expect("down is registered");
+        if let Err(err) = ctx.call(down, &[Value::from_raw(n.make())]) {
+            thread.error(&err.to_string());
+        }
+    }
+
+    let module = ctx.make_module();
+    let number = ctx.type_number();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "reenter", Some(reenter), null, &[number])
+        .expect("Failed to export native");
+    let name = "host".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+
+    let script = ctx
+        .compile_module(
+            "import reenter from host\nexport fn down(n: number) { if n > 0 { reenter(n - 1) } }",
+            "recursive",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(script)
+        .expect("Failed to execute module");
+    let down = export(&mut ctx, script, "down");
+    ctx.registry_set("down", down);
+
+    ctx.call(down, &[Value::from_raw(3.0.make())])
+        .expect("Shallow recursion is allowed");
+    let result = ctx.call(down, &[Value::from_raw(1000.0.make())]);
+    assert!(matches!(result, Err(Error::CallDepth { limit: 16 })));
+    assert_eq!(ctx.max_call_depth(), Some(16));
+
+    // The failure above doesn't leak into the next run
+    ctx.call(down, &[Value::from_raw(3.0.make())])
+        .expect("Shallow recursion is allowed after a failure");
+    ctx.run("let x = 1").expect("Failed to run script");
+}
+
+#[test]
+fn test_script_recursion_depth() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    // Recursion that never calls a native is bounded by bolt's own call stack
+    fn frames<T, const N: usize>(_field: fn(&sys::bt_Thread) -> &[T; N]) -> u32 {
+        N as u32
+    }
+    let stack = frames(|thread| &thread.callstack);
+    let result = ctx.run("fn down(n: number): number { return down(n + 1) }\ndown(0)");
+    assert!(matches!(result, Err(Error::CallDepth { limit }) if limit == stack));
+    ctx.run("fn down(n: number): number { if n > 0 { return down(n - 1) } return n }\ndown(8)")
+        .expect("Shallow recursion is allowed after a failure");
+
+    ctx.set_max_call_depth(1000);
+    let result = ctx.run("fn up(n: number): number { return up(n + 1) }\nup(0)");
+    assert!(matches!(result, Err(Error::CallDepth { limit }) if limit == stack));
+}
+
+#[test]
+fn test_native_panic() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    ctx.add_prelude_fn("checked_div", |(a, b): (f64, f64)| {
+        if b == 0.0 {
+            panic!("division by zero");
+        }
+        a / b
+    })
+    .expect("Failed to add checked_div");
+
+    match ctx.run("let x = checked_div(1, 0)") {
+        Err(Error::HostPanic { message }) => assert_eq!(message, "division by zero"),
+        other => panic!("expected a host panic, got {other:?}"),
+    }
+    ctx.run("let x = checked_div(1, 2)")
+        .expect("Context is usable after a panic");
+}
+
+#[test]
+fn test_finalizer_panic() {
+    struct Fragile;
+
+    impl BoltUserdata for Fragile {
+        const NAME: &'static str = "Fragile";
+    }
+
+    impl Drop for Fragile {
+        fn drop(&mut self) {
+            panic!("dropped badly");
+        }
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.create_userdata(Fragile).unwrap();
+
+    // Collecting the unreachable userdata runs its `Drop` from bolt, which mustn't abort
+    ctx.gc_stress(true);
+    let _ = ctx.run("let garbage = [1, 2, 3]");
+    ctx.gc_stress(false);
+    ctx.run("let after = [4, 5, 6]")
+        .expect("The context is usable after a finalizer panicked");
+}
+
+#[test]
+fn test_rule_set() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let number = ctx.type_number();
+    let mut rules = RuleSet::new(&mut ctx, "Order", &[("total", number), ("items", number)])
+        .expect("Failed to create rule set");
+    rules
+        .add_rule(&mut ctx, "large", "input.total > 100")
+        .expect("Failed to add rule");
+    rules
+        .add_rule(&mut ctx, "average", "input.total / input.items")
+        .expect("Failed to add rule");
+    assert!(
+        rules
+            .add_rule(&mut ctx, "broken", "input.missing +")
+            .is_err()
+    );
+    assert_eq!(rules.rule_names().collect::<Vec<_>>(), ["large", "average"]);
+
+    let small = [Value::from_raw(50.0.make()), Value::from_raw(5.0.make())];
+    let large = [Value::from_raw(300.0.make()), Value::from_raw(3.0.make())];
+    let results = rules
+        .evaluate(&mut ctx, &[&small, &large])
+        .expect("Failed to evaluate rules");
+
+    assert_eq!(
+        results,
+        vec![
+            vec![RuleOutcome::Matched(false), RuleOutcome::Score(10.0)],
+            vec![RuleOutcome::Matched(true), RuleOutcome::Score(100.0)],
+        ]
+    );
+    assert!(rules.evaluate(&mut ctx, &[&small[..1]]).is_err());
+    rules.release(&mut ctx);
+}
+
+#[test]
+fn test_module_path_os() {
+    let mut dir = std::env::temp_dir();
+    #[cfg(unix)]
+    dir.push(<std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(b"bolt_\xffpaths"));
+    #[cfg(not(unix))]
+    dir.push("bolt_paths");
+    std::fs::create_dir_all(dir.join("nested")).expect("Failed to create module dir");
+    std::fs::write(dir.join("greeting.bolt"), "export let greeting = \"hi\"")
+        .expect("Failed to write module");
+
+    let mut ctx = Context::builder()
+        .path_normalization(PathNormalization::Lexical)
+        .build();
+    ctx.open_all_std();
+    let mut spec = dir.join("nested").join("..").into_os_string();
+    spec.push(std::path::MAIN_SEPARATOR_STR);
+    spec.push("%s.bolt");
+    ctx.append_module_path_os(&spec)
+        .expect("Failed to append module path");
+
+    ctx.run("import greeting from greeting")
+        .expect("Failed to import module from a non-UTF-8 path");
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_type_introspection() {
+    let mut ctx = Context::new();
+
+    let number = ctx.type_number();
+    let string = ctx.type_string();
+    assert_eq!(number.kind(), TypeKind::Number);
+    assert_eq!(string.kind(), TypeKind::String);
+    assert_eq!(number.name(), "number");
+
+    let mut numbers = ctx.make_array_type(number);
+    assert_eq!(numbers.kind(), TypeKind::Array);
+    assert!(
+        numbers
+            .array_element_type()
+            .is_some_and(|mut inner| inner.type_is_equal(number))
+    );
+    assert!(numbers.signature_args().is_none());
+    assert!(numbers.type_is_equal(numbers));
+
+    let signature = CallSignature {
+        args: vec![number, string],
+        return_ty: number,
+    }
+    .make_type(&mut ctx);
+    assert_eq!(signature.kind(), TypeKind::Signature);
+    assert_eq!(signature.signature_args().map(|args| args.len()), Some(2));
+    assert!(signature.return_type().is_some());
+
+    let result = ctx.result_type();
+    assert_eq!(result.kind(), TypeKind::Tableshape);
+    let mut fields: Vec<String> = result
+        .fields()
+        .expect("tableshape has fields")
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    fields.sort();
+    assert_eq!(fields, ["err", "ok"]);
+}
+
+#[test]
+fn test_import_cycle() {
+    let dir = unique_temp_dir("bolt_import_cycle");
+    std::fs::create_dir_all(&dir).expect("Failed to create module dir");
+    std::fs::write(dir.join("a.bolt"), "import b\nexport let x = 1").expect("write a");
+    std::fs::write(dir.join("b.bolt"), "import c\nexport let y = 2").expect("write b");
+    std::fs::write(dir.join("c.bolt"), "import a\nexport let z = 3").expect("write c");
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.append_module_path_os(dir.join("%s.bolt"))
+        .expect("Failed to append module path");
+
+    match ctx.run("import a") {
+        Err(Error::ImportCycle { chain }) => assert_eq!(chain, ["a", "b", "c", "a"]),
+        other => panic!("expected an import cycle, got {other:?}"),
+    }
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_module_sources() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.append_module_path("%s.bolt")
+        .expect("Failed to append module path");
+    ctx.add_module_source("embedded_greeting", "export let greeting = \"hi\"")
+        .expect("Failed to add module source");
+    ctx.add_module_source(
+        "embedded_shout",
+        "import greeting from embedded_greeting\nexport let shout = greeting + \"!\"",
+    )
+    .expect("Failed to add module source");
+
+    ctx.run("import shout from embedded_shout\nimport print from core\nprint(shout)")
+        .expect("Failed to import embedded modules");
+
+    assert!(ctx.remove_module_source("embedded_shout"));
+    assert!(!ctx.remove_module_source("embedded_shout"));
+    assert!(matches!(
+        ctx.add_module_source("broken", "let x = \"\0\""),
+        Err(Error::StringConversion(_))
+    ));
+
+    // Names match exactly, so `import shared` never picks up a source that merely ends
+    // with it
+    ctx.add_module_source("lib/shared", "export let which = \"lib\"")
+        .expect("Failed to add module source");
+    ctx.add_module_source("shared", "export let which = \"root\"")
+        .expect("Failed to add module source");
+    for _ in 0..8 {
+        ctx.run("import which from shared\nimport throw from core\nif which != \"root\" { throw(\"bad\") }")
+            .expect("Failed to import the exact match");
+    }
+}
+
+#[test]
+fn test_module_tree() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.append_module_path("%s.bolt")
+        .expect("Failed to append module path");
+
+    let added = ctx
+        .add_module_tree(&[
+            (
+                "tree_main.bolt",
+                "import value from tree_dep\nexport let doubled = value * 2",
+            ),
+            ("./tree_dep.bolt", "export let value = 21"),
+            ("mods\\util.bolt", "export let util = 1"),
+            ("README.md", "not a script"),
+        ])
+        .expect("Failed to add module tree");
+    assert_eq!(added, 3);
+    assert!(ctx.remove_module_source("mods/util"));
+
+    ctx.run("import doubled from tree_main\nimport throw from core\nif doubled != 42 { throw(\"bad\") }")
+        .expect("Failed to import from the embedded tree");
+}
+
+#[cfg(feature = "include-dir")]
+#[test]
+fn test_module_dir() {
+    use include_dir::{Dir, DirEntry, File};
+
+    static SCRIPTS: Dir = Dir::new(
+        "",
+        &[
+            DirEntry::File(File::new(
+                "dir_main.bolt",
+                b"import util from mods/dir_util\nexport let doubled = util * 2",
+            )),
+            DirEntry::File(File::new("notes.txt", b"not a script")),
+            DirEntry::Dir(Dir::new(
+                "mods",
+                &[DirEntry::File(File::new(
+                    "mods/dir_util.bolt",
+                    b"export let util = 21",
+                ))],
+            )),
+        ],
+    );
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.append_module_path("%s.bolt")
+        .expect("Failed to append module path");
+    assert_eq!(ctx.add_module_dir(&SCRIPTS).expect("Failed to add dir"), 2);
+
+    ctx.run(
+        "import doubled from dir_main\nimport throw from core\nif doubled != 42 { throw(\"bad\") }",
+    )
+    .expect("Failed to import from the embedded dir");
+    assert!(ctx.remove_module_source("mods/dir_util"));
+}
+
+#[test]
+fn test_compile_cache() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    let source = "export let answer = 42";
+    assert_eq!(ctx.compile_cache_stats(), None);
+
+    ctx.set_compile_cache(true);
+    let first = ctx
+        .compile_module(source, "first")
+        .expect("Failed to compile module");
+    let second = ctx
+        .compile_module(source, "second")
+        .expect("Failed to compile module");
+    let other = ctx
+        .compile_module("export let answer = 7", "other")
+        .expect("Failed to compile module");
+    assert_eq!(first.as_ptr(), second.as_ptr());
+    assert_ne!(first.as_ptr(), other.as_ptr());
+    assert_eq!(
+        ctx.compile_cache_stats(),
+        Some(CompileCacheStats {
+            hits: 1,
+            misses: 2,
+            entries: 2,
+        })
+    );
+
+    ctx.clear_compile_cache();
+    assert_eq!(
+        ctx.compile_cache_stats().map(|stats| stats.entries),
+        Some(0)
+    );
+    ctx.set_compile_cache(false);
+    assert_eq!(ctx.compile_cache_stats(), None);
+    ctx.compile_module(source, "uncached")
+        .expect("Failed to compile module");
+    assert_eq!(ctx.compile_cache_stats(), None);
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn test_hot_reload() {
+    let dir = unique_temp_dir("bolt_hot_reload");
+    std::fs::create_dir_all(&dir).expect("Failed to create module dir");
+    let path = dir.join("tuning.bolt");
+    std::fs::write(&path, "export let speed = 1").expect("write tuning");
+
+    let reloads = std::rc::Rc::new(std::cell::Cell::new(0));
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    let mut reloader = HotReloader::new().on_reload({
+        let reloads = reloads.clone();
+        move |_ctx, name, old, new| {
+            assert_eq!(name, "tuning");
+            assert_ne!(old.as_ptr(), new.as_ptr());
+            reloads.set(reloads.get() + 1);
+        }
+    });
+    reloader
+        .watch(&mut ctx, "tuning", &path)
+        .expect("Failed to watch module");
+    assert!(reloader.poll(&mut ctx).is_empty());
+
+    let bump = |source: &str, secs: u64| {
+        std::fs::write(&path, source).expect("write tuning");
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+            .expect("Failed to bump modification time");
+    };
+    bump("export let speed = 2", 1_000);
+    let reloaded = reloader.poll(&mut ctx);
+    assert_eq!(reloaded.len(), 1);
+    assert!(reloaded[0].1.is_ok());
+    assert_eq!(reloads.get(), 1);
+    ctx.run("import speed from tuning\nimport throw from core\nif speed != 2 { throw(\"stale\") }")
+        .expect("Imports should see the reloaded module");
+
+    bump("export let speed: number = \"broken\"", 2_000);
+    let reloaded = reloader.poll(&mut ctx);
+    assert!(reloaded[0].1.is_err());
+    assert_eq!(reloads.get(), 1);
+
+    reloader.clear(&mut ctx);
+
+    // Dropping a reloader releases its modules, whether or not its context is still open
+    let mut dropped = HotReloader::new();
+    dropped
+        .watch(&mut ctx, "tuning", &path)
+        .expect("Failed to watch module");
+    drop(dropped);
+    ctx.gc_set_next_cycle(0);
+    ctx.run("import speed from tuning")
+        .expect("The module stays registered after its reloader is dropped");
+
+    let mut outlived = HotReloader::new();
+    outlived
+        .watch(&mut ctx, "tuning", &path)
+        .expect("Failed to watch module");
+    drop(ctx);
+    drop(outlived);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_unregister_module() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let register = |ctx: &mut Context, source: &str| {
+        let module = ctx
+            .compile_module(source, "swappable")
+            .expect("Failed to compile module");
+        ctx.execute_module(module)
+            .expect("Failed to execute module");
+        let name = Value::from_raw("swappable".make_with_context(ctx));
+        ctx.register_module(name, module);
+        module
+    };
+    let check = "import version from swappable\nimport throw from core\nif version != 2 { throw(\"stale\") }";
+
+    let first = register(&mut ctx, "export let version = 1");
+    assert!(ctx.run(check).is_err());
+    let second = register(&mut ctx, "export let version = 2");
+    assert_eq!(
+        ctx.registered_module("swappable")
+            .map(|module| module.as_ptr()),
+        Some(second.as_ptr())
+    );
+    assert_ne!(first.as_ptr(), second.as_ptr());
+    ctx.run(check)
+        .expect("Imports should see the replacement module");
+
+    let removed = ctx.unregister_module("swappable");
+    assert_eq!(removed.map(|module| module.as_ptr()), Some(second.as_ptr()));
+    assert!(ctx.registered_module("swappable").is_none());
+    assert!(ctx.unregister_module("swappable").is_none());
+    assert!(ctx.run("import version from swappable").is_err());
+}
+
+#[test]
+fn test_debug_value() {
+    let mut ctx = Context::new();
+
+    assert_eq!(Value::from_raw(1.5.make()).display(&mut ctx), "1.5");
+
+    let inner = ctx.make_array(2);
+    inner.push(&mut ctx, 1.0);
+    inner.push(&mut ctx, "two");
+    let tbl = ctx.make_table(2);
+    tbl.set(&mut ctx, "items", inner);
+
+    let value = Value::from_raw(tbl.as_object().make());
+    assert_eq!(
+        ctx.debug_value(value, 4),
+        "{\n  items: [\n    1,\n    \"two\",\n  ],\n}"
+    );
+    assert_eq!(ctx.debug_value(value, 1), "{\n  items: [...],\n}");
+    assert_eq!(ctx.debug_value(value, 0), "{...}");
+}
+
+#[test]
+fn test_run_parse_errors() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    match ctx.run("let = 5\nlet y: number = (") {
+        Err(Error::Parse(diagnostics)) => {
+            assert!(!diagnostics.is_empty());
+            assert!(
+                diagnostics
+                    .iter()
+                    .all(|diagnostic| diagnostic.kind == DiagnosticKind::Parse)
+            );
+        }
+        other => panic!("expected parse errors, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_error_kinds() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    match ctx.compile_module("let x: number = \"text\"", "mistyped") {
+        Err(Error::Compile(diagnostics)) => {
+            assert!(!diagnostics.is_empty());
+            assert!(
+                diagnostics
+                    .iter()
+                    .all(|diagnostic| diagnostic.kind == DiagnosticKind::Compile)
+            );
+        }
+        other => panic!("expected compile errors, got {other:?}"),
+    }
+
+    match ctx.run("import throw from core\nlet x = 1\nthrow(\"boom\")") {
+        Err(Error::Runtime { message, line, .. }) => {
+            assert!(message.contains("boom"));
+            assert_eq!(line, 3);
+        }
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_runtime_traceback() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let source = "import throw from core\nexport fn inner() { throw(\"deep\") }\nexport fn outer() { inner() }\nouter()";
+    match ctx.run(source) {
+        Err(err @ Error::Runtime { .. }) => {
+            let Error::Runtime { traceback, .. } = &err else {
+                unreachable!()
+            };
+            // Innermost first, from the native that threw out to the top level
+            let functions = traceback
+                .frames
+                .iter()
+                .map(|frame| frame.function.as_str())
+                .skip_while(|function| *function == "throw")
+                .collect::<Vec<_>>();
+            assert_eq!(functions, ["inner", "outer", "<top level>"]);
+            assert_eq!(traceback.frames[0].line, Some(2));
+            assert!(
+                err.to_string()
+                    .contains("Traceback (most recent call last):")
+            );
+        }
+        other => panic!("expected a runtime error, got {other:?}"),
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_levels() {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<(tracing::Level, String)>>>);
+
+    struct Kind(String);
+
+    impl Visit for Kind {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "kind" {
+                self.0 = format!("{value:?}");
+            }
+        }
+    }
+
+    impl tracing::Subscriber for Collect {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &tracing::Event<'_>) {
+            if event.metadata().target() != "bolt" {
+                return;
+            }
+            let mut kind = Kind(String::new());
+            event.record(&mut kind);
+            self.0
+                .lock()
+                .unwrap()
+                .push((*event.metadata().level(), kind.0));
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let collect = Collect::default();
+    tracing::subscriber::with_default(collect.clone(), || {
+        let mut ctx = Context::new();
+        ctx.open_all_std();
+        // Straight through bolt, so nothing captures the errors and they're emitted
+        unsafe {
+            sys::bt_compile_module(
+                ctx.as_ptr(),
+                c"let x: number = \"nope\"".as_ptr(),
+                c"bad".as_ptr(),
+            );
+            sys::bt_run(
+                ctx.as_ptr(),
+                c"import throw from core\nthrow(\"boom\")".as_ptr(),
+            );
+        }
+    });
+
+    let events = collect.0.lock().unwrap();
+    assert!(events.iter().any(|(level, kind)| {
+        *level == tracing::Level::WARN && (kind == "Parse Error" || kind == "Compile Error")
+    }));
+    assert!(
+        events
+            .iter()
+            .any(|(level, kind)| *level == tracing::Level::ERROR && kind == "Runtime Error")
+    );
+}
+
+#[test]
+fn test_diagnostic_spans() {
+    let source = "let a = 1\nlet total: number = missing_name + 1";
+    let diagnostic = Diagnostic {
+        kind: DiagnosticKind::Compile,
+        module: "spans".to_owned(),
+        message: "unknown identifier".to_owned(),
+        line: 2,
+        col: 21,
+        span: None,
+    };
+    let span = diagnostic
+        .span_in(source)
+        .expect("position is inside the source");
+    assert_eq!(&source[span.start..span.end], "missing_name");
+
+    let past_end = Diagnostic {
+        line: 3,
+        ..diagnostic
+    };
+    assert_eq!(past_end.span_in(source), None);
+
+    // Every error is kept, each with the span of its own source text
+    let source = "let a: number = missing_one\nlet b: number = missing_two";
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    match ctx.compile_module(source, "spans") {
+        Err(Error::Compile(diagnostics)) | Err(Error::Parse(diagnostics)) => {
+            let spans = diagnostics
+                .iter()
+                .filter(|d| d.module == "spans")
+                .map(|d| {
+                    let span = d.span.expect("diagnostic should have a span");
+                    (d.line, &source[span.start..span.end])
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(spans, [(1, "missing_one"), (2, "missing_two")]);
+        }
+        other => panic!("expected compile errors, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_value_equality() {
+    let mut ctx = Context::new();
+
+    let a = Value::from_raw("key".make_with_context(&mut ctx));
+    let b = Value::from_raw("key".to_owned().make_with_context(&mut ctx));
+    assert!(a.bolt_eq(&b));
+    assert!(!a.bolt_eq(&Value::from_raw(1.0.make())));
+
+    let mut map = std::collections::HashMap::new();
+    map.insert(HashableValue(a), 1);
+    map.insert(HashableValue(Value::from_raw(0.0.make())), 2);
+    assert_eq!(map.get(&HashableValue(b)), Some(&1));
+    assert_eq!(
+        map.get(&HashableValue(Value::from_raw((-0.0).make()))),
+        Some(&2)
+    );
+
+    // Keys have to equal themselves, so NaN is found again
+    let nan = HashableValue(Value::from_raw(f64::NAN.make()));
+    assert_eq!(nan, nan);
+    map.insert(nan, 3);
+    assert_eq!(
+        map.get(&HashableValue(Value::from_raw(f64::NAN.make()))),
+        Some(&3)
+    );
+}
+
+#[test]
+fn test_native_call_budget() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    // Natives registered before the budget is set are counted too
+    extern "C" fn tick(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "tick", Some(tick), null, &[])
+        .expect("Failed to export native");
+    let module_name = "ticker".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(module_name), module);
+    ctx.set_native_call_budget(5);
+
+    let result = ctx.run("import tick from ticker\nfor i in 0 to 10 { tick() }");
+    assert!(matches!(result, Err(Error::NativeCallBudget)));
+    assert_eq!(ctx.remaining_native_calls(), Some(0));
+
+    ctx.set_native_call_budget(100);
+    ctx.run("import tick from ticker\nfor i in 0 to 3 { tick() }")
+        .expect("Enough calls left to finish");
+    assert_eq!(ctx.remaining_native_calls(), Some(97));
+
+    // So are the standard library's
+    ctx.set_native_call_budget(3);
+    let result = ctx.run("import sqrt from math\nfor i in 0 to 10 { sqrt(4) }");
+    assert!(matches!(result, Err(Error::NativeCallBudget)));
+
+    ctx.clear_native_call_budget();
+    assert_eq!(ctx.remaining_native_calls(), None);
+}
+
+#[test]
+fn test_sync_context() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let shared = std::sync::Arc::new(
+        SyncContext::new(|ctx| {
+            ctx.open_all_std();
+            Ok(())
+        })
+        .expect("Failed to open shared context"),
+    );
+    assert_send_sync(&*shared);
+
+    let workers: Vec<_> = (0..4)
+        .map(|n| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                shared.with(move |ctx| {
+                    let source = format!("export let result = {n} * 2");
+                    let module = ctx
+                        .compile_module(source.as_str(), format!("worker{n}").as_str())
+                        .expect("Failed to compile");
+                    ctx.execute_module(module).expect("Failed to execute");
+                    export(ctx, module, "result").as_number()
+                })
+            })
+        })
+        .collect();
+    for (n, worker) in workers.into_iter().enumerate() {
+        assert_eq!(worker.join().unwrap(), Some(n as f64 * 2.0));
+    }
+
+    // A panic reaches the caller, and the context keeps working
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        shared.with(|_| panic!("host bug"))
+    }));
+    assert!(panicked.is_err());
+    assert!(shared.with(|ctx| ctx.run("let x = 1").is_ok()));
+
+    let failed = SyncContext::new(|_| Err(Error::bolt("no config")));
+    assert!(matches!(failed, Err(Error::BoltError { msg }) if msg == "no config"));
+}
+
+#[test]
+fn test_interrupt_from_another_thread() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    extern "C" fn tick(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "tick", Some(tick), null, &[])
+        .expect("Failed to export native");
+    let module_name = "ticker".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(module_name), module);
+
+    // Natives registered before the handle was taken still check it
+    let handle = ctx.interrupt_handle();
+    assert_send_sync(&handle);
+
+    let stopper = std::thread::spawn({
+        let handle = handle.clone();
+        move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            handle.interrupt();
+        }
+    });
+    let result = ctx.run("import tick from ticker\nfor i in 0 to 1000000000 { tick() }");
+    stopper.join().expect("Stopper thread panicked");
+    assert!(matches!(result, Err(Error::Interrupted)));
+    assert!(!handle.is_interrupted());
+
+    handle.interrupt();
+    assert!(handle.clear());
+    ctx.run("import tick from ticker\ntick()")
+        .expect("A cleared interrupt doesn't stop the next script");
+}
+
+#[test]
+fn test_watchdog() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let watchdog = Watchdog::start(ctx.interrupt_handle(), std::time::Duration::from_millis(50));
+    let result = ctx.run("import sqrt from math\nfor i in 0 to 1000000000 { sqrt(i) }");
+    assert!(matches!(result, Err(Error::Interrupted)));
+    assert!(watchdog.disarm());
+
+    let watchdog = Watchdog::start(ctx.interrupt_handle(), std::time::Duration::from_secs(10));
+    ctx.run("import sqrt from math\nsqrt(4)")
+        .expect("Short script should finish before the watchdog fires");
+    assert!(!watchdog.disarm());
+    assert!(!ctx.interrupt_handle().is_interrupted());
+}
+
+#[test]
+fn test_watchdog_interrupt() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    extern "C" fn tick(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "tick", Some(tick), null, &[])
+        .expect("Failed to export native");
+    let module_name = "test_module".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(module_name), module);
+
+    let watchdog = Watchdog::start(ctx.interrupt_handle(), std::time::Duration::from_millis(50));
+    let result = ctx.run(
+        "import tick from test_module
+         for i in 0 to 1000000000 { tick() }",
+    );
+    assert!(watchdog.disarm());
+    assert!(matches!(result, Err(Error::Interrupted)));
+
+    let watchdog = Watchdog::start(ctx.interrupt_handle(), std::time::Duration::from_secs(10));
+    ctx.run(
+        "import tick from test_module
+         tick()",
+    )
+    .expect("Short script should finish before the watchdog fires");
+    assert!(!watchdog.disarm());
+
+    let spin = ctx
+        .compile_module(
+            "import tick from test_module
+             export fn spin() { for i in 0 to 1000000000 { tick() } }",
+            "spinner",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(spin).expect("Failed to execute module");
+    let spin = export(&mut ctx, spin, "spin");
+    let watchdog = Watchdog::start(ctx.interrupt_handle(), std::time::Duration::from_millis(50));
+    let result = ctx.call(spin, &[]);
+    assert!(watchdog.disarm());
+    assert!(matches!(result, Err(Error::Interrupted)));
+}
+
+#[test]
+fn test_sequence_values() {
+    let mut ctx = Context::new();
+
+    let squares = ctx.make_array_from_iter((1..=4).map(|i| (i * i) as f64));
+    let items: Vec<f64> = squares
+        .iter(&mut ctx)
+        .filter_map(|v| v.as_number())
+        .collect();
+    assert_eq!(items, vec![1.0, 4.0, 9.0, 16.0]);
+
+    let names = vec!["a", "b"];
+    let names = Value::from_raw(names.make_with_context(&mut ctx));
+    let names = <types::Array as FromBoltValue>::from(names.0).expect("slices become arrays");
+    assert_eq!(names.len(), 2);
+
+    let pairs = [("width", 10.0), ("height", 20.0)];
+    let table = Value::from_raw(pairs[..].make_with_context(&mut ctx));
+    let table =
+        <types::Table as FromBoltValue>::from(table.0).expect("slices of pairs become tables");
+    assert_eq!(
+        table.get_str("height").and_then(|v| v.as_number()),
+        Some(20.0)
+    );
+}
+
+#[test]
+fn test_strings_with_nuls() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let text = "before\0after";
+    let value = text.make_with_context(&mut ctx);
+    let back = <String as FromBoltValue>::from(value).expect("strings read back");
+    assert_eq!(back, text);
+
+    assert_eq!(ctx.make_string(text).to_string_lossy(), text);
+    let interned = ctx.get_or_make_interned(text).expect("NULs are accepted");
+    assert_eq!(interned.to_string_lossy(), text);
+    let prefix = ctx.make_string_len(text, 8).expect("NULs are accepted");
+    assert_eq!(prefix.to_string_lossy(), "before\0a");
+}
+
+#[test]
+fn test_dedup_strings() {
+    let mut ctx = Context::new();
+
+    let rows: Vec<Vec<(&str, String)>> = (0..3)
+        .map(|_| vec![("kind", "sensor".to_owned())])
+        .collect();
+    let rows = Value::from_raw(rows.make_with_context(&mut ctx));
+
+    let report = ctx.dedup_strings(rows);
+    assert_eq!(report.replaced, 3);
+    assert!(report.bytes_saved > 0);
+
+    let again = ctx.dedup_strings(rows);
+    assert_eq!(again, DedupReport::default());
+}
+
+#[test]
+fn test_call_handle() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx
+        .compile_module(
+            "import throw from core\nexport fn scale(x: number, unit: string): number { if x < 0 { throw(\"negative\") } return x * 2 }",
+            "call_handle",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let scale = export(&mut ctx, module, "scale");
+
+    let mut handle = CallHandle::<(f64, InternedStr), f64>::new(&mut ctx, scale)
+        .expect("Signature should match");
+    let unit = handle.intern(&mut ctx, "cm").expect("Failed to intern");
+    for i in 0..10 {
+        let result = handle
+            .call_fast(&mut ctx, (i as f64, unit))
+            .expect("Call failed");
+        assert_eq!(result, i as f64 * 2.0);
+    }
+
+    // Calls are seen by hooks, and a failed call doesn't poison the next one
+    let calls = std::rc::Rc::new(std::cell::Cell::new(0));
+    ctx.set_hook(HookMask::CALL, {
+        let calls = calls.clone();
+        move |_| calls.set(calls.get() + 1)
+    });
+    assert!(handle.call_fast(&mut ctx, (-1.0, unit)).is_err());
+    let result = handle
+        .call_fast(&mut ctx, (3.0, unit))
+        .expect("Call after a failure failed");
+    assert_eq!(result, 6.0);
+    assert!(calls.get() >= 2);
+    ctx.clear_hook();
+    handle.release(&mut ctx);
+
+    assert!(CallHandle::<(f64,), f64>::new(&mut ctx, scale).is_err());
+    assert!(CallHandle::<(f64, InternedStr), bool>::new(&mut ctx, scale).is_err());
+}
+
+#[test]
+fn test_root_scope() {
+    let mut ctx = Context::new();
+
+    let len = ctx.root_scope(|scope| {
+        let arr = scope.make_array(4);
+        scope.root(arr.as_object());
+
+        // Make every allocation below trigger a collection
+        scope.gc_set_next_cycle(0);
+        for i in 0..64 {
+            arr.push(scope, format!("item {i}"));
+        }
+        arr.len()
+    });
+    assert_eq!(len, 64);
+
+    let failed: Result<(), Error> = ctx.root_scope(|scope| {
+        let tbl = scope.make_table(1);
+        scope.root(tbl.as_object());
+        Err(Error::bolt("bailing out with the table still rooted"))?;
+        Ok(())
+    });
+    assert!(failed.is_err());
+}
+
+#[test]
+fn test_with_rollback() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let original = Value::from_raw(1.0.make());
+    ctx.registry_set("config", original);
+
+    let failed: Result<(), Error> = ctx.with_rollback(|tx| {
+        let tbl = tx.make_table(2);
+        tx.track(tbl);
+        tbl.set(tx, "half", "built");
+        tx.registry_set("config", Value::from_raw(tbl.make()));
+        tx.registry_set("extra", Value::from_raw(2.0.make()));
+        assert_eq!(tx.tracked(), 1);
+        Err(Error::bolt("bailing out halfway"))
+    });
+    assert!(failed.is_err());
+    assert!(ctx.registry_get("config").is_some_and(|v| v == original));
+    assert!(ctx.registry_get("extra").is_none());
+
+    let kept = ctx.with_rollback(|tx| {
+        let tbl = tx.make_table(1);
+        tx.track(tbl);
+        tbl.set(tx, "done", true);
+        tx.registry_set("config", Value::from_raw(tbl.make()));
+        Ok(tbl)
+    });
+    let kept = kept.expect("Transaction should succeed");
+    ctx.gc_set_next_cycle(0);
+    for i in 0..32 {
+        let _ = format!("garbage {i}").make_with_context(&mut ctx);
+    }
+    let stored = ctx
+        .registry_get("config")
+        .expect("Committed write was lost");
+    assert!(stored == Value::from_raw(kept.make()));
+}
+
+#[test]
+fn test_module_builder_validation() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    extern "C" fn noop(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+
+    let number = ctx.type_number();
+    let null = ctx.type_null();
+    let unregistered = ctx
+        .make_tableshape_type(c"Hidden", true)
+        .expect("Failed to make tableshape");
+
+    let problems = ModuleBuilder::new("broken")
+        .native("run", Some(noop), null, &[number])
+        .native("run", Some(noop), null, &[])
+        .native("inspect", Some(noop), null, &[unregistered])
+        .value("bad name", number, Value::from_raw(1.0.make()))
+        .finish_validated(&mut ctx)
+        .expect_err("Module should fail validation");
+    assert!(matches!(&problems[..], [
+        ModuleError::DuplicateExport(name),
+        ModuleError::UnregisteredType { export, .. },
+        ModuleError::InvalidName(bad),
+    ] if name == "run" && export == "inspect" && bad == "bad name"));
+    assert!(ctx.get_module("broken").is_err());
+
+    ModuleBuilder::new("valid")
+        .native("run", Some(noop), null, &[number])
+        .value("answer", number, Value::from_raw(42.0.make()))
+        .finish_validated(&mut ctx)
+        .expect("Module should validate");
+    ctx.run("import run, answer from valid\nrun(answer)")
+        .expect("Failed to use validated module");
+}
+
+#[test]
+fn test_rooted_handle() {
+    let mut ctx = Context::new();
+
+    let tbl = ctx.make_table(1);
+    let rooted = Rooted::new(&mut ctx, tbl);
+    let copy = rooted.clone();
+    drop(rooted);
+
+    // Make every allocation below trigger a collection while only the handle keeps the
+    // table alive
+    ctx.gc_set_next_cycle(0);
+    for i in 0..64 {
+        let key = Value::from_raw(format!("key {i}").make_with_context(&mut ctx));
+        copy.get()
+            .set(&mut ctx, key, Value::from_raw((i as f64).make()));
+    }
+    assert_eq!(copy.get().len(), 64);
+
+    let module = ctx
+        .compile_module(
+            "export fn double(x: number): number { return x * 2 }",
+            "rooted",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let func = export(&mut ctx, module, "double")
+        .as_object()
+        .expect("Module did not export its function");
+    let callback = Rooted::new(&mut ctx, func);
+
+    ctx.gc_set_next_cycle(0);
+    let callable = Value::from_raw(callback.get().make());
+    let returned = ctx
+        .call(callable, &[Value::from_raw(21.0.make())])
+        .expect("Failed to call rooted function");
+    assert_eq!(returned.as_number(), Some(42.0));
+
+    // Handles outliving their context are inert
+    drop(ctx);
+    drop(callback);
+}
+
+#[test]
+fn test_log_module() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Records = Rc<RefCell<Vec<(LogLevel, String, Vec<(String, String)>)>>>;
+
+    struct Capture {
+        min: LogLevel,
+        records: Records,
+    }
+
+    impl ScriptLogger for Capture {
+        fn enabled(&self, level: LogLevel) -> bool {
+            level >= self.min
+        }
+
+        fn log(&self, level: LogLevel, message: &str, fields: &[(String, String)]) {
+            self.records
+                .borrow_mut()
+                .push((level, message.to_owned(), fields.to_vec()));
+        }
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let records = Records::default();
+    ctx.open_log(Capture {
+        min: LogLevel::Info,
+        records: records.clone(),
+    })
+    .expect("Failed to open log module");
+
+    ctx.run(
+        r#"
+        import log
+        log.debug("hidden")
+        log.info("started")
+        log.warn("slow frame", { ms: 40 })
+        "#,
+    )
+    .expect("Failed to run script");
+
+    let records = records.borrow();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0], (LogLevel::Info, "started".to_owned(), vec![]));
+    assert_eq!(records[1].0, LogLevel::Warn);
+    assert_eq!(records[1].1, "slow frame");
+    assert_eq!(records[1].2, vec![("ms".to_owned(), "40".to_owned())]);
+}
+
+#[test]
+fn test_registry() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    assert!(ctx.registry_get("on_tick").is_none());
+
+    let tbl = ctx.make_table(1);
+    let value = Value::from_raw(tbl.make());
+    assert!(ctx.registry_set("state", value).is_none());
+    tbl.set(&mut ctx, "count", 3.0);
+
+    // Only the registry references the table now, so it has to survive collections
+    ctx.gc_set_next_cycle(0);
+    for i in 0..32 {
+        let _ = format!("garbage {i}").make_with_context(&mut ctx);
+    }
+
+    let stored = ctx.registry_get("state").expect("Registry lost its value");
+    let stored =
+        <types::Table as FromBoltValue>::from(stored.0).expect("Stored value isn't a table");
+    assert_eq!(
+        stored.get_str("count").and_then(|v| v.as_number()),
+        Some(3.0)
+    );
+
+    let replaced = ctx.registry_set("state", Value::from_raw(1.0.make()));
+    assert!(replaced.is_some_and(|old| old == value));
+    assert_eq!(
+        ctx.registry_remove("state").and_then(|v| v.as_number()),
+        Some(1.0)
+    );
+    assert!(ctx.registry_get("state").is_none());
+}
+
+#[test]
+fn test_host_handles() {
+    #[derive(Debug, PartialEq)]
+    struct Entity {
+        hp: f64,
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let entity = ctx.insert_handle(Entity { hp: 10.0 });
+    let other = ctx.insert_handle("not an entity");
+    assert_ne!(entity, other);
+
+    // Handles round-trip through scripts as numbers
+    let func = ctx
+        .compile_expr::<HostHandle>("h", &["h"])
+        .expect("Failed to compile expression");
+    let returned = func
+        .eval(&mut ctx, &[entity])
+        .expect("Failed to pass handle through script");
+    assert_eq!(returned, entity);
+    func.release(&mut ctx);
+
+    let hp = ctx.with_handle(entity, |_, e: &mut Entity| {
+        e.hp -= 3.0;
+        e.hp
+    });
+    assert_eq!(hp, Some(7.0));
+    assert!(ctx.with_handle(other, |_, _: &mut Entity| ()).is_none());
+
+    // A panic while the object is detached doesn't lose it
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        ctx.with_handle(entity, |_, _: &mut Entity| panic!("host bug"))
+    }));
+    assert!(panicked.is_err());
+    assert!(ctx.is_handle_valid(entity));
+
+    // Numbers that can't be a handle are refused rather than truncated
+    for bad in [0.0, 1.5, -1.0, 2f64.powi(60)] {
+        assert!(matches!(
+            <HostHandle as FromBoltValue>::from(bad.make()),
+            Err(ArgError::InvalidHandle(_))
+        ));
+    }
+
+    let removed = ctx.remove_handle(entity).expect("Handle was valid");
+    assert_eq!(removed.downcast_ref::<Entity>(), Some(&Entity { hp: 7.0 }));
+    assert!(!ctx.is_handle_valid(entity));
+    assert!(ctx.with_handle(entity, |_, _: &mut Entity| ()).is_none());
+
+    // Removed handles are never handed out again
+    let next = ctx.insert_handle(Entity { hp: 1.0 });
+    assert_ne!(next, entity);
+}
+
+#[test]
+fn test_data_module() {
+    use std::collections::HashMap;
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let strings: HashMap<String, String> = (0..200)
+        .map(|i| (format!("line_{i}"), format!("Line number {i}")))
+        .collect();
+    ctx.register_data_module("localization", &strings)
+        .expect("Failed to register data module");
+
+    let mut limits = HashMap::new();
+    limits.insert("max_hp", 100.0);
+    limits.insert("max_speed", 7.5);
+    let module = ctx
+        .register_data_module("limits", &limits)
+        .expect("Failed to register data module");
+
+    let exports: Vec<_> = module.exports(&mut ctx).map(|(name, _, _)| name).collect();
+    assert_eq!(exports, ["max_hp", "max_speed"]);
+
+    ctx.run(
+        r#"
+        import localization
+        import limits
+        let greeting: string = localization.line_42
+        let total: number = limits.max_hp + limits.max_speed
+        "#,
+    )
+    .expect("Scripts should read typed data exports");
+
+    assert!(
+        ctx.run("import limits\nlimits.max_hp = 1").is_err(),
+        "Data module exports must be read-only"
+    );
+}
+
+#[test]
+fn test_memory_limit() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.set_memory_limit(256 * 1024);
+
+    // Scripts that call natives are stopped at the first call past the limit
+    extern "C" fn tick(_ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {}
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "tick", Some(tick), null, &[])
+        .expect("Failed to export native");
+    let name = "ticker".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+    let err = ctx
+        .run(
+            r#"
+            import tick from ticker
+            let s = "abcdefgh"
+            for i in 0 to 40 { s = s + s; tick() }
+            "#,
+        )
+        .expect_err("Script should hit the memory limit");
+    assert!(matches!(err, Error::MemoryLimit), "unexpected error: {err}");
+
+    // Ones that don't still fail once they finish
+    let err = ctx
+        .run(
+            r#"
+            let s = "abcdefgh"
+            for i in 0 to 20 { s = s + s }
+            "#,
+        )
+        .expect_err("Script should hit the memory limit");
+    assert!(matches!(err, Error::MemoryLimit), "unexpected error: {err}");
+
+    // The context stays usable, and small scripts still fit under the limit
+    ctx.gc_set_next_cycle(0);
+    ctx.run("let x = 1 + 2").expect("Small script should run");
+
+    ctx.clear_memory_limit();
+    ctx.run(
+        r#"
+        let s = "abcdefgh"
+        for i in 0 to 16 { s = s + s }
+        "#,
+    )
+    .expect("Script should run once the limit is cleared");
+
+    // Objects the host builds count towards the limit as well, even after another context
+    // ran code on this thread, once this one is entered again
+    let mut other = Context::new();
+    other.run("let y = 1").expect("Other context should run");
+    ctx.set_memory_limit(256 * 1024);
+    let before = ctx.memory_used();
+    let _big = ctx.make_string_empty(512 * 1024);
+    assert!(ctx.memory_used() >= before + 512 * 1024);
+    let err = ctx
+        .run("import tick from ticker\ntick()")
+        .expect_err("Host allocations should count towards the limit");
+    assert!(matches!(err, Error::MemoryLimit), "unexpected error: {err}");
+}
+
+#[test]
+fn test_lazy_std() {
+    let mut ctx = Context::builder().lazy_std(true).build();
+    ctx.open_all_std();
+
+    let loaded = |ctx: &mut Context, name: &str| {
+        let name = Value::from_raw(name.make_with_context(ctx));
+        ctx.find_module(name, true).is_some()
+    };
+    assert!(loaded(&mut ctx, "core"));
+    assert!(!loaded(&mut ctx, "math"));
+    assert!(!loaded(&mut ctx, "regex"));
+
+    ctx.run("import math\nlet root: number = math.sqrt(16)")
+        .expect("Deferred module should load on import");
+    assert!(loaded(&mut ctx, "math"));
+    assert!(!loaded(&mut ctx, "regex"));
+
+    ctx.get_module("regex")
+        .expect("Deferred module should load on lookup");
+}
+
+#[test]
+fn test_container_access() {
+    let mut ctx = Context::new();
+
+    let arr = ctx.make_array_from_iter([1.0, 2.0, 3.0]);
+    assert_eq!(arr.at(&mut ctx, 2).unwrap().as_number(), Some(3.0));
+    let err = arr.at(&mut ctx, 3).expect_err("index 3 is out of bounds");
+    assert!(matches!(err, Error::IndexOutOfBounds { idx: 3, len: 3 }));
+
+    let sum: f64 = (&arr).into_iter().filter_map(|v| v.as_number()).sum();
+    assert_eq!(sum, 6.0);
+
+    let tbl = ctx.make_table(2);
+    tbl.set(&mut ctx, "a", 1.0);
+    tbl.set(&mut ctx, "b", 2.0);
+    assert_eq!(tbl.at(&mut ctx, "b").unwrap().as_number(), Some(2.0));
+    let err = tbl.at(&mut ctx, "missing").expect_err("key is missing");
+    assert_eq!(err.to_string(), "key missing not found in table");
+
+    let mut total = 0.0;
+    for (_, value) in &tbl {
+        total += value.as_number().unwrap_or_default();
+    }
+    assert_eq!(total, 3.0);
+}
+
+#[test]
+fn test_custom_allocator() {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct Counts {
+        allocations: Cell<usize>,
+        live_bytes: Cell<usize>,
+    }
+
+    struct Counting(Rc<Counts>);
+
+    impl BoltAllocator for Counting {
+        fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.0.allocations.set(self.0.allocations.get() + 1);
+            self.0
+                .live_bytes
+                .set(self.0.live_bytes.get() + layout.size());
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, old: Layout, new_size: usize) -> *mut u8 {
+            self.0
+                .live_bytes
+                .set(self.0.live_bytes.get() - old.size() + new_size);
+            unsafe { System.realloc(ptr, old, new_size) }
+        }
+
+        unsafe fn free(&self, ptr: *mut u8, layout: Layout) {
+            self.0
+                .live_bytes
+                .set(self.0.live_bytes.get() - layout.size());
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    let counts = Rc::new(Counts::default());
+    let mut ctx = Context::builder()
+        .allocator(Counting(counts.clone()))
+        .build();
+    ctx.open_all_std();
+    assert!(counts.allocations.get() > 0, "opening should allocate");
+
+    let before = counts.allocations.get();
+    ctx.run("let words = [\"a\", \"b\", \"c\"]")
+        .expect("Failed to run script");
+    assert!(counts.allocations.get() > before);
+    assert!(ctx.memory_used() > 0);
+
+    drop(ctx);
+    assert_eq!(counts.live_bytes.get(), 0, "everything should be freed");
+}
+
+#[test]
+fn test_fixed_size_arrays() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let position: [f32; 3] = [1.0, 2.5, -4.0];
+    let value = position.make_with_context(&mut ctx);
+    let back = <[f32; 3] as FromBoltValue>::from(value).expect("Round trip should succeed");
+    assert_eq!(back, position);
+
+    let short = <[f32; 4] as FromBoltValue>::from(value);
+    assert!(matches!(
+        short,
+        Err(ArgError::LengthMismatch {
+            expected: 4,
+            actual: 3
+        })
+    ));
+
+    let two = Value::from_raw("two".make_with_context(&mut ctx));
+    let mixed = [Value::from_raw(1.0.make()), two].make_with_context(&mut ctx);
+    assert!(matches!(
+        <[f64; 2] as FromBoltValue>::from(mixed),
+        Err(ArgError::BadItem { idx: 1, .. })
+    ));
+
+    let mut ty = <[f32; 3] as ScalarTypeSignature>::make_type(&mut ctx);
+    let number = ctx.type_number();
+    let expected = ctx.make_array_type(number);
+    assert!(ty.type_is_equal(expected));
+}
+
+#[test]
+fn test_unified_errors() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    fn read_pair(value: Value) -> Result<[f64; 2], Error> {
+        Ok(<[f64; 2] as FromBoltValue>::from(value.0)?)
+    }
+    let triple = Value::from_raw([1.0, 2.0, 3.0].make_with_context(&mut ctx));
+    let err = read_pair(triple).expect_err("three items don't fit a pair");
+    assert!(matches!(err, Error::Arg(ArgError::LengthMismatch { .. })));
+    assert_eq!(err.to_string(), "expected 2 items, got 3");
+
+    let missing = (|| -> Result<_, Error> { Ok(ctx.get_module("nowhere")?) })();
+    assert!(matches!(
+        missing,
+        Err(Error::Module(ModuleError::NotFound(_)))
+    ));
+    assert!(matches!(ctx.import("nowhere"), Err(Error::Module(_))));
+}
+
+#[test]
+fn test_gc_stress() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let growth = ctx.gc_get_growth_pct();
+    ctx.gc_stress(true);
+    assert!(ctx.is_gc_stressed());
+
+    // Every wrapper below allocates several objects, each preceded by a full collection
+    let rows: Vec<Vec<(String, f64)>> = (0..8)
+        .map(|i| {
+            vec![
+                ("id".to_owned(), i as f64),
+                ("score".to_owned(), i as f64 * 1.5),
+            ]
+        })
+        .collect();
+    let value = rows.make_with_context(&mut ctx);
+    let arr = <types::Array as FromBoltValue>::from(value).expect("rows become an array");
+    ctx.push_root(arr.as_object());
+    assert_eq!(arr.len(), 8);
+    for i in 0..8 {
+        let row = arr.at(&mut ctx, i).expect("row in bounds");
+        let row = <types::Table as FromBoltValue>::from(row.0).expect("rows are tables");
+        let id = row.get_str("id").and_then(|v| v.as_number());
+        let score = row.get_str("score").and_then(|v| v.as_number());
+        assert_eq!(id, Some(i as f64));
+        assert_eq!(score, Some(i as f64 * 1.5));
+    }
+    ctx.run("let total = 0\nfor i in 0 to 32 { total = total + i }")
+        .expect("Script should survive stress mode");
+    ctx.pop_root();
+
+    ctx.gc_stress(false);
+    assert!(!ctx.is_gc_stressed());
+    assert_eq!(ctx.gc_get_growth_pct(), growth);
+}
+
+#[cfg(feature = "handle-checks")]
+#[test]
+#[should_panic(expected = "use of collected bolt object")]
+fn test_stale_handle_after_collection() {
+    let mut ctx = Context::new();
+
+    // Nothing roots the table, so the full collection before the next allocation frees it
+    let table = ctx.make_table(4);
+    ctx.gc_stress(true);
+    let _ = ctx.make_table(1);
+    ctx.gc_stress(false);
+
+    table.len();
+}
+
+#[test]
+fn test_context_ref_does_not_close() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    {
+        let mut borrowed = unsafe { ContextRef::from_raw(ctx.as_ptr()) };
+        borrowed
+            .run("let x = 1")
+            .expect("Borrowed context should run code");
+    }
+
+    // Dropping the borrow must leave the owning context open
+    ctx.run("let y = 2")
+        .expect("Owning context should still be usable");
+}
+
+#[cfg(feature = "math-types")]
+#[test]
+fn test_math_types() {
+    use bolt_rs::math::{Mat4, Vec3};
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.open_math_types().expect("Failed to open math types");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            import vecmath
+            export fn step(pos: Vec3, vel: Vec3): Vec3 {
+                return pos.add(vel.scale(2))
+            }
+            export fn place(): Vec3 {
+                let v = vecmath.vec3(1, 0, 0)
+                v.y = 2
+                return vecmath.mat4_translation(0, 0, 5).transform(v)
+            }
+            "#,
+            "physics",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (step, place) = (
+        export(&mut ctx, module, "step"),
+        export(&mut ctx, module, "place"),
+    );
+
+    let pos = Value::from_raw(Vec3::new(1.0, 1.0, 1.0).make_with_context(&mut ctx));
+    let vel = Value::from_raw(Vec3::new(0.5, 0.0, -1.0).make_with_context(&mut ctx));
+    let moved = ctx.call(step, &[pos, vel]).expect("Failed to call step");
+    assert_eq!(
+        <Vec3 as FromBoltValue>::from(moved.0).unwrap(),
+        Vec3::new(2.0, 1.0, -1.0)
+    );
+
+    let placed = ctx.call(place, &[]).expect("Failed to call place");
+    assert_eq!(
+        <Vec3 as FromBoltValue>::from(placed.0).unwrap(),
+        Vec3::new(1.0, 2.0, 5.0)
+    );
+
+    let identity = Mat4::IDENTITY.make_with_context(&mut ctx);
+    assert!(<Vec3 as FromBoltValue>::from(identity).is_err());
+}
+
+#[test]
+fn test_invalid_export_names() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx.make_module();
+    let number = ctx.type_number();
+    for bad in ["bad name", "1st", "a-b", "", "let"] {
+        let key = Value::from_raw(bad.make_with_context(&mut ctx));
+        let err = ctx
+            .module_export(module, number, key, Value::from_raw(1.0.make()))
+            .expect_err("Invalid names should be rejected");
+        assert!(
+            matches!(&err, Error::Module(ModuleError::InvalidName(name)) if name == bad),
+            "unexpected error: {err}"
+        );
+    }
+
+    let key = Value::from_raw("answer_2".make_with_context(&mut ctx));
+    ctx.module_export(module, number, key, Value::from_raw(42.0.make()))
+        .expect("Valid names should be exported");
+
+    let any = ctx.type_any();
+    let name = Value::from_raw("my type".make_with_context(&mut ctx));
+    assert!(matches!(
+        ctx.register_type(name, any),
+        Err(Error::Module(ModuleError::InvalidName(_)))
+    ));
+
+    let mut data = std::collections::HashMap::new();
+    data.insert("not ok", 1.0);
+    assert!(matches!(
+        ctx.register_data_module("data", &data),
+        Err(Error::Module(ModuleError::InvalidName(_)))
+    ));
+}
+
+#[test]
+fn test_record_and_replay() {
+    use bolt_rs::replay::{Event, RecordedValue, Recording};
+
+    let setup = |ctx: &mut Context| {
+        ctx.open_all_std();
+        let module = ctx
+            .compile_module(
+                "export fn add(a: number, b: number): number { return a + b }",
+                "calc",
+            )
+            .expect("Failed to compile module");
+        ctx.execute_module(module)
+            .expect("Failed to execute module");
+        let add = export(ctx, module, "add");
+        add
+    };
+
+    let mut ctx = Context::new();
+    let add = setup(&mut ctx);
+    ctx.start_recording();
+    ctx.run("import calc\nlet x: number = calc.add(1, 2)")
+        .expect("Failed to run script");
+    let three = ctx
+        .call(
+            add,
+            &[Value::from_raw(1.0.make()), Value::from_raw(2.0.make())],
+        )
+        .expect("Failed to call add");
+    assert_eq!(three.as_number(), Some(3.0));
+    assert!(ctx.run("let y: number = \"nope\"").is_err());
+    let recording = ctx.stop_recording().expect("Recording was active");
+    assert!(!ctx.is_recording());
+
+    assert_eq!(recording.events.len(), 3);
+    assert!(matches!(
+        &recording.events[1],
+        Event::Call { args, outcome: Ok(RecordedValue::Number(n)), .. }
+            if *n == 3.0 && args == &[RecordedValue::Number(1.0), RecordedValue::Number(2.0)]
+    ));
+    assert!(recording.events[2].outcome().is_err());
+
+    let mut file = Vec::new();
+    recording
+        .write_to(&mut file)
+        .expect("Failed to write recording");
+    let loaded = Recording::read_from(file.as_slice()).expect("Failed to read recording");
+    assert_eq!(loaded, recording);
+
+    // A string length that overflows is rejected rather than panicking
+    let corrupt = format!("bolt-replay 1\nrun 0000000000000000 {}:x", usize::MAX);
+    assert!(matches!(
+        Recording::read_from(corrupt.as_bytes()),
+        Err(Error::Replay { .. })
+    ));
+
+    let mut fresh = Context::new();
+    let fresh_add = setup(&mut fresh);
+    let divergences = loaded.replay(&mut fresh, |_, _| Some(fresh_add));
+    assert!(
+        divergences.is_empty(),
+        "unexpected divergences: {divergences:?}"
+    );
+
+    let mut fresh = Context::new();
+    setup(&mut fresh);
+    let unresolved = loaded.replay(&mut fresh, |_, _| None);
+    assert_eq!(unresolved.len(), 1);
+    assert_eq!(unresolved[0].index, 1);
+}
+
+#[test]
+fn test_typed_events() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let module = ctx
+        .compile_module(
+            r#"
+            let total = [0]
+            export fn on_damage(source: string, amount: number) {
+                if source == "fire" { total[0] = total[0] + amount * 2 }
+                else { total[0] = total[0] + amount }
+            }
+            export fn on_other(flag: bool) {}
+            export fn total_damage(): number { return total[0] }
+            "#,
+            "events_test",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (on_damage, on_other, total) = (
+        export(&mut ctx, module, "on_damage"),
+        export(&mut ctx, module, "on_other"),
+        export(&mut ctx, module, "total_damage"),
+    );
+
+    assert!(
+        ctx.events()
+            .subscribe_typed::<(String, f64)>("damage", on_other)
+            .is_err()
+    );
+    let sub = ctx
+        .events()
+        .subscribe_typed::<(String, f64)>("damage", on_damage)
+        .expect("Signature should match");
+    ctx.events()
+        .subscribe_typed::<(String, f64)>("damage", on_damage)
+        .expect("Signature should match");
+    assert_eq!(ctx.events().handler_count("damage"), 2);
+
+    let called = ctx
+        .events()
+        .emit("damage", ("fire".to_string(), 5.0))
+        .expect("Emit failed");
+    assert_eq!(called, 2);
+    assert!(ctx.events().emit("damage", (1.0,)).is_err());
+    assert_eq!(ctx.events().emit("nothing", (1.0,)).unwrap(), 0);
+
+    assert!(ctx.events().unsubscribe(sub));
+    assert!(!ctx.events().unsubscribe(sub));
+    ctx.events()
+        .emit("damage", ("ice".to_string(), 1.0))
+        .expect("Emit failed");
+
+    let total = ctx.call(total, &[]).expect("Failed to read total");
+    assert_eq!(total.as_number(), Some(21.0));
+}
+
+#[test]
+fn test_unsubscribe_during_emit() {
+    thread_local! {
+        static VICTIM: std::cell::Cell<Option<Subscription>> = const { std::cell::Cell::new(None) };
+    }
+    extern "C" fn drop_victim(ctx: *mut sys::bt_Context, _thr: *mut sys::bt_Thread) {
+        let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+        if let Some(victim) = VICTIM.take() {
+            ctx.events().unsubscribe(victim);
+        }
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "drop_victim", Some(drop_victim), null, &[])
+        .expect("Failed to export native");
+    let name = "victims".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+
+    let module = ctx
+        .compile_module(
+            r#"
+            import drop_victim from victims
+            let hits = [0]
+            export fn first(amount: number) { drop_victim() }
+            export fn victim(amount: number) { hits[0] = hits[0] + 1 }
+            export fn hits_so_far(): number { return hits[0] }
+            "#,
+            "emitting",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (first, victim, hits) = (
+        export(&mut ctx, module, "first"),
+        export(&mut ctx, module, "victim"),
+        export(&mut ctx, module, "hits_so_far"),
+    );
+
+    ctx.events()
+        .subscribe_typed::<(f64,)>("tick", first)
+        .expect("Signature should match");
+    let sub = ctx
+        .events()
+        .subscribe_typed::<(f64,)>("tick", victim)
+        .expect("Signature should match");
+    VICTIM.set(Some(sub));
+
+    // Collections during dispatch mustn't free the handler that was just unsubscribed
+    ctx.gc_set_next_cycle(0);
+    let called = ctx.events().emit("tick", (1.0,)).expect("Emit failed");
+    assert_eq!(called, 1);
+    assert_eq!(ctx.events().handler_count("tick"), 1);
+    let hits = ctx.call(hits, &[]).expect("Failed to read hits");
+    assert_eq!(hits.as_number(), Some(0.0));
+}
+
+#[test]
+fn test_typed_userdata() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Inventory {
+        items: Vec<String>,
+        dropped: Rc<Cell<bool>>,
+    }
+
+    impl BoltUserdata for Inventory {
+        const NAME: &'static str = "Inventory";
+    }
+
+    impl Drop for Inventory {
+        fn drop(&mut self) {
+            self.dropped.set(true);
+        }
+    }
+
+    struct Other;
+
+    impl BoltUserdata for Other {
+        const NAME: &'static str = "Other";
+    }
+
+    let dropped = Rc::new(Cell::new(false));
+    {
+        let mut ctx = Context::new();
+        ctx.open_all_std();
+
+        let inventory = ctx
+            .create_userdata(Inventory {
+                items: vec!["sword".to_owned()],
+                dropped: dropped.clone(),
+            })
+            .expect("Failed to create userdata");
+        ctx.push_root(inventory.as_object());
+
+        assert!(inventory.is::<Inventory>());
+        assert!(matches!(
+            inventory.borrow::<Other>(),
+            Err(Error::UserdataType { expected: "Other" })
+        ));
+        inventory
+            .borrow_mut::<Inventory>()
+            .expect("Userdata should hold an Inventory")
+            .items
+            .push("shield".to_owned());
+        assert_eq!(
+            inventory
+                .borrow::<Inventory>()
+                .map(|inv| inv.items.len())
+                .ok(),
+            Some(2)
+        );
+
+        // Handles are Copy, so a second handle to the same object shares its borrow state
+        let alias = inventory;
+        {
+            let _writer = inventory.borrow_mut::<Inventory>().unwrap();
+            assert!(matches!(
+                alias.borrow::<Inventory>(),
+                Err(Error::AlreadyBorrowed { ty: "Inventory" })
+            ));
+        }
+        let reader = inventory.borrow::<Inventory>().unwrap();
+        assert!(alias.borrow::<Inventory>().is_ok());
+        assert!(alias.borrow_mut::<Inventory>().is_err());
+        drop(reader);
+
+        let ty = ctx.register_userdata::<Inventory>().unwrap();
+        assert_eq!(
+            ctx.register_userdata::<Inventory>().unwrap().as_ptr(),
+            ty.as_ptr()
+        );
+
+        ctx.pop_root();
+        assert!(!dropped.get());
+    }
+    assert!(dropped.get(), "Closing the context should drop the value");
+}
+
+#[test]
+fn test_shared_prelude() {
+    use bolt_rs::replay::RecordedValue;
+
+    let prelude = Prelude::builder()
+        .module("game", |module| {
+            module
+                .value("max_players", RecordedValue::Number(16.0))
+                .value("title", RecordedValue::String("Arena".to_owned()))
+        })
+        .script(
+            "util",
+            "export fn double(x: number): number { return x * 2 }",
+        )
+        .build()
+        .expect("Failed to build prelude");
+    assert_eq!(prelude.module_names().collect::<Vec<_>>(), ["game", "util"]);
+
+    let mut pool: Vec<Context> = (0..8)
+        .map(|_| {
+            let mut ctx = Context::builder().prelude(&prelude).build();
+            ctx.open_all_std();
+            ctx
+        })
+        .collect();
+
+    let loaded = |ctx: &mut Context, name: &str| {
+        let name = Value::from_raw(name.make_with_context(ctx));
+        ctx.find_module(name, true).is_some()
+    };
+    assert!(!loaded(&mut pool[0], "game"));
+
+    pool[0]
+        .run(
+            r#"
+            import game
+            import util
+            let players: number = util.double(game.max_players)
+            let title: string = game.title
+            "#,
+        )
+        .expect("Prelude modules should be importable");
+    assert!(loaded(&mut pool[0], "game"));
+    assert!(!loaded(&mut pool[1], "game"));
+    assert!(pool[1].get_module("util").is_ok());
+
+    assert!(matches!(
+        Prelude::builder().script("not valid", "").build(),
+        Err(Error::Module(ModuleError::InvalidName(_)))
+    ));
+
+    // A module that fails to build fails the import with the reason
+    let broken = Prelude::builder()
+        .script("broken", "export let x: number = \"nope\"")
+        .build()
+        .expect("Failed to build prelude");
+    let mut ctx = Context::builder().prelude(&broken).build();
+    ctx.open_all_std();
+    match ctx.run("import broken") {
+        Err(Error::Compile(diagnostics)) => {
+            assert!(diagnostics.iter().any(|d| d.module == "broken"))
+        }
+        other => panic!("expected the prelude module's compile error, got {other:?}"),
+    }
+    ctx.run("let fine = 1")
+        .expect("The failure isn't reported twice");
+}
+
+#[test]
+fn test_bolt_methods() {
+    struct Sprite {
+        x: f64,
+        y: f64,
+    }
+
+    impl BoltUserdata for Sprite {
+        const NAME: &'static str = "Sprite";
+    }
+
+    #[bolt_methods]
+    impl Sprite {
+        fn move_to(&mut self, x: f64, y: f64) {
+            self.x = x;
+            self.y = y;
+        }
+
+        #[bolt(rename = "distance")]
+        fn distance_from_origin(&self) -> f64 {
+            (self.x * self.x + self.y * self.y).sqrt()
+        }
+
+        #[bolt(skip)]
+        #[allow(dead_code)]
+        fn internal(&self) {}
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.register_methods::<Sprite>()
+        .expect("Failed to register methods");
+    ctx.register_methods::<Sprite>()
+        .expect("Registering twice should be a no-op");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            export fn go(s: Sprite): number {
+                s.move_to(3, 4)
+                return s.distance()
+            }
+            "#,
+            "sprites",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let go = export(&mut ctx, module, "go");
+
+    let sprite = ctx
+        .create_userdata(Sprite { x: 0.0, y: 0.0 })
+        .expect("Failed to create userdata");
+    ctx.push_root(sprite.as_object());
+    let distance = ctx
+        .call(go, &[Value::from_raw(sprite.make())])
+        .expect("Failed to call go");
+    assert_eq!(distance.as_number(), Some(5.0));
+    assert_eq!(sprite.borrow::<Sprite>().unwrap().y, 4.0);
+
+    // A method called while the host holds a conflicting borrow fails instead of aliasing
+    let held = sprite.borrow::<Sprite>().unwrap();
+    assert!(ctx.call(go, &[Value::from_raw(sprite.make())]).is_err());
+    drop(held);
+    ctx.pop_root();
+}
+
+#[test]
+fn test_run_with_bindings() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let params = ctx.make_table(1);
+    params.set(&mut ctx, "scale", 2.0);
+    let input = Value::from_raw(21.0.make());
+    let params = Value::from_raw(params.make());
+    ctx.push_root(params.as_object().unwrap());
+
+    ctx.run_with(
+        "let scaled: number = input * 2",
+        &[("input", input), ("params", params)],
+    )
+    .expect("Bindings should be visible to the chunk");
+    assert!(
+        ctx.run_with("let s: string = input", &[("input", input)])
+            .is_err()
+    );
+
+    // Bindings only exist for the chunk they were passed to
+    assert!(ctx.run("let leaked = input").is_err());
+    ctx.run_with("let again = input", &[("input", input)])
+        .expect("A binding can be reused by a later chunk");
+
+    assert!(matches!(
+        ctx.run_with("", &[("input", input), ("input", input)]),
+        Err(Error::Module(ModuleError::DuplicateExport(name))) if name == "input"
+    ));
+    assert!(matches!(
+        ctx.run_with("", &[("not valid", input)]),
+        Err(Error::Module(ModuleError::InvalidName(_)))
+    ));
+
+    let limit = Value::from_raw("limit".make_with_context(&mut ctx));
+    let number = ctx.type_number();
+    ctx.register_prelude(limit, number, Value::from_raw(1.0.make()));
+    assert!(ctx.run_with("", &[("limit", input)]).is_err());
+    ctx.pop_root();
+}
+
+#[test]
+fn test_try_new() {
+    let mut ctx = Context::try_new().expect("Context should open");
+    ctx.open_all_std();
+    ctx.run("let x = 1").expect("Failed to run");
+
+    let built = Context::builder().lazy_std(true).try_build();
+    assert!(built.is_ok());
+
+    let err = Error::Open(OpenError::Rejected);
+    assert!(err.to_string().starts_with("failed to open context"));
+}
+
+#[test]
+fn test_derive_userdata_fields() {
+    #[derive(BoltUserdata)]
+    #[bolt(name = "Player")]
+    struct Player {
+        name: String,
+        #[bolt(rename = "hp")]
+        health: f64,
+        #[bolt(readonly)]
+        level: f64,
+        alive: bool,
+        #[bolt(skip)]
+        _secret: u64,
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.register_userdata::<Player>()
+        .expect("Failed to register userdata");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            export fn hurt(p: Player, amount: number): string {
+                p.hp = p.hp - amount
+                p.alive = p.hp > 0
+                return p.name
+            }
+            export fn level(p: Player): number { return p.level }
+            "#,
+            "players",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (hurt, level) = (
+        export(&mut ctx, module, "hurt"),
+        export(&mut ctx, module, "level"),
+    );
+
+    let player = ctx
+        .create_userdata(Player {
+            name: "ada".to_owned(),
+            health: 10.0,
+            level: 3.0,
+            alive: true,
+            _secret: 7,
+        })
+        .expect("Failed to create userdata");
+    ctx.push_root(player.as_object());
+    let arg = Value::from_raw(player.make());
+
+    let name = ctx
+        .call(hurt, &[arg, Value::from_raw(25.0.make())])
+        .expect("Failed to call hurt");
+    assert_eq!(<String as FromBoltValue>::from(name.0).unwrap(), "ada");
+    {
+        let player = player.borrow::<Player>().unwrap();
+        assert_eq!(player.health, -15.0);
+        assert!(!player.alive);
+    }
+    let level = ctx.call(level, &[arg]).expect("Failed to call level");
+    assert_eq!(level.as_number(), Some(3.0));
+
+    let held = player.borrow_mut::<Player>().unwrap();
+    assert!(ctx.call(hurt, &[arg, Value::from_raw(1.0.make())]).is_err());
+    drop(held);
+    ctx.pop_root();
+}
+
+#[test]
+fn test_userdata_operators() {
+    #[derive(BoltUserdata, Debug, PartialEq)]
+    struct Vec2 {
+        x: f64,
+        y: f64,
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.type_builder::<Vec2>()
+        .and_then(|ty| {
+            ty.meta_add(|a, b| Vec2 {
+                x: a.x + b.x,
+                y: a.y + b.y,
+            })
+        })
+        .and_then(|ty| ty.meta_eq(|a, b| a == b))
+        .and_then(|ty| {
+            ty.meta_index(|v, idx: f64| match idx as usize {
+                0 => Ok(v.x),
+                1 => Ok(v.y),
+                _ => Err(Error::bolt("Vec2 index out of range")),
+            })
+        })
+        .expect("Failed to add operators");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            export fn add(a: Vec2, b: Vec2): Vec2 { return a + b }
+            export fn same(a: Vec2, b: Vec2): bool { return a == b }
+            export fn at(a: Vec2, i: number): number { return a[i] }
+            "#,
+            "vectors",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (add, same, at) = (
+        export(&mut ctx, module, "add"),
+        export(&mut ctx, module, "same"),
+        export(&mut ctx, module, "at"),
+    );
+
+    let a = ctx.create_userdata(Vec2 { x: 1.0, y: 2.0 }).unwrap();
+    ctx.push_root(a.as_object());
+    let b = ctx.create_userdata(Vec2 { x: 3.0, y: 4.0 }).unwrap();
+    ctx.push_root(b.as_object());
+    let (a, b) = (Value::from_raw(a.make()), Value::from_raw(b.make()));
+
+    let sum = ctx.call(add, &[a, b]).expect("Failed to call add");
+    let sum = <types::Userdata as FromBoltValue>::from(sum.0).unwrap();
+    assert_eq!(*sum.borrow::<Vec2>().unwrap(), Vec2 { x: 4.0, y: 6.0 });
+
+    let equal = |ctx: &mut Context, lhs, rhs| {
+        let result = ctx.call(same, &[lhs, rhs]).expect("Failed to call same");
+        <bool as FromBoltValue>::from(result.0).unwrap()
+    };
+    assert!(equal(&mut ctx, a, a));
+    assert!(!equal(&mut ctx, a, b));
+
+    let y = ctx
+        .call(at, &[b, Value::from_raw(1.0.make())])
+        .expect("Failed to call at");
+    assert_eq!(y.as_number(), Some(4.0));
+    assert!(ctx.call(at, &[b, Value::from_raw(2.0.make())]).is_err());
+
+    ctx.pop_root();
+    ctx.pop_root();
+}
+
+#[test]
+fn test_tuple_returns() {
+    unsafe extern "C" fn divmod(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut ctx = unsafe { ContextRef::from_raw(ctx) };
+        let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+        let (num, den) = thread.args::<(f64, f64)>().unwrap();
+        thread.return_vals(&mut ctx, ((num / den).floor(), num % den));
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    let ret = <Tuple<(f64, f64)> as ScalarTypeSignature>::make_type(&mut ctx);
+    let number = ctx.type_number();
+    ModuleBuilder::new("calc")
+        .native("divmod", Some(divmod), ret, &[number, number])
+        .finish_validated(&mut ctx)
+        .expect("Module should validate");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            import calc
+            export fn split(x: number): [number] { return calc.divmod(x, 3) }
+            "#,
+            "split",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let split = export(&mut ctx, module, "split");
+
+    let result = ctx
+        .call(split, &[Value::from_raw(7.0.make())])
+        .expect("Failed to call split");
+    assert_eq!(
+        <Tuple<(f64, f64)> as FromBoltValue>::from(result.0).unwrap(),
+        Tuple((2.0, 1.0))
+    );
+    assert!(matches!(
+        <Tuple<(f64, f64, f64)> as FromBoltValue>::from(result.0),
+        Err(ArgError::LengthMismatch {
+            expected: 3,
+            actual: 2
+        })
+    ));
+
+    let mixed = Tuple((1.0, "one".to_owned())).make_with_context(&mut ctx);
+    assert!(matches!(
+        <Tuple<(f64, f64)> as FromBoltValue>::from(mixed),
+        Err(ArgError::BadItem { idx: 1, .. })
+    ));
+}
+
+#[test]
+fn test_userdata_properties() {
+    struct Playlist {
+        songs: Vec<String>,
+        volume: f64,
+    }
+
+    impl BoltUserdata for Playlist {
+        const NAME: &'static str = "Playlist";
+
+        fn register_fields(ctx: &mut Context, ty: types::Type) -> Result<(), Error> {
+            ctx.userdata_add_property(ty, "length", |p: &Playlist| p.songs.len() as f64)?;
+            ctx.userdata_add_property(ty, "is_empty", |p: &Playlist| p.songs.is_empty())?;
+            ctx.userdata_add_property_mut(
+                ty,
+                "volume",
+                |p: &Playlist| p.volume,
+                |p: &mut Playlist, volume: f64| p.volume = volume.clamp(0.0, 1.0),
+            )
+        }
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.register_userdata::<Playlist>()
+        .expect("Failed to register userdata");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            export fn describe(p: Playlist): number {
+                if p.is_empty { return 0 }
+                p.volume = p.volume * 4
+                return p.length
+            }
+            export fn truncate(p: Playlist) { p.length = 0 }
+            "#,
+            "playlists",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let (describe, truncate) = (
+        export(&mut ctx, module, "describe"),
+        export(&mut ctx, module, "truncate"),
+    );
+
+    let playlist = ctx
+        .create_userdata(Playlist {
+            songs: vec!["intro".to_owned(), "outro".to_owned()],
+            volume: 0.5,
+        })
+        .expect("Failed to create userdata");
+    ctx.push_root(playlist.as_object());
+    let arg = Value::from_raw(playlist.make());
+
+    let length = ctx.call(describe, &[arg]).expect("Failed to call describe");
+    assert_eq!(length.as_number(), Some(2.0));
+    assert_eq!(playlist.borrow::<Playlist>().unwrap().volume, 1.0);
+
+    assert!(ctx.call(truncate, &[arg]).is_err());
+    assert_eq!(playlist.borrow::<Playlist>().unwrap().songs.len(), 2);
+
+    // Properties go through the same borrow checks as fields
+    let held = playlist.borrow_mut::<Playlist>().unwrap();
+    assert!(ctx.call(describe, &[arg]).is_err());
+    drop(held);
+    ctx.pop_root();
+}
+
+#[test]
+fn test_trait_object_userdata() {
+    trait EventSink {
+        fn send(&mut self, event: &str);
+        fn count(&self) -> f64;
+    }
+
+    struct Collector(Vec<String>);
+
+    impl EventSink for Collector {
+        fn send(&mut self, event: &str) {
+            self.0.push(event.to_owned());
+        }
+
+        fn count(&self) -> f64 {
+            self.0.len() as f64
+        }
+    }
+
+    impl BoltUserdata for Box<dyn EventSink> {
+        const NAME: &'static str = "EventSink";
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.type_builder::<Box<dyn EventSink>>()
+        .and_then(|ty| ty.method_mut("send", |sink, (event,): (String,)| sink.send(&event)))
+        .and_then(|ty| ty.method("count", |sink, (): ()| sink.count()))
+        .expect("Failed to add methods");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            export fn fire(sink: EventSink): number {
+                sink.send("start")
+                sink.send("stop")
+                return sink.count()
+            }
+            "#,
+            "sinks",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let fire = export(&mut ctx, module, "fire");
+
+    let sink = ctx
+        .create_userdata(Box::new(Collector(Vec::new())) as Box<dyn EventSink>)
+        .expect("Failed to create userdata");
+    ctx.push_root(sink.as_object());
+
+    let count = ctx
+        .call(fire, &[Value::from_raw(sink.make())])
+        .expect("Failed to call fire");
+    assert_eq!(count.as_number(), Some(2.0));
+    assert_eq!(sink.borrow::<Box<dyn EventSink>>().unwrap().count(), 2.0);
+    ctx.pop_root();
+}
+
+#[test]
+fn test_custom_io() {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Default, Clone)]
+    struct Assets(std::rc::Rc<RefCell<HashMap<PathBuf, String>>>);
+
+    /// Appends whatever is written to one entry of the assets
+    struct Entry(Assets, PathBuf);
+
+    impl std::io::Read for Entry {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl std::io::Write for Entry {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let text = std::str::from_utf8(buf).map_err(std::io::Error::other)?;
+            let mut assets = self.0.0.borrow_mut();
+            assets.entry(self.1.clone()).or_default().push_str(text);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl BoltIo for Assets {
+        fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn BoltFile>> {
+            if mode == OpenMode::Read {
+                let contents = self.0.borrow().get(path).cloned();
+                let contents = contents.ok_or(std::io::ErrorKind::NotFound)?;
+                return Ok(Box::new(std::io::Cursor::new(contents.into_bytes())));
+            }
+            if !path.starts_with("saves") {
+                return Err(std::io::ErrorKind::PermissionDenied.into());
+            }
+            if mode == OpenMode::Write {
+                self.0.borrow_mut().insert(path.to_owned(), String::new());
+            }
+            Ok(Box::new(Entry(self.clone(), path.to_owned())))
+        }
+    }
+
+    let assets = Assets::default();
+    assets
+        .0
+        .borrow_mut()
+        .insert("levels/1.txt".into(), "forest".to_owned());
+
+    let mut ctx = Context::new();
+    ctx.open_std(StdLib::ALL_SAFE);
+    ctx.set_io(assets.clone()).expect("Failed to set io");
+    ctx.run(
+        "import files\nif files.exists(\"levels/1.txt\") { files.write(\"saves/last.txt\", files.read(\"levels/1.txt\")) }",
+    )
+    .expect("Failed to run");
+    assert_eq!(
+        assets
+            .0
+            .borrow()
+            .get(Path::new("saves/last.txt"))
+            .map(String::as_str),
+        Some("forest")
+    );
+
+    ctx.run(
+        "import open from files\nlet log = open(\"saves/log.txt\", \"a\")\nlog.write(\"entered \")\nlog.write(open(\"levels/1.txt\", \"r\").read())\nlog.close()",
+    )
+    .expect("Failed to run");
+    assert_eq!(
+        assets
+            .0
+            .borrow()
+            .get(Path::new("saves/log.txt"))
+            .map(String::as_str),
+        Some("entered forest")
+    );
+
+    let err = ctx
+        .run("import write from files\nwrite(\"config.txt\", \"hacked\")")
+        .expect_err("writes outside saves are refused");
+    assert!(err.to_string().contains("config.txt"));
+    assert!(
+        ctx.run("import read from files\nread(\"levels/2.txt\")")
+            .is_err()
+    );
+    assert!(
+        ctx.run("import open from files\nlet log = open(\"saves/log.txt\", \"a\")\nlog.close()\nlog.write(\"late\")")
+            .is_err()
+    );
+
+    // Opening the standard library keeps both modules
+    ctx.open_all_std();
+    ctx.run("import files\nimport io\nfiles.read(\"levels/1.txt\")")
+        .expect("Failed to run");
+}
+
+#[test]
+fn test_restrict_filesystem() {
+    let dir = unique_temp_dir("bolt_sandbox");
+    let (allowed, outside) = (dir.join("allowed"), dir.join("outside"));
+    std::fs::create_dir_all(&allowed).expect("Failed to create allowed dir");
+    std::fs::create_dir_all(&outside).expect("Failed to create outside dir");
+    std::fs::write(allowed.join("inside.bolt"), "export let x = 1").expect("write inside");
+    std::fs::write(outside.join("secret.bolt"), "export let y = 2").expect("write secret");
+    #[cfg(unix)]
+    {
+        let link = allowed.join("escape.bolt");
+        std::fs::remove_file(&link).ok();
+        std::os::unix::fs::symlink(outside.join("secret.bolt"), &link).expect("symlink");
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_std(StdLib::ALL);
+    for spec in [
+        allowed.join("%s.bolt"),
+        allowed.join("..").join("outside").join("%s.bolt"),
+    ] {
+        ctx.append_module_path_os(spec.as_os_str())
+            .expect("Failed to append module path");
+    }
+    ctx.restrict_filesystem(std::slice::from_ref(&allowed))
+        .expect("Failed to restrict");
+    assert_eq!(
+        ctx.filesystem_roots(),
+        Some(vec![std::fs::canonicalize(&allowed).unwrap()])
+    );
+
+    ctx.run("import inside").expect("modules inside load");
+    assert!(ctx.run("import secret").is_err());
+    #[cfg(unix)]
+    assert!(ctx.run("import escape").is_err());
+
+    let target = allowed.join("out.txt");
+    let path = Value::from_raw(target.to_str().unwrap().make_with_context(&mut ctx));
+    ctx.run_with(
+        "import files\nfiles.write(path, \"saved\")",
+        &[("path", path)],
+    )
+    .expect("writes inside are allowed");
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "saved");
+    let secret = outside.join("secret.bolt");
+    let path = Value::from_raw(secret.to_str().unwrap().make_with_context(&mut ctx));
+    let err = ctx
+        .run_with("import read from files\nread(path)", &[("path", path)])
+        .expect_err("reads outside are refused");
+    assert!(err.to_string().contains("outside the directories"));
+
+    // The std io opened before restricting is gone, and opening the std again leaves it out
+    assert!(ctx.run("import io").is_err());
+    ctx.open_all_std();
+    assert!(ctx.run("import io").is_err());
+    ctx.run("import math\nimport files")
+        .expect("the rest of the std opens");
+
+    assert!(
+        Context::new()
+            .restrict_filesystem(&[dir.join("missing")])
+            .is_err()
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_open_std() {
+    assert!(StdLib::ALL.contains(StdLib::CORE | StdLib::IO | StdLib::REGEX));
+    assert!(!StdLib::ALL_SAFE.contains(StdLib::IO));
+    assert_eq!(
+        (StdLib::MATH | StdLib::CORE).names().collect::<Vec<_>>(),
+        ["core", "math"]
+    );
+
+    let mut ctx = Context::new();
+    ctx.open_std(StdLib::CORE | StdLib::MATH);
+    ctx.run("import print from core\nimport sqrt from math\nprint(sqrt(4))")
+        .expect("core and math are open");
+    assert!(ctx.run("import regex").is_err());
+
+    let mut ctx = Context::builder().lazy_std(true).build();
+    ctx.open_std(StdLib::ALL_SAFE);
+    ctx.run("import sqrt from math\nimport regex")
+        .expect("deferred modules open on import");
+    assert!(ctx.run("import io").is_err());
+}
+
+#[test]
+fn test_scoped_functions() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let mut total = 0.0;
+    let mut calls = Vec::new();
+    let add = ctx
+        .scope(|scope| {
+            let add = scope.create_function(|(n,): (f64,)| {
+                total += n;
+                total
+            })?;
+            let log = scope.create_function(|(msg,): (String,)| calls.push(msg))?;
+            scope.call(add, &[Value::from_raw(2.0.make())])?;
+            let returned = scope.call(add, &[Value::from_raw(3.0.make())])?;
+            assert_eq!(<f64 as FromBoltValue>::from(returned.0).unwrap(), 5.0);
+            let msg = scope.make_value("hi");
+            scope.call(log, &[msg])?;
+            Ok::<_, Error>(add)
+        })
+        .expect("Failed to run scope");
+    assert_eq!(total, 5.0);
+    assert_eq!(calls, ["hi"]);
+
+    let err = ctx
+        .call(add, &[Value::from_raw(1.0.make())])
+        .expect_err("the scope has ended");
+    assert!(err.to_string().contains("after its scope ended"));
+}
+
+#[test]
+fn test_thread_frames() {
+    thread_local! {
+        static SEEN: std::cell::RefCell<Vec<StackFrame>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    extern "C" fn probe(ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let ctx = unsafe { ContextRef::from_raw(ctx) };
+        let thread = unsafe { Thread::from_raw_unchecked(thr) };
+        SEEN.with(|seen| *seen.borrow_mut() = thread.frames(&ctx));
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    // Natives are only known by name while they're tracked
+    ctx.set_native_stats(true);
+    let module = ctx.make_module();
+    let null = ctx.type_null();
+    ctx.module_export_native(module, "probe", Some(probe), null, &[])
+        .expect("Failed to export native");
+    let name = "probes".make_with_context(&mut ctx);
+    ctx.register_module(Value::from_raw(name), module);
+
+    let script = ctx
+        .compile_module(
+            "import probe from probes\nexport fn outer() { probe() }\nouter()",
+            "stacked",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(script)
+        .expect("Failed to execute module");
+
+    let frames = SEEN.with(|seen| seen.take());
+    let functions: Vec<&str> = frames.iter().map(|frame| frame.function.as_str()).collect();
+    assert_eq!(functions, ["probe", "outer", "<top level>"]);
+    assert_eq!(frames[1].module.as_deref(), Some("stacked"));
+    assert!(frames.iter().all(|frame| frame.line.is_none()));
+
+    let idle = ctx.make_thread();
+    assert!(idle.frames(&ctx).is_empty());
+    ctx.destroy_thread(idle);
+}
+
+#[test]
+fn test_channel() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+
+    let (sender, events) = std::sync::mpsc::channel::<String>();
+    ctx.register_channel("events", sender, Backpressure::Block)
+        .expect("Failed to register channel");
+    ctx.run("import send from events\nsend(\"loaded\")\nsend(\"started\")")
+        .expect("Failed to run");
+    let consumer = std::thread::spawn(move || events.try_iter().collect::<Vec<_>>());
+
+    let (sender, bounded) = std::sync::mpsc::sync_channel::<f64>(1);
+    ctx.register_channel("dropping", sender.clone(), Backpressure::Drop)
+        .expect("Failed to register channel");
+    ctx.register_channel("strict", sender, Backpressure::Error)
+        .expect("Failed to register channel");
+    ctx.run("import send from dropping\nsend(1)\nsend(2)")
+        .expect("Failed to run");
+    let err = ctx
+        .run("import send from strict\nsend(3)")
+        .expect_err("a full channel fails the script");
+    assert!(err.to_string().contains("channel is full"));
+    assert_eq!(bounded.try_iter().collect::<Vec<_>>(), vec![1.0]);
+
+    assert_eq!(consumer.join().unwrap(), vec!["loaded", "started"]);
+}
+
+#[test]
+fn test_parallel_run_all() {
+    use replay::RecordedValue;
+
+    let sources: Vec<String> = (0..20)
+        .map(|n| format!("import scale from tenant\nexport let result = {n} * scale"))
+        .chain([
+            "export let result = \"done\"".to_owned(),
+            "nope(".to_owned(),
+            String::new(),
+        ])
+        .collect();
+    let outcomes = parallel::run_all_with_workers(4, &sources, |ctx| {
+        ctx.open_all_std();
+        let data = std::collections::HashMap::from([("scale", 2.0)]);
+        ctx.register_data_module("tenant", &data).map(|_| ())
+    });
+
+    assert_eq!(outcomes.len(), sources.len());
+    for (n, outcome) in outcomes[..20].iter().enumerate() {
+        assert!(matches!(outcome, Ok(RecordedValue::Number(x)) if *x == n as f64 * 2.0));
+    }
+    assert!(matches!(&outcomes[20], Ok(RecordedValue::String(s)) if s == "done"));
+    assert!(matches!(
+        outcomes[21],
+        Err(Error::Parse(_) | Error::Compile(_))
+    ));
+    assert!(matches!(outcomes[22], Ok(RecordedValue::Null)));
+
+    // A failed init is reported as the error it returned, for every script it failed for
+    let failed = parallel::run_all_with_workers(1, &["export let result = 1", ""], |_| {
+        Err(Error::bolt("no tenant"))
+    });
+    assert!(
+        failed
+            .iter()
+            .all(|outcome| matches!(outcome, Err(Error::BoltError { msg }) if msg == "no tenant"))
+    );
+
+    // Interrupted scripts are stopped without holding up the rest
+    let settings = parallel::Settings {
+        workers: 2,
+        interrupt_after: Some(std::time::Duration::from_millis(50)),
+    };
+    let timed = parallel::run_all_with(
+        settings,
+        &[
+            "import sqrt from math\nwhile true { sqrt(4) }",
+            "export let result = 7",
+        ],
+        |ctx| {
+            ctx.open_all_std();
+            Ok(())
+        },
+    );
+    assert!(matches!(timed[0], Err(Error::Interrupted)));
+    assert!(matches!(timed[1], Ok(RecordedValue::Number(n)) if n == 7.0));
+}
+
+#[test]
+fn test_transfer_between_contexts() {
+    let mut src = Context::new();
+    src.open_all_std();
+    let mut dst = Context::new();
+
+    let module = src
+        .compile_module(
+            r#"
+            let shared = [1, "two"]
+            export let result = { name: "tenant", items: shared, again: shared }
+            export fn handler() {}
+            "#,
+            "results",
+        )
+        .expect("Failed to compile module");
+    src.execute_module(module)
+        .expect("Failed to execute module");
+    let (result, handler) = (
+        export(&mut src, module, "result"),
+        export(&mut src, module, "handler"),
+    );
+
+    let copy = result
+        .transfer(&mut src, &mut dst)
+        .expect("Failed to transfer");
+    let table = <types::Table as FromBoltValue>::from(copy.0).expect("tables copy as tables");
+    let name = table.get_str("name").expect("name was copied");
+    assert_eq!(<String as FromBoltValue>::from(name.0).unwrap(), "tenant");
+    let items = table.get_str("items").expect("items were copied");
+    let again = table.get_str("again").expect("again was copied");
+    assert_eq!(items, again);
+    assert_ne!(copy, result);
+    let items = <types::Array as FromBoltValue>::from(items.0).expect("arrays copy as arrays");
+    let items: Vec<Value> = items.iter(&mut dst).collect();
+    assert_eq!(<f64 as FromBoltValue>::from(items[0].0).unwrap(), 1.0);
+    assert_eq!(<String as FromBoltValue>::from(items[1].0).unwrap(), "two");
+
+    assert!(handler.transfer(&mut src, &mut dst).is_err());
+    let replaced = handler
+        .transfer_with(&mut src, &mut dst, |_, _, _| {
+            Ok(Value::from_raw(0.0.make()))
+        })
+        .expect("Failed to transfer");
+    assert_eq!(<f64 as FromBoltValue>::from(replaced.0).unwrap(), 0.0);
+}
+
+#[test]
+fn test_context_pool() {
+    let inits = std::rc::Rc::new(std::cell::Cell::new(0));
+    let counted = inits.clone();
+    let pool = ContextPool::new(2, move |ctx| {
+        counted.set(counted.get() + 1);
+        ctx.open_all_std();
+        ctx.registry_set("tenant", Value::from_raw(7.0.make()));
+        Ok(())
+    })
+    .expect("Failed to create pool");
+    assert_eq!((inits.get(), pool.idle()), (2, 2));
+
+    let first = {
+        let mut ctx = pool.checkout().expect("Failed to check out");
+        assert_eq!(pool.idle(), 1);
+        let tenant = ctx.registry_get("tenant").expect("init ran");
+        assert_eq!(<f64 as FromBoltValue>::from(tenant.0).unwrap(), 7.0);
+        ctx.run("import print from core\nprint(1)")
+            .expect("Failed to run");
+        ctx.as_ptr()
+    };
+    assert_eq!(pool.idle(), 2);
+
+    // Per-request settings don't carry over to the next checkout
+    {
+        let mut ctx = pool.checkout().expect("Failed to check out");
+        ctx.set_native_call_budget(10);
+        ctx.set_max_call_depth(4);
+        ctx.set_memory_limit(1 << 20);
+        ctx.set_hook(HookMask::CALL, |_| {});
+        ctx.start_recording();
+        ctx.interrupt();
+    }
+    let mut ctx = pool.checkout().expect("Failed to check out");
+    assert_eq!(ctx.as_ptr(), first);
+    assert_eq!(ctx.remaining_native_calls(), None);
+    assert_eq!(ctx.max_call_depth(), None);
+    assert!(!ctx.is_recording());
+    assert!(!ctx.interrupt_handle().is_interrupted());
+    ctx.run("import print from core\nprint(2)")
+        .expect("Failed to run after recycling");
+
+    // Recycling never runs init again
+    assert_eq!(ctx.as_ptr(), first);
+    ctx.discard().expect("Failed to replace the context");
+    assert_eq!((inits.get(), pool.idle()), (3, 2));
+
+    // Checking out more than the pool holds opens extras, which are closed on return
+    let held: Vec<_> = (0..3)
+        .map(|_| pool.checkout().expect("Failed to check out"))
+        .collect();
+    assert_eq!((inits.get(), pool.idle()), (4, 0));
+    drop(held);
+    assert_eq!(pool.idle(), 2);
+
+    let failing = ContextPool::new(1, |_| Err(Error::bolt("init failed")));
+    assert!(failing.is_err());
+}
+
+#[test]
+fn test_execute_on_thread() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    let module = ctx
+        .compile_module(
+            r#"
+            let count = 0
+            export fn bump(): number {
+                count = count + 1
+                return count
+            }
+            export fn scale(n: number, by: number): number { return n * by }
+            "#,
+            "threaded",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let mut callable = |name: &str| {
+        let value = export(&mut ctx, module, name);
+        <types::Callable as FromBoltValue>::from(value.0).expect("exports are callable")
+    };
+    let (bump, scale) = (callable("bump"), callable("scale"));
+
+    let thread = ctx.make_thread();
+    for expected in 1..=3 {
+        let returned = ctx
+            .execute_on_thread(&thread, bump)
+            .expect("Failed to execute");
+        assert_eq!(
+            <f64 as FromBoltValue>::from(returned.0).unwrap(),
+            expected as f64
+        );
+    }
+    let args = [Value::from_raw(3.0.make()), Value::from_raw(4.0.make())];
+    let returned = ctx
+        .call_on_thread(&thread, scale, &args)
+        .expect("Failed to call");
+    assert_eq!(<f64 as FromBoltValue>::from(returned.0).unwrap(), 12.0);
+    ctx.destroy_thread(thread);
+
+    assert!(<types::Callable as FromBoltValue>::from(1.0.make()).is_err());
+}
+
+#[test]
+fn test_pcall() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.open_errors().expect("Failed to open errors");
+
+    let mut load_check = |source: &str, name: &str| {
+        let module = ctx
+            .compile_module(source, name)
+            .expect("Failed to compile module");
+        ctx.execute_module(module)
+            .expect("Failed to execute module");
+        export(&mut ctx, module, "check")
+    };
+    let check = load_check(
+        r#"
+        import throw from core
+        export fn check(n: number): number {
+            if n < 0 { throw("negative") }
+            return n * 2
+        }
+        "#,
+        "checks",
+    );
+    let check_zero = load_check(
+        r#"
+        import throw from errors
+        export fn check(n: number): number {
+            if n == 0 { throw({ code: "E_ZERO", message: "zero" }) }
+            return n
+        }
+        "#,
+        "zero_checks",
+    );
+
+    let err = ctx
+        .pcall(check, &[Value::from_raw((-1.0).make())])
+        .expect_err("negative input throws");
+    assert!(err.message.contains("negative"));
+    assert!(err.thrown().is_none());
+    assert!(matches!(*err.error, Error::Runtime { .. }));
+
+    let err = ctx
+        .pcall(check_zero, &[Value::from_raw(0.0.make())])
+        .expect_err("zero input throws");
+    assert_eq!(
+        err.thrown().map(|thrown| thrown.code.as_str()),
+        Some("E_ZERO")
+    );
+
+    // Failed calls leave nothing behind for the next one
+    let doubled = ctx
+        .pcall(check, &[Value::from_raw(4.0.make())])
+        .expect("positive input succeeds");
+    assert_eq!(doubled.as_number(), Some(8.0));
+    ctx.run("let x = 1 + 1").expect("Context is still usable");
+}
+
+#[test]
+fn test_thrown_value() {
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.open_errors().expect("Failed to open errors");
+
+    let source = r#"
+        import throw_value from errors
+        throw_value({ label: "Volume", max: 11 })
+    "#;
+    match ctx.run(source) {
+        Err(Error::Script(thrown)) => {
+            assert!(thrown.code.is_empty());
+            // The thrown table has to outlive collections after the run
+            ctx.gc_stress(true);
+            ctx.run("let garbage = [1, 2, 3]")
+                .expect("Failed to allocate");
+            ctx.gc_stress(false);
+            let config: SliderConfig = thrown
+                .get(&ctx)
+                .expect("thrown value is alive")
+                .expect("thrown table reads back");
+            assert_eq!(config.tooltip, "Volume");
+            assert_eq!(config.max, 11.0);
+            assert!(thrown.get::<f64>(&ctx).expect("alive").is_err());
+            assert!(matches!(
+                thrown.data,
+                bolt_rs::replay::RecordedValue::Table(_)
+            ));
+
+            // Other contexts never see it, and neither does this one after the next throw
+            assert!(thrown.value(&Context::new()).is_none());
+            let _ = ctx.run("import throw_value from errors\nthrow_value(1)");
+            assert!(thrown.value(&ctx).is_none());
+            assert!(matches!(
+                thrown.data,
+                bolt_rs::replay::RecordedValue::Table(_)
+            ));
+        }
+        other => panic!("expected a thrown value, got {other:?}"),
+    }
+
+    match ctx.run("import throw_value from errors\nthrow_value(404)") {
+        Err(err @ Error::Script(_)) => assert_eq!(err.to_string(), "404"),
+        other => panic!("expected a thrown value, got {other:?}"),
+    }
+
+    // Errors can leave the context's thread, e.g. through `anyhow`
+    fn send_sync<T: Send + Sync + 'static>() {}
+    send_sync::<Error>();
+}
+
+#[test]
+fn test_script_throw() {
+    use bolt_rs::replay::RecordedValue;
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    ctx.open_errors().expect("Failed to open errors");
+
+    let module = ctx
+        .compile_module(
+            r#"
+            import throw from errors
+            export fn login(user: string): string {
+                if user == "mallory" {
+                    throw({ code: "E_DENIED", message: "not allowed", data: user })
+                }
+                return user
+            }
+            "#,
+            "auth",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let login = export(&mut ctx, module, "login");
+
+    let user = Value::from_raw("alice".make_with_context(&mut ctx));
+    assert!(ctx.call(login, &[user]).is_ok());
+
+    let user = Value::from_raw("mallory".make_with_context(&mut ctx));
+    match ctx.call(login, &[user]) {
+        Err(Error::Script(thrown)) => {
+            assert_eq!(thrown.code, "E_DENIED");
+            assert_eq!(thrown.message, "not allowed");
+            assert_eq!(thrown.data, RecordedValue::String("mallory".to_owned()));
+            assert_eq!(thrown.to_string(), "E_DENIED: not allowed");
+        }
+        other => panic!("Expected a script throw, got {other:?}"),
+    }
+
+    let err = ctx
+        .run("import throw from errors\nthrow({ code: \"E_TOP\", message: \"top level\", data: null })")
+        .expect_err("Throwing should fail the run");
+    assert!(matches!(err, Error::Script(ScriptThrow { ref code, .. }) if code == "E_TOP"));
+}
+
+#[test]
+fn test_userdata_type_registry() {
+    struct Counter(f64);
+
+    impl BoltUserdata for Counter {
+        const NAME: &'static str = "Counter";
+    }
+
+    struct Other;
+
+    impl BoltUserdata for Other {
+        const NAME: &'static str = "Other";
+    }
+
+    unsafe extern "C" fn bump(_ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+        let Ok((counter, by)) = thread.args::<(TypedUserdata<Counter>, f64)>() else {
+            let msg = c"bump: expected a Counter";
+            unsafe { sys::bt_runtime_error(thr, msg.as_ptr(), std::ptr::null_mut()) };
+            return;
+        };
+        counter.borrow_mut().unwrap().0 += by;
+        thread.return_val(&counter);
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    assert!(ctx.userdata_type::<Counter>().is_none());
+
+    // Nothing registers `Counter` explicitly, the signature finds it through the registry
+    let counter_type = <TypedUserdata<Counter> as ScalarTypeSignature>::make_type(&mut ctx);
+    let number = ctx.type_number();
+    ModuleBuilder::new("counters")
+        .native("bump", Some(bump), counter_type, &[counter_type, number])
+        .finish_validated(&mut ctx)
+        .expect("Module should validate");
+    assert!(
+        ctx.userdata_type::<Counter>()
+            .is_some_and(|ty| ty.as_ptr() == counter_type.as_ptr())
+    );
+
+    let module = ctx
+        .compile_module(
+            "import counters\nexport fn twice(c: Counter): Counter { return counters.bump(counters.bump(c, 1), 1) }",
+            "twice",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let twice = export(&mut ctx, module, "twice");
+
+    let counter = ctx.create_typed_userdata(Counter(1.0)).unwrap();
+    ctx.push_root(counter.userdata().as_object());
+    let result = ctx
+        .call(twice, &[Value::from_raw(counter.make())])
+        .expect("Failed to call twice");
+    let result = <TypedUserdata<Counter> as FromBoltValue>::from(result.0).unwrap();
+    assert_eq!(result.borrow().unwrap().0, 3.0);
+    ctx.pop_root();
+
+    let other = ctx.create_userdata(Other).unwrap();
+    assert!(matches!(
+        <TypedUserdata<Counter> as FromBoltValue>::from(other.make()),
+        Err(ArgError::UserdataType {
+            expected: "Counter"
+        })
+    ));
+}
+
+#[test]
+fn test_version_and_features() {
+    let mut ctx = Context::new();
+
+    let version = ctx.version();
+    assert_eq!(version, BoltVersion::CURRENT);
+    assert_eq!(
+        version.to_string(),
+        format!("{}.{}.{}", version.major, version.minor, version.revision)
+    );
+    assert!(BoltVersion::new(0, 1, 0) < BoltVersion::new(0, 2, 0));
+    assert!(BoltVersion::new(0, 9, 9) < BoltVersion::new(1, 0, 0));
+
+    let info = ctx.build_info();
+    assert_eq!(info.version, version);
+    assert!(["Debug", "Release", "RelWithDebInfo", "MinSizeRel"].contains(&info.profile));
+    assert_eq!(
+        ctx.supports(Feature::DebugAssertions),
+        info.debug_assertions
+    );
+
+    // Std modules are only available once they're opened
+    assert!(!ctx.supports(Feature::Std("math")));
+    ctx.open_all_std();
+    for module in info.std_modules {
+        assert!(ctx.supports(Feature::Std(module)));
+        ctx.run(format!("import {module}"))
+            .expect("A supported std module should import");
+    }
+    assert!(!ctx.supports(Feature::Std("telepathy")));
+
+    // Deferred ones count, since importing them opens them
+    let mut lazy = Context::builder().lazy_std(true).build();
+    lazy.open_all_std();
+    assert!(lazy.supports(Feature::Std("regex")));
+}
+
+#[test]
+fn test_userdata_arg_in_place() {
+    struct Mesh {
+        vertices: Vec<[f32; 3]>,
+    }
+
+    impl BoltUserdata for Mesh {
+        const NAME: &'static str = "Mesh";
+    }
+
+    unsafe extern "C" fn vertex_count(_ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let mut thread = unsafe { Thread::from_raw_unchecked(thr) };
+        let count = match thread.get_arg_userdata::<Mesh>(0) {
+            Ok(mesh) => mesh.vertices.len() as f64,
+            Err(_) => -1.0,
+        };
+        thread.return_val(&count);
+    }
+
+    unsafe extern "C" fn flatten(_ctx: *mut sys::bt_Context, thr: *mut sys::bt_Thread) {
+        let thread = unsafe { Thread::from_raw_unchecked(thr) };
+        if let Ok(mut mesh) = thread.get_arg_userdata_mut::<Mesh>(0) {
+            mesh.vertices.iter_mut().for_each(|vertex| vertex[1] = 0.0);
+        }
+    }
+
+    let mut ctx = Context::new();
+    ctx.open_all_std();
+    let mesh_type = <TypedUserdata<Mesh> as ScalarTypeSignature>::make_type(&mut ctx);
+    let (number, null) = (ctx.type_number(), ctx.type_null());
+    ModuleBuilder::new("meshes")
+        .native("vertex_count", Some(vertex_count), number, &[mesh_type])
+        .native("flatten", Some(flatten), null, &[mesh_type])
+        .finish_validated(&mut ctx)
+        .expect("Module should validate");
+
+    let module = ctx
+        .compile_module(
+            "import meshes\nexport fn process(m: Mesh): number { meshes.flatten(m) return meshes.vertex_count(m) }",
+            "process",
+        )
+        .expect("Failed to compile module");
+    ctx.execute_module(module)
+        .expect("Failed to execute module");
+    let process = export(&mut ctx, module, "process");
+
+    let mesh = ctx
+        .create_typed_userdata(Mesh {
+            vertices: vec![[1.0, 2.0, 3.0]; 1024],
+        })
+        .unwrap();
+    ctx.push_root(mesh.userdata().as_object());
+    let count = ctx
+        .call(process, &[Value::from_raw(mesh.make())])
+        .expect("Failed to call process");
+    assert_eq!(count.as_number(), Some(1024.0));
+    assert!(mesh.borrow().unwrap().vertices.iter().all(|v| v[1] == 0.0));
+    ctx.pop_root();
+}
+
+#[test]
+fn test_userdata_serialization_hooks() {
+    #[derive(Debug, PartialEq)]
+    struct Color(u32);
+
+    impl BoltUserdata for Color {
+        const NAME: &'static str = "Color";
+    }
+
+    struct Opaque;
+
+    impl BoltUserdata for Opaque {
+        const NAME: &'static str = "Opaque";
+    }
+
+    let mut ctx = Context::new();
+    ctx.userdata_serialize_with::<Color, _, _>(
+        |_ctx, color| Ok(Value::from_raw((color.0 as f64).make())),
+        |_ctx, value| {
+            let rgb = value.as_number().ok_or(Error::Serde {
+                msg: "expected a number".to_owned(),
+            })?;
+            Ok(Color(rgb as u32))
+        },
+    )
+    .expect("Failed to register hooks");
+
+    let color = ctx.create_userdata(Color(0xff8800)).unwrap();
+    ctx.push_root(color.as_object());
+    let encoded = ctx
+        .serialize_userdata(color)
+        .expect("Color should serialize");
+    ctx.push_root(encoded.as_object().unwrap());
+    let table = <types::Table as FromBoltValue>::from(encoded.0).expect("encoded as a table");
+    let name = table.get_str(userdata::SERIALIZED_TYPE_KEY).unwrap();
+    assert_eq!(<String as FromBoltValue>::from(name.0).unwrap(), "Color");
+
+    let decoded = ctx
+        .deserialize_userdata(encoded)
+        .expect("Color should deserialize")
+        .expect("encoded value is a userdata");
+    assert_ne!(decoded.as_ptr(), color.as_ptr());
+    assert_eq!(*decoded.borrow::<Color>().unwrap(), Color(0xff8800));
+    ctx.pop_root();
+    ctx.pop_root();
+
+    let plain = Value::from_raw(ctx.make_table(0).make());
+    assert!(ctx.deserialize_userdata(plain).unwrap().is_none());
+
+    let opaque = ctx.create_userdata(Opaque).unwrap();
+    assert!(matches!(
+        ctx.serialize_userdata(opaque),
+        Err(Error::NotSerializable { ty }) if ty == "Opaque"
+    ));
+}
+
+#[derive(Debug, PartialEq)]
+struct Color(u32);
+
+impl BoltUserdata for Color {
+    const NAME: &'static str = "Color";
+}
+
+fn serialize_colors(ctx: &mut Context) {
+    ctx.userdata_serialize_with::<Color, _, _>(
+        |_ctx, color| Ok(Value::from_raw((color.0 as f64).make())),
+        |_ctx, value| {
+            let rgb = value.as_number().ok_or(Error::Serde {
+                msg: "expected a number".to_owned(),
+            })?;
+            Ok(Color(rgb as u32))
+        },
+    )
+    .expect("Failed to register hooks");
+}
+
+#[test]
+fn test_replay_userdata() {
+    use bolt_rs::replay::{Event, RecordedValue, Recording};
+
+    let setup = |ctx: &mut Context| {
+        ctx.open_all_std();
+        serialize_colors(ctx);
+        let module = ctx
+            .compile_module("export fn same(c: Color): Color { return c }", "colors")
+            .expect("Failed to compile module");
+        ctx.execute_module(module)
+            .expect("Failed to execute module");
+        export(ctx, module, "same")
+    };
+
+    let mut ctx = Context::new();
+    let same = setup(&mut ctx);
+    let color = ctx.create_userdata(Color(0x336699)).unwrap();
+    ctx.push_root(color.as_object());
+    ctx.start_recording();
+    ctx.call(same, &[Value::from_raw(color.make())])
+        .expect("Failed to call same");
+    let recording = ctx.stop_recording().expect("Recording was active");
+    ctx.pop_root();
+
+    let Event::Call { args, outcome, .. } = &recording.events[0] else {
+        panic!("expected a call, got {:?}", recording.events[0]);
+    };
+    assert!(matches!(&args[..], [RecordedValue::Userdata(_)]));
+    assert_eq!(outcome.as_ref(), Ok(&args[0]));
+
+    let mut file = Vec::new();
+    recording
+        .write_to(&mut file)
+        .expect("Failed to write recording");
+    let loaded = Recording::read_from(file.as_slice()).expect("Failed to read recording");
+    assert_eq!(loaded, recording);
+
+    // A string length that overflows is rejected rather than panicking
+    let corrupt = format!("bolt-replay 1\nrun 0000000000000000 {}:x", usize::MAX);
+    assert!(matches!(
+        Recording::read_from(corrupt.as_bytes()),
+        Err(Error::Replay { .. })
+    ));
+
+    // The argument is rebuilt into a new Color through the hooks
+    let mut fresh = Context::new();
+    let fresh_same = setup(&mut fresh);
+    let divergences = loaded.replay(&mut fresh, |_, _| Some(fresh_same));
+    assert!(
+        divergences.is_empty(),
+        "unexpected divergences: {divergences:?}"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_userdata() {
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Encoded {
+        #[serde(rename = "$userdata")]
+        ty: String,
+        value: u32,
+    }
+
+    let mut ctx = Context::new();
+    serialize_colors(&mut ctx);
+    let color = ctx.create_userdata(Color(0x336699)).unwrap();
+    ctx.push_root(color.as_object());
+    let encoded: Encoded = bolt_rs::serde::from_value(&mut ctx, Value::from_raw(color.make()))
+        .expect("Failed to deserialize");
+    ctx.pop_root();
+    assert_eq!(
+        encoded,
+        Encoded {
+            ty: "Color".to_owned(),
+            value: 0x336699,
+        }
+    );
+
+    // A map shaped like an encoded userdata stays a map unless asked for
+    let table = bolt_rs::serde::to_value(&mut ctx, &encoded).expect("Failed to serialize");
+    assert!(matches!(
+        table.as_object().map(|obj| obj.value_type()),
+        Some(ValueType::Table)
+    ));
+    let rebuilt =
+        bolt_rs::serde::to_value_with_userdata(&mut ctx, &encoded).expect("Failed to serialize");
+    let rebuilt = <types::Userdata as FromBoltValue>::from(rebuilt.0).expect("a userdata");
+    assert_eq!(*rebuilt.borrow::<Color>().unwrap(), Color(0x336699));
+}
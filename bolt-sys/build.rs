@@ -1,7 +1,13 @@
-use std::{env, path::PathBuf};
+use std::{env, fs, path::PathBuf};
 
 fn main() {
-    let dst = cmake::Config::new("bolt").build_target("bolt").build();
+    let mut config = cmake::Config::new("bolt");
+    // Recorded so bolt-rs can tell whether bolt was built with its debug assertions
+    println!(
+        "cargo:rustc-env=BOLT_CMAKE_PROFILE={}",
+        config.get_profile()
+    );
+    let dst = config.build_target("bolt").build();
 
     println!(
         "cargo:rustc-link-search=native={}/build/bolt",
@@ -9,7 +15,23 @@ fn main() {
     );
     println!("cargo:rustc-link-lib=static=bolt");
 
-    let bindings = bindgen::Builder::default()
+    // Every standard library module has a `boltstd_<name>.h` declaring its
+    // `boltstd_open_<name>`, next to the `boltstd.h` that opens them all
+    let mut std_modules: Vec<String> = fs::read_dir("./bolt/bolt/boltstd")
+        .expect("Couldn't list bolt's standard library")
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let file = path.file_name()?.to_str()?;
+            let module = file.strip_prefix("boltstd_")?.strip_suffix(".h")?;
+            let header = fs::read_to_string(&path).ok()?;
+            header
+                .contains(&format!("boltstd_open_{module}"))
+                .then(|| module.to_owned())
+        })
+        .collect();
+    std_modules.sort();
+
+    let mut bindings = bindgen::Builder::default()
         .header("./bolt/bolt/bolt.h")
         .header("./bolt/bolt/bt_context.h")
         .header("./bolt/bolt/bt_value.h")
@@ -25,22 +47,36 @@ fn main() {
         .header("./bolt/bolt/bt_embedding.h")
         .header("./bolt/bolt/bt_userdata.h")
         .header("./bolt/bolt/boltstd/boltstd.h")
-        .header("./bolt/bolt/boltstd/boltstd_core.h")
-        .header("./bolt/bolt/boltstd/boltstd_arrays.h")
-        .header("./bolt/bolt/boltstd/boltstd_strings.h")
-        .header("./bolt/bolt/boltstd/boltstd_tables.h")
-        .header("./bolt/bolt/boltstd/boltstd_math.h")
-        .header("./bolt/bolt/boltstd/boltstd_io.h")
-        .header("./bolt/bolt/boltstd/boltstd_meta.h")
-        .header("./bolt/bolt/boltstd/boltstd_regex.h")
+        // Only bolt's own API, not the libc items its headers pull in
+        .allowlist_function("bt_.*|boltstd_.*")
+        .allowlist_type("bt_.*")
+        .allowlist_var("BT_.*|BOLT_.*")
+        // Enums bolt-rs matches on are typed, e.g. `bt_ErrorType::BT_ERROR_PARSE`
+        .newtype_enum("bt_ErrorType|bt_ObjectType|bt_TypeCategory")
         .derive_debug(true)
         .derive_copy(true)
-        .derive_default(true)
-        .generate()
-        .expect("Unable to generate bindings");
+        .derive_default(true);
+    for module in &std_modules {
+        bindings = bindings.header(format!("./bolt/bolt/boltstd/boltstd_{module}.h"));
+    }
+    let bindings = bindings.generate().expect("Unable to generate bindings");
 
     let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
         .write_to_file(out_path.join("bindings.rs"))
         .expect("Couldn't write bindings!");
+
+    let names: Vec<String> = std_modules
+        .iter()
+        .map(|module| format!("{module:?}"))
+        .collect();
+    fs::write(
+        out_path.join("std_modules.rs"),
+        format!(
+            "/// The standard library modules compiled into bolt, by import name\n\
+             pub const STD_MODULES: &[&str] = &[{}];\n",
+            names.join(", ")
+        ),
+    )
+    .expect("Couldn't write the standard library modules!");
 }
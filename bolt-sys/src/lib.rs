@@ -1 +1,30 @@
+//! Raw bindings to the bolt C API
+//!
+//! Generation is restricted to bolt's own items: functions prefixed `bt_` or `boltstd_`,
+//! `bt_` types and the `BT_`/`BOLT_` constants. Whatever libc declarations the headers pull
+//! in aren't re-exported.
+//!
+//! # Stability
+//!
+//! bolt-rs only builds on the following parts of [`sys`], which are kept source-compatible
+//! across bolt submodule updates (bumping the submodule breaks them only in a major release):
+//!
+//! - Opening and closing contexts: `bt_open`, `bt_close`, `bt_default_handlers` and the
+//!   `bt_Handlers` callbacks
+//! - Running code: `bt_run`, `bt_compile_module`, `bt_execute`, `bt_execute_with_args`,
+//!   `bt_runtime_error`, and the native calling convention (`bt_NativeProc`, `bt_arg`,
+//!   `bt_argc`, `bt_return`, `bt_get_returned`)
+//! - Values: the `bt_make_*`, `bt_is_*` and `bt_get_*` value helpers and `bt_value`/`bt_object`
+//! - Objects: strings, arrays, tables, modules, natives and userdata, through their `bt_make_*`,
+//!   `bt_array_*`, `bt_table_*`, `bt_module_*` and `bt_userdata_*` functions
+//! - Types: the `bt_type_*` builtins and constructors, and the fields of `bt_Type` that name and
+//!   categorize a type
+//! - The collector: `bt_add_ref`, `bt_remove_ref`, `bt_push_root`, `bt_pop_root`, `bt_gc_*`
+//! - `boltstd_open_*` for each standard library module
+//! - The enums `bt_ErrorType`, `bt_ObjectType` and `bt_TypeCategory`, which are newtypes with
+//!   associated constants, e.g. `bt_ErrorType::BT_ERROR_PARSE`
+//!
+//! Everything else is exposed as bindgen generates it, for use at your own risk: internal
+//! structs such as the parser and compiler may change layout with any bolt revision.
+
 pub mod sys;
@@ -5,6 +5,18 @@
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+/// How the vendored bolt was built
+pub mod build {
+    /// The CMake build type bolt was compiled with, e.g. `Debug` or `Release`
+    pub const PROFILE: &str = env!("BOLT_CMAKE_PROFILE");
+
+    /// Whether bolt's own assertions are compiled in, which CMake only does for `Debug`
+    pub const DEBUG_ASSERTIONS: bool = matches!(PROFILE.as_bytes(), b"Debug");
+
+    // Found by the build script from the standard library headers
+    include!(concat!(env!("OUT_DIR"), "/std_modules.rs"));
+}
+
 /// bt_Object mask field helpers
 pub mod object_mask {
     pub const MARK_BIT: u64 = 0x1;